@@ -0,0 +1,200 @@
+use proc_macro::{Delimiter, TokenStream, TokenTree};
+
+use crate::util::{find_top_level, is_group, is_ident, is_punct, split_commas, to_source, AngleDepth};
+
+/// The parts of a trait definition that the generated impl needs.
+struct TraitDef {
+    name: String,
+    /// Generic parameters declared on the impl, without bounds or defaults.
+    params: Vec<String>,
+    /// Arguments naming the trait object type, e.g. `'a, T, N, H = H`.
+    args: Vec<String>,
+    /// Predicates for the impl's where clause.
+    preds: Vec<String>,
+}
+
+pub fn expand(attr: TokenStream, item: TokenStream) -> Result<String, String> {
+    let kinds = parse_kinds(attr)?;
+    let def = parse_trait(item)?;
+
+    let args = def.args.join(", ");
+    let body = |arm: &str, names: &str| {
+        format!("::pi_any::impl_downcast! {{ @{} [pub] [{}] [{}] {} }}\n", arm, def.name, args, names)
+    };
+    let many = kinds.len() > 1;
+    let mut methods = body("impl_body", "");
+    if kinds.is_empty() || kinds.contains(&"box") {
+        methods += &body("impl_body_mut", "");
+    }
+    for kind in &kinds {
+        let names = if many {
+            format!(
+                "downcast_{0} downcast_{0}_expect try_downcast_{0} downcast_{0}_unchecked downcast_{0}_pin",
+                kind
+            )
+        } else {
+            "downcast downcast_expect try_downcast downcast_unchecked downcast_pin".to_string()
+        };
+        methods += &body(&format!("impl_body_{}", kind), &names);
+    }
+
+    let where_clause = if def.preds.is_empty() {
+        String::new()
+    } else {
+        format!("where {}", def.preds.join(", "))
+    };
+    Ok(format!(
+        "#[allow(dead_code)] impl<{}> dyn {}<{}> {} {{ {} }}",
+        def.params.join(", "),
+        def.name,
+        args,
+        where_clause,
+        methods
+    ))
+}
+
+/// Parses `box, rc, arc` in any order and combination.
+fn parse_kinds(attr: TokenStream) -> Result<Vec<&'static str>, String> {
+    let tokens: Vec<TokenTree> = attr.into_iter().collect();
+    let mut kinds = Vec::new();
+    for part in split_commas(&tokens) {
+        let kind = match to_source(&part).as_str() {
+            "box" => "box",
+            "rc" => "rc",
+            "arc" => "arc",
+            other => return Err(format!("unknown downcastable option `{}`, expected `box`, `rc` or `arc`", other)),
+        };
+        if !kinds.contains(&kind) {
+            kinds.push(kind);
+        }
+    }
+    Ok(kinds)
+}
+
+fn parse_trait(item: TokenStream) -> Result<TraitDef, String> {
+    let tokens: Vec<TokenTree> = item.into_iter().collect();
+    let mut i = 0;
+
+    // Attributes, visibility and qualifiers before `trait`.
+    loop {
+        if is_punct(tokens.get(i), '#') {
+            i += 2;
+        } else if is_ident(tokens.get(i), "pub") {
+            i += 1;
+            if is_group(tokens.get(i), Delimiter::Parenthesis) {
+                i += 1;
+            }
+        } else if is_ident(tokens.get(i), "unsafe") || is_ident(tokens.get(i), "auto") {
+            i += 1;
+        } else {
+            break;
+        }
+    }
+    if !is_ident(tokens.get(i), "trait") {
+        return Err("#[downcastable] can only be applied to a trait definition".to_string());
+    }
+    let name = match tokens.get(i + 1) {
+        Some(TokenTree::Ident(name)) => name.to_string(),
+        _ => return Err("expected the trait name".to_string()),
+    };
+    i += 2;
+
+    let mut def = TraitDef { name, params: Vec::new(), args: Vec::new(), preds: Vec::new() };
+
+    // Generic parameters.
+    if is_punct(tokens.get(i), '<') {
+        let mut depth = AngleDepth::new();
+        let start = i + 1;
+        let end = (i..tokens.len())
+            .find(|&j| {
+                depth.feed(&tokens[j]);
+                depth.depth() == 0
+            })
+            .ok_or("unterminated generic parameter list")?;
+        for param in split_commas(&tokens[start..end]) {
+            parse_param(&param, &mut def)?;
+        }
+        i = end + 1;
+    }
+
+    // Skip the supertraits, then take the where clause up to the body.
+    let body_at = find_top_level(&tokens[i..], |tt| is_group(Some(tt), Delimiter::Brace))
+        .map(|j| i + j)
+        .ok_or("expected the trait body")?;
+    if let Some(j) = find_top_level(&tokens[i..body_at], |tt| is_ident(Some(tt), "where")) {
+        for pred in split_commas(&tokens[i + j + 1..body_at]) {
+            // Predicates on `Self` constrain implementors, not the trait object.
+            if !is_ident(pred.first(), "Self") {
+                def.preds.push(to_source(&pred));
+            }
+        }
+    }
+
+    // Associated types become extra parameters bound to themselves.
+    if let Some(TokenTree::Group(body)) = tokens.get(body_at) {
+        let body: Vec<TokenTree> = body.stream().into_iter().collect();
+        let mut j = 0;
+        while j < body.len() {
+            if is_ident(body.get(j), "type") {
+                let end = (j..body.len()).find(|&k| is_punct(body.get(k), ';')).unwrap_or(body.len());
+                parse_assoc(&body[j + 1..end], &mut def)?;
+                j = end;
+            }
+            j += 1;
+        }
+    }
+    Ok(def)
+}
+
+fn parse_param(param: &[TokenTree], def: &mut TraitDef) -> Result<(), String> {
+    // Drop a default value, which is not allowed on impls.
+    let param = match find_top_level(param, |tt| is_punct(Some(tt), '=')) {
+        Some(eq) => &param[..eq],
+        None => param,
+    };
+    if is_punct(param.first(), '\'') {
+        let name = to_source(&param[..2]);
+        // `Any` only exists for `'static` data, so only that instantiation can be erased.
+        def.preds.push(format!("{}: 'static", name));
+        if param.len() > 3 {
+            def.preds.push(format!("{}: {}", name, to_source(&param[3..])));
+        }
+        def.params.push(name.clone());
+        def.args.push(name);
+    } else if is_ident(param.first(), "const") {
+        let name = to_source(&param[1..2]);
+        def.params.push(to_source(param));
+        def.args.push(name);
+    } else {
+        let name = match param.first() {
+            Some(TokenTree::Ident(name)) => name.to_string(),
+            _ => return Err(format!("unsupported generic parameter `{}`", to_source(param))),
+        };
+        def.preds.push(format!("{}: 'static", name));
+        if param.len() > 2 {
+            def.preds.push(format!("{}: {}", name, to_source(&param[2..])));
+        }
+        def.params.push(name.clone());
+        def.args.push(name);
+    }
+    Ok(())
+}
+
+fn parse_assoc(decl: &[TokenTree], def: &mut TraitDef) -> Result<(), String> {
+    let name = match decl.first() {
+        Some(TokenTree::Ident(name)) => name.to_string(),
+        _ => return Err("expected the associated type name".to_string()),
+    };
+    if is_punct(decl.get(1), '<') {
+        return Err(format!("generic associated type `{}` cannot be used in a trait object", name));
+    }
+    def.preds.push(format!("{}: 'static", name));
+    if is_punct(decl.get(1), ':') {
+        let end = find_top_level(decl, |tt| is_ident(Some(tt), "where") || is_punct(Some(tt), '='))
+            .unwrap_or(decl.len());
+        def.preds.push(format!("{}: {}", name, to_source(&decl[2..end])));
+    }
+    def.params.push(name.clone());
+    def.args.push(format!("{0} = {0}", name));
+    Ok(())
+}
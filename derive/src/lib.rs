@@ -0,0 +1,53 @@
+//! Procedural macros for [pi_any](https://github.com/GaiaWorld/pi_any), enabled through its
+//! `derive` feature.
+
+use proc_macro::TokenStream;
+
+mod downcastable;
+mod reflect;
+mod util;
+
+/// Generates downcasting methods for the trait object of the annotated trait.
+///
+/// Without arguments this emits what `impl_downcast!` does. `box`, `rc` and `arc` add the
+/// owning downcasts for the respective pointers; a single one is named `downcast`, several
+/// are named `downcast_box`, `downcast_rc` and `downcast_arc`. Generic parameters
+/// (lifetimes, types and consts), their bounds, the where clause and associated types are
+/// taken from the trait definition itself.
+///
+/// ```ignore
+/// #[downcastable(box, arc)]
+/// trait Source<'cfg, T: Clone, const N: usize>: BoxAny + ArcAny where T: Send {
+///     type Item: Copy;
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn downcastable(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let generated = match downcastable::expand(attr, item.clone()) {
+        Ok(generated) => generated,
+        Err(msg) => format!("::core::compile_error!({:?});", msg),
+    };
+    let mut output = item;
+    output.extend(generated.parse::<TokenStream>().unwrap());
+    output
+}
+
+/// Implements `Reflect` for a struct, giving access to its fields by name. Tuple fields are
+/// named by their index. A field marked `#[reflect(skip)]` is left out.
+///
+/// ```ignore
+/// #[derive(Reflect)]
+/// struct Transform<T: Copy> {
+///     pos: [T; 2],
+///     #[reflect(skip)]
+///     dirty: bool,
+/// }
+/// ```
+#[proc_macro_derive(Reflect, attributes(reflect))]
+pub fn derive_reflect(item: TokenStream) -> TokenStream {
+    let generated = match reflect::expand(item) {
+        Ok(generated) => generated,
+        Err(msg) => format!("::core::compile_error!({:?});", msg),
+    };
+    generated.parse().unwrap()
+}
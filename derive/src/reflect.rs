@@ -0,0 +1,173 @@
+use proc_macro::{Delimiter, TokenStream, TokenTree};
+
+use crate::util::{find_top_level, is_group, is_ident, is_punct, split_commas, to_source, AngleDepth};
+
+/// A field of the struct: the name used for lookups and the expression accessing it.
+struct Field {
+    name: String,
+    member: String,
+}
+
+pub fn expand(item: TokenStream) -> Result<String, String> {
+    let tokens: Vec<TokenTree> = item.into_iter().collect();
+    let mut i = 0;
+
+    // Attributes and visibility before `struct`.
+    loop {
+        if is_punct(tokens.get(i), '#') {
+            i += 2;
+        } else if is_ident(tokens.get(i), "pub") {
+            i += 1;
+            if is_group(tokens.get(i), Delimiter::Parenthesis) {
+                i += 1;
+            }
+        } else {
+            break;
+        }
+    }
+    if !is_ident(tokens.get(i), "struct") {
+        return Err("#[derive(Reflect)] can only be applied to a struct".to_string());
+    }
+    let name = match tokens.get(i + 1) {
+        Some(TokenTree::Ident(name)) => name.to_string(),
+        _ => return Err("expected the struct name".to_string()),
+    };
+    i += 2;
+
+    let mut params = Vec::new();
+    let mut args = Vec::new();
+    let mut preds = Vec::new();
+    if is_punct(tokens.get(i), '<') {
+        let mut depth = AngleDepth::new();
+        let start = i + 1;
+        let end = (i..tokens.len())
+            .find(|&j| {
+                depth.feed(&tokens[j]);
+                depth.depth() == 0
+            })
+            .ok_or("unterminated generic parameter list")?;
+        for param in split_commas(&tokens[start..end]) {
+            let param = match find_top_level(&param, |tt| is_punct(Some(tt), '=')) {
+                Some(eq) => &param[..eq],
+                None => &param[..],
+            };
+            if is_punct(param.first(), '\'') {
+                let lt = to_source(&param[..2]);
+                // Fields are handed out as `dyn Any`, which only exists for `'static` data.
+                preds.push(format!("{}: 'static", lt));
+                args.push(lt);
+            } else if is_ident(param.first(), "const") {
+                args.push(to_source(&param[1..2]));
+            } else {
+                let ty = to_source(&param[..1]);
+                preds.push(format!("{}: 'static", ty));
+                args.push(ty);
+            }
+            params.push(to_source(param));
+        }
+        i = end + 1;
+    }
+
+    // The where clause comes before braced fields and after tuple fields.
+    let body_at = find_top_level(&tokens[i..], |tt| {
+        is_group(Some(tt), Delimiter::Brace)
+            || is_group(Some(tt), Delimiter::Parenthesis)
+            || is_punct(Some(tt), ';')
+    })
+    .map(|j| i + j)
+    .ok_or("expected the struct fields")?;
+    let where_at = find_top_level(&tokens[i..], |tt| is_ident(Some(tt), "where")).map(|j| i + j);
+    if let Some(where_at) = where_at {
+        let end = if where_at < body_at {
+            body_at
+        } else {
+            find_top_level(&tokens[where_at..], |tt| is_punct(Some(tt), ';'))
+                .map_or(tokens.len(), |j| where_at + j)
+        };
+        preds.extend(split_commas(&tokens[where_at + 1..end]).iter().map(|pred| to_source(pred)));
+    }
+
+    let fields = match &tokens[body_at] {
+        TokenTree::Group(body) => parse_fields(body.stream(), body.delimiter() == Delimiter::Brace)?,
+        _ => Vec::new(),
+    };
+
+    let names: Vec<_> = fields.iter().map(|f| format!("{:?}", f.name)).collect();
+    let arms = |mutability: &str| -> String {
+        fields
+            .iter()
+            .map(|f| format!("{:?} => ::core::option::Option::Some(&{}self.{}),", f.name, mutability, f.member))
+            .collect()
+    };
+    let where_clause = if preds.is_empty() { String::new() } else { format!("where {}", preds.join(", ")) };
+    Ok(format!(
+        "impl<{params}> ::pi_any::Reflect for {name}<{args}> {where_clause} {{
+            fn field_names(&self) -> &'static [&'static str] {{
+                &[{names}]
+            }}
+            fn field(&self, name: &str) -> ::core::option::Option<&dyn ::core::any::Any> {{
+                match name {{ {arms} _ => ::core::option::Option::None }}
+            }}
+            fn field_mut(&mut self, name: &str) -> ::core::option::Option<&mut dyn ::core::any::Any> {{
+                match name {{ {arms_mut} _ => ::core::option::Option::None }}
+            }}
+        }}",
+        params = params.join(", "),
+        args = args.join(", "),
+        names = names.join(", "),
+        arms = arms(""),
+        arms_mut = arms("mut "),
+    ))
+}
+
+fn parse_fields(body: TokenStream, named: bool) -> Result<Vec<Field>, String> {
+    let tokens: Vec<TokenTree> = body.into_iter().collect();
+    let mut fields = Vec::new();
+    for (index, field) in split_commas(&tokens).iter().enumerate() {
+        let mut i = 0;
+        let mut skip = false;
+        loop {
+            if is_punct(field.get(i), '#') {
+                if let Some(TokenTree::Group(attr)) = field.get(i + 1) {
+                    skip |= is_skip(attr.stream())?;
+                }
+                i += 2;
+            } else if is_ident(field.get(i), "pub") {
+                i += 1;
+                if is_group(field.get(i), Delimiter::Parenthesis) {
+                    i += 1;
+                }
+            } else {
+                break;
+            }
+        }
+        if skip {
+            continue;
+        }
+        if !named {
+            fields.push(Field { name: index.to_string(), member: index.to_string() });
+            continue;
+        }
+        match field.get(i) {
+            Some(TokenTree::Ident(ident)) => {
+                let member = ident.to_string();
+                let name = member.strip_prefix("r#").unwrap_or(&member).to_string();
+                fields.push(Field { name, member });
+            }
+            _ => return Err(format!("expected a field name in `{}`", to_source(field))),
+        }
+    }
+    Ok(fields)
+}
+
+/// Returns true for `reflect(skip)`, false for other attributes.
+fn is_skip(attr: TokenStream) -> Result<bool, String> {
+    let tokens: Vec<TokenTree> = attr.into_iter().collect();
+    if !is_ident(tokens.first(), "reflect") {
+        return Ok(false);
+    }
+    match tokens.get(1) {
+        Some(TokenTree::Group(args)) if args.stream().to_string() == "skip" => Ok(true),
+        _ => Err(format!("unknown reflect attribute `{}`, expected `reflect(skip)`", to_source(&tokens))),
+    }
+}
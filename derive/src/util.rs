@@ -0,0 +1,73 @@
+use proc_macro::{Delimiter, Spacing, TokenStream, TokenTree};
+
+/// Joins tokens back into source text.
+pub fn to_source(tokens: &[TokenTree]) -> String {
+    tokens.iter().cloned().collect::<TokenStream>().to_string()
+}
+
+pub fn is_punct(tt: Option<&TokenTree>, ch: char) -> bool {
+    matches!(tt, Some(TokenTree::Punct(p)) if p.as_char() == ch)
+}
+
+pub fn is_ident(tt: Option<&TokenTree>, name: &str) -> bool {
+    matches!(tt, Some(TokenTree::Ident(i)) if i.to_string() == name)
+}
+
+pub fn is_group(tt: Option<&TokenTree>, delimiter: Delimiter) -> bool {
+    matches!(tt, Some(TokenTree::Group(g)) if g.delimiter() == delimiter)
+}
+
+/// Walks `tokens` and tracks the nesting of `<`/`>`, which unlike the other brackets are
+/// not grouped by the tokenizer. `->` is not treated as a closing angle bracket.
+pub struct AngleDepth {
+    depth: usize,
+    arrow: bool,
+}
+
+impl AngleDepth {
+    pub fn new() -> Self {
+        AngleDepth { depth: 0, arrow: false }
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Feeds one token and returns the depth *before* the token.
+    pub fn feed(&mut self, tt: &TokenTree) -> usize {
+        let before = self.depth;
+        if let TokenTree::Punct(p) = tt {
+            match p.as_char() {
+                '<' => self.depth += 1,
+                '>' if !self.arrow => self.depth = self.depth.saturating_sub(1),
+                _ => (),
+            }
+            self.arrow = p.as_char() == '-' && p.spacing() == Spacing::Joint;
+        } else {
+            self.arrow = false;
+        }
+        before
+    }
+}
+
+/// Splits `tokens` on the commas that are not nested inside angle brackets. Empty trailing
+/// segments are dropped.
+pub fn split_commas(tokens: &[TokenTree]) -> Vec<Vec<TokenTree>> {
+    let mut parts = vec![Vec::new()];
+    let mut depth = AngleDepth::new();
+    for tt in tokens {
+        if depth.feed(tt) == 0 && is_punct(Some(tt), ',') {
+            parts.push(Vec::new());
+        } else {
+            parts.last_mut().unwrap().push(tt.clone());
+        }
+    }
+    parts.retain(|p| !p.is_empty());
+    parts
+}
+
+/// Returns the position of the first token at angle depth zero that satisfies `pred`.
+pub fn find_top_level(tokens: &[TokenTree], pred: impl Fn(&TokenTree) -> bool) -> Option<usize> {
+    let mut depth = AngleDepth::new();
+    tokens.iter().position(|tt| depth.feed(tt) == 0 && pred(tt))
+}
@@ -0,0 +1,88 @@
+use std::rc::Rc;
+use std::sync::Arc;
+
+use pi_any::{downcastable, ArcAny, BoxAny, RcAny};
+
+#[downcastable]
+trait Plain: BoxAny {}
+
+#[downcastable(box)]
+pub(crate) trait Boxed<T: Clone>: BoxAny
+where
+    T: Default,
+{
+    type H: Copy;
+
+    fn get(&self) -> T where Self: Sized {
+        T::default()
+    }
+}
+
+#[downcastable(rc, arc)]
+trait Shared<'cfg, F: Fn(u8) -> u8, const N: usize>: RcAny + ArcAny {
+    fn config(&self) -> &'cfg str;
+}
+
+#[derive(Debug)]
+struct Foo(u32);
+impl Plain for Foo {}
+impl Boxed<u8> for Foo { type H = f32; }
+impl<F: Fn(u8) -> u8> Shared<'static, F, 4> for Foo {
+    fn config(&self) -> &'static str { "foo" }
+}
+
+#[derive(Debug)]
+struct Bar;
+impl Plain for Bar {}
+impl Boxed<u8> for Bar { type H = f32; }
+impl<F: Fn(u8) -> u8> Shared<'static, F, 4> for Bar {
+    fn config(&self) -> &'static str { "bar" }
+}
+
+type Double = fn(u8) -> u8;
+
+#[test]
+fn plain() {
+    let mut base: Box<dyn Plain> = Box::new(Foo(42));
+    assert!(base.is::<Foo>());
+    assert!(base.downcast_ref::<Bar>().is_none());
+    base.downcast_mut::<Foo>().unwrap().0 = 6 * 9;
+    assert_eq!(base.downcast_ref_expect::<Foo>("foo").0, 6 * 9);
+}
+
+#[test]
+fn boxed() {
+    assert_eq!(Boxed::get(&Bar), 0u8);
+    let base: Box<dyn Boxed<u8, H = f32>> = Box::new(Foo(42));
+    let base = base.downcast::<Bar>().unwrap_err();
+    assert_eq!(base.downcast::<Foo>().map_err(|_| "Shouldn't happen.").unwrap().0, 42);
+}
+
+#[test]
+fn shared() {
+    let base: Rc<dyn Shared<'static, Double, 4>> = Rc::new(Foo(1));
+    assert_eq!(base.config(), "foo");
+    let base = base.downcast_rc::<Bar>().unwrap_err();
+    assert_eq!(base.downcast_rc::<Foo>().map_err(|_| "Shouldn't happen.").unwrap().0, 1);
+
+    let base: Arc<dyn Shared<'static, Double, 4>> = Arc::new(Bar);
+    let base = base.downcast_arc::<Foo>().unwrap_err();
+    assert!(base.downcast_arc::<Bar>().is_ok());
+}
+
+#[downcastable]
+trait Projected<I>: BoxAny
+where
+    I: Iterator,
+    I::Item: Copy,
+    for<'a> &'a I: Clone,
+{
+}
+
+impl Projected<std::vec::IntoIter<u8>> for Foo {}
+
+#[test]
+fn projected() {
+    let base: Box<dyn Projected<std::vec::IntoIter<u8>>> = Box::new(Foo(7));
+    assert_eq!(base.downcast_ref::<Foo>().unwrap().0, 7);
+}
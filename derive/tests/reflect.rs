@@ -0,0 +1,50 @@
+use std::any::TypeId;
+
+use pi_any::{BoxAny, Reflect, ReflectExt};
+
+trait Component: BoxAny + Reflect {}
+
+#[derive(Reflect, Default)]
+pub struct Transform<T: Copy + 'static = f32> {
+    pub pos: [T; 2],
+    pub(crate) r#type: u8,
+    #[reflect(skip)]
+    #[allow(dead_code)]
+    dirty: bool,
+}
+impl<T: Copy> Component for Transform<T> {}
+
+#[derive(Reflect)]
+struct Name<'a, const N: usize>(&'a str, [u8; N])
+where
+    'a: 'static;
+
+#[derive(Reflect)]
+struct Marker;
+
+#[test]
+fn named() {
+    let mut component: Box<dyn Component> = Box::new(Transform::<f32>::default());
+    assert_eq!(component.field_names(), ["pos", "type"]);
+    assert!(component.field("dirty").is_none());
+    assert!(component.set_field("pos", [1.0f32, 2.0]).is_ok());
+    *component.get_field_mut::<u8>("type").unwrap() = 3;
+    let types: Vec<_> = component.fields().map(|(_, value)| value.type_id()).collect();
+    assert_eq!(types, [TypeId::of::<[f32; 2]>(), TypeId::of::<u8>()]);
+
+    let transform = component.into_any().downcast::<Transform>().unwrap();
+    assert_eq!((transform.pos, transform.r#type), ([1.0, 2.0], 3));
+}
+
+#[test]
+fn tuple_and_unit() {
+    let mut name = Name("ann", [1, 2]);
+    assert_eq!(name.field_names(), ["0", "1"]);
+    assert_eq!(name.get_field::<&str>("0"), Some(&"ann"));
+    assert_eq!(name.set_field("1", [3u8]), Err([3]));
+    assert!(name.set_field("1", [3u8, 4]).is_ok());
+    assert_eq!(name.1, [3, 4]);
+
+    assert!(Marker.field_names().is_empty());
+    assert!(Marker.field("0").is_none());
+}
@@ -0,0 +1,291 @@
+//! `AnyFn`, a function whose arguments are looked up by type in an `AnyMap`.
+
+use std::any::{type_name, Any, TypeId};
+use std::error::Error;
+use std::fmt;
+
+use crate::AnyMap;
+
+/// A parameter of a function wrapped in an `AnyFn`: `&T` or `&mut T`, borrowing the value
+/// of type `T` in the `AnyMap` of arguments.
+pub trait FnParam {
+    /// The type of the value in the `AnyMap`.
+    type Value: Any;
+    /// The argument passed to the function.
+    type Item<'a>;
+    /// True if the function is given a mutable borrow.
+    const MUT: bool;
+
+    fn borrow(value: &mut Self::Value) -> Self::Item<'_>;
+}
+
+impl<T: Any> FnParam for &T {
+    type Value = T;
+    type Item<'a> = &'a T;
+    const MUT: bool = false;
+
+    fn borrow(value: &mut T) -> &T {
+        value
+    }
+}
+
+impl<T: Any> FnParam for &mut T {
+    type Value = T;
+    type Item<'a> = &'a mut T;
+    const MUT: bool = true;
+
+    fn borrow(value: &mut T) -> &mut T {
+        value
+    }
+}
+
+/// Implemented for closures and functions of up to eight `FnParam`s returning an `Any`
+/// value, which `AnyFn::new` accepts. `P` is the tuple of parameter types.
+pub trait IntoAnyFn<P> {
+    fn into_any_fn(self) -> AnyFn;
+}
+
+/// The argument an `AnyFn` was called without.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MissingArg {
+    pub type_name: &'static str,
+}
+
+impl fmt::Display for MissingArg {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "no argument of type `{}`", self.type_name)
+    }
+}
+
+impl Error for MissingArg {}
+
+struct Param {
+    type_id: TypeId,
+    type_name: &'static str,
+    mutable: bool,
+}
+
+type Erased = Box<dyn FnMut(&mut AnyMap) -> Box<dyn Any> + Send>;
+
+/// A function with any signature of `&T` and `&mut T` parameters, called with the values
+/// of those types in an `AnyMap`.
+///
+/// The parameter types can be inspected before calling, e.g. by a scheduler that runs
+/// functions without conflicting borrows side by side. A type may appear only once among
+/// the parameters.
+///
+/// ```
+/// use pi_any::{AnyFn, AnyMap};
+///
+/// struct Gravity(f32);
+/// struct Velocity(f32);
+///
+/// let mut systems = vec![
+///     AnyFn::new(|g: &Gravity, v: &mut Velocity| v.0 -= g.0),
+///     AnyFn::new(|v: &Velocity| v.0 * 2.0),
+/// ];
+/// assert!(systems[0].conflicts_with(&systems[1]));
+///
+/// let mut world = AnyMap::new();
+/// world.insert(Gravity(9.8));
+/// world.insert(Velocity(0.0));
+/// systems[0].call(&mut world).unwrap();
+/// let doubled = systems[1].call(&mut world).unwrap();
+/// assert_eq!(*doubled.downcast::<f32>().unwrap(), -19.6);
+///
+/// world.remove::<Gravity>();
+/// assert!(!systems[0].can_call(&world));
+/// assert!(systems[0].call(&mut world).is_err());
+/// ```
+pub struct AnyFn {
+    params: Vec<Param>,
+    f: Erased,
+}
+
+impl AnyFn {
+    /// Wraps `f`. Panics if a type appears more than once among its parameters.
+    pub fn new<P>(f: impl IntoAnyFn<P>) -> Self {
+        f.into_any_fn()
+    }
+
+    fn from_parts(params: Vec<Param>, f: Erased) -> Self {
+        for (i, param) in params.iter().enumerate() {
+            assert!(
+                params[..i].iter().all(|p| p.type_id != param.type_id),
+                "`{}` appears more than once among the parameters",
+                param.type_name
+            );
+        }
+        AnyFn { params, f }
+    }
+
+    /// Returns the `TypeId`s of the values the function borrows, in parameter order.
+    pub fn params(&self) -> impl Iterator<Item = TypeId> + '_ {
+        self.params.iter().map(|p| p.type_id)
+    }
+
+    /// Returns the `TypeId`s of the values the function borrows mutably.
+    pub fn writes(&self) -> impl Iterator<Item = TypeId> + '_ {
+        self.params.iter().filter(|p| p.mutable).map(|p| p.type_id)
+    }
+
+    /// Returns true if one of the functions borrows mutably a value the other borrows.
+    pub fn conflicts_with(&self, other: &AnyFn) -> bool {
+        self.params.iter().any(|a| {
+            other.params.iter().any(|b| a.type_id == b.type_id && (a.mutable || b.mutable))
+        })
+    }
+
+    /// Returns true if `args` holds a value for every parameter.
+    pub fn can_call(&self, args: &AnyMap) -> bool {
+        self.missing(args).is_none()
+    }
+
+    /// Calls the function with the values in `args` and returns its boxed result, or the
+    /// first missing argument. The arguments are taken out of `args` during the call and
+    /// put back afterwards, also if the function panics.
+    pub fn call(&mut self, args: &mut AnyMap) -> Result<Box<dyn Any>, MissingArg> {
+        match self.missing(args) {
+            Some(type_name) => Err(MissingArg { type_name }),
+            None => Ok((self.f)(args)),
+        }
+    }
+
+    fn missing(&self, args: &AnyMap) -> Option<&'static str> {
+        self.params.iter().find(|p| !args.contains_id(p.type_id)).map(|p| p.type_name)
+    }
+}
+
+impl fmt::Debug for AnyFn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let params: Vec<_> = self
+            .params
+            .iter()
+            .map(|p| format!("&{}{}", if p.mutable { "mut " } else { "" }, p.type_name))
+            .collect();
+        f.debug_struct("AnyFn").field("params", &params).finish()
+    }
+}
+
+macro_rules! impl_into_any_fn {
+    ($($p:ident),*) => {
+        impl<F, R, $($p),*> IntoAnyFn<($($p,)*)> for F
+        where
+            F: FnMut($($p),*) -> R + FnMut($($p::Item<'_>),*) -> R + Send + 'static,
+            R: Any,
+            $($p: FnParam,)*
+        {
+            #[allow(non_snake_case, unused_variables, unused_mut)]
+            fn into_any_fn(mut self) -> AnyFn {
+                // Names the signature taking the borrowed items, out of the two `F` has.
+                #[allow(clippy::too_many_arguments)]
+                fn call<R, $($p),*>(mut f: impl FnMut($($p),*) -> R, $($p: $p),*) -> R {
+                    f($($p),*)
+                }
+                let params = vec![$(Param {
+                    type_id: TypeId::of::<$p::Value>(),
+                    type_name: type_name::<$p::Value>(),
+                    mutable: $p::MUT,
+                }),*];
+                AnyFn::from_parts(params, Box::new(move |args: &mut AnyMap| {
+                    let mut taken = args.take_entries([$(TypeId::of::<$p::Value>()),*]);
+                    let mut values = taken.values_mut();
+                    $(let $p = values.next().unwrap().as_any_mut().downcast_mut().unwrap();)*
+                    Box::new(call(&mut self, $($p::borrow($p)),*))
+                }))
+            }
+        }
+    };
+}
+
+impl_into_any_fn!();
+impl_into_any_fn!(P0);
+impl_into_any_fn!(P0, P1);
+impl_into_any_fn!(P0, P1, P2);
+impl_into_any_fn!(P0, P1, P2, P3);
+impl_into_any_fn!(P0, P1, P2, P3, P4);
+impl_into_any_fn!(P0, P1, P2, P3, P4, P5);
+impl_into_any_fn!(P0, P1, P2, P3, P4, P5, P6);
+impl_into_any_fn!(P0, P1, P2, P3, P4, P5, P6, P7);
+
+#[cfg(test)]
+mod test {
+    use std::any::{type_name, TypeId};
+
+    use super::{AnyFn, MissingArg};
+    use crate::AnyMap;
+
+    #[derive(Debug, PartialEq)]
+    struct Foo(u32);
+    #[derive(Debug, PartialEq)]
+    struct Bar(&'static str);
+
+    fn add(foo: &mut Foo, n: &u32) {
+        foo.0 += n;
+    }
+
+    #[test]
+    fn test() {
+        let mut args = AnyMap::new();
+        args.insert(Foo(1));
+        args.insert(2u32);
+
+        let mut f = AnyFn::new(add);
+        assert_eq!(f.params().collect::<Vec<_>>(), [TypeId::of::<Foo>(), TypeId::of::<u32>()]);
+        assert_eq!(f.writes().collect::<Vec<_>>(), [TypeId::of::<Foo>()]);
+        assert!(f.call(&mut args).unwrap().is::<()>());
+        assert_eq!(args.get::<Foo>(), Some(&Foo(3)));
+        assert_eq!(args.len(), 2);
+
+        let mut g = AnyFn::new(|foo: &Foo, bar: &Bar| format!("{} {}", bar.0, foo.0));
+        assert!(!g.can_call(&args));
+        let missing = MissingArg { type_name: type_name::<Bar>() };
+        assert_eq!(g.call(&mut args).err(), Some(missing));
+        args.insert(Bar("foo"));
+        assert_eq!(*g.call(&mut args).unwrap().downcast::<String>().unwrap(), "foo 3");
+        let debug =
+            format!("AnyFn {{ params: [\"&{}\", \"&{}\"] }}", type_name::<Foo>(), type_name::<Bar>());
+        assert_eq!(format!("{:?}", g), debug);
+
+        let mut count = 0;
+        let mut h = AnyFn::new(move || {
+            count += 1;
+            count
+        });
+        h.call(&mut args).unwrap();
+        assert_eq!(*h.call(&mut args).unwrap().downcast::<i32>().unwrap(), 2);
+
+        assert!(f.conflicts_with(&g));
+        assert!(!g.conflicts_with(&AnyFn::new(|_: &Foo, _: &u32| ())));
+        assert!(!h.conflicts_with(&f));
+    }
+
+    #[test]
+    fn test_entries_kept() {
+        let mut args = AnyMap::new();
+        args.insert_eq(Foo(1));
+        args.insert(2u32);
+        let mut f = AnyFn::new(|foo: &mut Foo, n: &u32| {
+            foo.0 += n;
+            assert!(foo.0 < 5, "too large");
+        });
+        f.call(&mut args).unwrap();
+
+        // The argument inserted by `insert_eq` still compares by value.
+        let mut expected = AnyMap::new();
+        expected.insert(Foo(3));
+        expected.insert(2u32);
+        assert!(!args.diff(&expected).contains::<Foo>());
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| f.call(&mut args)));
+        assert!(result.is_err());
+        assert_eq!(args.get::<Foo>(), Some(&Foo(5)));
+        assert_eq!(args.len(), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "appears more than once")]
+    fn test_duplicate() {
+        AnyFn::new(|_: &Foo, _: &mut Foo| ());
+    }
+}
@@ -0,0 +1,519 @@
+//! `AnyMap`, a map holding at most one value of each type.
+
+use std::any::{type_name, Any, TypeId};
+use std::collections::hash_map::{self, HashMap};
+use std::marker::PhantomData;
+use std::fmt;
+
+use crate::{AnyBounds, AsAny, AsMutAny, Bounded, Bounds, BoxAny, DynEq, FailurePolicy};
+use crate::MissingValue;
+
+/// Compares a value with another of the same map key, as `DynEq` does.
+type EqFn = fn(&dyn Any, &dyn Any) -> bool;
+
+struct Stored<B: Bounds> {
+    value: Box<B::Value>,
+    /// Set for values inserted by `insert_eq`.
+    eq: Option<EqFn>,
+}
+
+impl<B: Bounds> Stored<B> {
+    fn new(value: Box<B::Value>, eq: Option<EqFn>) -> Self {
+        Stored { value, eq }
+    }
+}
+
+/// A map from types to a value of that type.
+///
+/// ```
+/// use pi_any::AnyMap;
+///
+/// struct Gravity(f32);
+///
+/// let mut map = AnyMap::new();
+/// map.insert(Gravity(9.8));
+/// map.insert("title");
+/// assert_eq!(map.get::<Gravity>().unwrap().0, 9.8);
+/// map.get_mut::<Gravity>().unwrap().0 = 1.6;
+/// assert_eq!(map.remove::<Gravity>().unwrap().0, 1.6);
+/// assert!(!map.contains::<Gravity>());
+/// assert_eq!(map.get::<&str>(), Some(&"title"));
+/// ```
+///
+/// The values may be of any type, and so the map is neither `Send` nor `Sync`. A map made
+/// by `with_bounds` only takes values meeting its `Bounds`, and is `Send` or `Sync` if they
+/// are.
+pub struct AnyMap<B: Bounds = AnyBounds> {
+    values: HashMap<TypeId, Stored<B>>,
+    policy: Option<FailurePolicy>,
+}
+
+/// What `AnyMap::merge` does with a type both maps hold a value of.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergePolicy {
+    /// Keeps the value of the map merged into.
+    KeepExisting,
+    /// Replaces it with the value of the other map.
+    Overwrite,
+}
+
+/// The types whose values differ between two maps, as found by `AnyMap::diff`, each sorted
+/// by `TypeId`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AnyMapDiff {
+    /// Types only the other map holds a value of.
+    pub inserted: Vec<TypeId>,
+    /// Types only this map holds a value of.
+    pub removed: Vec<TypeId>,
+    /// Types both maps hold a value of, and whose values aren't known to be equal.
+    pub modified: Vec<TypeId>,
+}
+
+impl AnyMapDiff {
+    /// Returns true if the maps hold equal values of the same types.
+    pub fn is_empty(&self) -> bool {
+        self.inserted.is_empty() && self.removed.is_empty() && self.modified.is_empty()
+    }
+
+    /// Returns true if the value of type `T` was inserted, removed or modified.
+    pub fn contains<T: Any>(&self) -> bool {
+        let id = TypeId::of::<T>();
+        [&self.inserted, &self.removed, &self.modified].iter().any(|ids| ids.contains(&id))
+    }
+}
+
+impl AnyMap {
+    pub fn new() -> Self {
+        AnyMap::with_bounds(AnyBounds)
+    }
+}
+
+impl<B: Bounds> AnyMap<B> {
+    /// Creates a map taking the values meeting `bounds`.
+    ///
+    /// ```
+    /// use pi_any::{AnyMap, SendBounds};
+    ///
+    /// let mut map = AnyMap::with_bounds(SendBounds);
+    /// map.insert(String::from("sent"));
+    /// let map = std::thread::spawn(move || map).join().unwrap();
+    /// assert_eq!(map.get::<String>().unwrap(), "sent");
+    /// ```
+    pub fn with_bounds(_bounds: B) -> Self {
+        AnyMap { values: HashMap::new(), policy: None }
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Returns true if the map holds a value of type `T`.
+    pub fn contains<T: Any>(&self) -> bool {
+        self.values.contains_key(&TypeId::of::<T>())
+    }
+
+    pub(crate) fn contains_id(&self, type_id: TypeId) -> bool {
+        self.values.contains_key(&type_id)
+    }
+
+    /// Inserts `value`, returning the previous value of type `T` if there was one.
+    pub fn insert<T: Bounded<B>>(&mut self, value: T) -> Option<T> {
+        self.values.insert(TypeId::of::<T>(), Stored::new(value.__into_value(), None)).map(unbox)
+    }
+
+    /// Inserts `value` like `insert`, and lets `diff` compare it with the value of type `T`
+    /// in another map.
+    pub fn insert_eq<T: Bounded<B> + PartialEq>(&mut self, value: T) -> Option<T> {
+        let eq: EqFn = |a, b| a.downcast_ref::<T>().unwrap().dyn_eq(b);
+        let stored = Stored::new(value.__into_value(), Some(eq));
+        self.values.insert(TypeId::of::<T>(), stored).map(unbox)
+    }
+
+    pub fn get<T: Any>(&self) -> Option<&T> {
+        self.values.get(&TypeId::of::<T>()).and_then(|e| (*e.value).as_any().downcast_ref())
+    }
+
+    /// Returns the value of type `T`, or a `MissingValue` handled by the failure policy if
+    /// there is none.
+    #[track_caller]
+    pub fn try_get<T: Any>(&self) -> Result<&T, MissingValue> {
+        match self.get() {
+            Some(value) => Ok(value),
+            None => Err(self.missing::<T>("AnyMap::try_get")),
+        }
+    }
+
+    /// Returns the value of type `T` mutably, or a `MissingValue` handled by the failure
+    /// policy if there is none.
+    #[track_caller]
+    pub fn try_get_mut<T: Any>(&mut self) -> Result<&mut T, MissingValue> {
+        if !self.contains::<T>() {
+            return Err(self.missing::<T>("AnyMap::try_get_mut"));
+        }
+        Ok(self.get_mut().unwrap())
+    }
+
+    #[track_caller]
+    fn missing<T: Any>(&self, msg: &str) -> MissingValue {
+        crate::__fail(self.policy, msg, MissingValue { type_name: type_name::<T>() })
+    }
+
+    /// Sets the failure policy of `try_get` and `try_get_mut`, or lets them follow that of the
+    /// crate if it is `None`.
+    pub fn set_failure_policy(&mut self, policy: Option<FailurePolicy>) {
+        self.policy = policy;
+    }
+
+    pub fn get_mut<T: Any>(&mut self) -> Option<&mut T> {
+        let entry = self.values.get_mut(&TypeId::of::<T>())?;
+        (*entry.value).as_any_mut().downcast_mut()
+    }
+
+    /// Returns the value of type `T`, inserting the result of `f` first if there is none.
+    pub fn get_or_insert_with<T: Bounded<B>>(&mut self, f: impl FnOnce() -> T) -> &mut T {
+        self.entry::<T>().or_insert_with(f)
+    }
+
+    /// Returns the entry of type `T`, to look up, insert or modify its value in one step.
+    ///
+    /// ```
+    /// use pi_any::AnyMap;
+    ///
+    /// #[derive(Default)]
+    /// struct Frames(u32);
+    ///
+    /// let mut map = AnyMap::new();
+    /// for _ in 0..3 {
+    ///     map.entry::<Frames>().and_modify(|f| f.0 += 1).or_default();
+    /// }
+    /// assert_eq!(map.get::<Frames>().unwrap().0, 2);
+    /// ```
+    pub fn entry<T: Bounded<B>>(&mut self) -> AnyMapEntry<'_, T, B> {
+        AnyMapEntry { inner: self.values.entry(TypeId::of::<T>()), marker: PhantomData }
+    }
+
+    /// Removes and returns the value of type `T`.
+    pub fn remove<T: Any>(&mut self) -> Option<T> {
+        self.values.remove(&TypeId::of::<T>()).map(unbox)
+    }
+
+    pub fn clear(&mut self) {
+        self.values.clear()
+    }
+
+    /// Moves the value of type `T` from `from` to `to`, replacing the value `to` held, if
+    /// any. Returns false if `from` holds no value of type `T`.
+    ///
+    /// The box is moved as it is, so the value stays at the same address, and one inserted
+    /// by `insert_eq` can still be compared by `diff`.
+    ///
+    /// ```
+    /// use pi_any::AnyMap;
+    ///
+    /// struct Mesh(Vec<u8>);
+    ///
+    /// let (mut load, mut render) = (AnyMap::new(), AnyMap::new());
+    /// load.insert(Mesh(vec![1, 2, 3]));
+    /// let addr = load.get::<Mesh>().unwrap() as *const Mesh;
+    /// assert!(AnyMap::move_entry::<Mesh>(&mut load, &mut render));
+    /// assert!(!load.contains::<Mesh>());
+    /// assert_eq!(render.get::<Mesh>().unwrap() as *const Mesh, addr);
+    /// ```
+    pub fn move_entry<T: Any>(from: &mut Self, to: &mut Self) -> bool {
+        match from.values.remove(&TypeId::of::<T>()) {
+            Some(stored) => {
+                to.values.insert(TypeId::of::<T>(), stored);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Moves the values of `other` into the map, deciding by `policy` which value to keep of
+    /// a type both maps hold, e.g. to layer user settings over the defaults.
+    ///
+    /// ```
+    /// use pi_any::{AnyMap, MergePolicy};
+    ///
+    /// #[derive(PartialEq)]
+    /// struct Volume(u8);
+    /// #[derive(PartialEq)]
+    /// struct Theme(&'static str);
+    ///
+    /// let mut config = AnyMap::new();
+    /// config.insert_eq(Volume(50));
+    /// config.insert_eq(Theme("light"));
+    /// let defaults = AnyMap::new();
+    /// let mut user = AnyMap::new();
+    /// user.insert_eq(Theme("dark"));
+    ///
+    /// let diff = config.diff(&defaults);
+    /// assert!(diff.removed.len() == 2 && diff.inserted.is_empty());
+    /// let diff = config.diff(&user);
+    /// assert!(diff.contains::<Theme>() && diff.modified.len() == 1);
+    ///
+    /// config.merge(user, MergePolicy::Overwrite);
+    /// assert_eq!(config.get::<Theme>().unwrap().0, "dark");
+    /// assert_eq!(config.get::<Volume>().unwrap().0, 50);
+    /// ```
+    pub fn merge(&mut self, other: AnyMap<B>, policy: MergePolicy) {
+        for (id, entry) in other.values {
+            match policy {
+                MergePolicy::KeepExisting => {
+                    self.values.entry(id).or_insert(entry);
+                }
+                MergePolicy::Overwrite => {
+                    self.values.insert(id, entry);
+                }
+            }
+        }
+    }
+
+    /// Returns the changes from this map to `other`.
+    ///
+    /// Values of a type both maps hold are compared with `DynEq` if either was inserted by
+    /// `insert_eq`, and are taken as modified otherwise.
+    pub fn diff(&self, other: &AnyMap<B>) -> AnyMapDiff {
+        let mut diff = AnyMapDiff::default();
+        for (id, entry) in &self.values {
+            match other.values.get(id) {
+                None => diff.removed.push(*id),
+                Some(theirs) => {
+                    let (a, b) = ((*entry.value).as_any(), (*theirs.value).as_any());
+                    if !entry.eq.or(theirs.eq).is_some_and(|eq| eq(a, b)) {
+                        diff.modified.push(*id);
+                    }
+                }
+            }
+        }
+        diff.inserted = other.values.keys().filter(|id| !self.contains_id(**id)).copied().collect();
+        diff.inserted.sort_unstable();
+        diff.removed.sort_unstable();
+        diff.modified.sort_unstable();
+        diff
+    }
+}
+
+impl AnyMap {
+    pub(crate) fn values(&self) -> impl Iterator<Item = &dyn BoxAny> {
+        self.values.values().map(|e| &*e.value)
+    }
+
+    /// Inserts a boxed value under the `TypeId` of its concrete type.
+    pub(crate) fn insert_box(&mut self, value: Box<dyn BoxAny>) {
+        self.values.insert((*value).type_id_of(), Stored::new(value, None));
+    }
+
+    /// Takes the entries of the types in `type_ids` out of the map, which must hold a value
+    /// of each, until the returned guard is dropped.
+    pub(crate) fn take_entries<const N: usize>(&mut self, type_ids: [TypeId; N]) -> Taken<'_, N> {
+        let entries = type_ids.map(|id| Some((id, self.values.remove(&id).unwrap())));
+        Taken { map: self, entries }
+    }
+}
+
+/// Entries taken out of an `AnyMap` as they are, boxes and `insert_eq` comparisons
+/// included, and put back when dropped, also on unwind.
+pub(crate) struct Taken<'a, const N: usize> {
+    map: &'a mut AnyMap,
+    entries: [Option<(TypeId, Stored<AnyBounds>)>; N],
+}
+
+impl<const N: usize> Taken<'_, N> {
+    /// Returns the values, in the order of the `TypeId`s they were taken by.
+    pub(crate) fn values_mut(&mut self) -> impl Iterator<Item = &mut dyn BoxAny> {
+        self.entries.iter_mut().map(|entry| &mut *entry.as_mut().unwrap().1.value)
+    }
+}
+
+impl<const N: usize> Drop for Taken<'_, N> {
+    fn drop(&mut self) {
+        self.map.values.extend(self.entries.iter_mut().filter_map(Option::take));
+    }
+}
+
+/// The place of the value of type `T` in an `AnyMap`, which may be empty, as returned by
+/// `AnyMap::entry`.
+pub struct AnyMapEntry<'a, T, B: Bounds = AnyBounds> {
+    inner: hash_map::Entry<'a, TypeId, Stored<B>>,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<'a, T: Bounded<B>, B: Bounds> AnyMapEntry<'a, T, B> {
+    /// Returns true if the map holds a value of type `T`.
+    pub fn is_occupied(&self) -> bool {
+        matches!(self.inner, hash_map::Entry::Occupied(_))
+    }
+
+    /// Returns the value, inserting `value` first if there is none.
+    pub fn or_insert(self, value: T) -> &'a mut T {
+        self.or_insert_with(|| value)
+    }
+
+    /// Returns the value, inserting the result of `f` first if there is none.
+    pub fn or_insert_with(self, f: impl FnOnce() -> T) -> &'a mut T {
+        let stored = self.inner.or_insert_with(|| Stored::new(f().__into_value(), None));
+        (*stored.value).as_any_mut().downcast_mut().unwrap()
+    }
+
+    /// Returns the value, inserting `T::default()` first if there is none.
+    pub fn or_default(self) -> &'a mut T
+    where
+        T: Default,
+    {
+        self.or_insert_with(T::default)
+    }
+
+    /// Calls `f` with the value if there is one.
+    pub fn and_modify(mut self, f: impl FnOnce(&mut T)) -> Self {
+        if let hash_map::Entry::Occupied(entry) = &mut self.inner {
+            f((*entry.get_mut().value).as_any_mut().downcast_mut().unwrap());
+        }
+        self
+    }
+}
+
+impl<T: Bounded<B>, B: Bounds> fmt::Debug for AnyMapEntry<'_, T, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AnyMapEntry")
+            .field("type", &type_name::<T>())
+            .field("occupied", &self.is_occupied())
+            .finish()
+    }
+}
+
+/// Unboxes a value stored under the `TypeId` of `T`.
+fn unbox<T: Any, B: Bounds>(stored: Stored<B>) -> T {
+    *stored.value.into_any().downcast::<T>().unwrap()
+}
+
+impl<B: Bounds + Default> Default for AnyMap<B> {
+    fn default() -> Self {
+        AnyMap::with_bounds(B::default())
+    }
+}
+
+impl<B: Bounds> fmt::Debug for AnyMap<B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.values.values().map(|e| (*e.value).type_name())).finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::any::TypeId;
+    use std::rc::Rc;
+
+    use super::{AnyMap, MergePolicy};
+    use crate::MissingValue;
+
+    #[derive(Debug, PartialEq)]
+    struct Foo(u32);
+    #[derive(Debug, PartialEq)]
+    struct Bar;
+
+    #[test]
+    fn test() {
+        let mut map = AnyMap::new();
+        assert!(map.is_empty());
+        assert_eq!(map.insert(Foo(1)), None);
+        assert_eq!(map.insert(Foo(2)), Some(Foo(1)));
+        assert_eq!(map.len(), 1);
+        assert!(map.contains::<Foo>());
+        assert!(!map.contains::<Bar>());
+        assert_eq!(map.get::<Bar>(), None);
+        let missing = MissingValue { type_name: std::any::type_name::<Bar>() };
+        assert_eq!(map.try_get::<Bar>(), Err(missing));
+        map.try_get_mut::<Foo>().unwrap().0 += 0;
+
+        map.get_mut::<Foo>().unwrap().0 += 1;
+        assert_eq!(map.get::<Foo>(), Some(&Foo(3)));
+        assert_eq!(map.get_or_insert_with(|| Foo(0)), &Foo(3));
+        assert_eq!(map.get_or_insert_with(|| Bar), &Bar);
+        assert_eq!(map.len(), 2);
+
+        assert_eq!(map.remove::<Foo>(), Some(Foo(3)));
+        assert_eq!(map.remove::<Foo>(), None);
+        assert_eq!(format!("{:?}", map), format!("{{{:?}}}", std::any::type_name::<Bar>()));
+    }
+
+    #[test]
+    fn test_drop() {
+        let value = Rc::new(());
+        let mut map = AnyMap::new();
+        map.insert(value.clone());
+        assert_eq!(Rc::strong_count(&value), 2);
+        map.clear();
+        assert_eq!(Rc::strong_count(&value), 1);
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut base = AnyMap::new();
+        base.insert_eq(Foo(1));
+        base.insert(Bar);
+        base.insert_eq(1u8);
+        let mut layer = AnyMap::new();
+        layer.insert_eq(Foo(1));
+        layer.insert(Bar);
+        layer.insert_eq(2u8);
+        layer.insert("new");
+
+        let diff = base.diff(&layer);
+        assert_eq!(diff.inserted, [TypeId::of::<&str>()]);
+        assert!(diff.removed.is_empty());
+        // `Bar` can't be compared, as it was inserted by `insert`.
+        let mut modified = vec![TypeId::of::<Bar>(), TypeId::of::<u8>()];
+        modified.sort();
+        assert_eq!(diff.modified, modified);
+        assert!(!diff.contains::<Foo>() && diff.contains::<&str>());
+        assert_eq!(layer.diff(&base).removed, [TypeId::of::<&str>()]);
+        assert!(base.diff(&base).modified == [TypeId::of::<Bar>()]);
+
+        let mut kept = AnyMap::new();
+        kept.insert_eq(3u8);
+        kept.merge(base, MergePolicy::KeepExisting);
+        assert_eq!((kept.get::<u8>(), kept.len()), (Some(&3), 3));
+        kept.merge(layer, MergePolicy::Overwrite);
+        assert_eq!((kept.get::<u8>(), kept.get::<&str>()), (Some(&2), Some(&"new")));
+        assert_eq!(kept.len(), 4);
+        assert!(kept.diff(&kept).removed.is_empty());
+    }
+
+    #[test]
+    fn test_entry() {
+        let mut map = AnyMap::new();
+        assert!(!map.entry::<Foo>().is_occupied());
+        map.entry::<Foo>().and_modify(|foo| foo.0 = 10).or_insert(Foo(1)).0 += 1;
+        assert_eq!(map.get::<Foo>(), Some(&Foo(2)));
+        let entry = map.entry::<Foo>().and_modify(|foo| foo.0 *= 10);
+        assert!(entry.is_occupied());
+        assert!(format!("{:?}", entry).ends_with("occupied: true }"));
+        assert_eq!(entry.or_insert_with(|| unreachable!()), &Foo(20));
+        assert_eq!(*map.entry::<u32>().or_default(), 0);
+        assert_eq!(map.len(), 2);
+    }
+
+    #[test]
+    fn test_move_entry() {
+        let (mut from, mut to) = (AnyMap::new(), AnyMap::new());
+        from.insert_eq(Foo(1));
+        from.insert(Bar);
+        to.insert_eq(Foo(2));
+        let addr = from.get::<Foo>().unwrap() as *const Foo;
+        assert!(AnyMap::move_entry::<Foo>(&mut from, &mut to));
+        assert!(!AnyMap::move_entry::<Foo>(&mut from, &mut to));
+        assert!(!AnyMap::move_entry::<u8>(&mut from, &mut to));
+        assert_eq!((from.len(), to.len()), (1, 1));
+        assert_eq!(to.get::<Foo>().unwrap() as *const Foo, addr);
+
+        // The comparison of `insert_eq` moves with the value.
+        let mut other = AnyMap::new();
+        other.insert(Foo(1));
+        assert!(to.diff(&other).is_empty());
+    }
+}
@@ -0,0 +1,232 @@
+//! `AnyArena`, a slot map of values of any type addressed by generational handles.
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::any::Any;
+use core::fmt;
+
+use crate::BoxAny;
+
+/// A key into an `AnyArena`.
+///
+/// A handle stays valid until its value is removed; after that it no longer finds anything,
+/// even once the slot holds a new value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Handle {
+    index: u32,
+    generation: u32,
+}
+
+struct Slot {
+    // Even while free, odd while occupied.
+    generation: u32,
+    value: Option<Box<dyn BoxAny>>,
+}
+
+/// Stores values of any type and hands out `Handle`s to them, so subsystems can refer to
+/// values by a `Copy` key rather than sharing them through `Rc`s.
+///
+/// ```
+/// use pi_any::AnyArena;
+///
+/// struct Mesh(&'static str);
+/// struct Light(f32);
+///
+/// let mut arena = AnyArena::new();
+/// let mesh = arena.insert(Mesh("cube"));
+/// let light = arena.insert(Light(0.5));
+///
+/// assert_eq!(arena.get::<Mesh>(mesh).unwrap().0, "cube");
+/// assert!(arena.get::<Light>(mesh).is_none());
+/// arena.get_mut::<Light>(light).unwrap().0 = 1.0;
+///
+/// assert_eq!(arena.remove::<Mesh>(mesh).unwrap().0, "cube");
+/// assert!(!arena.contains(mesh));
+/// ```
+#[derive(Default)]
+pub struct AnyArena {
+    slots: Vec<Slot>,
+    free: Vec<u32>,
+    len: usize,
+}
+
+impl AnyArena {
+    pub fn new() -> Self {
+        AnyArena { slots: Vec::new(), free: Vec::new(), len: 0 }
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        AnyArena { slots: Vec::with_capacity(capacity), free: Vec::new(), len: 0 }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Stores `value`, returning its handle.
+    pub fn insert<T: Any>(&mut self, value: T) -> Handle {
+        let value: Box<dyn BoxAny> = Box::new(value);
+        self.len += 1;
+        match self.free.pop() {
+            Some(index) => {
+                let slot = &mut self.slots[index as usize];
+                slot.generation = slot.generation.wrapping_add(1);
+                slot.value = Some(value);
+                Handle { index, generation: slot.generation }
+            }
+            None => {
+                let index = u32::try_from(self.slots.len()).expect("AnyArena is full");
+                self.slots.push(Slot { generation: 1, value: Some(value) });
+                Handle { index, generation: 1 }
+            }
+        }
+    }
+
+    /// Returns true if `handle` refers to a value.
+    pub fn contains(&self, handle: Handle) -> bool {
+        self.value(handle).is_some()
+    }
+
+    /// Returns true if `handle` refers to a value of type `T`.
+    pub fn is<T: Any>(&self, handle: Handle) -> bool {
+        self.value(handle).is_some_and(|value| value.as_any().is::<T>())
+    }
+
+    /// Returns the type name of the value `handle` refers to.
+    pub fn type_name(&self, handle: Handle) -> Option<&'static str> {
+        self.value(handle).map(|value| value.type_name())
+    }
+
+    /// Returns the value `handle` refers to, if it is a `T`.
+    pub fn get<T: Any>(&self, handle: Handle) -> Option<&T> {
+        self.value(handle)?.as_any().downcast_ref()
+    }
+
+    /// Returns the value `handle` refers to, if it is a `T`.
+    pub fn get_mut<T: Any>(&mut self, handle: Handle) -> Option<&mut T> {
+        let slot = self.slots.get_mut(handle.index as usize)?;
+        match &mut slot.value {
+            Some(value) if slot.generation == handle.generation => {
+                (**value).as_any_mut().downcast_mut()
+            }
+            _ => None,
+        }
+    }
+
+    /// Removes and returns the value `handle` refers to, if it is a `T`. A value of another
+    /// type is left in place.
+    pub fn remove<T: Any>(&mut self, handle: Handle) -> Option<T> {
+        if !self.is::<T>(handle) {
+            return None;
+        }
+        let value = self.remove_any(handle)?;
+        Some(*value.into_any().downcast().unwrap())
+    }
+
+    /// Removes and returns the value `handle` refers to, whatever its type.
+    pub fn remove_any(&mut self, handle: Handle) -> Option<Box<dyn BoxAny>> {
+        self.value(handle)?;
+        let slot = &mut self.slots[handle.index as usize];
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free.push(handle.index);
+        self.len -= 1;
+        slot.value.take()
+    }
+
+    /// Removes all values, invalidating every handle.
+    pub fn clear(&mut self) {
+        for (index, slot) in self.slots.iter_mut().enumerate() {
+            if slot.value.take().is_some() {
+                slot.generation = slot.generation.wrapping_add(1);
+                self.free.push(index as u32);
+            }
+        }
+        self.len = 0;
+    }
+
+    /// Returns the handles of all values, with their type names, in no particular order.
+    pub fn handles(&self) -> impl Iterator<Item = (Handle, &'static str)> + '_ {
+        self.slots.iter().enumerate().filter_map(|(index, slot)| {
+            let value = slot.value.as_ref()?;
+            Some((Handle { index: index as u32, generation: slot.generation }, value.type_name()))
+        })
+    }
+
+    fn value(&self, handle: Handle) -> Option<&dyn BoxAny> {
+        let slot = self.slots.get(handle.index as usize)?;
+        match &slot.value {
+            Some(value) if slot.generation == handle.generation => Some(&**value),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Debug for AnyArena {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.handles()).finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+
+    use super::AnyArena;
+
+    #[derive(Debug, PartialEq)]
+    struct Foo(u32);
+    #[derive(Debug, PartialEq)]
+    struct Bar;
+
+    #[test]
+    fn test() {
+        let mut arena = AnyArena::new();
+        let foo = arena.insert(Foo(1));
+        let bar = arena.insert(Bar);
+        assert_eq!(arena.len(), 2);
+        assert!(arena.is::<Foo>(foo) && !arena.is::<Foo>(bar));
+        assert!(arena.type_name(bar).unwrap().ends_with("Bar"));
+
+        arena.get_mut::<Foo>(foo).unwrap().0 += 1;
+        assert_eq!(arena.get::<Foo>(foo), Some(&Foo(2)));
+        assert_eq!(arena.get::<Foo>(bar), None);
+
+        // A value of the wrong type stays put.
+        assert_eq!(arena.remove::<Bar>(foo), None);
+        assert_eq!(arena.remove::<Foo>(foo), Some(Foo(2)));
+        assert_eq!(arena.remove::<Foo>(foo), None);
+        assert_eq!(arena.len(), 1);
+        assert_eq!(arena.handles().map(|(h, _)| h).collect::<Vec<_>>(), [bar]);
+    }
+
+    #[test]
+    fn test_stale() {
+        let mut arena = AnyArena::with_capacity(1);
+        let old = arena.insert(Foo(1));
+        arena.remove_any(old).unwrap();
+        let new = arena.insert(Foo(2));
+        assert_ne!(old, new);
+        assert!(!arena.contains(old));
+        assert_eq!(arena.get::<Foo>(old), None);
+        assert_eq!(arena.get_mut::<Foo>(old), None);
+        assert!(arena.remove_any(old).is_none());
+        assert_eq!(arena.get::<Foo>(new), Some(&Foo(2)));
+    }
+
+    #[test]
+    fn test_clear() {
+        let value = Rc::new(());
+        let mut arena = AnyArena::new();
+        let handle = arena.insert(value.clone());
+        arena.clear();
+        assert!(arena.is_empty());
+        assert_eq!(Rc::strong_count(&value), 1);
+        assert!(!arena.contains(handle));
+        let reused = arena.insert(Bar);
+        assert!(!arena.contains(handle) && arena.contains(reused));
+    }
+}
@@ -0,0 +1,176 @@
+//! `AtomicAnyArc`, a trait object that can be replaced atomically while it is being read.
+
+use std::any::Any;
+use std::fmt;
+use std::sync::Arc;
+
+use arc_swap::ArcSwap;
+
+use crate::{AsAny, DowncastGuard};
+
+/// A trait object that threads read and downcast without locking while another replaces
+/// it, e.g. a config or an asset reloaded while the game runs.
+///
+/// `ArcSwap` swaps thin pointers only, so the trait object is boxed: the value is an
+/// `Arc<Box<D>>`, with `D` a `dyn Trait + Send + Sync`. Readers keep the value they loaded
+/// until they drop it, whatever replaces it in the meantime.
+///
+/// ```
+/// # #[macro_use]
+/// # extern crate pi_any;
+/// use pi_any::{AtomicAnyArc, BoxAny};
+///
+/// trait Config: BoxAny + Send + Sync {}
+/// impl_downcast!(Config + Send + Sync);
+///
+/// struct Graphics { vsync: bool }
+/// impl Config for Graphics {}
+///
+/// # fn main() {
+/// let config = AtomicAnyArc::<dyn Config + Send + Sync>::new(Box::new(Graphics { vsync: false }));
+/// let old = config.load_downcast_ref::<Graphics>().unwrap();
+/// config.store(Box::new(Graphics { vsync: true }));
+/// assert!(!old.vsync && config.load_downcast_ref::<Graphics>().unwrap().vsync);
+/// # }
+/// ```
+pub struct AtomicAnyArc<D: ?Sized> {
+    value: ArcSwap<Box<D>>,
+}
+
+impl<D: AsAny + Send + Sync + ?Sized> AtomicAnyArc<D> {
+    pub fn new(value: Box<D>) -> Self {
+        AtomicAnyArc { value: ArcSwap::from_pointee(value) }
+    }
+
+    /// Returns the current value.
+    pub fn load(&self) -> Arc<Box<D>> {
+        self.value.load_full()
+    }
+
+    /// Returns true if the current value is of type `T`.
+    pub fn is<T: Any>(&self) -> bool {
+        let loaded = self.value.load();
+        let value: &D = &loaded;
+        AsAny::as_any(value).is::<T>()
+    }
+
+    /// Returns the current value as a `T`, or `None` if it isn't of type `T`. The value is
+    /// kept until the guard is dropped, even if it is replaced.
+    pub fn load_downcast_ref<T: Any>(&self) -> Option<DowncastGuard<Arc<Box<D>>, T>> {
+        DowncastGuard::new(self.load()).ok()
+    }
+
+    /// Replaces the value.
+    pub fn store(&self, value: Box<D>) {
+        self.value.store(Arc::new(value))
+    }
+
+    /// Replaces the value, returning the previous one.
+    pub fn swap(&self, value: Box<D>) -> Arc<Box<D>> {
+        self.value.swap(Arc::new(value))
+    }
+
+    /// Replaces the value with `new` if it is still `current`, as loaded by
+    /// `load_downcast_ref`, returning the previous value. Returns `new` if the value was
+    /// replaced in the meantime, so a reload based on an outdated value can be retried.
+    pub fn compare_and_swap_typed<T: Any>(
+        &self,
+        current: &DowncastGuard<Arc<Box<D>>, T>,
+        new: Box<D>,
+    ) -> Result<Arc<Box<D>>, Box<D>> {
+        let current = current.inner();
+        let new = Arc::new(new);
+        let previous = self.value.compare_and_swap(current, new.clone());
+        if Arc::ptr_eq(&previous, current) {
+            return Ok(current.clone());
+        }
+        drop(previous);
+        // `new` wasn't stored, so this is its only reference.
+        Err(Arc::into_inner(new).unwrap())
+    }
+}
+
+impl<D: AsAny + ?Sized> fmt::Debug for AtomicAnyArc<D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let loaded = self.value.load();
+        let value: &D = &loaded;
+        f.debug_struct("AtomicAnyArc").field("type", &AsAny::type_name(value)).finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+    use std::thread;
+
+    use super::AtomicAnyArc;
+    use crate::BoxAny;
+
+    trait Asset: BoxAny + Send + Sync {}
+    crate::impl_downcast!(Asset + Send + Sync);
+
+    #[derive(Debug, PartialEq)]
+    struct Mesh(u32);
+    impl Asset for Mesh {}
+    #[derive(Debug, PartialEq)]
+    struct Missing;
+    impl Asset for Missing {}
+
+    type Slot = AtomicAnyArc<dyn Asset + Send + Sync>;
+
+    #[test]
+    fn test() {
+        let slot = Slot::new(Box::new(Missing));
+        assert!(slot.is::<Missing>() && slot.load_downcast_ref::<Mesh>().is_none());
+        assert!(format!("{:?}", slot).contains("Missing"));
+        let previous = slot.swap(Box::new(Mesh(1)));
+        assert!(previous.is::<Missing>());
+        assert_eq!(*slot.load_downcast_ref::<Mesh>().unwrap(), Mesh(1));
+        assert_eq!(slot.load().downcast_ref::<Mesh>(), Some(&Mesh(1)));
+    }
+
+    #[test]
+    fn test_compare_and_swap() {
+        let slot = Slot::new(Box::new(Mesh(1)));
+        let old = slot.load_downcast_ref::<Mesh>().unwrap();
+        let swapped = slot.compare_and_swap_typed(&old, Box::new(Mesh(old.0 + 1))).ok().unwrap();
+        assert!(swapped.is::<Mesh>());
+        // `old` was replaced, as a different value of the same type.
+        let new = slot.compare_and_swap_typed(&old, Box::new(Mesh(10))).err().unwrap();
+        assert!(new.is::<Mesh>());
+        assert_eq!(*slot.load_downcast_ref::<Mesh>().unwrap(), Mesh(2));
+
+        // Values of a zero-sized type are told apart too.
+        let slot = Slot::new(Box::new(Missing));
+        let old = slot.load_downcast_ref::<Missing>().unwrap();
+        slot.store(Box::new(Missing));
+        assert!(slot.compare_and_swap_typed(&old, Box::new(Mesh(1))).is_err());
+    }
+
+    #[test]
+    fn test_threads() {
+        let slot = Arc::new(Slot::new(Box::new(Mesh(0))));
+        let writers: Vec<_> = (0..4)
+            .map(|_| {
+                let slot = slot.clone();
+                thread::spawn(move || {
+                    for _ in 0..100 {
+                        let mut new = Box::new(Mesh(0)) as Box<dyn Asset + Send + Sync>;
+                        loop {
+                            let mesh = slot.load_downcast_ref::<Mesh>().unwrap();
+                            *new.downcast_mut::<Mesh>().unwrap() = Mesh(mesh.0 + 1);
+                            match slot.compare_and_swap_typed(&mesh, new) {
+                                Ok(_) => break,
+                                Err(value) => new = value,
+                            }
+                        }
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+        assert_eq!(*slot.load_downcast_ref::<Mesh>().unwrap(), Mesh(400));
+    }
+}
@@ -0,0 +1,200 @@
+//! `Bounds`, the auto traits a container requires of what it holds.
+
+use std::any::Any;
+
+use crate::BoxAny;
+
+mod private {
+    /// Keeps `Bounds` from being implemented outside this crate.
+    pub trait Sealed {}
+}
+
+/// The auto traits `AnyMap`, `EventBus` and the factories of `TypeRegistry` require of the
+/// values, messages and functions they hold, and so the auto traits they implement.
+///
+/// It is implemented by `AnyBounds`, `SendBounds` and `SendSyncBounds` only: the defaults
+/// keep the bounds each container had, and single-threaded tools can drop them, e.g. to hold
+/// an `Rc`, while servers keep them to share the containers between threads.
+///
+/// ```
+/// use std::rc::Rc;
+/// use pi_any::{AnyBounds, AnyMap, EventBus, SendSyncBounds};
+///
+/// let mut local = AnyMap::new();
+/// local.insert(Rc::new("not Send"));
+///
+/// let mut bus = EventBus::with_bounds(AnyBounds);
+/// let seen = Rc::new(std::cell::Cell::new(0));
+/// let count = seen.clone();
+/// bus.subscribe(move |n: &u32| count.set(count.get() + n));
+/// bus.publish(Box::new(3u32));
+/// assert_eq!(seen.get(), 3);
+///
+/// let mut shared = AnyMap::with_bounds(SendSyncBounds);
+/// shared.insert(5u8);
+/// std::thread::scope(|s| {
+///     s.spawn(|| assert_eq!(shared.get::<u8>(), Some(&5)));
+/// });
+/// ```
+pub trait Bounds: private::Sealed + Sized + 'static {
+    /// `dyn BoxAny` with the auto traits, as which an `AnyMap` holds its values.
+    type Value: ?Sized + BoxAny;
+    /// `dyn Any` with the auto traits, the type of the messages of an `EventBus`.
+    type Message: ?Sized;
+    /// A handler or interceptor of an `EventBus`.
+    type Handler: ?Sized + FnMut(&dyn Any) -> bool;
+    /// A filter of the handlers of an `EventBus`.
+    type Filter: ?Sized + Fn(&dyn Any) -> bool;
+    /// A factory of a `TypeRegistry`.
+    type Factory<B: ?Sized>: ?Sized + Fn() -> Box<B>;
+
+    #[doc(hidden)]
+    fn __message(message: &Self::Message) -> &dyn Any;
+}
+
+/// No auto traits, the bounds of `AnyMap` by default.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct AnyBounds;
+
+/// `Send`, the bounds of `EventBus` by default.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct SendBounds;
+
+/// `Send + Sync`, the bounds of the factories of `TypeRegistry` by default.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct SendSyncBounds;
+
+/// Implemented for the types meeting the bounds `B`, to box them as the trait objects of
+/// `B`.
+pub trait Bounded<B: Bounds>: Any {
+    #[doc(hidden)]
+    fn __into_value(self) -> Box<B::Value>
+    where
+        Self: Sized;
+
+    #[doc(hidden)]
+    fn __into_handler<T: Any>(self) -> Box<B::Handler>
+    where
+        Self: FnMut(&T) + Sized;
+
+    #[doc(hidden)]
+    fn __into_interceptor(self) -> Box<B::Handler>
+    where
+        Self: FnMut(&dyn Any) -> bool + Sized;
+
+    #[doc(hidden)]
+    fn __into_filter<T: Any>(self) -> Box<B::Filter>
+    where
+        Self: Fn(&T) -> bool + Sized;
+
+    #[doc(hidden)]
+    fn __into_factory<O: ?Sized>(self) -> Box<B::Factory<O>>
+    where
+        Self: Fn() -> Box<O> + Sized;
+}
+
+/// Implements `Bounds` for `$bounds`, and `Bounded<$bounds>` for the types meeting them.
+macro_rules! impl_bounds {
+    ($bounds:ident $(, $auto:ident)*) => {
+        impl private::Sealed for $bounds {}
+
+        impl Bounds for $bounds {
+            type Value = dyn BoxAny $(+ $auto)*;
+            type Message = dyn Any $(+ $auto)*;
+            type Handler = dyn FnMut(&dyn Any) -> bool $(+ $auto)*;
+            type Filter = dyn Fn(&dyn Any) -> bool $(+ $auto)*;
+            type Factory<B: ?Sized> = dyn Fn() -> Box<B> $(+ $auto)*;
+
+            fn __message(message: &Self::Message) -> &dyn Any {
+                message
+            }
+        }
+
+        impl<T: Any $(+ $auto)*> Bounded<$bounds> for T {
+            fn __into_value(self) -> Box<dyn BoxAny $(+ $auto)*> {
+                Box::new(self)
+            }
+
+            fn __into_handler<M: Any>(mut self) -> Box<dyn FnMut(&dyn Any) -> bool $(+ $auto)*>
+            where
+                Self: FnMut(&M),
+            {
+                Box::new(move |msg| {
+                    self(msg.downcast_ref().unwrap());
+                    true
+                })
+            }
+
+            fn __into_interceptor(self) -> Box<dyn FnMut(&dyn Any) -> bool $(+ $auto)*>
+            where
+                Self: FnMut(&dyn Any) -> bool,
+            {
+                Box::new(self)
+            }
+
+            fn __into_filter<M: Any>(self) -> Box<dyn Fn(&dyn Any) -> bool $(+ $auto)*>
+            where
+                Self: Fn(&M) -> bool,
+            {
+                Box::new(move |msg| self(msg.downcast_ref().unwrap()))
+            }
+
+            fn __into_factory<O: ?Sized>(self) -> Box<dyn Fn() -> Box<O> $(+ $auto)*>
+            where
+                Self: Fn() -> Box<O>,
+            {
+                Box::new(self)
+            }
+        }
+    };
+}
+
+impl_bounds!(AnyBounds);
+impl_bounds!(SendBounds, Send);
+impl_bounds!(SendSyncBounds, Send, Sync);
+
+#[cfg(test)]
+mod test {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::{AnyBounds, SendBounds, SendSyncBounds};
+    use crate::{AnyMap, BoxAny, EventBus, TypeRegistry};
+
+    fn assert_send<T: Send>() {}
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn test_auto_traits() {
+        assert_send::<AnyMap<SendBounds>>();
+        assert_send_sync::<AnyMap<SendSyncBounds>>();
+        assert_send::<EventBus>();
+        assert_send_sync::<EventBus<SendSyncBounds>>();
+        assert_send_sync::<TypeRegistry<dyn BoxAny>>();
+    }
+
+    #[test]
+    fn test_local() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let mut bus = EventBus::with_bounds(AnyBounds);
+        let (a, b) = (log.clone(), log.clone());
+        bus.intercept(move |_| {
+            a.borrow_mut().push(0);
+            true
+        });
+        bus.subscribe_filtered(|n: &u32| *n > 1, move |n| b.borrow_mut().push(*n));
+        bus.queue(Box::new(Rc::new(1u32)));
+        bus.queue(Box::new(2u32));
+        assert_eq!(bus.flush(), 1);
+        assert_eq!(*log.borrow(), [0, 0, 2]);
+
+        let mut map = AnyMap::new();
+        map.insert(log.clone());
+        assert_eq!(map.get::<Rc<RefCell<Vec<u32>>>>().unwrap().borrow().len(), 3);
+
+        let mut registry = TypeRegistry::<dyn BoxAny, _>::with_bounds(AnyBounds);
+        registry.register::<Rc<RefCell<Vec<u32>>>>("log", move || Box::new(log.clone()));
+        let value = registry.construct("log").unwrap();
+        assert!(value.as_any().is::<Rc<RefCell<Vec<u32>>>>());
+    }
+}
@@ -0,0 +1,187 @@
+//! `BumpArena`, moving values into a caller's `bumpalo::Bump` and dropping them all at once.
+//!
+//! `Bump` never drops what it holds, so the arena keeps the destructors of its values and
+//! runs them on the raw pointers `Bump` handed out; this module is allowed to use `unsafe`
+//! for that like `clone`.
+
+use alloc::vec::Vec;
+use core::any::Any;
+use core::cell::{Cell, RefCell};
+use core::fmt;
+use core::mem::needs_drop;
+use core::ptr;
+
+use bumpalo::Bump;
+
+/// Drops a value of the type it was made for.
+type DropFn = unsafe fn(*mut u8);
+
+/// Values of any type allocated in a `bumpalo::Bump`, which are dropped together by `reset`.
+///
+/// `alloc` takes `&self`, so many values can be allocated and borrowed at once, e.g. the
+/// events of a frame as `&dyn Event`, which downcast like any other trait object. `reset`
+/// then drops them all and resets the `Bump`, which keeps its largest chunk of memory for
+/// the next frame, so steady use allocates nothing. Unlike `AnyArena`, values can't be
+/// removed one by one.
+///
+/// ```
+/// # #[macro_use]
+/// # extern crate pi_any;
+/// # extern crate bumpalo;
+/// use bumpalo::Bump;
+/// use pi_any::{BoxAny, BumpArena};
+///
+/// trait Event: BoxAny {}
+/// impl_downcast!(Event);
+///
+/// struct Click(u32, u32);
+/// impl Event for Click {}
+/// struct Key(char);
+/// impl Event for Key {}
+///
+/// # fn main() {
+/// let mut bump = Bump::new();
+/// let mut arena = BumpArena::new(&mut bump);
+/// for frame in 0..3 {
+///     let events: Vec<&dyn Event> = vec![arena.alloc(Click(frame, 0)), arena.alloc(Key('a'))];
+///     let clicks: Vec<_> = events.iter().filter_map(|e| e.downcast_ref::<Click>()).collect();
+///     assert_eq!(clicks[0].0, frame);
+///     arena.reset();
+/// }
+/// # }
+/// ```
+pub struct BumpArena<'bump> {
+    bump: &'bump mut Bump,
+    // The values that need dropping, in the order they were allocated.
+    drops: RefCell<Vec<(*mut u8, DropFn)>>,
+    len: Cell<usize>,
+}
+
+impl<'bump> BumpArena<'bump> {
+    /// Creates an arena allocating in `bump`, which is reset along with the arena.
+    pub fn new(bump: &'bump mut Bump) -> Self {
+        BumpArena { bump, drops: RefCell::new(Vec::new()), len: Cell::new(0) }
+    }
+
+    /// Returns the number of values allocated since the last `reset`.
+    pub fn len(&self) -> usize {
+        self.len.get()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len.get() == 0
+    }
+
+    /// Returns the `Bump` the values are allocated in.
+    pub fn bump(&self) -> &Bump {
+        self.bump
+    }
+
+    /// Moves `value` into the arena, where it stays until the next `reset`.
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc<T: Any>(&self, value: T) -> &mut T {
+        let ptr: *mut T = self.bump.alloc(value);
+        if needs_drop::<T>() {
+            self.drops.borrow_mut().push((ptr.cast(), drop_value::<T>));
+        }
+        self.len.set(self.len.get() + 1);
+        // SAFETY: `Bump::alloc` gave memory no other allocation uses, and the value is only
+        // reachable through this reference until `reset` or `drop`, which take `&mut self`.
+        unsafe { &mut *ptr }
+    }
+
+    /// Drops every value in the arena, in the order they were allocated, and resets the
+    /// `Bump`.
+    pub fn reset(&mut self) {
+        self.drop_values();
+        self.bump.reset();
+    }
+
+    fn drop_values(&mut self) {
+        self.len.set(0);
+        // Taken first, so a panicking destructor leaks the rest rather than dropping twice.
+        let drops = core::mem::take(self.drops.get_mut());
+        for (ptr, drop) in drops {
+            // SAFETY: `ptr` holds a live value of the type `drop` was made for; `&mut self`
+            // guarantees no reference to it remains.
+            unsafe { drop(ptr) };
+        }
+    }
+}
+
+/// # Safety
+///
+/// `ptr` must point to a valid `T`, which is not used afterwards.
+unsafe fn drop_value<T>(ptr: *mut u8) {
+    // SAFETY: guaranteed by the caller.
+    unsafe { ptr::drop_in_place(ptr.cast::<T>()) }
+}
+
+impl Drop for BumpArena<'_> {
+    fn drop(&mut self) {
+        self.drop_values();
+    }
+}
+
+impl fmt::Debug for BumpArena<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BumpArena")
+            .field("len", &self.len())
+            .field("allocated_bytes", &self.bump.allocated_bytes())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+
+    use bumpalo::Bump;
+
+    use super::BumpArena;
+    use crate::AsAny;
+
+    #[repr(align(64))]
+    struct Aligned(u8);
+
+    #[test]
+    fn test() {
+        let mut bump = Bump::new();
+        let mut arena = BumpArena::new(&mut bump);
+        let a = arena.alloc(1u8);
+        let b: &mut dyn AsAny = arena.alloc(String::from("b"));
+        let c = arena.alloc(Aligned(3));
+        *a += 1;
+        let b = (*b).as_any().downcast_ref::<String>().unwrap();
+        assert_eq!((*a, b.as_str(), c.0), (2, "b", 3));
+        assert_eq!(c as *const Aligned as usize % 64, 0);
+
+        let big = arena.alloc([7u8; 10_000]);
+        assert_eq!(big[9_999], 7);
+        let zst = arena.alloc(());
+        assert_eq!(*zst, ());
+        assert_eq!(arena.len(), 5);
+        assert!(arena.bump().allocated_bytes() >= 10_000);
+        assert!(format!("{:?}", arena).starts_with("BumpArena { len: 5"));
+
+        arena.reset();
+        assert!(arena.is_empty());
+        assert_eq!(*arena.alloc(5u64), 5);
+    }
+
+    #[test]
+    fn test_drop() {
+        let counter = Rc::new(());
+        let mut bump = Bump::new();
+        let mut arena = BumpArena::new(&mut bump);
+        for _ in 0..1000 {
+            arena.alloc(counter.clone());
+        }
+        assert_eq!(Rc::strong_count(&counter), 1001);
+        arena.reset();
+        assert_eq!(Rc::strong_count(&counter), 1);
+        arena.alloc(counter.clone());
+        drop(arena);
+        assert_eq!(Rc::strong_count(&counter), 1);
+    }
+}
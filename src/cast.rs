@@ -0,0 +1,221 @@
+//! `CastRegistry`, casting between trait objects of unrelated traits.
+
+use std::any::{type_name, Any, TypeId};
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::{AsAny, BoxAny};
+
+type RefCast<U> = Box<dyn Fn(&dyn Any) -> &U + Send + Sync>;
+type MutCast<U> = Box<dyn Fn(&mut dyn Any) -> &mut U + Send + Sync>;
+type BoxCast<U> = Box<dyn Fn(Box<dyn Any>) -> Box<U> + Send + Sync>;
+
+/// The casts of one concrete type to `U`, called once the type is known to match.
+struct Caster<U: ?Sized> {
+    as_ref: RefCast<U>,
+    as_mut: MutCast<U>,
+    into_box: BoxCast<U>,
+}
+
+/// Casts a trait object to another trait object type implemented by its concrete type,
+/// e.g. from `&dyn Component` to `&dyn Render`, without naming the concrete type.
+///
+/// Each pair of concrete type and target is registered once, usually with
+/// `register_casts!`; code that only sees the trait objects, such as a plugin host, can
+/// then query for any registered target.
+///
+/// ```
+/// # #[macro_use]
+/// # extern crate pi_any;
+/// use pi_any::{BoxAny, CastRegistry};
+///
+/// trait Component: BoxAny {}
+/// trait Render { fn draw(&self) -> String; }
+///
+/// struct Sprite;
+/// impl Component for Sprite {}
+/// impl Render for Sprite { fn draw(&self) -> String { "sprite".into() } }
+/// struct Timer;
+/// impl Component for Timer {}
+///
+/// # fn main() {
+/// let mut casts = CastRegistry::new();
+/// register_casts!(casts, Sprite => dyn Render);
+///
+/// let components: Vec<Box<dyn Component>> = vec![Box::new(Sprite), Box::new(Timer)];
+/// let drawn: Vec<_> = components
+///     .iter()
+///     .filter_map(|c| casts.cast::<dyn Render, _>(&**c))
+///     .map(|r| r.draw())
+///     .collect();
+/// assert_eq!(drawn, ["sprite"]);
+/// # }
+/// ```
+#[derive(Default)]
+pub struct CastRegistry {
+    // Keyed by the concrete type and the target; the values are `Caster<U>`s.
+    casters: HashMap<(TypeId, TypeId), Box<dyn Any + Send + Sync>>,
+}
+
+impl CastRegistry {
+    pub fn new() -> Self {
+        CastRegistry { casters: HashMap::new() }
+    }
+
+    pub fn len(&self) -> usize {
+        self.casters.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.casters.is_empty()
+    }
+
+    /// Registers the casts of `T` to `U`, typically unsizing coercions written as `|v| v`,
+    /// replacing earlier ones. `register_casts!` writes this call.
+    pub fn register<T: Any, U: ?Sized + 'static>(
+        &mut self,
+        as_ref: fn(&T) -> &U,
+        as_mut: fn(&mut T) -> &mut U,
+        into_box: fn(Box<T>) -> Box<U>,
+    ) {
+        let caster = Caster::<U> {
+            as_ref: Box::new(move |v| as_ref(v.downcast_ref().unwrap())),
+            as_mut: Box::new(move |v| as_mut(v.downcast_mut().unwrap())),
+            into_box: Box::new(move |v| into_box(v.downcast().unwrap())),
+        };
+        self.casters.insert((TypeId::of::<T>(), TypeId::of::<U>()), Box::new(caster));
+    }
+
+    /// Returns true if the concrete type of `value` can be cast to `U`.
+    pub fn can_cast<U: ?Sized + 'static, V: AsAny + ?Sized>(&self, value: &V) -> bool {
+        self.casters.contains_key(&(value.type_id_of(), TypeId::of::<U>()))
+    }
+
+    /// Casts `value` to `&U`, if its concrete type has a registered cast to `U`.
+    pub fn cast<'a, U: ?Sized + 'static, V: AsAny + ?Sized>(&self, value: &'a V) -> Option<&'a U> {
+        let any = value.as_any();
+        Some((self.caster::<U>(any.type_id())?.as_ref)(any))
+    }
+
+    /// Casts `value` to `&mut U`, if its concrete type has a registered cast to `U`.
+    pub fn cast_mut<'a, U, V>(&self, value: &'a mut V) -> Option<&'a mut U>
+    where
+        U: ?Sized + 'static,
+        V: BoxAny + ?Sized,
+    {
+        let any = value.as_any_mut();
+        let caster = self.caster::<U>((*any).type_id())?;
+        Some((caster.as_mut)(any))
+    }
+
+    /// Casts `value` to `Box<U>`, if its concrete type has a registered cast to `U`, or
+    /// returns it unchanged.
+    pub fn cast_box<U, V>(&self, value: Box<V>) -> Result<Box<U>, Box<V>>
+    where
+        U: ?Sized + 'static,
+        V: BoxAny + ?Sized,
+    {
+        match self.caster::<U>((*value).type_id_of()) {
+            Some(caster) => Ok((caster.into_box)(value.into_any())),
+            None => Err(value),
+        }
+    }
+
+    fn caster<U: ?Sized + 'static>(&self, type_id: TypeId) -> Option<&Caster<U>> {
+        let caster = self.casters.get(&(type_id, TypeId::of::<U>()))?;
+        Some(caster.downcast_ref().unwrap_or_else(|| unreachable!("{}", type_name::<U>())))
+    }
+}
+
+impl fmt::Debug for CastRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CastRegistry").field("len", &self.len()).finish()
+    }
+}
+
+/// Registers casts of concrete types to trait object types in a `CastRegistry`.
+///
+/// ```
+/// # #[macro_use]
+/// # extern crate pi_any;
+/// # use pi_any::CastRegistry;
+/// # trait A {} trait B {}
+/// # struct Foo; impl A for Foo {} impl B for Foo {}
+/// # struct Bar; impl A for Bar {}
+/// # fn main() {
+/// let mut casts = CastRegistry::new();
+/// register_casts!(casts, Foo => dyn A, dyn B; Bar => dyn A);
+/// assert_eq!(casts.len(), 3);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! register_casts {
+    ($registry:expr, $($ty:ty => $($target:ty),+);+ $(;)?) => {{
+        let registry: &mut $crate::CastRegistry = &mut $registry;
+        $($(
+            registry.register::<$ty, $target>(|v| v, |v| v, |v| v);
+        )+)+
+    }};
+}
+
+#[cfg(test)]
+mod test {
+    use super::CastRegistry;
+    use crate::BoxAny;
+
+    trait Base: BoxAny {}
+    trait Named {
+        fn name(&self) -> String;
+    }
+    trait Counter {
+        fn bump(&mut self) -> u32;
+    }
+
+    struct Foo(u32);
+    impl Base for Foo {}
+    impl Named for Foo {
+        fn name(&self) -> String {
+            format!("foo {}", self.0)
+        }
+    }
+    impl Counter for Foo {
+        fn bump(&mut self) -> u32 {
+            self.0 += 1;
+            self.0
+        }
+    }
+    struct Bar;
+    impl Base for Bar {}
+    impl Named for Bar {
+        fn name(&self) -> String {
+            "bar".to_string()
+        }
+    }
+
+    #[test]
+    fn test() {
+        let mut casts = CastRegistry::new();
+        crate::register_casts!(casts, Foo => dyn Named, dyn Counter; Bar => dyn Named);
+        assert_eq!(casts.len(), 3);
+
+        let mut values: Vec<Box<dyn Base>> = vec![Box::new(Foo(1)), Box::new(Bar)];
+        let names: Vec<_> = values
+            .iter()
+            .filter_map(|v| casts.cast::<dyn Named, _>(&**v))
+            .map(|n| n.name())
+            .collect();
+        assert_eq!(names, ["foo 1", "bar"]);
+
+        assert!(casts.can_cast::<dyn Counter, _>(&*values[0]));
+        assert!(!casts.can_cast::<dyn Counter, _>(&*values[1]));
+        assert_eq!(casts.cast_mut::<dyn Counter, _>(&mut *values[0]).unwrap().bump(), 2);
+        assert!(casts.cast_mut::<dyn Counter, _>(&mut *values[1]).is_none());
+
+        let bar = values.pop().unwrap();
+        let bar = casts.cast_box::<dyn Counter, _>(bar).err().unwrap();
+        let named: Box<dyn Named> = casts.cast_box(bar).ok().unwrap();
+        assert_eq!(named.name(), "bar");
+        let foo: Box<dyn Named> = casts.cast_box(values.pop().unwrap()).ok().unwrap();
+        assert_eq!(foo.name(), "foo 2");
+    }
+}
@@ -0,0 +1,227 @@
+//! `AnyCell`, a `RefCell` of a value of any type, borrowed and downcast in one step.
+
+use alloc::boxed::Box;
+use core::any::{type_name, Any};
+use core::cell::{Ref, RefCell, RefMut};
+use core::error::Error;
+use core::fmt;
+
+use crate::{BoxAny, FailurePolicy};
+
+/// Why an `AnyCell` could not be borrowed as a `T`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CellError {
+    /// The value is mutably borrowed.
+    BorrowedMut,
+    /// The value is borrowed, so it cannot be borrowed mutably.
+    Borrowed,
+    /// The value is not a `T`.
+    Type { expected: &'static str, found: &'static str },
+}
+
+impl fmt::Display for CellError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CellError::BorrowedMut => f.write_str("the value is already mutably borrowed"),
+            CellError::Borrowed => f.write_str("the value is already borrowed"),
+            CellError::Type { expected, found } => {
+                write!(f, "expected `{}`, found `{}`", expected, found)
+            }
+        }
+    }
+}
+
+impl Error for CellError {}
+
+/// A mutable memory location holding a value of any type, like a `RefCell<Box<dyn Any>>`
+/// whose borrows are checked for both conflicts and type at once.
+///
+/// ```
+/// use pi_any::{AnyCell, CellError};
+///
+/// let cell = AnyCell::new(vec![1, 2]);
+/// cell.try_borrow_mut::<Vec<i32>>().unwrap().push(3);
+///
+/// let items = cell.borrow::<Vec<i32>>();
+/// assert_eq!(*items, [1, 2, 3]);
+/// assert_eq!(cell.try_borrow_mut::<Vec<i32>>().unwrap_err(), CellError::Borrowed);
+/// assert!(matches!(cell.try_borrow::<String>(), Err(CellError::Type { .. })));
+/// ```
+pub struct AnyCell {
+    value: RefCell<Box<dyn BoxAny>>,
+    policy: Option<FailurePolicy>,
+}
+
+impl AnyCell {
+    pub fn new<T: Any>(value: T) -> Self {
+        AnyCell { value: RefCell::new(Box::new(value)), policy: None }
+    }
+
+    /// Returns true if the value is a `T`, or false if it is mutably borrowed.
+    pub fn is<T: Any>(&self) -> bool {
+        self.value.try_borrow().is_ok_and(|value| (**value).as_any().is::<T>())
+    }
+
+    /// Borrows the value as a `T`, failing if it is mutably borrowed or not a `T`, with the
+    /// error handled by the failure policy.
+    #[track_caller]
+    pub fn try_borrow<T: Any>(&self) -> Result<Ref<'_, T>, CellError> {
+        self.borrow_as().map_err(|e| crate::__fail(self.policy, "AnyCell::try_borrow", e))
+    }
+
+    /// Mutably borrows the value as a `T`, failing if it is borrowed or not a `T`, with the
+    /// error handled by the failure policy.
+    #[track_caller]
+    pub fn try_borrow_mut<T: Any>(&self) -> Result<RefMut<'_, T>, CellError> {
+        self.borrow_mut_as().map_err(|e| crate::__fail(self.policy, "AnyCell::try_borrow_mut", e))
+    }
+
+    /// Sets the failure policy of `try_borrow` and `try_borrow_mut`, or lets them follow that
+    /// of the crate if it is `None`.
+    pub fn set_failure_policy(&mut self, policy: Option<FailurePolicy>) {
+        self.policy = policy;
+    }
+
+    fn borrow_as<T: Any>(&self) -> Result<Ref<'_, T>, CellError> {
+        let value = self.value.try_borrow().map_err(|_| CellError::BorrowedMut)?;
+        let found = (**value).type_name();
+        Ref::filter_map(value, |value| (**value).as_any().downcast_ref())
+            .map_err(|_| CellError::Type { expected: type_name::<T>(), found })
+    }
+
+    fn borrow_mut_as<T: Any>(&self) -> Result<RefMut<'_, T>, CellError> {
+        let value = self.value.try_borrow_mut().map_err(|_| CellError::Borrowed)?;
+        let found = (**value).type_name();
+        RefMut::filter_map(value, |value| (**value).as_any_mut().downcast_mut())
+            .map_err(|_| CellError::Type { expected: type_name::<T>(), found })
+    }
+
+    /// Borrows the value as a `T`.
+    ///
+    /// # Panics
+    ///
+    /// If the value is mutably borrowed or not a `T`.
+    #[track_caller]
+    pub fn borrow<T: Any>(&self) -> Ref<'_, T> {
+        self.borrow_as().unwrap_or_else(|e| failed("AnyCell::borrow", e))
+    }
+
+    /// Mutably borrows the value as a `T`.
+    ///
+    /// # Panics
+    ///
+    /// If the value is borrowed or not a `T`.
+    #[track_caller]
+    pub fn borrow_mut<T: Any>(&self) -> RefMut<'_, T> {
+        self.borrow_mut_as().unwrap_or_else(|e| failed("AnyCell::borrow_mut", e))
+    }
+
+    /// Returns the value as a `T` without borrowing, which `&mut self` makes unnecessary.
+    pub fn get_mut<T: Any>(&mut self) -> Option<&mut T> {
+        (**self.value.get_mut()).as_any_mut().downcast_mut()
+    }
+
+    /// Replaces the value, which may be of any type, returning the old one.
+    ///
+    /// # Panics
+    ///
+    /// If the value is borrowed.
+    pub fn replace<T: Any>(&self, value: T) -> Box<dyn BoxAny> {
+        self.value.replace(Box::new(value))
+    }
+
+    /// Unwraps the value as a `T`, or returns the cell if it is not a `T`.
+    pub fn into_inner<T: Any>(self) -> Result<T, Self> {
+        if !(**self.value.borrow()).as_any().is::<T>() {
+            return Err(self);
+        }
+        Ok(*self.value.into_inner().into_any().downcast().unwrap())
+    }
+}
+
+#[cold]
+#[track_caller]
+fn failed(msg: &str, e: CellError) -> ! {
+    match e {
+        CellError::Type { expected, found } => crate::__downcast_failed(msg, expected, found),
+        e => panic!("{}: {}", msg, e),
+    }
+}
+
+impl fmt::Debug for AnyCell {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.value.try_borrow() {
+            Ok(value) => f.debug_struct("AnyCell").field("type", &(**value).type_name()).finish(),
+            Err(_) => f.debug_struct("AnyCell").field("type", &format_args!("<borrowed>")).finish(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{AnyCell, CellError};
+    use crate::FailurePolicy;
+
+    #[derive(Debug, PartialEq)]
+    struct Foo(u32);
+
+    #[test]
+    fn test_borrow() {
+        let cell = AnyCell::new(Foo(1));
+        assert!(cell.is::<Foo>());
+        {
+            let a = cell.borrow::<Foo>();
+            let b = cell.try_borrow::<Foo>().unwrap();
+            assert_eq!((a.0, b.0), (1, 1));
+            assert_eq!(cell.try_borrow_mut::<Foo>().unwrap_err(), CellError::Borrowed);
+        }
+        {
+            let mut foo = cell.borrow_mut::<Foo>();
+            foo.0 += 1;
+            assert_eq!(cell.try_borrow::<Foo>().unwrap_err(), CellError::BorrowedMut);
+            assert!(!cell.is::<Foo>());
+        }
+        match cell.try_borrow::<u8>() {
+            Err(CellError::Type { expected, found }) => {
+                assert_eq!(expected, "u8");
+                assert!(found.ends_with("Foo"));
+            }
+            _ => unreachable!(),
+        }
+        assert_eq!(*cell.borrow::<Foo>(), Foo(2));
+    }
+
+    #[test]
+    fn test_replace() {
+        let mut cell = AnyCell::new(Foo(1));
+        cell.get_mut::<Foo>().unwrap().0 = 3;
+        let old = cell.replace("text");
+        assert_eq!(old.into_any().downcast_ref::<Foo>(), Some(&Foo(3)));
+        let cell = cell.into_inner::<Foo>().unwrap_err();
+        assert_eq!(cell.into_inner::<&str>().ok(), Some("text"));
+    }
+
+    #[test]
+    #[should_panic(expected = "AnyCell::borrow_mut: expected `u8`")]
+    fn test_wrong_type() {
+        AnyCell::new(Foo(1)).borrow_mut::<u8>();
+    }
+
+    #[test]
+    #[should_panic(expected = "AnyCell::borrow: the value is already mutably borrowed")]
+    fn test_conflict() {
+        let cell = AnyCell::new(Foo(1));
+        let _foo = cell.borrow_mut::<Foo>();
+        cell.borrow::<Foo>();
+    }
+
+    #[test]
+    #[should_panic(expected = "AnyCell::try_borrow_mut: the value is already borrowed")]
+    fn test_policy() {
+        let mut cell = AnyCell::new(Foo(1));
+        assert!(cell.try_borrow::<u8>().is_err());
+        cell.set_failure_policy(Some(FailurePolicy::Panic));
+        let _foo = cell.borrow::<Foo>();
+        let _ = cell.try_borrow_mut::<Foo>();
+    }
+}
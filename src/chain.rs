@@ -0,0 +1,130 @@
+//! `DowncastChain`, a reusable dispatcher on the concrete type behind a trait object.
+
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::marker::PhantomData;
+
+use crate::AsAny;
+
+type Handler<'f, A, R> = Box<dyn Fn(&A) -> R + 'f>;
+
+/// Dispatches `&V` values, typically `&dyn Trait`, to the handler registered for their
+/// concrete type.
+///
+/// Unlike `match_downcast!`, the chain is built once and looks the handler up by `TypeId`,
+/// so applying it to many values does not try every type in turn.
+///
+/// ```
+/// use pi_any::{BoxAny, DowncastChain};
+///
+/// trait Shape: BoxAny {}
+/// struct Circle(f32);
+/// impl Shape for Circle {}
+/// struct Rect(f32, f32);
+/// impl Shape for Rect {}
+/// struct Line;
+/// impl Shape for Line {}
+///
+/// let area = DowncastChain::<dyn Shape, f32>::new()
+///     .on::<Circle>(|c| 3.0 * c.0 * c.0)
+///     .on::<Rect>(|r| r.0 * r.1)
+///     .otherwise(|_| 0.0);
+///
+/// let shapes: Vec<Box<dyn Shape>> = vec![Box::new(Rect(2.0, 3.0)), Box::new(Line)];
+/// let total: f32 = shapes.iter().filter_map(|s| area.apply(&**s)).sum();
+/// assert_eq!(total, 6.0);
+/// ```
+pub struct DowncastChain<'f, V: ?Sized, R> {
+    handlers: HashMap<TypeId, Handler<'f, dyn Any, R>>,
+    otherwise: Option<Handler<'f, V, R>>,
+    _value: PhantomData<fn(&V)>,
+}
+
+impl<'f, V: AsAny + ?Sized, R> DowncastChain<'f, V, R> {
+    pub fn new() -> Self {
+        DowncastChain { handlers: HashMap::new(), otherwise: None, _value: PhantomData }
+    }
+
+    /// Registers `handler` for values of type `T`. If `T` already has a handler, the first
+    /// one is kept, as in a chain of `if let`s.
+    pub fn on<T: Any>(mut self, handler: impl Fn(&T) -> R + 'f) -> Self {
+        self.handlers.entry(TypeId::of::<T>()).or_insert_with(|| {
+            Box::new(move |any: &dyn Any| handler(any.downcast_ref::<T>().unwrap()))
+        });
+        self
+    }
+
+    /// Sets the handler for values of all types without a handler of their own.
+    pub fn otherwise(mut self, handler: impl Fn(&V) -> R + 'f) -> Self {
+        self.otherwise = Some(Box::new(handler));
+        self
+    }
+
+    /// Returns true if values of type `T` have a handler of their own.
+    pub fn handles<T: Any>(&self) -> bool {
+        self.handlers.contains_key(&TypeId::of::<T>())
+    }
+
+    /// Runs the handler for the concrete type of `value`, or the `otherwise` handler.
+    /// Returns `None` if neither exists.
+    pub fn apply(&self, value: &V) -> Option<R> {
+        let any = value.as_any();
+        match self.handlers.get(&any.type_id()) {
+            Some(handler) => Some(handler(any)),
+            None => self.otherwise.as_ref().map(|handler| handler(value)),
+        }
+    }
+}
+
+impl<'f, V: AsAny + ?Sized, R> Default for DowncastChain<'f, V, R> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::Cell;
+
+    use super::DowncastChain;
+    use crate::BoxAny;
+
+    trait Base: BoxAny {}
+
+    struct Foo(u32);
+    impl Base for Foo {}
+    struct Bar;
+    impl Base for Bar {}
+    struct Baz;
+    impl Base for Baz {}
+
+    #[test]
+    fn test_dispatch() {
+        let chain = DowncastChain::<dyn Base, u32>::new()
+            .on::<Foo>(|foo| foo.0)
+            .on::<Foo>(|_| unreachable!())
+            .on::<Bar>(|_| 1);
+        assert!(chain.handles::<Foo>());
+        assert!(!chain.handles::<Baz>());
+
+        let values: Vec<Box<dyn Base>> = vec![Box::new(Foo(5)), Box::new(Bar), Box::new(Baz)];
+        let results: Vec<_> = values.iter().map(|v| chain.apply(&**v)).collect();
+        assert_eq!(results, [Some(5), Some(1), None]);
+    }
+
+    #[test]
+    fn test_otherwise() {
+        let unknown = Cell::new(0);
+        let chain = DowncastChain::<dyn Base, ()>::default()
+            .on::<Foo>(|_| ())
+            .otherwise(|other| {
+                assert!(other.type_name().ends_with("Baz"));
+                unknown.set(unknown.get() + 1);
+            });
+        for _ in 0..3 {
+            chain.apply(&Baz);
+            chain.apply(&Foo(0));
+        }
+        assert_eq!(unknown.get(), 3);
+    }
+}
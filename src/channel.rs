@@ -0,0 +1,298 @@
+//! `any_channel`, an mpsc channel of type-erased messages received by type.
+
+use std::any::{Any, TypeId};
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::sync::mpsc::{self, Receiver, Sender};
+
+type Message = Box<dyn Any + Send>;
+type Handler = Box<dyn FnMut(Message) + Send>;
+type Arm<'r, R> = (TypeId, Box<dyn FnOnce(Message) -> R + 'r>);
+
+/// Creates a channel of messages of any `Send` type, e.g. the commands that worker threads
+/// send to the main loop, which takes them by type.
+///
+/// Like `std::sync::mpsc::channel`, the sender can be cloned and the receiver can't.
+///
+/// ```
+/// use std::thread;
+/// use pi_any::any_channel;
+///
+/// struct Loaded(&'static str);
+/// struct Progress(u8);
+///
+/// let (tx, mut rx) = any_channel();
+/// let worker = tx.clone();
+/// thread::spawn(move || {
+///     worker.send(Progress(50)).ok();
+///     worker.send(Loaded("level.bin")).ok();
+/// });
+/// drop(tx);
+///
+/// // Waits for the `Loaded`, setting the `Progress` aside.
+/// assert_eq!(rx.recv_typed::<Loaded>().unwrap().0, "level.bin");
+/// assert_eq!(rx.try_recv_typed::<Progress>().unwrap().0, 50);
+/// assert!(rx.recv().is_none());
+/// ```
+pub fn any_channel() -> (AnySender, AnyReceiver) {
+    let (sender, receiver) = mpsc::channel();
+    let receiver =
+        AnyReceiver { inner: receiver, stash: VecDeque::new(), handlers: HashMap::new() };
+    (AnySender { inner: sender }, receiver)
+}
+
+/// The sending half of an `any_channel`.
+#[derive(Clone)]
+pub struct AnySender {
+    inner: Sender<Message>,
+}
+
+impl AnySender {
+    /// Sends `value`, or returns it if the receiver was dropped.
+    pub fn send<T: Any + Send>(&self, value: T) -> Result<(), T> {
+        self.inner.send(Box::new(value)).map_err(|e| *e.0.downcast().unwrap())
+    }
+
+    /// Sends a boxed message, or returns it if the receiver was dropped.
+    pub fn send_boxed(&self, msg: Message) -> Result<(), Message> {
+        self.inner.send(msg).map_err(|e| e.0)
+    }
+}
+
+impl fmt::Debug for AnySender {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AnySender").finish_non_exhaustive()
+    }
+}
+
+/// The receiving half of an `any_channel`.
+///
+/// Messages passed over while waiting for a certain type are set aside, and received later
+/// in the order they were sent.
+pub struct AnyReceiver {
+    inner: Receiver<Message>,
+    stash: VecDeque<Message>,
+    handlers: HashMap<TypeId, Handler>,
+}
+
+impl AnyReceiver {
+    /// Waits for the next message, returning `None` once all senders were dropped and no
+    /// message is left.
+    pub fn recv(&mut self) -> Option<Message> {
+        self.take_first(|_| true, true)
+    }
+
+    /// Returns the next message if one has arrived.
+    pub fn try_recv(&mut self) -> Option<Message> {
+        self.take_first(|_| true, false)
+    }
+
+    /// Waits for the next message of type `T`, returning `None` once all senders were
+    /// dropped and no such message is left.
+    pub fn recv_typed<T: Any>(&mut self) -> Option<T> {
+        let msg = self.take_first(|id| id == TypeId::of::<T>(), true)?;
+        Some(*msg.downcast().unwrap())
+    }
+
+    /// Returns the next message of type `T` if one has arrived.
+    pub fn try_recv_typed<T: Any>(&mut self) -> Option<T> {
+        let msg = self.take_first(|id| id == TypeId::of::<T>(), false)?;
+        Some(*msg.downcast().unwrap())
+    }
+
+    /// Returns the number of messages set aside.
+    pub fn pending(&self) -> usize {
+        self.stash.len()
+    }
+
+    /// Has `dispatch` call `handler` with the messages of type `T`, replacing the handler
+    /// registered for `T` before.
+    pub fn on<T: Any>(&mut self, mut handler: impl FnMut(T) + Send + 'static) {
+        let handler: Handler = Box::new(move |msg| handler(*msg.downcast().unwrap()));
+        self.handlers.insert(TypeId::of::<T>(), handler);
+    }
+
+    /// Calls the handlers registered by `on` with the messages that have arrived, without
+    /// waiting, and returns how many were handled. Messages of other types are set aside.
+    pub fn dispatch(&mut self) -> usize {
+        let mut handled = 0;
+        let mut unhandled = VecDeque::new();
+        loop {
+            let msg = match self.stash.pop_front() {
+                Some(msg) => msg,
+                None => match self.inner.try_recv() {
+                    Ok(msg) => msg,
+                    Err(_) => break,
+                },
+            };
+            match self.handlers.get_mut(&(*msg).type_id()) {
+                Some(handler) => {
+                    handler(msg);
+                    handled += 1;
+                }
+                None => unhandled.push_back(msg),
+            }
+        }
+        self.stash = unhandled;
+        handled
+    }
+
+    /// Starts receiving the next message of one of several types, each handled by its own
+    /// closure, like the `select!` of channel crates.
+    ///
+    /// ```
+    /// use pi_any::any_channel;
+    ///
+    /// let (tx, mut rx) = any_channel();
+    /// tx.send(1.5f32).ok();
+    /// tx.send(7u8).ok();
+    ///
+    /// let mut select = || {
+    ///     rx.select().on(|n: u8| format!("byte {}", n)).on(|s: String| s).try_recv()
+    /// };
+    /// assert_eq!(select().as_deref(), Some("byte 7"));
+    /// assert_eq!(select(), None);
+    /// assert_eq!(rx.try_recv_typed::<f32>(), Some(1.5));
+    /// ```
+    pub fn select<R>(&mut self) -> AnySelect<'_, R> {
+        AnySelect { receiver: self, arms: Vec::new() }
+    }
+
+    /// Takes the first message whose type `matches`, setting aside the others received.
+    fn take_first(&mut self, matches: impl Fn(TypeId) -> bool, block: bool) -> Option<Message> {
+        if let Some(i) = self.stash.iter().position(|msg| matches((**msg).type_id())) {
+            return self.stash.remove(i);
+        }
+        loop {
+            let msg = if block { self.inner.recv().ok()? } else { self.inner.try_recv().ok()? };
+            if matches((*msg).type_id()) {
+                return Some(msg);
+            }
+            self.stash.push_back(msg);
+        }
+    }
+}
+
+impl fmt::Debug for AnyReceiver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AnyReceiver")
+            .field("pending", &self.stash.len())
+            .field("handlers", &self.handlers.len())
+            .finish()
+    }
+}
+
+/// Receiving of the next message of one of several types, as started by
+/// `AnyReceiver::select`.
+pub struct AnySelect<'r, R> {
+    receiver: &'r mut AnyReceiver,
+    arms: Vec<Arm<'r, R>>,
+}
+
+impl<'r, R> AnySelect<'r, R> {
+    /// Handles a message of type `T` with `arm`, unless an earlier arm is for `T` as well.
+    pub fn on<T: Any>(mut self, arm: impl FnOnce(T) -> R + 'r) -> Self {
+        self.arms.push((TypeId::of::<T>(), Box::new(|msg| arm(*msg.downcast().unwrap()))));
+        self
+    }
+
+    /// Waits for a message of one of the types, returning what its arm returns, or `None`
+    /// once all senders were dropped and no such message is left.
+    pub fn recv(self) -> Option<R> {
+        self.take(true)
+    }
+
+    /// Handles a message of one of the types if one has arrived.
+    pub fn try_recv(self) -> Option<R> {
+        self.take(false)
+    }
+
+    fn take(mut self, block: bool) -> Option<R> {
+        let arms = &self.arms;
+        let msg = self.receiver.take_first(|id| arms.iter().any(|arm| arm.0 == id), block)?;
+        let i = self.arms.iter().position(|arm| arm.0 == (*msg).type_id()).unwrap();
+        Some((self.arms.swap_remove(i).1)(msg))
+    }
+}
+
+impl<R> fmt::Debug for AnySelect<'_, R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AnySelect").field("arms", &self.arms.len()).finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+
+    use super::any_channel;
+
+    #[derive(Debug, PartialEq)]
+    struct Foo(u32);
+    #[derive(Debug, PartialEq)]
+    struct Bar(&'static str);
+
+    #[test]
+    fn test_recv() {
+        let (tx, mut rx) = any_channel();
+        tx.send(Foo(1)).unwrap();
+        tx.send(Bar("a")).unwrap();
+        tx.send(Foo(2)).unwrap();
+        assert_eq!(rx.recv_typed::<Bar>(), Some(Bar("a")));
+        assert_eq!(rx.pending(), 1);
+        assert_eq!(rx.try_recv_typed::<Bar>(), None);
+        assert_eq!(rx.pending(), 2);
+        assert_eq!(*rx.recv().unwrap().downcast::<Foo>().unwrap(), Foo(1));
+        assert!(format!("{:?}", rx).contains("pending: 1"));
+
+        let handle = thread::spawn(move || {
+            tx.send(1u8).unwrap();
+            tx.send_boxed(Box::new(Bar("b"))).unwrap();
+        });
+        assert_eq!(rx.recv_typed::<Bar>(), Some(Bar("b")));
+        handle.join().unwrap();
+        assert_eq!(rx.recv_typed::<Bar>(), None);
+        assert_eq!(rx.recv_typed::<Foo>(), Some(Foo(2)));
+        assert_eq!(rx.recv_typed::<u8>(), Some(1));
+        assert!(rx.recv().is_none() && rx.try_recv().is_none());
+
+        let (tx, rx) = any_channel();
+        drop(rx);
+        assert_eq!(tx.send(Foo(3)), Err(Foo(3)));
+    }
+
+    #[test]
+    fn test_dispatch() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let (tx, mut rx) = any_channel();
+        let (a, b) = (log.clone(), log.clone());
+        rx.on(move |foo: Foo| a.lock().unwrap().push(foo.0.to_string()));
+        rx.on(move |bar: Bar| b.lock().unwrap().push(bar.0.to_string()));
+        tx.send(Foo(1)).unwrap();
+        tx.send(2u8).unwrap();
+        tx.send(Bar("c")).unwrap();
+        assert_eq!(rx.dispatch(), 2);
+        assert_eq!(*log.lock().unwrap(), ["1", "c"]);
+        assert_eq!(rx.pending(), 1);
+        assert_eq!(rx.dispatch(), 0);
+        assert_eq!(rx.try_recv_typed::<u8>(), Some(2));
+    }
+
+    #[test]
+    fn test_select() {
+        let (tx, mut rx) = any_channel();
+        tx.send(0u8).unwrap();
+        tx.send(Bar("a")).unwrap();
+        tx.send(Foo(1)).unwrap();
+        let mut select = || {
+            rx.select().on(|foo: Foo| foo.0).on(|bar: Bar| bar.0.len() as u32).on(|_: Foo| 0).recv()
+        };
+        assert_eq!(select(), Some(1));
+        assert_eq!(select(), Some(1));
+        drop(tx);
+        assert_eq!(select(), None);
+        assert_eq!(rx.select::<()>().try_recv(), None);
+        assert_eq!(rx.try_recv_typed::<u8>(), Some(0));
+    }
+}
@@ -0,0 +1,190 @@
+//! `CloneAny`, cloning trait objects behind a `Box`.
+//!
+//! Cloning needs the vtable of the trait object for the new box, which safe code can't
+//! attach to a fresh allocation, so this module is allowed to use `unsafe` like
+//! `unchecked`.
+
+use alloc::boxed::Box;
+use alloc::rc::Rc;
+use alloc::sync::Arc;
+use core::any::Any;
+use core::ops::Deref;
+
+use crate::{AsAny, AsMutAny, BoxAny};
+
+mod private {
+    /// Keeps `CloneAny` from being implemented, or its method called, outside this crate.
+    pub struct Token;
+}
+
+/// Implemented for every `Clone` type, so a trait extending `CloneAny` can be cloned behind
+/// a `Box` with `clone_box`.
+///
+/// `impl_clone_box!` generates a `clone_box` method and `Clone` for `Box<dyn Trait>`:
+///
+/// ```
+/// # #[macro_use]
+/// # extern crate pi_any;
+/// use pi_any::CloneAny;
+///
+/// trait Shape: CloneAny {
+///     fn radius(&self) -> f32;
+/// }
+/// impl_clone_box!(Shape);
+///
+/// #[derive(Clone)]
+/// struct Circle(f32);
+/// impl Shape for Circle {
+///     fn radius(&self) -> f32 { self.0 }
+/// }
+///
+/// # fn main() {
+/// let shapes: Vec<Box<dyn Shape>> = vec![Box::new(Circle(1.0))];
+/// let copies = shapes.clone();
+/// assert_eq!(copies[0].radius(), 1.0);
+/// assert_eq!(shapes[0].clone_box().radius(), 1.0);
+/// # }
+/// ```
+pub trait CloneAny: BoxAny {
+    /// Clones `self` into a new box, returning its data pointer.
+    #[doc(hidden)]
+    fn __clone_raw(&self, _: private::Token) -> *mut ();
+}
+
+impl<T: Any + Clone> CloneAny for T {
+    fn __clone_raw(&self, _: private::Token) -> *mut () {
+        Box::into_raw(Box::new(self.clone())) as *mut ()
+    }
+}
+
+/// Clones the value behind `value`, typically a `&dyn Trait` with `Trait: CloneAny`, into a
+/// new box.
+pub fn clone_box<T: CloneAny + ?Sized>(value: &T) -> Box<T> {
+    let mut ptr = value as *const T;
+    // SAFETY: a pointer to `T` starts with its data pointer, followed by the metadata (the
+    // vtable) if `T` is unsized, which the assert double checks. `__clone_raw` returns a
+    // boxed clone of the same concrete type, since `CloneAny` is only implemented by the
+    // blanket impl, so replacing the data pointer yields a valid owning pointer to `T`.
+    unsafe {
+        let data = &mut ptr as *mut *const T as *mut *mut ();
+        assert_eq!(*data as *const (), value as *const T as *const ());
+        *data = value.__clone_raw(private::Token);
+        Box::from_raw(ptr as *mut T)
+    }
+}
+
+/// A shared pointer whose value `downcast_make_mut`, generated by `impl_clone_box!`, clones
+/// when it is shared, like `Rc::make_mut`. Implemented for `Rc` and `Arc`.
+pub trait SharedPtr<S: ?Sized>: Deref<Target = S> {
+    /// Returns a mutable reference to the value, cloning it into a new allocation first if
+    /// there are other pointers to it, weak ones included.
+    fn make_unique(&mut self) -> &mut S;
+}
+
+impl<S: CloneAny + ?Sized> SharedPtr<S> for Rc<S> {
+    fn make_unique(&mut self) -> &mut S {
+        if Rc::get_mut(self).is_none() {
+            *self = Rc::from(clone_box(&**self));
+        }
+        Rc::get_mut(self).expect("the `Rc` was just made unique")
+    }
+}
+
+impl<S: CloneAny + ?Sized> SharedPtr<S> for Arc<S> {
+    fn make_unique(&mut self) -> &mut S {
+        if Arc::get_mut(self).is_none() {
+            *self = Arc::from(clone_box(&**self));
+        }
+        Arc::get_mut(self).expect("the `Arc` was just made unique")
+    }
+}
+
+#[doc(hidden)]
+pub fn __downcast_make_mut<T: Any, S: CloneAny + ?Sized>(
+    this: &mut impl SharedPtr<S>,
+) -> Option<&mut T> {
+    if !AsAny::as_any(&**this).is::<T>() {
+        return None;
+    }
+    AsMutAny::as_any_mut(this.make_unique()).downcast_mut()
+}
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+    use std::sync::Arc;
+
+    use super::{clone_box, CloneAny, SharedPtr};
+
+    trait Base: CloneAny {
+        fn value(&self) -> u32;
+    }
+    crate::impl_clone_box!(Base);
+    crate::impl_downcast_box!(Base);
+
+    #[derive(Clone)]
+    struct Foo(Rc<u32>);
+    impl Base for Foo {
+        fn value(&self) -> u32 {
+            *self.0
+        }
+    }
+    #[derive(Clone)]
+    struct Zst;
+    impl Base for Zst {
+        fn value(&self) -> u32 {
+            0
+        }
+    }
+
+    #[test]
+    fn test() {
+        let rc = Rc::new(7);
+        let values: Vec<Box<dyn Base>> = vec![Box::new(Foo(rc.clone())), Box::new(Zst)];
+        let copies = values.clone();
+        assert_eq!(Rc::strong_count(&rc), 3);
+        assert_eq!(copies[0].value(), 7);
+        assert!(copies[1].is::<Zst>());
+        assert!(Rc::ptr_eq(&copies[0].downcast_ref::<Foo>().unwrap().0, &rc));
+
+        let copy = values[0].clone_box();
+        assert_eq!(copy.downcast::<Foo>().map_err(|_| ()).unwrap().value(), 7);
+        drop((values, copies));
+        assert_eq!(Rc::strong_count(&rc), 1);
+
+        // Sized values clone too.
+        assert_eq!(clone_box(&3u8), Box::new(3));
+    }
+
+    #[test]
+    fn test_make_mut() {
+        let rc = Rc::new(1);
+        let mut base: Rc<dyn Base> = Rc::new(Foo(rc.clone()));
+        let other = base.clone();
+        assert!(<dyn Base>::downcast_make_mut::<Zst>(&mut base).is_none());
+        assert!(Rc::ptr_eq(&base, &other));
+
+        <dyn Base>::downcast_make_mut::<Foo>(&mut base).unwrap().0 = Rc::new(2);
+        assert!(!Rc::ptr_eq(&base, &other));
+        assert_eq!((base.value(), other.value()), (2, 1));
+        let ptr = Rc::as_ptr(&base);
+        <dyn Base>::downcast_make_mut::<Foo>(&mut base).unwrap();
+        assert!(std::ptr::addr_eq(Rc::as_ptr(&base), ptr));
+
+        let weak = Rc::downgrade(&base);
+        base.make_unique();
+        assert!(weak.upgrade().is_none());
+    }
+
+    #[test]
+    fn test_make_mut_arc() {
+        let mut base: Arc<dyn Base> = Arc::new(Zst);
+        assert!(<dyn Base>::downcast_make_mut::<Zst>(&mut base).is_some());
+        let other = base.clone();
+        assert!(<dyn Base>::downcast_make_mut::<Foo>(&mut base).is_none());
+        assert!(Arc::ptr_eq(&base, &other));
+        assert!(<dyn Base>::downcast_make_mut::<Zst>(&mut base).is_some());
+        assert!(!Arc::ptr_eq(&base, &other));
+        assert_eq!(Arc::strong_count(&other), 1);
+    }
+}
@@ -0,0 +1,518 @@
+//! A compact binary envelope for trait objects whose types are in a `TypeRegistry`.
+//!
+//! An envelope is the registered name of the concrete type followed by the payload written
+//! by its codec, each prefixed with its length:
+//!
+//! ```text
+//! name len: u16 LE | name: UTF-8 | payload len: u32 LE | payload
+//! ```
+//!
+//! Names are chosen by the application, so unlike `TypeId`s they are stable across builds
+//! and can be sent between processes.
+//!
+//! An `AnyMap` is encoded as the number of its values followed by their envelopes:
+//!
+//! ```text
+//! count: u32 LE | envelope | envelope | ...
+//! ```
+//!
+//! Envelopes naming a type the registry doesn't know, e.g. written by a newer build, can be
+//! kept as `UnknownTypeValue`s and written back as they were.
+
+use std::any::{type_name, Any};
+use std::error::Error;
+use std::fmt;
+
+use crate::{AnyMap, AsAny, Bounds, BoxAny, Pod, TypeRegistry};
+
+type Encoder = Box<dyn Fn(&dyn Any, &mut Vec<u8>) + Send + Sync>;
+type Decoder<B> = Box<dyn Fn(&[u8]) -> Option<Box<B>> + Send + Sync>;
+
+pub(crate) struct Codec<B: ?Sized> {
+    encode: Encoder,
+    decode: Decoder<B>,
+}
+
+/// Why a value could not be encoded into, or decoded from, an envelope.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CodecError {
+    /// The type of the value is not registered.
+    Unregistered { type_name: &'static str },
+    /// The type registered under `name` has no codec.
+    NoCodec { name: String },
+    /// The envelope names a type that is not registered.
+    UnknownName { name: String },
+    /// The envelope is cut short or followed by extra bytes.
+    Length,
+    /// The name in the envelope is not UTF-8, or too long to encode.
+    Name,
+    /// The codec of the type registered under `name` rejected the payload.
+    Payload { name: String },
+}
+
+impl fmt::Display for CodecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CodecError::Unregistered { type_name } => write!(f, "`{}` is not registered", type_name),
+            CodecError::NoCodec { name } => write!(f, "`{}` has no codec", name),
+            CodecError::UnknownName { name } => write!(f, "no type is registered as `{}`", name),
+            CodecError::Length => f.write_str("the envelope has the wrong length"),
+            CodecError::Name => f.write_str("the type name is not valid"),
+            CodecError::Payload { name } => write!(f, "invalid payload for `{}`", name),
+        }
+    }
+}
+
+impl Error for CodecError {}
+
+/// An envelope naming a type that is not registered, kept as it was read so it can be
+/// inspected and encoded again unchanged.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct UnknownTypeValue {
+    /// The name in the envelope.
+    pub name: String,
+    /// The payload, as written by the codec of the type.
+    pub payload: Vec<u8>,
+}
+
+impl UnknownTypeValue {
+    /// Reads an envelope without decoding its payload, whether its type is registered or not.
+    pub fn from_envelope(bytes: &[u8]) -> Result<Self, CodecError> {
+        let (name, payload) = parse(bytes)?;
+        Ok(UnknownTypeValue { name: name.to_string(), payload: payload.to_vec() })
+    }
+
+    /// Writes the envelope again, as it was read.
+    pub fn to_envelope(&self) -> Result<Vec<u8>, CodecError> {
+        let name_len = u16::try_from(self.name.len()).map_err(|_| CodecError::Name)?;
+        let payload_len = u32::try_from(self.payload.len()).map_err(|_| CodecError::Length)?;
+        let mut out = Vec::with_capacity(2 + self.name.len() + 4 + self.payload.len());
+        out.extend_from_slice(&name_len.to_le_bytes());
+        out.extend_from_slice(self.name.as_bytes());
+        out.extend_from_slice(&payload_len.to_le_bytes());
+        out.extend_from_slice(&self.payload);
+        Ok(out)
+    }
+}
+
+impl<B: AsAny + ?Sized, S: Bounds> TypeRegistry<B, S> {
+    /// Sets the codec of `T`, which must already be registered: `encode` appends the payload
+    /// of a value to the buffer, and `decode` rebuilds the value from exactly that payload,
+    /// returning `None` if it is invalid. Returns false if `T` is not registered.
+    pub fn register_codec<T: Any>(
+        &mut self,
+        encode: impl Fn(&T, &mut Vec<u8>) + Send + Sync + 'static,
+        decode: impl Fn(&[u8]) -> Option<Box<B>> + Send + Sync + 'static,
+    ) -> bool {
+        let Some(name) = self.name_of::<T>().map(str::to_string) else {
+            return false;
+        };
+        let encode: Encoder = Box::new(move |value, out| encode(value.downcast_ref().unwrap(), out));
+        self.entries.get_mut(&name).unwrap().codec = Some(Codec { encode, decode: Box::new(decode) });
+        true
+    }
+
+    /// Sets the codec of the `Pod` type `T`, whose payload is its `Pod` encoding. `wrap`
+    /// boxes decoded values, e.g. `|v| Box::new(v)`.
+    pub fn register_pod_codec<T: Pod>(
+        &mut self,
+        wrap: impl Fn(T) -> Box<B> + Send + Sync + 'static,
+    ) -> bool {
+        self.register_codec::<T>(
+            |value, out| value.write_le(out),
+            move |bytes| (bytes.len() == T::SIZE).then(|| wrap(T::read_le(bytes))),
+        )
+    }
+
+    /// Returns true if the type registered under `name` has a codec.
+    pub fn has_codec(&self, name: &str) -> bool {
+        self.entries.get(name).is_some_and(|entry| entry.codec.is_some())
+    }
+
+    /// Encodes `value` into an envelope, using the codec of its concrete type.
+    pub fn encode(&self, value: &B) -> Result<Vec<u8>, CodecError> {
+        let any = value.as_any();
+        let name = self
+            .name_of_id(any.type_id())
+            .ok_or(CodecError::Unregistered { type_name: value.type_name() })?;
+        let codec = self.entries[name]
+            .codec
+            .as_ref()
+            .ok_or_else(|| CodecError::NoCodec { name: name.to_string() })?;
+        let name_len = u16::try_from(name.len()).map_err(|_| CodecError::Name)?;
+
+        let mut out = Vec::with_capacity(2 + name.len() + 4);
+        out.extend_from_slice(&name_len.to_le_bytes());
+        out.extend_from_slice(name.as_bytes());
+        out.extend_from_slice(&[0; 4]);
+        let start = out.len();
+        (codec.encode)(any, &mut out);
+        let payload_len = u32::try_from(out.len() - start).map_err(|_| CodecError::Length)?;
+        out[start - 4..start].copy_from_slice(&payload_len.to_le_bytes());
+        Ok(out)
+    }
+
+    /// Decodes a value from an envelope made by `encode`, using the codec of the type
+    /// registered under the name it contains.
+    pub fn decode(&self, bytes: &[u8]) -> Result<Box<B>, CodecError> {
+        let (name, payload) = parse(bytes)?;
+        self.decode_payload(name, payload)
+    }
+
+    /// Decodes a value like `decode`, but keeps an envelope naming a type that is not
+    /// registered as an `UnknownTypeValue` rather than failing.
+    ///
+    /// ```
+    /// use pi_any::{BoxAny, TypeRegistry};
+    ///
+    /// let mut newer = TypeRegistry::<dyn BoxAny>::new();
+    /// newer.register::<u32>("score", || Box::new(0u32));
+    /// newer.register_pod_codec::<u32>(|v| Box::new(v));
+    /// let bytes = newer.encode(&7u32).unwrap();
+    ///
+    /// let older = TypeRegistry::<dyn BoxAny>::new();
+    /// let unknown = older.decode_or_keep(&bytes).unwrap().err().unwrap();
+    /// assert_eq!(unknown.name, "score");
+    /// assert_eq!(unknown.payload, [7, 0, 0, 0]);
+    /// assert_eq!(unknown.to_envelope().unwrap(), bytes);
+    /// ```
+    pub fn decode_or_keep(
+        &self,
+        bytes: &[u8],
+    ) -> Result<Result<Box<B>, UnknownTypeValue>, CodecError> {
+        let (name, payload) = parse(bytes)?;
+        if !self.entries.contains_key(name) {
+            let unknown = UnknownTypeValue { name: name.to_string(), payload: payload.to_vec() };
+            return Ok(Err(unknown));
+        }
+        self.decode_payload(name, payload).map(Ok)
+    }
+
+    fn decode_payload(&self, name: &str, payload: &[u8]) -> Result<Box<B>, CodecError> {
+        let entry = self
+            .entries
+            .get(name)
+            .ok_or_else(|| CodecError::UnknownName { name: name.to_string() })?;
+        let codec = entry
+            .codec
+            .as_ref()
+            .ok_or_else(|| CodecError::NoCodec { name: name.to_string() })?;
+        let value =
+            (codec.decode)(payload).ok_or_else(|| CodecError::Payload { name: name.to_string() })?;
+        debug_assert!(
+            (*value).type_id_of() == entry.type_id,
+            "the codec of `{}` decoded a `{}`, not a `{}`",
+            name,
+            (*value).type_name(),
+            type_name::<B>()
+        );
+        Ok(value)
+    }
+}
+
+impl<S: Bounds> TypeRegistry<dyn BoxAny, S> {
+    /// Encodes the values of `map`, e.g. the resources of an entity, with the codecs of
+    /// their types. The envelopes are sorted, so equal maps encode to the same bytes.
+    ///
+    /// A value whose type is not registered or has no codec is skipped if
+    /// `skip_unregistered` is true, and fails the encoding otherwise. The envelopes kept by
+    /// `decode_map_keep_unknown` are written back as they were.
+    ///
+    /// ```
+    /// use pi_any::{AnyMap, BoxAny, TypeRegistry};
+    ///
+    /// struct Gold(u32);
+    /// struct Cursor;
+    ///
+    /// let mut registry = TypeRegistry::<dyn BoxAny>::new();
+    /// registry.register::<Gold>("gold", || Box::new(Gold(0)));
+    /// registry.register_codec::<Gold>(
+    ///     |gold, out| out.extend_from_slice(&gold.0.to_le_bytes()),
+    ///     |bytes| Some(Box::new(Gold(u32::from_le_bytes(bytes.try_into().ok()?)))),
+    /// );
+    ///
+    /// let mut map = AnyMap::new();
+    /// map.insert(Gold(30));
+    /// map.insert(Cursor);
+    /// assert!(registry.encode_map(&map, false).is_err());
+    /// let bytes = registry.encode_map(&map, true).unwrap();
+    ///
+    /// let map = registry.decode_map(&bytes).unwrap();
+    /// assert_eq!(map.get::<Gold>().unwrap().0, 30);
+    /// assert!(!map.contains::<Cursor>());
+    /// ```
+    pub fn encode_map(&self, map: &AnyMap, skip_unregistered: bool) -> Result<Vec<u8>, CodecError> {
+        let mut envelopes = Vec::with_capacity(map.len());
+        for value in map.values() {
+            if let Some(unknown) = value.as_any().downcast_ref::<Vec<UnknownTypeValue>>() {
+                for value in unknown {
+                    envelopes.push(value.to_envelope()?);
+                }
+                continue;
+            }
+            match self.encode(value) {
+                Ok(envelope) => envelopes.push(envelope),
+                Err(CodecError::Unregistered { .. } | CodecError::NoCodec { .. })
+                    if skip_unregistered => {}
+                Err(e) => return Err(e),
+            }
+        }
+        envelopes.sort_unstable();
+        let count = u32::try_from(envelopes.len()).map_err(|_| CodecError::Length)?;
+        let mut out = count.to_le_bytes().to_vec();
+        envelopes.iter().for_each(|envelope| out.extend_from_slice(envelope));
+        Ok(out)
+    }
+
+    /// Decodes a map encoded by `encode_map`.
+    pub fn decode_map(&self, bytes: &[u8]) -> Result<AnyMap, CodecError> {
+        self.decode_map_with(bytes, false)
+    }
+
+    /// Decodes a map like `decode_map`, but keeps the envelopes naming types that are not
+    /// registered, e.g. in a snapshot written by a newer build. They are put in the map as a
+    /// `Vec<UnknownTypeValue>`, which `encode_map` writes back, so no data is lost by loading
+    /// and saving the snapshot again.
+    ///
+    /// ```
+    /// use pi_any::{AnyMap, BoxAny, TypeRegistry, UnknownTypeValue};
+    ///
+    /// let mut newer = TypeRegistry::<dyn BoxAny>::new();
+    /// newer.register::<u32>("score", || Box::new(0u32));
+    /// newer.register::<u8>("level", || Box::new(0u8));
+    /// newer.register_pod_codec::<u32>(|v| Box::new(v));
+    /// newer.register_pod_codec::<u8>(|v| Box::new(v));
+    /// let mut map = AnyMap::new();
+    /// map.insert(7u32);
+    /// map.insert(2u8);
+    /// let bytes = newer.encode_map(&map, false).unwrap();
+    ///
+    /// let mut older = TypeRegistry::<dyn BoxAny>::new();
+    /// older.register::<u32>("score", || Box::new(0u32));
+    /// older.register_pod_codec::<u32>(|v| Box::new(v));
+    /// assert!(older.decode_map(&bytes).is_err());
+    /// let mut map = older.decode_map_keep_unknown(&bytes).unwrap();
+    /// assert_eq!(map.get::<Vec<UnknownTypeValue>>().unwrap()[0].name, "level");
+    /// *map.get_mut::<u32>().unwrap() += 1;
+    ///
+    /// let map = newer.decode_map(&older.encode_map(&map, false).unwrap()).unwrap();
+    /// assert_eq!((map.get::<u32>(), map.get::<u8>()), (Some(&8), Some(&2)));
+    /// ```
+    pub fn decode_map_keep_unknown(&self, bytes: &[u8]) -> Result<AnyMap, CodecError> {
+        self.decode_map_with(bytes, true)
+    }
+
+    fn decode_map_with(&self, bytes: &[u8], keep_unknown: bool) -> Result<AnyMap, CodecError> {
+        let (count, mut rest) = split(bytes, 4)?;
+        let mut map = AnyMap::new();
+        let mut unknown = Vec::new();
+        for _ in 0..u32::from_le_bytes(count.try_into().unwrap()) {
+            let (envelope, next) = split(rest, envelope_len(rest)?)?;
+            match self.decode_or_keep(envelope)? {
+                Ok(value) => map.insert_box(value),
+                Err(value) if keep_unknown => unknown.push(value),
+                Err(value) => return Err(CodecError::UnknownName { name: value.name }),
+            }
+            rest = next;
+        }
+        if !rest.is_empty() {
+            return Err(CodecError::Length);
+        }
+        if !unknown.is_empty() {
+            map.insert(unknown);
+        }
+        Ok(map)
+    }
+}
+
+/// Splits an envelope into the name and the payload.
+fn parse(bytes: &[u8]) -> Result<(&str, &[u8]), CodecError> {
+    let (name_len, rest) = split(bytes, 2)?;
+    let (name, rest) = split(rest, u16::from_le_bytes(name_len.try_into().unwrap()) as usize)?;
+    let (payload_len, payload) = split(rest, 4)?;
+    if payload.len() != u32::from_le_bytes(payload_len.try_into().unwrap()) as usize {
+        return Err(CodecError::Length);
+    }
+    let name = std::str::from_utf8(name).map_err(|_| CodecError::Name)?;
+    Ok((name, payload))
+}
+
+/// Returns the length of the envelope at the start of `bytes`, as given by its header.
+fn envelope_len(bytes: &[u8]) -> Result<usize, CodecError> {
+    let (name_len, rest) = split(bytes, 2)?;
+    let name_len = u16::from_le_bytes(name_len.try_into().unwrap()) as usize;
+    let (payload_len, _) = split(split(rest, name_len)?.1, 4)?;
+    let payload_len = u32::from_le_bytes(payload_len.try_into().unwrap()) as usize;
+    Ok(2 + name_len + 4 + payload_len)
+}
+
+fn split(bytes: &[u8], at: usize) -> Result<(&[u8], &[u8]), CodecError> {
+    if bytes.len() < at {
+        return Err(CodecError::Length);
+    }
+    Ok(bytes.split_at(at))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{CodecError, UnknownTypeValue};
+    use crate::{AnyMap, BoxAny, TypeRegistry};
+
+    trait Message: BoxAny {}
+    crate::impl_downcast_box!(Message);
+
+    #[derive(Debug, PartialEq)]
+    struct Move(i32, i32);
+    impl Message for Move {}
+    #[derive(Debug, PartialEq)]
+    struct Chat(String);
+    impl Message for Chat {}
+    impl Message for u32 {}
+    impl Message for () {}
+    impl Message for bool {}
+
+    fn registry() -> TypeRegistry<dyn Message> {
+        let mut registry = TypeRegistry::<dyn Message>::new();
+        registry.register::<Move>("move", || Box::new(Move(0, 0)));
+        registry.register::<Chat>("chat", || Box::new(Chat(String::new())));
+        registry.register::<u32>("tick", || Box::new(0u32));
+        registry.register::<()>("ping", || Box::new(()));
+        assert!(registry.register_codec::<Move>(
+            |m, out| [m.0, m.1].iter().for_each(|v| out.extend_from_slice(&v.to_le_bytes())),
+            |bytes| {
+                let v = |i: usize| Some(i32::from_le_bytes(bytes.get(i..i + 4)?.try_into().ok()?));
+                if bytes.len() != 8 {
+                    return None;
+                }
+                Some(Box::new(Move(v(0)?, v(4)?)))
+            },
+        ));
+        registry.register_codec::<Chat>(
+            |c, out| out.extend_from_slice(c.0.as_bytes()),
+            |bytes| Some(Box::new(Chat(String::from_utf8(bytes.to_vec()).ok()?))),
+        );
+        registry.register_pod_codec::<u32>(|v| Box::new(v));
+        assert!(!registry.register_pod_codec::<u8>(|v| Box::new(v as u32)));
+        registry
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let registry = registry();
+        let messages: Vec<Box<dyn Message>> =
+            vec![Box::new(Move(-1, 2)), Box::new(Chat("hi".into())), Box::new(7u32)];
+        for message in &messages {
+            let bytes = registry.encode(&**message).unwrap();
+            let decoded = registry.decode(&bytes).unwrap();
+            assert_eq!(decoded.type_name(), message.type_name());
+        }
+        let bytes = registry.encode(&Chat("hi".into())).unwrap();
+        assert_eq!(bytes, b"\x04\x00chat\x02\x00\x00\x00hi");
+        assert_eq!(registry.decode(&bytes).unwrap().downcast_ref(), Some(&Chat("hi".into())));
+    }
+
+    #[test]
+    fn test_errors() {
+        let registry = registry();
+        assert!(registry.has_codec("move") && !registry.has_codec("ping"));
+        assert_eq!(registry.encode(&()), Err(CodecError::NoCodec { name: "ping".into() }));
+        assert!(matches!(registry.encode(&true), Err(CodecError::Unregistered { .. })));
+        assert!(matches!(registry.encode(&Move(0, 0)).map(|b| b.len()), Ok(18)));
+
+        let decode = |bytes: &[u8]| registry.decode(bytes).err();
+        let bytes = registry.encode(&7u32).unwrap();
+        assert_eq!(decode(&bytes[..bytes.len() - 1]), Some(CodecError::Length));
+        assert_eq!(decode(&[bytes.as_slice(), &[0]].concat()), Some(CodecError::Length));
+        assert_eq!(decode(b"\x04\x00tock\0\0\0\0"), Some(CodecError::UnknownName { name: "tock".into() }));
+        assert_eq!(decode(b"\x04\x00move\x01\0\0\0\0"), Some(CodecError::Payload { name: "move".into() }));
+        assert_eq!(decode(b"\x02\x00\xff\xfe\0\0\0\0"), Some(CodecError::Name));
+    }
+
+    #[test]
+    fn test_map() {
+        let mut registry = TypeRegistry::<dyn BoxAny>::new();
+        registry.register::<u32>("tick", || Box::new(0u32));
+        registry.register::<String>("name", || Box::new(String::new()));
+        registry.register::<()>("ping", || Box::new(()));
+        registry.register_pod_codec::<u32>(|v| Box::new(v));
+        registry.register_codec::<String>(
+            |s, out| out.extend_from_slice(s.as_bytes()),
+            |bytes| Some(Box::new(String::from_utf8(bytes.to_vec()).ok()?)),
+        );
+
+        let mut map = AnyMap::new();
+        map.insert(7u32);
+        map.insert(String::from("hero"));
+        let bytes = registry.encode_map(&map, false).unwrap();
+        assert_eq!(&bytes[..4], &[2, 0, 0, 0]);
+        let decoded = registry.decode_map(&bytes).unwrap();
+        assert_eq!(decoded.get::<u32>(), Some(&7));
+        assert_eq!(decoded.get::<String>().unwrap(), "hero");
+        assert_eq!(registry.encode_map(&decoded, false).unwrap(), bytes);
+
+        map.insert(());
+        let err = registry.encode_map(&map, false);
+        assert_eq!(err, Err(CodecError::NoCodec { name: "ping".into() }));
+        map.insert(true);
+        assert_eq!(registry.encode_map(&map, true).unwrap(), bytes);
+        map.remove::<()>();
+        assert!(matches!(registry.encode_map(&map, false), Err(CodecError::Unregistered { .. })));
+
+        assert_eq!(registry.decode_map(&bytes[..bytes.len() - 1]).err(), Some(CodecError::Length));
+        let trailing = [bytes.as_slice(), &[0]].concat();
+        assert_eq!(registry.decode_map(&trailing).err(), Some(CodecError::Length));
+        assert!(registry.decode_map(&[0; 4]).unwrap().is_empty());
+    }
+    #[test]
+    fn test_unknown() {
+        let registry = registry();
+        let mut older = TypeRegistry::<dyn Message>::new();
+        older.register::<u32>("tick", || Box::new(0u32));
+        older.register_pod_codec::<u32>(|v| Box::new(v));
+
+        let bytes = registry.encode(&Chat("hi".into())).unwrap();
+        let unknown = older.decode_or_keep(&bytes).unwrap().err().unwrap();
+        assert_eq!(unknown, UnknownTypeValue { name: "chat".into(), payload: b"hi".to_vec() });
+        assert_eq!(UnknownTypeValue::from_envelope(&bytes), Ok(unknown.clone()));
+        assert_eq!(unknown.to_envelope().unwrap(), bytes);
+        let tick = older.decode_or_keep(&registry.encode(&7u32).unwrap()).unwrap().ok().unwrap();
+        assert_eq!(tick.downcast_ref::<u32>(), Some(&7));
+        assert_eq!(older.decode_or_keep(&bytes[1..]).err(), Some(CodecError::Length));
+        // A registered type whose payload is rejected still fails.
+        let bad = UnknownTypeValue { name: "tick".into(), payload: vec![1] };
+        assert!(older.decode_or_keep(&bad.to_envelope().unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_map_unknown() {
+        let mut registry = TypeRegistry::<dyn BoxAny>::new();
+        registry.register::<u32>("tick", || Box::new(0u32));
+        registry.register::<u8>("level", || Box::new(0u8));
+        registry.register::<u16>("rank", || Box::new(0u16));
+        registry.register_pod_codec::<u32>(|v| Box::new(v));
+        registry.register_pod_codec::<u8>(|v| Box::new(v));
+        registry.register_pod_codec::<u16>(|v| Box::new(v));
+        let mut older = TypeRegistry::<dyn BoxAny>::new();
+        older.register::<u32>("tick", || Box::new(0u32));
+        older.register_pod_codec::<u32>(|v| Box::new(v));
+
+        let mut map = AnyMap::new();
+        map.insert(7u32);
+        map.insert(2u8);
+        map.insert(3u16);
+        let bytes = registry.encode_map(&map, false).unwrap();
+        let err = older.decode_map(&bytes).err().unwrap();
+        assert!(matches!(err, CodecError::UnknownName { .. }));
+
+        let kept = older.decode_map_keep_unknown(&bytes).unwrap();
+        assert_eq!(kept.len(), 2);
+        let unknown = kept.get::<Vec<UnknownTypeValue>>().unwrap();
+        let mut names: Vec<_> = unknown.iter().map(|value| &value.name).collect();
+        names.sort();
+        assert_eq!(names, ["level", "rank"]);
+        assert_eq!(older.encode_map(&kept, false).unwrap(), bytes);
+        assert!(older.decode_map_keep_unknown(&older.encode_map(&AnyMap::new(), false).unwrap())
+            .unwrap()
+            .is_empty());
+    }
+}
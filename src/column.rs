@@ -0,0 +1,336 @@
+//! `AnyColumn`, contiguous storage for values of a single type that is only known at
+//! runtime.
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::any::{type_name, TypeId};
+use core::fmt;
+
+use crate::pod::{Pod, PodError, PodLayout};
+use crate::{BoxAny, FailurePolicy, TypeMismatch};
+
+/// The operations on a `Vec<T>` that don't need to know `T`.
+trait Column: BoxAny {
+    fn len(&self) -> usize;
+    fn swap_remove(&mut self, index: usize);
+    fn truncate(&mut self, len: usize);
+    fn reserve(&mut self, additional: usize);
+}
+crate::impl_downcast_box!(Column);
+
+impl<T: 'static> Column for Vec<T> {
+    fn len(&self) -> usize {
+        Vec::len(self)
+    }
+    fn swap_remove(&mut self, index: usize) {
+        Vec::swap_remove(self, index);
+    }
+    fn truncate(&mut self, len: usize) {
+        Vec::truncate(self, len)
+    }
+    fn reserve(&mut self, additional: usize) {
+        Vec::reserve(self, additional)
+    }
+}
+
+/// The byte conversions of a column created for a `Pod` type.
+#[derive(Clone, Copy)]
+struct PodFns {
+    layout: PodLayout,
+    export: fn(&dyn Column, &mut Vec<u8>),
+    import: fn(&mut dyn Column, &[u8]),
+}
+
+impl PodFns {
+    fn of<T: Pod>() -> Self {
+        PodFns {
+            layout: PodLayout::of::<T>(),
+            export: |data, out| {
+                for v in data.downcast_ref::<Vec<T>>().unwrap() {
+                    v.write_le(out);
+                }
+            },
+            import: |data, bytes| {
+                let vec = data.downcast_mut::<Vec<T>>().unwrap();
+                vec.extend(bytes.chunks_exact(T::SIZE).map(T::read_le));
+            },
+        }
+    }
+}
+
+/// A `Vec<T>` with `T` erased.
+///
+/// The item type is fixed on construction. Typed access goes through `as_slice::<T>()` and
+/// friends, which return `None` for any other type, while removal and length queries work
+/// without naming the type, so a store can manage columns of different types uniformly.
+///
+/// Columns created with `new_pod` or `from_pod_vec` can also be exported to and imported
+/// from bytes, e.g. for saving or uploading bulk data.
+pub struct AnyColumn {
+    type_id: TypeId,
+    type_name: &'static str,
+    data: Box<dyn Column>,
+    pod: Option<PodFns>,
+    policy: Option<FailurePolicy>,
+}
+
+impl AnyColumn {
+    /// Creates an empty column for items of type `T`.
+    pub fn new<T: 'static>() -> Self {
+        Self::from_vec(Vec::<T>::new())
+    }
+
+    /// Creates an empty column for items of type `T` with room for `capacity` items.
+    pub fn with_capacity<T: 'static>(capacity: usize) -> Self {
+        Self::from_vec(Vec::<T>::with_capacity(capacity))
+    }
+
+    /// Creates a column holding the items of `vec`.
+    pub fn from_vec<T: 'static>(vec: Vec<T>) -> Self {
+        AnyColumn {
+            type_id: TypeId::of::<T>(),
+            type_name: type_name::<T>(),
+            data: Box::new(vec),
+            pod: None,
+            policy: None,
+        }
+    }
+
+    /// Creates an empty column for `Pod` items, which can be exported to and imported from
+    /// bytes.
+    pub fn new_pod<T: Pod>() -> Self {
+        Self::from_pod_vec(Vec::<T>::new())
+    }
+
+    /// Creates a column for `Pod` items holding the items of `vec`.
+    pub fn from_pod_vec<T: Pod>(vec: Vec<T>) -> Self {
+        AnyColumn { pod: Some(PodFns::of::<T>()), ..Self::from_vec(vec) }
+    }
+
+    /// Returns the `TypeId` of the items.
+    pub fn item_type_id(&self) -> TypeId {
+        self.type_id
+    }
+
+    /// Returns the name of the item type, as given by `std::any::type_name`.
+    pub fn item_type_name(&self) -> &'static str {
+        self.type_name
+    }
+
+    /// Returns true if the items are of type `T`.
+    pub fn is<T: 'static>(&self) -> bool {
+        self.type_id == TypeId::of::<T>()
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Reserves room for at least `additional` more items.
+    pub fn reserve(&mut self, additional: usize) {
+        self.data.reserve(additional)
+    }
+
+    /// Appends `value`, panicking if the items are not of type `T`.
+    #[track_caller]
+    pub fn push<T: 'static>(&mut self, value: T) {
+        match self.as_vec_mut::<T>() {
+            Some(vec) => vec.push(value),
+            None => crate::__downcast_failed("AnyColumn::push", type_name::<T>(), self.type_name),
+        }
+    }
+
+    /// Appends `value`, or gives it back if the items are not of type `T`, after handling
+    /// the `TypeMismatch` by the failure policy.
+    #[track_caller]
+    pub fn try_push<T: 'static>(&mut self, value: T) -> Result<(), T> {
+        match self.as_vec_mut::<T>() {
+            Some(vec) => {
+                vec.push(value);
+                Ok(())
+            }
+            None => {
+                let mismatch = TypeMismatch { expected: type_name::<T>(), found: self.type_name };
+                crate::__fail(self.policy, "AnyColumn::try_push", mismatch);
+                Err(value)
+            }
+        }
+    }
+
+    /// Sets the failure policy of `try_push`, or lets it follow that of the crate if it is
+    /// `None`.
+    pub fn set_failure_policy(&mut self, policy: Option<FailurePolicy>) {
+        self.policy = policy;
+    }
+
+    /// Removes the item at `index` and drops it, replacing it with the last item.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn swap_remove(&mut self, index: usize) {
+        self.data.swap_remove(index)
+    }
+
+    /// Drops the items from `len` on; does nothing if the column is not longer than `len`.
+    pub fn truncate(&mut self, len: usize) {
+        self.data.truncate(len)
+    }
+
+    /// Drops all items.
+    pub fn clear(&mut self) {
+        self.truncate(0)
+    }
+
+    /// Returns the items if they are of type `T`.
+    pub fn as_slice<T: 'static>(&self) -> Option<&[T]> {
+        self.as_vec::<T>().map(Vec::as_slice)
+    }
+
+    /// Returns the items mutably if they are of type `T`.
+    pub fn as_mut_slice<T: 'static>(&mut self) -> Option<&mut [T]> {
+        self.as_vec_mut::<T>().map(Vec::as_mut_slice)
+    }
+
+    /// Returns the underlying `Vec<T>` if the items are of type `T`.
+    pub fn as_vec<T: 'static>(&self) -> Option<&Vec<T>> {
+        self.data.downcast_ref::<Vec<T>>()
+    }
+
+    /// Returns the underlying `Vec<T>` mutably if the items are of type `T`, e.g. to drain it.
+    pub fn as_vec_mut<T: 'static>(&mut self) -> Option<&mut Vec<T>> {
+        self.data.downcast_mut::<Vec<T>>()
+    }
+
+    /// Converts the column back into a `Vec<T>`, or returns it unchanged if the items are
+    /// not of type `T`.
+    pub fn into_vec<T: 'static>(self) -> Result<Vec<T>, Self> {
+        let AnyColumn { type_id, type_name, data, pod, policy } = self;
+        match data.downcast::<Vec<T>>() {
+            Ok(vec) => Ok(*vec),
+            Err(data) => Err(AnyColumn { type_id, type_name, data, pod, policy }),
+        }
+    }
+
+    /// Returns the layout of the items if the column was created for a `Pod` type.
+    pub fn pod_layout(&self) -> Option<PodLayout> {
+        self.pod.map(|pod| pod.layout)
+    }
+
+    /// Encodes the items as little-endian bytes, or returns `None` if the column was not
+    /// created for a `Pod` type.
+    pub fn export_bytes(&self) -> Option<Vec<u8>> {
+        let pod = self.pod?;
+        let mut out = Vec::with_capacity(pod.layout.size * self.len());
+        (pod.export)(&*self.data, &mut out);
+        Some(out)
+    }
+
+    /// Appends the items encoded in `bytes`, which must have been exported from a column
+    /// with the same `layout`, and returns how many were added.
+    pub fn import_bytes(&mut self, layout: PodLayout, bytes: &[u8]) -> Result<usize, PodError> {
+        let pod = self.pod.ok_or(PodError::NotPod { type_name: self.type_name })?;
+        if layout != pod.layout {
+            return Err(PodError::Layout { expected: pod.layout, found: layout });
+        }
+        let size = pod.layout.size;
+        if size == 0 && bytes.is_empty() {
+            return Ok(0);
+        }
+        if size == 0 || bytes.len() % size != 0 {
+            return Err(PodError::Length { len: bytes.len(), size });
+        }
+        (pod.import)(&mut *self.data, bytes);
+        Ok(bytes.len() / size)
+    }
+}
+
+impl fmt::Debug for AnyColumn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AnyColumn").field("type", &self.type_name).field("len", &self.len()).finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+
+    use super::AnyColumn;
+    use crate::{FailurePolicy, PodError, PodLayout};
+
+    #[test]
+    fn test_typed_access() {
+        let mut column = AnyColumn::new::<u32>();
+        assert!(column.is::<u32>());
+        assert!(column.is_empty());
+        column.push(1u32);
+        column.push(2u32);
+        assert_eq!(column.try_push(3u8), Err(3u8));
+        assert_eq!(column.as_slice::<u32>(), Some(&[1, 2][..]));
+        assert!(column.as_slice::<u8>().is_none());
+
+        column.as_mut_slice::<u32>().unwrap()[0] = 7;
+        column.as_vec_mut::<u32>().unwrap().extend([8, 9]);
+        assert_eq!(column.len(), 4);
+        let column = column.into_vec::<u8>().unwrap_err();
+        assert_eq!(column.into_vec::<u32>().unwrap(), vec![7, 2, 8, 9]);
+    }
+
+    #[test]
+    fn test_erased_removal() {
+        let item = Rc::new(());
+        let mut column = AnyColumn::from_vec(vec![item.clone(), item.clone(), item.clone()]);
+        assert_eq!(Rc::strong_count(&item), 4);
+
+        column.swap_remove(0);
+        assert_eq!(column.len(), 2);
+        assert_eq!(Rc::strong_count(&item), 3);
+
+        column.clear();
+        assert!(column.is_empty());
+        assert_eq!(Rc::strong_count(&item), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "AnyColumn::push: expected `u8`, found `u32`")]
+    fn test_push_mismatch() {
+        AnyColumn::new::<u32>().push(1u8);
+    }
+
+    #[test]
+    #[should_panic(expected = "AnyColumn::try_push: expected `u8`, found `u32`")]
+    fn test_try_push_policy() {
+        let mut column = AnyColumn::new::<u32>();
+        assert_eq!(column.try_push(1u8), Err(1));
+        column.set_failure_policy(Some(FailurePolicy::Panic));
+        let _ = column.try_push(1u8);
+    }
+
+    #[test]
+    fn test_pod_bytes() {
+        let column = AnyColumn::from_pod_vec(vec![[1u16, 2], [3, 4]]);
+        let layout = column.pod_layout().unwrap();
+        assert_eq!(layout, PodLayout { size: 4, align: 2 });
+        let bytes = column.export_bytes().unwrap();
+        assert_eq!(bytes, [1, 0, 2, 0, 3, 0, 4, 0]);
+
+        let mut copy = AnyColumn::new_pod::<[u16; 2]>();
+        assert_eq!(copy.import_bytes(layout, &bytes), Ok(2));
+        assert_eq!(copy.as_slice::<[u16; 2]>(), Some(&[[1, 2], [3, 4]][..]));
+        assert_eq!(copy.import_bytes(layout, &bytes[..3]), Err(PodError::Length { len: 3, size: 4 }));
+
+        let mut other = AnyColumn::new_pod::<u32>();
+        assert_eq!(
+            other.import_bytes(layout, &bytes),
+            Err(PodError::Layout { expected: PodLayout { size: 4, align: 4 }, found: layout })
+        );
+
+        let mut plain = AnyColumn::new::<u32>();
+        assert!(plain.export_bytes().is_none());
+        assert!(matches!(plain.import_bytes(layout, &bytes), Err(PodError::NotPod { .. })));
+    }
+}
@@ -0,0 +1,239 @@
+//! `CommandDispatcher`, routing boxed commands to handlers by type, with undo and redo.
+
+use std::any::TypeId;
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::BoxAny;
+
+/// A command that can be sent to a `CommandDispatcher`. Implement it with `impl_command!`.
+pub trait Command: BoxAny {}
+crate::impl_downcast_box!(Command);
+
+/// Implements `Command` for the given types.
+///
+/// ```
+/// # #[macro_use]
+/// # extern crate pi_any;
+/// struct Save;
+/// struct Rename(String);
+/// impl_command!(Save, Rename);
+/// # fn main() {}
+/// ```
+#[macro_export]
+macro_rules! impl_command {
+    ($($ty:ty),+ $(,)?) => {
+        $( impl $crate::Command for $ty {} )+
+    };
+}
+
+type Handler<C> = Box<dyn FnMut(&mut dyn Command, &mut C)>;
+
+struct Handlers<C> {
+    apply: Option<Handler<C>>,
+    undo: Option<Handler<C>>,
+}
+
+/// Applies commands to a context of type `C` with the handler registered for their type,
+/// and keeps the applied commands so they can be undone and redone.
+///
+/// Handlers get the command mutably, so they can record what undoing it needs. Applying a
+/// command whose type has no undo handler clears the history, as it cannot be undone past.
+///
+/// ```
+/// # #[macro_use]
+/// # extern crate pi_any;
+/// use pi_any::CommandDispatcher;
+///
+/// struct Push(char);
+/// struct Clear(String);
+/// impl_command!(Push, Clear);
+///
+/// # fn main() {
+/// let mut text = String::new();
+/// let mut commands = CommandDispatcher::new();
+/// commands.on(|c: &mut Push, text: &mut String| text.push(c.0));
+/// commands.on_undo(|_: &mut Push, text: &mut String| { text.pop(); });
+/// commands.on(|c: &mut Clear, text: &mut String| c.0 = std::mem::take(text));
+///
+/// commands.dispatch(Box::new(Push('a')), &mut text).ok().unwrap();
+/// commands.dispatch(Box::new(Push('b')), &mut text).ok().unwrap();
+/// assert!(commands.undo(&mut text));
+/// assert_eq!(text, "a");
+/// assert!(commands.redo(&mut text));
+/// assert_eq!(text, "ab");
+///
+/// commands.dispatch(Box::new(Clear(String::new())), &mut text).ok().unwrap();
+/// assert!(!commands.can_undo());
+/// # }
+/// ```
+pub struct CommandDispatcher<C> {
+    handlers: HashMap<TypeId, Handlers<C>>,
+    done: Vec<Box<dyn Command>>,
+    undone: Vec<Box<dyn Command>>,
+}
+
+impl<C> CommandDispatcher<C> {
+    pub fn new() -> Self {
+        CommandDispatcher { handlers: HashMap::new(), done: Vec::new(), undone: Vec::new() }
+    }
+
+    /// Applies commands of type `T` with `handler`, replacing an earlier one.
+    pub fn on<T: Command>(&mut self, handler: impl FnMut(&mut T, &mut C) + 'static) {
+        self.entry::<T>().apply = Some(erase(handler));
+    }
+
+    /// Undoes commands of type `T` with `handler`, replacing an earlier one.
+    pub fn on_undo<T: Command>(&mut self, handler: impl FnMut(&mut T, &mut C) + 'static) {
+        self.entry::<T>().undo = Some(erase(handler));
+    }
+
+    /// Returns true if commands of type `T` have a handler.
+    pub fn handles<T: Command>(&self) -> bool {
+        self.handlers.get(&TypeId::of::<T>()).is_some_and(|h| h.apply.is_some())
+    }
+
+    /// Applies `command` to `ctx`, or returns it if its type has no handler. The commands
+    /// undone before are dropped.
+    pub fn dispatch(
+        &mut self,
+        mut command: Box<dyn Command>,
+        ctx: &mut C,
+    ) -> Result<(), Box<dyn Command>> {
+        let type_id = (*command).type_id_of();
+        let Some(handlers) = self.handlers.get_mut(&type_id) else {
+            return Err(command);
+        };
+        let Some(apply) = &mut handlers.apply else {
+            return Err(command);
+        };
+        apply(&mut *command, ctx);
+        self.undone.clear();
+        if handlers.undo.is_some() {
+            self.done.push(command);
+        } else {
+            self.done.clear();
+        }
+        Ok(())
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.done.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.undone.is_empty()
+    }
+
+    /// Undoes the last command applied, returning false if there is none.
+    pub fn undo(&mut self, ctx: &mut C) -> bool {
+        let Some(mut command) = self.done.pop() else {
+            return false;
+        };
+        let handlers = self.handlers.get_mut(&(*command).type_id_of()).unwrap();
+        (handlers.undo.as_mut().unwrap())(&mut *command, ctx);
+        self.undone.push(command);
+        true
+    }
+
+    /// Applies the last command undone again, returning false if there is none.
+    pub fn redo(&mut self, ctx: &mut C) -> bool {
+        let Some(mut command) = self.undone.pop() else {
+            return false;
+        };
+        let handlers = self.handlers.get_mut(&(*command).type_id_of()).unwrap();
+        (handlers.apply.as_mut().unwrap())(&mut *command, ctx);
+        self.done.push(command);
+        true
+    }
+
+    /// Drops the commands that can be undone or redone.
+    pub fn clear_history(&mut self) {
+        self.done.clear();
+        self.undone.clear();
+    }
+
+    fn entry<T: Command>(&mut self) -> &mut Handlers<C> {
+        self.handlers.entry(TypeId::of::<T>()).or_insert(Handlers { apply: None, undo: None })
+    }
+}
+
+fn erase<T: Command, C>(mut handler: impl FnMut(&mut T, &mut C) + 'static) -> Handler<C> {
+    Box::new(move |command, ctx| handler(command.downcast_mut().unwrap(), ctx))
+}
+
+impl<C> Default for CommandDispatcher<C> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C> fmt::Debug for CommandDispatcher<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names = |commands: &[Box<dyn Command>]| -> Vec<_> {
+            commands.iter().map(|c| (**c).type_name()).collect()
+        };
+        f.debug_struct("CommandDispatcher")
+            .field("done", &names(&self.done))
+            .field("undone", &names(&self.undone))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::CommandDispatcher;
+
+    struct Add(i32);
+    struct Set(i32, Option<i32>);
+    struct Print;
+    struct Unknown;
+    crate::impl_command!(Add, Set, Print, Unknown);
+
+    fn dispatcher() -> CommandDispatcher<i32> {
+        let mut commands = CommandDispatcher::new();
+        commands.on(|c: &mut Add, n: &mut i32| *n += c.0);
+        commands.on_undo(|c: &mut Add, n: &mut i32| *n -= c.0);
+        commands.on(|c: &mut Set, n: &mut i32| c.1 = Some(std::mem::replace(n, c.0)));
+        commands.on_undo(|c: &mut Set, n: &mut i32| *n = c.1.take().unwrap());
+        commands.on(|_: &mut Print, _: &mut i32| ());
+        commands
+    }
+
+    #[test]
+    fn test() {
+        let mut n = 0;
+        let mut commands = dispatcher();
+        assert!(commands.handles::<Add>() && !commands.handles::<Unknown>());
+
+        commands.dispatch(Box::new(Add(2)), &mut n).ok().unwrap();
+        commands.dispatch(Box::new(Set(10, None)), &mut n).ok().unwrap();
+        commands.dispatch(Box::new(Add(1)), &mut n).ok().unwrap();
+        assert_eq!(n, 11);
+        assert!(commands.dispatch(Box::new(Unknown), &mut n).err().unwrap().is::<Unknown>());
+
+        assert!(commands.undo(&mut n) && commands.undo(&mut n));
+        assert_eq!(n, 2);
+        assert!(commands.redo(&mut n));
+        assert_eq!(n, 10);
+        assert_eq!(format!("{:?}", commands).matches("Add").count(), 2);
+
+        commands.dispatch(Box::new(Add(5)), &mut n).ok().unwrap();
+        assert!(!commands.can_redo());
+        assert!(commands.undo(&mut n) && commands.undo(&mut n) && commands.undo(&mut n));
+        assert!(!commands.undo(&mut n));
+        assert_eq!(n, 0);
+    }
+
+    #[test]
+    fn test_irreversible() {
+        let mut n = 0;
+        let mut commands = dispatcher();
+        commands.dispatch(Box::new(Add(1)), &mut n).ok().unwrap();
+        commands.undo(&mut n);
+        commands.dispatch(Box::new(Add(2)), &mut n).ok().unwrap();
+        commands.dispatch(Box::new(Print), &mut n).ok().unwrap();
+        assert!(!commands.can_undo() && !commands.can_redo());
+        assert_eq!(n, 2);
+    }
+}
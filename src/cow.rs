@@ -0,0 +1,170 @@
+//! `CowAny`, a borrowed trait object that is cloned once it is mutated.
+
+use alloc::boxed::Box;
+use core::any::Any;
+use core::fmt;
+use core::ops::Deref;
+
+use crate::{clone_box, AsAny, AsMutAny, CloneAny};
+
+/// A trait object that is either borrowed or owned, like `Cow<dyn Trait>` for a trait
+/// extending `CloneAny`.
+///
+/// Reading goes through `Deref` and `downcast_ref` without cloning. `to_mut::<T>()` clones a
+/// borrowed value into a box the first time it is called, and only if the value is a `T`,
+/// so e.g. a layer of settings over shared defaults copies the ones it changes.
+///
+/// ```
+/// # #[macro_use]
+/// # extern crate pi_any;
+/// use pi_any::{CloneAny, CowAny};
+///
+/// trait Setting: CloneAny {}
+/// impl_clone_box!(Setting);
+///
+/// #[derive(Clone)]
+/// struct Volume(u8);
+/// impl Setting for Volume {}
+///
+/// # fn main() {
+/// let default: Box<dyn Setting> = Box::new(Volume(5));
+/// let mut setting = CowAny::Borrowed(&*default);
+/// assert_eq!(setting.downcast_ref::<Volume>().unwrap().0, 5);
+/// assert!(setting.to_mut::<String>().is_none() && !setting.is_owned());
+///
+/// setting.to_mut::<Volume>().unwrap().0 = 8;
+/// assert!(setting.is_owned());
+/// assert_eq!(setting.downcast_ref::<Volume>().unwrap().0, 8);
+/// # }
+/// ```
+pub enum CowAny<'a, D: ?Sized + 'a> {
+    Borrowed(&'a D),
+    Owned(Box<D>),
+}
+
+impl<'a, D: CloneAny + ?Sized> CowAny<'a, D> {
+    /// Returns true if the value has been cloned or was given owned.
+    pub fn is_owned(&self) -> bool {
+        matches!(self, CowAny::Owned(_))
+    }
+
+    /// Returns true if the value is of type `T`.
+    #[inline]
+    pub fn is<T: Any>(&self) -> bool {
+        AsAny::as_any(&**self).is::<T>()
+    }
+
+    /// Returns a reference to the value if it is of type `T`, or `None` if it isn't.
+    #[inline]
+    pub fn downcast_ref<T: Any>(&self) -> Option<&T> {
+        AsAny::as_any(&**self).downcast_ref()
+    }
+
+    /// Returns a mutable reference to the value if it is of type `T`, cloning it first if
+    /// it is borrowed, or `None` without cloning if it isn't.
+    pub fn to_mut<T: Any>(&mut self) -> Option<&mut T> {
+        if !self.is::<T>() {
+            return None;
+        }
+        AsMutAny::as_any_mut(self.make_owned()).downcast_mut()
+    }
+
+    /// Returns a mutable reference to the value, cloning it first if it is borrowed.
+    pub fn make_owned(&mut self) -> &mut D {
+        if let CowAny::Borrowed(value) = *self {
+            *self = CowAny::Owned(clone_box(value));
+        }
+        match self {
+            CowAny::Owned(value) => value,
+            CowAny::Borrowed(_) => unreachable!(),
+        }
+    }
+
+    /// Returns the value in a box, cloning it if it is borrowed.
+    pub fn into_owned(self) -> Box<D> {
+        match self {
+            CowAny::Borrowed(value) => clone_box(value),
+            CowAny::Owned(value) => value,
+        }
+    }
+}
+
+impl<D: ?Sized> Deref for CowAny<'_, D> {
+    type Target = D;
+
+    fn deref(&self) -> &D {
+        match self {
+            CowAny::Borrowed(value) => value,
+            CowAny::Owned(value) => value,
+        }
+    }
+}
+
+impl<D: CloneAny + ?Sized> Clone for CowAny<'_, D> {
+    fn clone(&self) -> Self {
+        match self {
+            CowAny::Borrowed(value) => CowAny::Borrowed(value),
+            CowAny::Owned(value) => CowAny::Owned(clone_box(&**value)),
+        }
+    }
+}
+
+impl<'a, D: ?Sized> From<&'a D> for CowAny<'a, D> {
+    fn from(value: &'a D) -> Self {
+        CowAny::Borrowed(value)
+    }
+}
+
+impl<D: ?Sized> From<Box<D>> for CowAny<'_, D> {
+    fn from(value: Box<D>) -> Self {
+        CowAny::Owned(value)
+    }
+}
+
+impl<D: fmt::Debug + ?Sized> fmt::Debug for CowAny<'_, D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CowAny::Borrowed(value) => f.debug_tuple("Borrowed").field(&value).finish(),
+            CowAny::Owned(value) => f.debug_tuple("Owned").field(&value).finish(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+
+    use super::CowAny;
+    use crate::CloneAny;
+
+    trait Layer: CloneAny {}
+
+    #[derive(Clone)]
+    struct Foo(Rc<u32>);
+    impl Layer for Foo {}
+
+    #[test]
+    fn test() {
+        let rc = Rc::new(1);
+        let base: Box<dyn Layer> = Box::new(Foo(rc.clone()));
+        let mut cow = CowAny::from(&*base);
+        let copy = cow.clone();
+        assert!(cow.is::<Foo>() && !cow.is::<u32>());
+        assert!(cow.to_mut::<u32>().is_none());
+        assert_eq!(Rc::strong_count(&rc), 2);
+
+        cow.to_mut::<Foo>().unwrap().0 = Rc::new(2);
+        assert!(cow.is_owned() && !copy.is_owned());
+        assert_eq!(*cow.downcast_ref::<Foo>().unwrap().0, 2);
+        assert_eq!(*copy.downcast_ref::<Foo>().unwrap().0, 1);
+        cow.to_mut::<Foo>().unwrap();
+        assert_eq!(Rc::strong_count(&rc), 2);
+
+        let owned = copy.into_owned();
+        assert_eq!(Rc::strong_count(&rc), 3);
+        let cow: CowAny<dyn Layer> = owned.into();
+        assert!(cow.clone().is_owned());
+        drop((cow, base));
+        assert_eq!(Rc::strong_count(&rc), 1);
+    }
+}
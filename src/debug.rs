@@ -0,0 +1,15 @@
+//! `DynDebug`, formatting trait objects with the `Debug` of their concrete type.
+
+use core::fmt::Debug;
+
+/// Implemented for every `Debug` type, so a trait extending `DynDebug` can implement `Debug`
+/// for its trait objects with the `debug` option of `impl_downcast!`.
+pub trait DynDebug {
+    fn as_debug(&self) -> &dyn Debug;
+}
+
+impl<T: Debug> DynDebug for T {
+    fn as_debug(&self) -> &dyn Debug {
+        self
+    }
+}
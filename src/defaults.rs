@@ -0,0 +1,108 @@
+//! `DefaultRegistry`, constructing default values of types known only by their `TypeId`.
+
+use std::any::{type_name, Any, TypeId};
+use std::collections::HashMap;
+use std::fmt;
+
+struct Entry {
+    construct: fn() -> Box<dyn Any>,
+    name: &'static str,
+}
+
+/// Maps `TypeId`s to the `Default` impls of their types, so code that finds a type only at
+/// run time, such as a deserializer or an editor, can create a value of it.
+///
+/// Unlike `TypeRegistry`, types need no name, and the values are always `Box<dyn Any>`.
+///
+/// ```
+/// use std::any::TypeId;
+/// use pi_any::DefaultRegistry;
+///
+/// #[derive(Default)]
+/// struct Health(u32);
+///
+/// let mut defaults = DefaultRegistry::new();
+/// defaults.register_default::<Health>();
+/// defaults.register_default::<Vec<String>>();
+///
+/// let value = defaults.construct(TypeId::of::<Health>()).unwrap();
+/// assert_eq!(value.downcast_ref::<Health>().unwrap().0, 0);
+/// assert!(defaults.construct(TypeId::of::<u8>()).is_none());
+/// ```
+#[derive(Default)]
+pub struct DefaultRegistry {
+    entries: HashMap<TypeId, Entry>,
+}
+
+impl DefaultRegistry {
+    pub fn new() -> Self {
+        DefaultRegistry { entries: HashMap::new() }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Registers `T`, returning false if it was registered already.
+    pub fn register_default<T: Any + Default>(&mut self) -> bool {
+        let entry = Entry { construct: || Box::new(T::default()), name: type_name::<T>() };
+        self.entries.insert(TypeId::of::<T>(), entry).is_none()
+    }
+
+    pub fn contains(&self, type_id: TypeId) -> bool {
+        self.entries.contains_key(&type_id)
+    }
+
+    /// Returns the name of the type with `type_id`, as given by `std::any::type_name`, if it
+    /// is registered.
+    pub fn type_name(&self, type_id: TypeId) -> Option<&'static str> {
+        self.entries.get(&type_id).map(|entry| entry.name)
+    }
+
+    /// Creates the default value of the type with `type_id`, if it is registered.
+    pub fn construct(&self, type_id: TypeId) -> Option<Box<dyn Any>> {
+        self.entries.get(&type_id).map(|entry| (entry.construct)())
+    }
+}
+
+impl fmt::Debug for DefaultRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.entries.values().map(|entry| entry.name)).finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::any::TypeId;
+
+    use super::DefaultRegistry;
+
+    #[derive(Default, Debug, PartialEq)]
+    struct Foo {
+        name: String,
+        count: u32,
+    }
+
+    #[test]
+    fn test() {
+        let mut defaults = DefaultRegistry::new();
+        assert!(defaults.is_empty());
+        assert!(defaults.register_default::<Foo>());
+        assert!(defaults.register_default::<u64>());
+        assert!(!defaults.register_default::<Foo>());
+        assert_eq!(defaults.len(), 2);
+        assert!(defaults.contains(TypeId::of::<u64>()));
+        assert_eq!(defaults.type_name(TypeId::of::<u64>()), Some("u64"));
+        assert_eq!(defaults.type_name(TypeId::of::<u8>()), None);
+
+        let foo = defaults.construct(TypeId::of::<Foo>()).unwrap();
+        assert_eq!(foo.downcast_ref::<Foo>(), Some(&Foo::default()));
+        assert_eq!(*defaults.construct(TypeId::of::<u64>()).unwrap().downcast::<u64>().unwrap(), 0);
+        assert!(defaults.construct(TypeId::of::<String>()).is_none());
+        assert_eq!(format!("{:?}", DefaultRegistry::new()), "{}");
+    }
+}
@@ -0,0 +1,286 @@
+//! `TypeDescriptor`, hand-written reflection for types that can't implement `Reflect`.
+
+use std::any::{type_name, Any, TypeId};
+use std::fmt;
+use std::marker::PhantomData;
+
+use crate::{AsAny, Bounds, TypeRegistry};
+
+type Getter = Box<dyn (Fn(&dyn Any) -> &dyn Any) + Send + Sync>;
+// Returns the value back if it is not of the type of the field.
+type Setter = Box<dyn Fn(&mut dyn Any, Box<dyn Any>) -> Result<(), Box<dyn Any>> + Send + Sync>;
+
+struct Field {
+    name: &'static str,
+    type_id: TypeId,
+    type_name: &'static str,
+    get: Getter,
+    set: Setter,
+}
+
+/// The fields of a type, each read by a getter and written by a setter, for types that
+/// can't implement `Reflect`, such as those of other crates, so reflection-driven tooling
+/// can cover them too. Built by `TypeDescriptor::builder`, and kept in a `TypeRegistry` by
+/// `register_descriptor`.
+///
+/// ```
+/// use std::any::Any;
+/// use pi_any::{BoxAny, TypeDescriptor, TypeRegistry};
+///
+/// // Stands for a type of another crate, with a private field and a validating setter.
+/// struct Light { color: [u8; 3], intensity: f32 }
+/// impl Light {
+///     fn intensity(&self) -> &f32 { &self.intensity }
+///     fn set_intensity(&mut self, value: f32) { self.intensity = value.max(0.0) }
+/// }
+///
+/// let light = TypeDescriptor::builder::<Light>()
+///     .field("color", |l| &l.color, |l, color| l.color = color)
+///     .field("intensity", Light::intensity, Light::set_intensity)
+///     .build();
+///
+/// let mut registry = TypeRegistry::<dyn BoxAny>::new();
+/// registry.register::<Light>("Light", || Box::new(Light { color: [255; 3], intensity: 1.0 }));
+/// assert!(registry.register_descriptor(light));
+///
+/// let mut value = registry.construct("Light").unwrap();
+/// let descriptor = registry.descriptor_of(&*value).unwrap();
+/// assert_eq!(descriptor.field_names().collect::<Vec<_>>(), ["color", "intensity"]);
+/// let value: &mut dyn Any = value.as_any_mut();
+/// assert!(descriptor.set_field(value, "intensity", -2.0f32).is_ok());
+/// assert_eq!(descriptor.get_field::<f32>(value, "intensity"), Some(&0.0));
+/// assert_eq!(descriptor.set_field(value, "intensity", 2u8), Err(2));
+/// ```
+pub struct TypeDescriptor {
+    type_id: TypeId,
+    type_name: &'static str,
+    fields: Vec<Field>,
+}
+
+impl TypeDescriptor {
+    /// Returns a builder describing the fields of `T`.
+    pub fn builder<T: Any>() -> TypeDescriptorBuilder<T> {
+        let (type_id, type_name) = (TypeId::of::<T>(), type_name::<T>());
+        let descriptor = TypeDescriptor { type_id, type_name, fields: Vec::new() };
+        TypeDescriptorBuilder { descriptor, marker: PhantomData }
+    }
+
+    /// Returns the `TypeId` of the described type.
+    pub fn type_id(&self) -> TypeId {
+        self.type_id
+    }
+
+    /// Returns the name of the described type, as given by `std::any::type_name`.
+    pub fn type_name(&self) -> &'static str {
+        self.type_name
+    }
+
+    /// Returns the names of the fields, in the order they were added.
+    pub fn field_names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.fields.iter().map(|field| field.name)
+    }
+
+    /// Returns the `TypeId` and name of the type of the field `name`.
+    pub fn field_type(&self, name: &str) -> Option<(TypeId, &'static str)> {
+        self.find(name).map(|field| (field.type_id, field.type_name))
+    }
+
+    fn find(&self, name: &str) -> Option<&Field> {
+        self.fields.iter().find(|field| field.name == name)
+    }
+
+    /// Returns the field `name` of `value`, or `None` if there is no such field or `value`
+    /// is not of the described type.
+    pub fn field<'v>(&self, value: &'v dyn Any, name: &str) -> Option<&'v dyn Any> {
+        if value.type_id() != self.type_id {
+            return None;
+        }
+        Some((self.find(name)?.get)(value))
+    }
+
+    /// Returns the field `name` of `value` if it is of type `F`.
+    pub fn get_field<'v, F: Any>(&self, value: &'v dyn Any, name: &str) -> Option<&'v F> {
+        self.field(value, name)?.downcast_ref()
+    }
+
+    /// Sets the field `name` of `value` by its setter, or returns `field` if there is no such
+    /// field of its type or `value` is not of the described type.
+    pub fn set(
+        &self,
+        value: &mut dyn Any,
+        name: &str,
+        field: Box<dyn Any>,
+    ) -> Result<(), Box<dyn Any>> {
+        match self.find(name) {
+            Some(entry) if (*value).type_id() == self.type_id => (entry.set)(value, field),
+            _ => Err(field),
+        }
+    }
+
+    /// Sets the field `name` of `value` like `set`, or returns `field` if it can't be set.
+    pub fn set_field<F: Any>(&self, value: &mut dyn Any, name: &str, field: F) -> Result<(), F> {
+        self.set(value, name, Box::new(field)).map_err(|field| *field.downcast().unwrap())
+    }
+
+    /// Returns the fields of `value` with their names, in the order they were added, or
+    /// none if `value` is not of the described type.
+    pub fn fields<'a>(
+        &'a self,
+        value: &'a dyn Any,
+    ) -> impl Iterator<Item = (&'static str, &'a dyn Any)> + 'a {
+        let fields = if value.type_id() == self.type_id { &self.fields[..] } else { &[] };
+        fields.iter().map(move |field| (field.name, (field.get)(value)))
+    }
+}
+
+impl fmt::Debug for TypeDescriptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut fields = f.debug_map();
+        for field in &self.fields {
+            fields.entry(&field.name, &field.type_name);
+        }
+        fields.finish()
+    }
+}
+
+/// Adds the fields of a `TypeDescriptor` of `T`, as returned by `TypeDescriptor::builder`.
+pub struct TypeDescriptorBuilder<T> {
+    descriptor: TypeDescriptor,
+    marker: PhantomData<fn(T)>,
+}
+
+impl<T: Any> TypeDescriptorBuilder<T> {
+    /// Adds the field `name` of type `F`, read by `get` and written by `set`. A field added
+    /// under the name of another replaces it.
+    pub fn field<F: Any>(
+        mut self,
+        name: &'static str,
+        get: impl Fn(&T) -> &F + Send + Sync + 'static,
+        set: impl Fn(&mut T, F) + Send + Sync + 'static,
+    ) -> Self {
+        let field = Field {
+            name,
+            type_id: TypeId::of::<F>(),
+            type_name: type_name::<F>(),
+            get: Box::new(move |value| get(value.downcast_ref().unwrap())),
+            set: Box::new(move |value, field| {
+                set(value.downcast_mut().unwrap(), *field.downcast()?);
+                Ok(())
+            }),
+        };
+        let fields = &mut self.descriptor.fields;
+        match fields.iter_mut().find(|old| old.name == name) {
+            Some(old) => *old = field,
+            None => fields.push(field),
+        }
+        self
+    }
+
+    pub fn build(self) -> TypeDescriptor {
+        self.descriptor
+    }
+}
+
+impl<T> fmt::Debug for TypeDescriptorBuilder<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("TypeDescriptorBuilder").field(&self.descriptor).finish()
+    }
+}
+
+impl<B: AsAny + ?Sized, S: Bounds> TypeRegistry<B, S> {
+    /// Sets the descriptor of its type, which must already be registered. Returns false if
+    /// it is not registered.
+    pub fn register_descriptor(&mut self, descriptor: TypeDescriptor) -> bool {
+        let Some(name) = self.name_of_id(descriptor.type_id).map(str::to_string) else {
+            return false;
+        };
+        self.entries.get_mut(&name).unwrap().descriptor = Some(descriptor);
+        true
+    }
+
+    /// Returns the descriptor of the type registered under `name`.
+    pub fn descriptor(&self, name: &str) -> Option<&TypeDescriptor> {
+        self.entries.get(name)?.descriptor.as_ref()
+    }
+
+    /// Returns the descriptor of the type of `value`.
+    pub fn descriptor_of(&self, value: &B) -> Option<&TypeDescriptor> {
+        self.descriptor(self.name_of_id(value.type_id_of())?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::any::TypeId;
+
+    use super::TypeDescriptor;
+    use crate::{BoxAny, TypeRegistry};
+
+    mod foreign {
+        pub struct Size {
+            pub width: u32,
+            height: u32,
+        }
+
+        impl Size {
+            pub fn new(width: u32, height: u32) -> Self {
+                Size { width, height }
+            }
+            pub fn height(&self) -> &u32 {
+                &self.height
+            }
+            pub fn set_height(&mut self, height: u32) {
+                self.height = height.max(1);
+            }
+        }
+    }
+    use foreign::Size;
+
+    fn size() -> TypeDescriptor {
+        TypeDescriptor::builder::<Size>()
+            .field("width", |s| &s.width, |s, width| s.width = width)
+            .field("height", Size::height, Size::set_height)
+            .build()
+    }
+
+    #[test]
+    fn test() {
+        let descriptor = size();
+        assert_eq!(descriptor.type_id(), TypeId::of::<Size>());
+        assert_eq!(descriptor.field_type("height"), Some((TypeId::of::<u32>(), "u32")));
+        assert_eq!(descriptor.field_type("depth"), None);
+
+        let mut value = Size::new(2, 3);
+        assert_eq!(descriptor.get_field::<u32>(&value, "height"), Some(&3));
+        assert_eq!(descriptor.get_field::<u8>(&value, "height"), None);
+        assert_eq!(descriptor.set_field(&mut value, "height", 0u32), Ok(()));
+        assert_eq!(descriptor.set_field(&mut value, "width", 5u64), Err(5));
+        assert_eq!(descriptor.set_field(&mut value, "depth", 5u32), Err(5));
+        assert_eq!((value.width, *value.height()), (2, 1));
+
+        let fields: Vec<_> =
+            descriptor.fields(&value).map(|(name, field)| (name, field.downcast_ref())).collect();
+        assert_eq!(fields, [("width", Some(&2u32)), ("height", Some(&1u32))]);
+
+        // Values of other types have no fields.
+        let mut other = 7u32;
+        assert!(descriptor.field(&other, "width").is_none());
+        assert_eq!(descriptor.set_field(&mut other, "width", 1u32), Err(1));
+        assert_eq!(descriptor.fields(&other).count(), 0);
+        assert_eq!(format!("{:?}", descriptor), r#"{"width": "u32", "height": "u32"}"#);
+    }
+
+    #[test]
+    fn test_registry() {
+        let mut registry = TypeRegistry::<dyn BoxAny>::new();
+        assert!(!registry.register_descriptor(size()));
+        registry.register::<Size>("Size", || Box::new(Size::new(1, 1)));
+        assert!(registry.register_descriptor(size()));
+        assert!(registry.descriptor("Size").is_some());
+        assert!(registry.descriptor_of(&1u8).is_none());
+
+        let value = registry.construct("Size").unwrap();
+        let descriptor = registry.descriptor_of(&*value).unwrap();
+        assert_eq!(descriptor.get_field::<u32>(value.as_any(), "width"), Some(&1));
+    }
+}
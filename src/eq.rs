@@ -0,0 +1,88 @@
+//! `DynEq`, comparing trait objects for equality.
+
+use core::any::Any;
+
+use crate::AsAny;
+
+/// Implemented for every `PartialEq` type, so a trait extending `DynEq` can implement
+/// `PartialEq` for its trait objects with `impl_dyn_eq!`.
+///
+/// Values of different concrete types are never equal.
+///
+/// ```
+/// # #[macro_use]
+/// # extern crate pi_any;
+/// use pi_any::DynEq;
+///
+/// trait Event: DynEq {}
+/// impl_dyn_eq!(Event);
+///
+/// #[derive(PartialEq)]
+/// struct Click(i32, i32);
+/// impl Event for Click {}
+/// #[derive(PartialEq)]
+/// struct Key(char);
+/// impl Event for Key {}
+///
+/// # fn main() {
+/// let mut events: Vec<Box<dyn Event>> =
+///     vec![Box::new(Click(1, 2)), Box::new(Click(1, 2)), Box::new(Key('a'))];
+/// events.dedup();
+/// assert_eq!(events.len(), 2);
+/// assert!(*events[0] != *events[1]);
+/// # }
+/// ```
+pub trait DynEq: AsAny {
+    /// Returns true if `other` is of the same type as `self` and equal to it.
+    fn dyn_eq(&self, other: &dyn Any) -> bool;
+}
+
+impl<T: PartialEq + Any> DynEq for T {
+    fn dyn_eq(&self, other: &dyn Any) -> bool {
+        other.downcast_ref::<T>().is_some_and(|other| self == other)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::DynEq;
+
+    trait Base: DynEq {}
+    crate::impl_dyn_eq!(Base);
+
+    trait Generic<T: Copy>: DynEq {}
+    crate::impl_dyn_eq!(Generic<T> + Send where T: Copy);
+
+    #[derive(PartialEq)]
+    struct Foo(u32);
+    impl Base for Foo {}
+    impl Generic<u8> for Foo {}
+    #[derive(PartialEq)]
+    struct Bar(u32);
+    impl Base for Bar {}
+    #[derive(PartialEq)]
+    struct Float(f32);
+    impl Base for Float {}
+
+    #[test]
+    fn test() {
+        assert!(Foo(1).dyn_eq(&Foo(1)));
+        assert!(!Foo(1).dyn_eq(&Bar(1)));
+
+        let a: Box<dyn Base> = Box::new(Foo(1));
+        let b: Box<dyn Base> = Box::new(Foo(1));
+        let c: Box<dyn Base> = Box::new(Bar(1));
+        assert!(a == b);
+        assert!(a != c);
+        assert!(*a == *(&Foo(1) as &dyn Base));
+
+        let a: Box<dyn Generic<u8> + Send> = Box::new(Foo(2));
+        let b: Box<dyn Generic<u8> + Send> = Box::new(Foo(2));
+        assert!(a == b);
+
+        // `PartialEq` of the concrete type is used as is.
+        let nan: Box<dyn Base> = Box::new(Float(f32::NAN));
+        let other_nan: Box<dyn Base> = Box::new(Float(f32::NAN));
+        assert!(nan != other_nan);
+    }
+}
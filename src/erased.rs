@@ -0,0 +1,107 @@
+//! `SerializeAny` and `impl_serialize_any!`, serializing downcastable trait objects through
+//! `erased_serde`, untagged and without a registry.
+
+use crate::BoxAny;
+
+/// The trait to extend for trait objects that are downcast and serialized, implemented for
+/// every type that is both.
+///
+/// ```
+/// # #[macro_use]
+/// # extern crate pi_any;
+/// # extern crate serde;
+/// # extern crate serde_json;
+/// use pi_any::SerializeAny;
+/// use serde::Serialize;
+///
+/// trait Event: SerializeAny {}
+/// impl_downcast!(Event);
+/// impl_serialize_any!(Event);
+///
+/// #[derive(Serialize)]
+/// struct Click { x: i32, y: i32 }
+/// impl Event for Click {}
+///
+/// # fn main() {
+/// let event: Box<dyn Event> = Box::new(Click { x: 1, y: 2 });
+/// assert_eq!(serde_json::to_string(&event).unwrap(), r#"{"x":1,"y":2}"#);
+/// assert_eq!(event.downcast_ref::<Click>().unwrap().x, 1);
+/// # }
+/// ```
+pub trait SerializeAny: BoxAny + erased_serde::Serialize {}
+
+impl<T: BoxAny + erased_serde::Serialize> SerializeAny for T {}
+
+/// Implements `Serialize` for `dyn Trait`, `dyn Trait + Send` and `dyn Trait + Send + Sync`,
+/// serializing the object within as its own type does. The trait must extend
+/// `SerializeAny`.
+///
+/// Use `impl_serde_tagged!` instead to deserialize the trait objects as well.
+#[macro_export]
+macro_rules! impl_serialize_any {
+    ($trait_:path) => {
+        $crate::impl_serialize_any! { @impl dyn $trait_ }
+        $crate::impl_serialize_any! { @impl dyn $trait_ + $crate::__core::marker::Send }
+        $crate::impl_serialize_any! { @impl dyn $trait_ + $crate::__core::marker::Send + $crate::__core::marker::Sync }
+    };
+
+    (@impl $($object:tt)+) => {
+        impl $crate::__serde::Serialize for $($object)+ {
+            fn serialize<__S: $crate::__serde::Serializer>(
+                &self,
+                serializer: __S,
+            ) -> $crate::__core::result::Result<__S::Ok, __S::Error> {
+                $crate::__erased_serde::serialize(self, serializer)
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use serde::Serialize;
+
+    use super::SerializeAny;
+
+    trait Component: SerializeAny {}
+    crate::impl_downcast!(Component);
+    crate::impl_serialize_any!(Component);
+
+    mod nested {
+        pub trait Named: crate::SerializeAny {}
+    }
+    crate::impl_serialize_any!(self::nested::Named);
+
+    #[derive(Serialize)]
+    struct Position {
+        x: f32,
+        y: f32,
+    }
+    impl Component for Position {}
+    impl nested::Named for Position {}
+    #[derive(Serialize)]
+    struct Name(&'static str);
+    impl Component for Name {}
+
+    #[derive(Serialize)]
+    struct Entity {
+        components: BTreeMap<&'static str, Box<dyn Component + Send + Sync>>,
+    }
+
+    #[test]
+    fn test() {
+        let mut components = BTreeMap::<_, Box<dyn Component + Send + Sync>>::new();
+        components.insert("name", Box::new(Name("player")));
+        components.insert("position", Box::new(Position { x: 1.0, y: 2.5 }));
+        let json = serde_json::to_string(&Entity { components }).unwrap();
+        assert_eq!(json, r#"{"components":{"name":"player","position":{"x":1.0,"y":2.5}}}"#);
+
+        let component: Box<dyn Component> = Box::new(Name("a"));
+        assert_eq!(serde_json::to_string(&component).unwrap(), r#""a""#);
+        assert_eq!(component.downcast_ref::<Name>().unwrap().0, "a");
+        let named: &dyn nested::Named = &Position { x: 0.0, y: 0.0 };
+        assert_eq!(serde_json::to_string(named).unwrap(), r#"{"x":0.0,"y":0.0}"#);
+    }
+}
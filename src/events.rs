@@ -0,0 +1,278 @@
+//! `EventBus`, routing type-erased messages to the handlers subscribed to their type.
+
+use std::any::{Any, TypeId};
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+
+use crate::{Bounded, Bounds, SendBounds};
+
+struct Subscriber<B: Bounds> {
+    filter: Option<Box<B::Filter>>,
+    handler: Box<B::Handler>,
+}
+
+/// Delivers messages of any type to the handlers subscribed to that type.
+///
+/// `publish` delivers a message at once, while `queue` holds it until the next `flush`, so a
+/// frame can collect messages and handle them in one batch, in the order they were queued.
+///
+/// Interceptors added by `intercept` see every message before the handlers do, and
+/// `subscribe_filtered` only calls a handler with the messages its filter accepts.
+///
+/// The messages, handlers and interceptors must be `Send`, and so is the bus, unless it is
+/// made by `with_bounds` with other `Bounds`.
+///
+/// ```
+/// use std::sync::{Arc, Mutex};
+/// use pi_any::EventBus;
+///
+/// struct Damage(u32);
+///
+/// let total = Arc::new(Mutex::new(0));
+/// let mut bus = EventBus::new();
+/// let sum = total.clone();
+/// bus.subscribe(move |d: &Damage| *sum.lock().unwrap() += d.0);
+///
+/// assert_eq!(bus.publish(Box::new(Damage(3))), 1);
+/// bus.queue(Box::new(Damage(4)));
+/// bus.queue(Box::new("unhandled"));
+/// assert_eq!(*total.lock().unwrap(), 3);
+/// assert_eq!(bus.flush(), 1);
+/// assert_eq!(*total.lock().unwrap(), 7);
+/// ```
+pub struct EventBus<B: Bounds = SendBounds> {
+    handlers: HashMap<TypeId, Vec<Subscriber<B>>>,
+    interceptors: Vec<Box<B::Handler>>,
+    queue: VecDeque<Box<B::Message>>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        EventBus::with_bounds(SendBounds)
+    }
+}
+
+impl<B: Bounds> EventBus<B> {
+    /// Creates a bus taking the messages, handlers and interceptors meeting `bounds`.
+    pub fn with_bounds(_bounds: B) -> Self {
+        EventBus { handlers: HashMap::new(), interceptors: Vec::new(), queue: VecDeque::new() }
+    }
+
+    /// Calls `handler` with every message of type `T`, after the handlers subscribed before.
+    pub fn subscribe<T: Any>(&mut self, handler: impl FnMut(&T) + Bounded<B>) {
+        self.push::<T>(None, handler.__into_handler::<T>());
+    }
+
+    /// Calls `handler` with the messages of type `T` that `filter` returns true for, after
+    /// the handlers subscribed before.
+    ///
+    /// ```
+    /// use std::sync::{Arc, Mutex};
+    /// use pi_any::EventBus;
+    ///
+    /// struct Damage { target: u32, amount: u32 }
+    ///
+    /// let taken = Arc::new(Mutex::new(0));
+    /// let mut bus = EventBus::new();
+    /// let sum = taken.clone();
+    /// let player = |d: &Damage| d.target == 1;
+    /// bus.subscribe_filtered(player, move |d| *sum.lock().unwrap() += d.amount);
+    ///
+    /// assert_eq!(bus.publish(Box::new(Damage { target: 2, amount: 5 })), 0);
+    /// assert_eq!(bus.publish(Box::new(Damage { target: 1, amount: 3 })), 1);
+    /// assert_eq!(*taken.lock().unwrap(), 3);
+    /// ```
+    pub fn subscribe_filtered<T: Any>(
+        &mut self,
+        filter: impl Fn(&T) -> bool + Bounded<B>,
+        handler: impl FnMut(&T) + Bounded<B>,
+    ) {
+        let filter = filter.__into_filter::<T>();
+        self.push::<T>(Some(filter), handler.__into_handler::<T>());
+    }
+
+    fn push<T: Any>(&mut self, filter: Option<Box<B::Filter>>, handler: Box<B::Handler>) {
+        let subscriber = Subscriber { filter, handler };
+        self.handlers.entry(TypeId::of::<T>()).or_default().push(subscriber);
+    }
+
+    /// Calls `interceptor` with every published or flushed message, of any type, before the
+    /// handlers and after the interceptors added before, e.g. to log or record them. A
+    /// message `interceptor` returns false for is dropped without reaching the handlers or
+    /// the interceptors after it.
+    ///
+    /// ```
+    /// use std::any::Any;
+    /// use std::sync::{Arc, Mutex};
+    /// use pi_any::EventBus;
+    ///
+    /// struct Teleport(i32);
+    ///
+    /// let seen = Arc::new(Mutex::new(0));
+    /// let mut bus = EventBus::new();
+    /// let count = seen.clone();
+    /// bus.intercept(move |_| {
+    ///     *count.lock().unwrap() += 1;
+    ///     true
+    /// });
+    /// // Drops teleports too far to be legal.
+    /// bus.intercept(|msg: &dyn Any| msg.downcast_ref::<Teleport>().is_none_or(|t| t.0 < 100));
+    /// bus.subscribe(|_: &Teleport| {});
+    ///
+    /// assert_eq!(bus.publish(Box::new(Teleport(5))), 1);
+    /// assert_eq!(bus.publish(Box::new(Teleport(500))), 0);
+    /// assert_eq!(bus.publish(Box::new("unhandled")), 0);
+    /// assert_eq!(*seen.lock().unwrap(), 3);
+    /// ```
+    pub fn intercept(&mut self, interceptor: impl FnMut(&dyn Any) -> bool + Bounded<B>) {
+        self.interceptors.push(interceptor.__into_interceptor());
+    }
+
+    /// Returns true if a handler is subscribed to messages of type `T`.
+    pub fn has_subscribers<T: Any>(&self) -> bool {
+        self.handlers.contains_key(&TypeId::of::<T>())
+    }
+
+    /// Removes the handlers subscribed to messages of type `T`, returning how many there were.
+    pub fn unsubscribe_all<T: Any>(&mut self) -> usize {
+        self.handlers.remove(&TypeId::of::<T>()).map_or(0, |handlers| handlers.len())
+    }
+
+    /// Delivers `msg` to the handlers subscribed to its type at once, returning how many
+    /// were called. A message nobody subscribed to is dropped.
+    pub fn publish(&mut self, msg: Box<B::Message>) -> usize {
+        let msg = B::__message(&msg);
+        if !self.interceptors.iter_mut().all(|interceptor| interceptor(msg)) {
+            return 0;
+        }
+        let Some(subscribers) = self.handlers.get_mut(&msg.type_id()) else {
+            return 0;
+        };
+        let call = |s: &mut Subscriber<B>| {
+            s.filter.as_ref().is_none_or(|filter| filter(msg)) && (s.handler)(msg)
+        };
+        subscribers.iter_mut().map(call).filter(|called| *called).count()
+    }
+
+    /// Holds `msg` until the next `flush`.
+    pub fn queue(&mut self, msg: Box<B::Message>) {
+        self.queue.push_back(msg)
+    }
+
+    /// Returns the number of queued messages.
+    pub fn pending(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Delivers the queued messages in order, returning how many handler calls were made.
+    pub fn flush(&mut self) -> usize {
+        let mut calls = 0;
+        while let Some(msg) = self.queue.pop_front() {
+            calls += self.publish(msg);
+        }
+        calls
+    }
+}
+
+impl<B: Bounds + Default> Default for EventBus<B> {
+    fn default() -> Self {
+        EventBus::with_bounds(B::default())
+    }
+}
+
+impl<B: Bounds> fmt::Debug for EventBus<B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EventBus")
+            .field("types", &self.handlers.len())
+            .field("interceptors", &self.interceptors.len())
+            .field("pending", &self.queue.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::any::Any;
+    use std::sync::{Arc, Mutex};
+
+    use super::EventBus;
+
+    struct Foo(u32);
+    struct Bar(&'static str);
+
+    #[test]
+    fn test_publish() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut bus = EventBus::new();
+        let (a, b, c) = (log.clone(), log.clone(), log.clone());
+        bus.subscribe(move |foo: &Foo| a.lock().unwrap().push(format!("a{}", foo.0)));
+        bus.subscribe(move |foo: &Foo| b.lock().unwrap().push(format!("b{}", foo.0)));
+        bus.subscribe(move |bar: &Bar| c.lock().unwrap().push(bar.0.to_string()));
+        assert!(bus.has_subscribers::<Foo>());
+        assert!(!bus.has_subscribers::<u8>());
+
+        assert_eq!(bus.publish(Box::new(Foo(1))), 2);
+        assert_eq!(bus.publish(Box::new(Bar("bar"))), 1);
+        assert_eq!(bus.publish(Box::new(1u8)), 0);
+        assert_eq!(*log.lock().unwrap(), ["a1", "b1", "bar"]);
+
+        assert_eq!(bus.unsubscribe_all::<Foo>(), 2);
+        assert_eq!(bus.publish(Box::new(Foo(2))), 0);
+    }
+
+    #[test]
+    fn test_flush() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut bus = EventBus::new();
+        let (a, b) = (log.clone(), log.clone());
+        bus.subscribe(move |foo: &Foo| a.lock().unwrap().push(foo.0.to_string()));
+        bus.subscribe(move |bar: &Bar| b.lock().unwrap().push(bar.0.to_string()));
+
+        bus.queue(Box::new(Foo(1)));
+        bus.queue(Box::new(Bar("two")));
+        bus.queue(Box::new(Foo(3)));
+        assert_eq!(bus.pending(), 3);
+        assert!(log.lock().unwrap().is_empty());
+
+        assert_eq!(bus.flush(), 3);
+        assert_eq!(bus.pending(), 0);
+        assert_eq!(*log.lock().unwrap(), ["1", "two", "3"]);
+        assert_eq!(bus.flush(), 0);
+    }
+    #[test]
+    fn test_filter() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut bus = EventBus::new();
+        let (a, b) = (log.clone(), log.clone());
+        let even = |foo: &Foo| foo.0 % 2 == 0;
+        bus.subscribe_filtered(even, move |foo| a.lock().unwrap().push(foo.0));
+        bus.subscribe(move |foo: &Foo| b.lock().unwrap().push(foo.0 * 10));
+
+        assert_eq!(bus.publish(Box::new(Foo(1))), 1);
+        bus.queue(Box::new(Foo(2)));
+        assert_eq!(bus.flush(), 2);
+        assert_eq!(*log.lock().unwrap(), [10, 2, 20]);
+    }
+
+    #[test]
+    fn test_intercept() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut bus = EventBus::new();
+        let (a, b) = (log.clone(), log.clone());
+        bus.intercept(move |msg| {
+            let name = if msg.is::<Foo>() { "foo" } else { "other" };
+            a.lock().unwrap().push(name.to_string());
+            true
+        });
+        let empty = |msg: &dyn Any| msg.downcast_ref::<Bar>().is_some_and(|bar| bar.0.is_empty());
+        bus.intercept(move |msg| !empty(msg));
+        bus.subscribe(move |bar: &Bar| b.lock().unwrap().push(bar.0.to_string()));
+
+        assert_eq!(bus.publish(Box::new(Foo(1))), 0);
+        assert_eq!(bus.publish(Box::new(Bar("bar"))), 1);
+        bus.queue(Box::new(Bar("")));
+        assert_eq!(bus.flush(), 0);
+        assert_eq!(*log.lock().unwrap(), ["foo", "other", "bar", "other"]);
+        assert!(format!("{:?}", bus).contains("interceptors: 2"));
+    }
+}
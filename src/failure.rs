@@ -0,0 +1,296 @@
+//! Reporting of failed downcasts: those expected to succeed, such as `downcast_ref_expect`,
+//! and the errors of `try_downcast` and friends.
+
+use core::error::Error;
+use core::fmt;
+use core::sync::atomic::{AtomicU8, Ordering};
+
+#[cfg(feature = "std")]
+use crate::lock::RwLock;
+
+/// A failed downcast of a reference, as returned by the generated `try_downcast_ref` and
+/// `try_downcast_mut`, for when a mismatch is an error to report rather than a branch.
+///
+/// ```
+/// # #[macro_use]
+/// # extern crate pi_any;
+/// use pi_any::{BoxAny, TypeMismatch};
+///
+/// trait Node: BoxAny {}
+/// impl_downcast!(Node);
+///
+/// struct Mesh(usize);
+/// impl Node for Mesh {}
+/// struct Light;
+/// impl Node for Light {}
+///
+/// fn vertex_count(node: &dyn Node) -> Result<usize, TypeMismatch> {
+///     Ok(node.try_downcast_ref::<Mesh>()?.0)
+/// }
+///
+/// # fn main() {
+/// assert_eq!(vertex_count(&Mesh(3)), Ok(3));
+/// let err = vertex_count(&Light).unwrap_err();
+/// assert_eq!(err.to_string(), "expected `rust_out::Mesh`, found `rust_out::Light`");
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TypeMismatch {
+    pub expected: &'static str,
+    pub found: &'static str,
+}
+
+impl fmt::Display for TypeMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "expected `{}`, found `{}`", self.expected, self.found)
+    }
+}
+
+impl Error for TypeMismatch {}
+
+/// A value of some type that was asked for but is missing, as returned by `AnyMap::try_get`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MissingValue {
+    pub type_name: &'static str,
+}
+
+impl fmt::Display for MissingValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "no value of type `{}`", self.type_name)
+    }
+}
+
+impl Error for MissingValue {}
+
+/// A failed owning downcast, as returned by the generated `try_downcast` methods, which
+/// hands the original pointer back in `value`.
+///
+/// It implements `Error`, so the failure can be propagated with `?` and a message naming
+/// both types; `into_value` recovers the pointer to try another type.
+///
+/// ```
+/// # #[macro_use]
+/// # extern crate pi_any;
+/// use std::error::Error;
+/// use pi_any::BoxAny;
+///
+/// trait Asset: BoxAny {}
+/// impl_downcast_box!(Asset);
+///
+/// struct Texture(u32);
+/// impl Asset for Texture {}
+/// struct Sound;
+/// impl Asset for Sound {}
+///
+/// fn texture_size(asset: Box<dyn Asset>) -> Result<u32, Box<dyn Error>> {
+///     Ok(asset.try_downcast::<Texture>()?.0)
+/// }
+///
+/// # fn main() {
+/// assert_eq!(texture_size(Box::new(Texture(64))).unwrap(), 64);
+/// let err = texture_size(Box::new(Sound)).unwrap_err();
+/// assert!(err.to_string().starts_with("expected `rust_out::Texture`, found `rust_out::Sound`"));
+/// # }
+/// ```
+pub struct DowncastError<V> {
+    pub expected: &'static str,
+    pub found: &'static str,
+    /// The pointer that was to be downcast.
+    pub value: V,
+}
+
+impl<V> DowncastError<V> {
+    pub fn into_value(self) -> V {
+        self.value
+    }
+}
+
+impl<V> fmt::Debug for DowncastError<V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DowncastError")
+            .field("expected", &self.expected)
+            .field("found", &self.found)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<V> fmt::Display for DowncastError<V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "expected `{}`, found `{}`", self.expected, self.found)
+    }
+}
+
+impl<V> Error for DowncastError<V> {}
+
+/// What a fallible operation does when it fails, e.g. the generated `try_downcast_ref`,
+/// `AnyCell::try_borrow` or `AnyMap::try_get`, so the same code can fail loudly in
+/// development builds and degrade gracefully in shipping ones.
+///
+/// The policy of the crate is set by `set_failure_policy`. `AnyCell`, `AnyColumn` and
+/// `AnyMap` can override it with their own `set_failure_policy`.
+///
+/// ```
+/// use std::panic::{catch_unwind, AssertUnwindSafe};
+/// use pi_any::{set_failure_policy, AnyMap, FailurePolicy};
+///
+/// struct Score(u32);
+///
+/// let mut resources = AnyMap::new();
+/// assert!(resources.try_get::<Score>().is_err());
+///
+/// // A development build would rather find the missing value at once.
+/// set_failure_policy(FailurePolicy::Panic);
+/// assert!(catch_unwind(AssertUnwindSafe(|| resources.try_get::<Score>().is_ok())).is_err());
+///
+/// // Except for this map.
+/// resources.set_failure_policy(Some(FailurePolicy::Error));
+/// assert!(resources.try_get::<Score>().is_err());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FailurePolicy {
+    /// Panics with the error, naming the failed operation.
+    Panic,
+    /// Returns the error.
+    #[default]
+    Error,
+    /// Reports the failure to the hook installed by `set_failure_hook`, then returns the
+    /// error.
+    Hook,
+}
+
+impl FailurePolicy {
+    const ALL: [FailurePolicy; 3] =
+        [FailurePolicy::Panic, FailurePolicy::Error, FailurePolicy::Hook];
+}
+
+static POLICY: AtomicU8 = AtomicU8::new(FailurePolicy::Error as u8);
+
+/// Sets the failure policy of the crate, returning the previous one.
+pub fn set_failure_policy(policy: FailurePolicy) -> FailurePolicy {
+    FailurePolicy::ALL[POLICY.swap(policy as u8, Ordering::Relaxed) as usize]
+}
+
+/// Returns the failure policy of the crate, `FailurePolicy::Error` unless set otherwise.
+pub fn failure_policy() -> FailurePolicy {
+    FailurePolicy::ALL[POLICY.load(Ordering::Relaxed) as usize]
+}
+
+/// A failure reported to the hook: that of a downcast expected to succeed, or of a fallible
+/// operation under `FailurePolicy::Hook`.
+#[derive(Clone, Copy)]
+pub struct Failure<'a> {
+    /// The failed operation, or the message passed by the caller, e.g. to
+    /// `downcast_ref_expect`.
+    pub msg: &'a str,
+    pub error: &'a (dyn Error + 'a),
+}
+
+impl fmt::Debug for Failure<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Failure").field("msg", &self.msg).field("error", &self.error).finish()
+    }
+}
+
+impl fmt::Display for Failure<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.msg, self.error)
+    }
+}
+
+/// Called with every failure before the panic.
+#[cfg(feature = "std")]
+pub type FailureHook = fn(&Failure<'_>);
+
+#[cfg(feature = "std")]
+static HOOK: RwLock<Option<FailureHook>> = RwLock::new(None);
+
+/// Installs a hook that sees every failed `*_expect` downcast, and other operations that
+/// panic on a type mismatch, before the panic, as well as the failures of fallible
+/// operations under `FailurePolicy::Hook`; returns the previous hook.
+///
+/// The panics still happen afterwards, since these operations have no value to return. The
+/// hook lets shipping builds record the failure, e.g. in telemetry, while callers that must
+/// not panic use the `Option`/`Result` returning variants instead.
+#[cfg(feature = "std")]
+pub fn set_failure_hook(hook: Option<FailureHook>) -> Option<FailureHook> {
+    std::mem::replace(&mut *HOOK.write(), hook)
+}
+
+#[cfg(feature = "std")]
+fn call_hook(failure: &Failure<'_>) {
+    let hook = *HOOK.read();
+    if let Some(hook) = hook {
+        hook(failure);
+    }
+}
+
+#[cfg(not(feature = "std"))]
+fn call_hook(_: &Failure<'_>) {}
+
+/// Applies `policy`, or that of the crate if it is `None`, to the `error` of the operation
+/// `msg`, returning the error unless the policy panics.
+#[doc(hidden)]
+#[track_caller]
+pub fn __fail<E: Error>(policy: Option<FailurePolicy>, msg: &str, error: E) -> E {
+    match policy.unwrap_or_else(failure_policy) {
+        FailurePolicy::Panic => panic!("{}: {}", msg, error),
+        FailurePolicy::Error => {}
+        FailurePolicy::Hook => call_hook(&Failure { msg, error: &error }),
+    }
+    error
+}
+
+#[doc(hidden)]
+#[cold]
+#[inline(never)]
+#[track_caller]
+pub fn __downcast_failed(msg: &str, expected: &'static str, found: &'static str) -> ! {
+    let failure = Failure { msg, error: &TypeMismatch { expected, found } };
+    call_hook(&failure);
+    panic!("{}", failure)
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use std::panic::catch_unwind;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::{__fail, failure_policy, set_failure_hook, Failure, FailurePolicy, TypeMismatch};
+    use crate::AnyColumn;
+
+    static SEEN: AtomicUsize = AtomicUsize::new(0);
+
+    fn hook(failure: &Failure<'_>) {
+        // Other tests may fail downcasts concurrently; count only our own.
+        if failure.msg.starts_with("test ") || failure.to_string().contains("`i8`") {
+            SEEN.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn test_hook() {
+        set_failure_hook(Some(hook));
+        let result = catch_unwind(|| AnyColumn::new::<u32>().push(1i8));
+        assert!(result.is_err());
+        assert_eq!(SEEN.load(Ordering::SeqCst), 1);
+
+        let mismatch = TypeMismatch { expected: "u8", found: "u16" };
+        assert_eq!(__fail(Some(FailurePolicy::Hook), "test hook", mismatch), mismatch);
+        assert_eq!(__fail(Some(FailurePolicy::Error), "test error", mismatch), mismatch);
+        set_failure_hook(None);
+        assert_eq!(SEEN.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_policy() {
+        // The tests run in parallel, so the policy of the crate must stay as it is.
+        assert_eq!(failure_policy(), FailurePolicy::Error);
+        assert_eq!(super::set_failure_policy(FailurePolicy::Error), FailurePolicy::Error);
+
+        let mismatch = TypeMismatch { expected: "u8", found: "u16" };
+        let result = catch_unwind(|| __fail(Some(FailurePolicy::Panic), "test panic", mismatch));
+        let msg = *result.unwrap_err().downcast::<String>().unwrap();
+        assert_eq!(msg, "test panic: expected `u8`, found `u16`");
+        assert_eq!(__fail(None, "test default", mismatch), mismatch);
+    }
+}
@@ -0,0 +1,211 @@
+//! `FatAny`, a boxed trait object that keeps the `TypeId` of its value next to it.
+//!
+//! Downcasts compare the cached `TypeId` and then cast the pointer, without going through
+//! the vtable, so this module is allowed to use `unsafe` like `clone`.
+
+use alloc::boxed::Box;
+use core::any::{type_name, Any, TypeId};
+use core::fmt;
+use core::ops::{Deref, DerefMut};
+
+use crate::owned::concrete_type_id;
+use crate::{AsAny, __downcast_failed};
+
+/// A `Box<B>` together with the `TypeId` of the value in it, read once on construction.
+///
+/// `is` and failed downcasts only compare `TypeId`s, and successful ones cast the pointer,
+/// so none of them makes a virtual call, which adds up in dispatch loops. The trait object
+/// is reached through `Deref`.
+///
+/// ```
+/// # #[macro_use]
+/// # extern crate pi_any;
+/// use pi_any::{BoxAny, FatAny};
+///
+/// trait Event: BoxAny { fn name(&self) -> &str; }
+///
+/// struct Click(u32, u32);
+/// impl Event for Click { fn name(&self) -> &str { "click" } }
+/// struct Key(char);
+/// impl Event for Key { fn name(&self) -> &str { "key" } }
+///
+/// # fn main() {
+/// let events: Vec<FatAny<dyn Event>> = vec![
+///     FatAny::new(Box::new(Click(1, 2))),
+///     FatAny::new(Box::new(Key('a'))),
+/// ];
+/// let clicks = events.iter().filter_map(|e| e.downcast_ref::<Click>()).count();
+/// assert_eq!(clicks, 1);
+/// assert_eq!(events[1].name(), "key");
+/// # }
+/// ```
+pub struct FatAny<B: ?Sized> {
+    // Always the `TypeId` of the value in `value`, which is never replaced.
+    type_id: TypeId,
+    value: Box<B>,
+}
+
+impl<B: AsAny + ?Sized> FatAny<B> {
+    /// Panics if `as_any` of the value returns something else, which can only happen with a
+    /// hand-written `AsAny` for an unsized type.
+    pub fn new(value: Box<B>) -> Self {
+        let type_id = concrete_type_id(&*value).expect("`as_any` does not return the value itself");
+        FatAny { type_id, value }
+    }
+
+    /// Returns the `TypeId` of the value, without a virtual call.
+    pub fn type_id(&self) -> TypeId {
+        self.type_id
+    }
+
+    pub fn into_inner(self) -> Box<B> {
+        self.value
+    }
+
+    /// Returns true if the value is of type `T`.
+    #[inline]
+    pub fn is<T: Any>(&self) -> bool {
+        self.type_id == TypeId::of::<T>()
+    }
+
+    /// Returns a reference to the value if it is of type `T`, or `None` if it isn't.
+    #[inline]
+    pub fn downcast_ref<T: Any>(&self) -> Option<&T> {
+        if self.is::<T>() {
+            // SAFETY: the value is a `T`, as the cached `TypeId` says; `new` checked that it
+            // is the value itself that has that type.
+            Some(unsafe { &*(&*self.value as *const B).cast::<T>() })
+        } else {
+            None
+        }
+    }
+
+    /// Returns a mutable reference to the value if it is of type `T`, or `None` if it isn't.
+    #[inline]
+    pub fn downcast_mut<T: Any>(&mut self) -> Option<&mut T> {
+        if self.is::<T>() {
+            // SAFETY: as in `downcast_ref`; the borrow is unique because `self` is.
+            Some(unsafe { &mut *(&mut *self.value as *mut B).cast::<T>() })
+        } else {
+            None
+        }
+    }
+
+    /// Converts into a `Box<T>` if the value is of type `T`, or returns `self` if it isn't.
+    #[inline]
+    pub fn downcast<T: Any>(self) -> Result<Box<T>, Self> {
+        if self.is::<T>() {
+            // SAFETY: the value is a `T`, in an allocation made for a `T`.
+            Ok(unsafe { Box::from_raw(Box::into_raw(self.value).cast::<T>()) })
+        } else {
+            Err(self)
+        }
+    }
+
+    /// Returns a reference to the value, panicking with `msg` and both type names if it
+    /// isn't of type `T`.
+    #[inline]
+    #[track_caller]
+    pub fn downcast_ref_expect<T: Any>(&self, msg: &str) -> &T {
+        match self.downcast_ref() {
+            Some(v) => v,
+            None => __downcast_failed(msg, type_name::<T>(), (*self.value).type_name()),
+        }
+    }
+
+    /// Returns a mutable reference to the value, panicking with `msg` and both type names
+    /// if it isn't of type `T`.
+    #[inline]
+    #[track_caller]
+    pub fn downcast_mut_expect<T: Any>(&mut self, msg: &str) -> &mut T {
+        let found = (*self.value).type_name();
+        match self.downcast_mut() {
+            Some(v) => v,
+            None => __downcast_failed(msg, type_name::<T>(), found),
+        }
+    }
+
+    /// Converts into a `Box<T>`, panicking with `msg` and both type names if the value
+    /// isn't of type `T`.
+    #[inline]
+    #[track_caller]
+    pub fn downcast_expect<T: Any>(self, msg: &str) -> Box<T> {
+        match self.downcast() {
+            Ok(v) => v,
+            Err(this) => __downcast_failed(msg, type_name::<T>(), (*this.value).type_name()),
+        }
+    }
+}
+
+impl<B: AsAny + ?Sized> From<Box<B>> for FatAny<B> {
+    fn from(value: Box<B>) -> Self {
+        FatAny::new(value)
+    }
+}
+
+impl<B: ?Sized> Deref for FatAny<B> {
+    type Target = B;
+
+    fn deref(&self) -> &B {
+        &self.value
+    }
+}
+
+impl<B: ?Sized> DerefMut for FatAny<B> {
+    fn deref_mut(&mut self) -> &mut B {
+        &mut self.value
+    }
+}
+
+impl<B: fmt::Debug + ?Sized> fmt::Debug for FatAny<B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.value.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::any::TypeId;
+
+    use super::FatAny;
+    use crate::{BoxAny, DynDebug};
+
+    trait Base: BoxAny + DynDebug {
+        fn get(&self) -> u32;
+    }
+    crate::impl_downcast!(debug Base);
+
+    #[derive(Debug)]
+    struct Foo(u32);
+    impl Base for Foo {
+        fn get(&self) -> u32 { self.0 }
+    }
+    #[derive(Debug)]
+    struct Bar;
+    impl Base for Bar {
+        fn get(&self) -> u32 { 0 }
+    }
+
+    #[test]
+    fn test() {
+        let mut base: FatAny<dyn Base> = (Box::new(Foo(1)) as Box<dyn Base>).into();
+        assert_eq!(base.type_id(), TypeId::of::<Foo>());
+        assert!(base.is::<Foo>() && !base.is::<Bar>());
+        assert!(base.downcast_ref::<Bar>().is_none());
+        base.downcast_mut::<Foo>().unwrap().0 = 2;
+        base.downcast_mut_expect::<Foo>("foo").0 += 1;
+        assert_eq!(base.get(), 3);
+        assert_eq!(format!("{:?}", base), "Foo(3)");
+
+        let base = base.downcast::<Bar>().unwrap_err();
+        assert_eq!(base.downcast_expect::<Foo>("foo").0, 3);
+        let bar = FatAny::new(Box::new(Bar) as Box<dyn Base>);
+        assert!(bar.into_inner().is::<Bar>());
+    }
+
+    #[test]
+    #[should_panic(expected = "expected `u8`")]
+    fn test_expect() {
+        FatAny::new(Box::new(Bar) as Box<dyn Base>).downcast_ref_expect::<u8>("bar");
+    }
+}
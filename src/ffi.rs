@@ -0,0 +1,227 @@
+//! `AnyHandle`, a type-erased value that can cross a C ABI, and the C functions on it.
+//!
+//! The handle is a raw pointer with a type tag and a destructor, all of which C code can
+//! hand back in any state, so this module is allowed to use `unsafe` like `clone`.
+
+use alloc::boxed::Box;
+use core::ffi::c_void;
+use core::mem::ManuallyDrop;
+use core::ptr;
+
+/// A type that can be put in an `AnyHandle`, with a tag that identifies it on both sides
+/// of the C ABI.
+///
+/// # Safety
+///
+/// `TAG` must differ from the tag of every other `HandleType`, since a handle with that
+/// tag is taken to hold a `Self`. Tags are chosen by hand so they stay the same across
+/// builds and can be shared with C code as constants.
+pub unsafe trait HandleType: 'static {
+    const TAG: u64;
+}
+
+/// A value passed to C code as an opaque pointer, with a tag telling its type and the
+/// destructor to free it with.
+///
+/// Values made in Rust are boxed; `AnyHandle::new` and `downcast*` check the tag against
+/// `HandleType::TAG`. On the C side, `any_handle_downcast` returns the pointer if the tag
+/// matches, `any_handle_drop` frees the value, and `any_handle_new` wraps a pointer of its
+/// own, e.g. a C++ object, to pass it to Rust.
+///
+/// ```
+/// use pi_any::{any_handle_downcast, any_handle_drop, AnyHandle, HandleType};
+///
+/// struct Mesh { vertices: u32 }
+/// unsafe impl HandleType for Mesh { const TAG: u64 = 1; }
+///
+/// let mut handle = AnyHandle::new(Mesh { vertices: 3 });
+/// // What the C side would do with the handle.
+/// let mesh = unsafe { any_handle_downcast(&handle, 1) }.cast::<Mesh>();
+/// assert_eq!(unsafe { (*mesh).vertices }, 3);
+/// assert!(unsafe { any_handle_downcast(&handle, 2) }.is_null());
+///
+/// assert_eq!(handle.downcast_ref::<Mesh>().unwrap().vertices, 3);
+/// unsafe { any_handle_drop(&mut handle) };
+/// assert!(handle.is_empty());
+/// ```
+#[repr(C)]
+pub struct AnyHandle {
+    tag: u64,
+    data: *mut c_void,
+    drop: Option<unsafe extern "C" fn(*mut c_void)>,
+}
+
+impl AnyHandle {
+    pub fn new<T: HandleType>(value: T) -> Self {
+        AnyHandle {
+            tag: T::TAG,
+            data: Box::into_raw(Box::new(value)).cast(),
+            drop: Some(drop_box::<T>),
+        }
+    }
+
+    /// Returns the tag of the type of the value.
+    #[inline]
+    pub fn tag(&self) -> u64 {
+        self.tag
+    }
+
+    /// Returns true if the value has been dropped or taken.
+    pub fn is_empty(&self) -> bool {
+        self.data.is_null()
+    }
+
+    /// Returns true if the handle holds a `T`.
+    #[inline]
+    pub fn is<T: HandleType>(&self) -> bool {
+        self.tag == T::TAG && !self.data.is_null()
+    }
+
+    /// Returns a reference to the value if it is of type `T`, or `None` if it isn't.
+    #[inline]
+    pub fn downcast_ref<T: HandleType>(&self) -> Option<&T> {
+        // SAFETY: a handle with the tag of `T` holds a `T`, as `HandleType` and
+        // `any_handle_new` require.
+        self.is::<T>().then(|| unsafe { &*self.data.cast::<T>() })
+    }
+
+    /// Returns a mutable reference to the value if it is of type `T`, or `None` if it isn't.
+    #[inline]
+    pub fn downcast_mut<T: HandleType>(&mut self) -> Option<&mut T> {
+        // SAFETY: as in `downcast_ref`; the borrow is unique because `self` is.
+        self.is::<T>().then(|| unsafe { &mut *self.data.cast::<T>() })
+    }
+
+    /// Moves the value out if it is of type `T`, or returns `self` if it isn't.
+    pub fn downcast<T: HandleType>(self) -> Result<T, Self> {
+        if !self.is::<T>() {
+            return Err(self);
+        }
+        let this = ManuallyDrop::new(self);
+        // SAFETY: as in `downcast_ref`, in a box; the handle is forgotten, so the value
+        // is freed once.
+        Ok(*unsafe { Box::from_raw(this.data.cast::<T>()) })
+    }
+}
+
+impl Drop for AnyHandle {
+    fn drop(&mut self) {
+        // SAFETY: `self` is valid and not used afterwards.
+        unsafe { any_handle_drop(self) }
+    }
+}
+
+unsafe extern "C" fn drop_box<T>(data: *mut c_void) {
+    // SAFETY: `data` came from `Box::into_raw` in `AnyHandle::new`.
+    drop(unsafe { Box::from_raw(data.cast::<T>()) })
+}
+
+/// Wraps `data` in a handle tagged `tag`, freed by `drop` unless it is null.
+///
+/// # Safety
+///
+/// If `tag` is the tag of a `HandleType`, `data` and `drop` must have been taken from a
+/// handle made by `AnyHandle::new` for that type. `drop` must be safe to call once with
+/// `data`.
+#[no_mangle]
+pub unsafe extern "C" fn any_handle_new(
+    tag: u64,
+    data: *mut c_void,
+    drop: Option<unsafe extern "C" fn(*mut c_void)>,
+) -> AnyHandle {
+    AnyHandle { tag, data, drop }
+}
+
+/// Returns the value of the handle if its tag is `tag`, or null if it isn't, the handle is
+/// null or its value has been dropped.
+///
+/// # Safety
+///
+/// `handle` must be null or point to a valid `AnyHandle`.
+#[no_mangle]
+pub unsafe extern "C" fn any_handle_downcast(handle: *const AnyHandle, tag: u64) -> *mut c_void {
+    // SAFETY: guaranteed by the caller.
+    match unsafe { handle.as_ref() } {
+        Some(handle) if handle.tag == tag => handle.data,
+        _ => ptr::null_mut(),
+    }
+}
+
+/// Drops the value of the handle and leaves it empty; does nothing if the handle is null
+/// or already empty.
+///
+/// # Safety
+///
+/// `handle` must be null or point to a valid `AnyHandle`, and no pointer returned by
+/// `any_handle_downcast` for it may be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn any_handle_drop(handle: *mut AnyHandle) {
+    // SAFETY: guaranteed by the caller.
+    let Some(handle) = (unsafe { handle.as_mut() }) else { return };
+    let data = core::mem::replace(&mut handle.data, ptr::null_mut());
+    if let (false, Some(drop)) = (data.is_null(), handle.drop) {
+        // SAFETY: `data` was not freed yet, as it was not null; it is now taken out.
+        unsafe { drop(data) }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::ffi::c_void;
+    use std::rc::Rc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::{any_handle_downcast, any_handle_drop, any_handle_new, AnyHandle, HandleType};
+
+    struct Texture(Rc<u32>);
+    unsafe impl HandleType for Texture {
+        const TAG: u64 = 0x7465_7874;
+    }
+    #[derive(Debug)]
+    struct Sound;
+    unsafe impl HandleType for Sound {
+        const TAG: u64 = 2;
+    }
+
+    #[test]
+    fn test() {
+        let id = Rc::new(7);
+        let mut handle = AnyHandle::new(Texture(id.clone()));
+        assert!(handle.is::<Texture>() && !handle.is::<Sound>());
+        assert_eq!(handle.tag(), Texture::TAG);
+        assert!(handle.downcast_ref::<Sound>().is_none());
+        assert_eq!(*handle.downcast_mut::<Texture>().unwrap().0, 7);
+        let handle = handle.downcast::<Sound>().unwrap_err();
+        let texture = handle.downcast::<Texture>().ok().unwrap();
+        assert_eq!(Rc::strong_count(&id), 2);
+        drop(texture);
+
+        let mut handle = AnyHandle::new(Texture(id.clone()));
+        unsafe { any_handle_drop(&mut handle) };
+        assert!(handle.is_empty() && !handle.is::<Texture>());
+        assert_eq!(Rc::strong_count(&id), 1);
+        assert!(unsafe { any_handle_downcast(&handle, Texture::TAG) }.is_null());
+        unsafe { any_handle_drop(&mut handle) };
+        unsafe { any_handle_drop(std::ptr::null_mut()) };
+        drop(handle);
+        assert_eq!(Rc::strong_count(&id), 1);
+    }
+
+    #[test]
+    fn test_foreign() {
+        static DROPPED: AtomicUsize = AtomicUsize::new(0);
+        unsafe extern "C" fn free(data: *mut c_void) {
+            assert_eq!(unsafe { *data.cast::<u8>() }, 9);
+            DROPPED.fetch_add(1, Ordering::SeqCst);
+        }
+        let mut value = 9u8;
+        let data = (&mut value as *mut u8).cast();
+        let handle = unsafe { any_handle_new(1000, data, Some(free)) };
+        assert_eq!(unsafe { any_handle_downcast(&handle, 1000) }, data);
+        assert!(handle.downcast_ref::<Sound>().is_none());
+        drop(handle);
+        assert_eq!(DROPPED.load(Ordering::SeqCst), 1);
+        drop(unsafe { any_handle_new(1000, std::ptr::null_mut(), Some(free)) });
+        assert_eq!(DROPPED.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,208 @@
+//! Golden files of the envelopes of the types in a `TypeRegistry`, for tests catching changes
+//! to their encoding.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::{Bounds, CodecError, DynEq, TypeRegistry};
+
+/// What `TypeRegistry::check_golden_files` did with each registered type, by name.
+#[derive(Debug, Default)]
+pub struct GoldenReport {
+    /// Types without a golden file, whose sample was written to a new one.
+    pub written: Vec<String>,
+    /// Types whose golden file decoded into a value equal to their sample.
+    pub verified: Vec<String>,
+    /// Types without a codec.
+    pub skipped: Vec<String>,
+    /// Types whose golden file failed to decode, or decoded into another value.
+    pub failures: Vec<GoldenFailure>,
+}
+
+impl GoldenReport {
+    /// Returns true if no golden file failed.
+    pub fn is_ok(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+/// A golden file that no longer decodes into the sample of its type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GoldenFailure {
+    /// The name the type is registered under.
+    pub name: String,
+    pub path: PathBuf,
+    /// Why decoding failed, or `None` if it decoded into a value unequal to the sample.
+    pub error: Option<CodecError>,
+}
+
+impl fmt::Display for GoldenFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "`{}` doesn't decode {} ", self.name, self.path.display())?;
+        match &self.error {
+            Some(error) => write!(f, "any more: {}", error),
+            None => f.write_str("into its sample any more"),
+        }
+    }
+}
+
+impl<B: DynEq + ?Sized, S: Bounds> TypeRegistry<B, S> {
+    /// Checks the golden files in `dir`, one per registered type with a codec, holding the
+    /// envelope of its sample: the value its factory produces.
+    ///
+    /// A golden file must decode into a value equal to the sample, compared by `DynEq`, so
+    /// one test catches a change breaking the encoding of any registered type. A missing
+    /// golden file is written, to be committed with the tests; one changed on purpose is
+    /// deleted, to be written again. The file of a type is named after its registered name,
+    /// with the characters other than ASCII letters, digits, `-`, `_` and `.` replaced by `_`.
+    ///
+    /// Fails only if `dir` or a golden file can't be read or written.
+    ///
+    /// ```
+    /// # #[macro_use]
+    /// # extern crate pi_any;
+    /// use pi_any::{DynEq, TypeRegistry};
+    ///
+    /// trait Component: DynEq {}
+    /// impl_dyn_eq!(Component);
+    ///
+    /// #[derive(PartialEq)]
+    /// struct Health(u32);
+    /// impl Component for Health {}
+    ///
+    /// # fn main() {
+    /// let mut registry = TypeRegistry::<dyn Component>::new();
+    /// registry.register::<Health>("Health", || Box::new(Health(100)));
+    /// registry.register_codec::<Health>(
+    ///     |health, out| out.extend_from_slice(&health.0.to_le_bytes()),
+    ///     |bytes| Some(Box::new(Health(u32::from_le_bytes(bytes.try_into().ok()?)))),
+    /// );
+    ///
+    /// # let dir = std::env::temp_dir().join(format!("pi_any-doc-{}", std::process::id()));
+    /// // Writes the golden files of the first run, and checks them on the next ones.
+    /// let report = registry.check_golden_files(&dir).unwrap();
+    /// assert_eq!(report.written, ["Health"]);
+    /// let report = registry.check_golden_files(&dir).unwrap();
+    /// assert!(report.is_ok() && report.verified == ["Health"]);
+    /// # std::fs::remove_dir_all(&dir).unwrap();
+    /// # }
+    /// ```
+    pub fn check_golden_files(&self, dir: impl AsRef<Path>) -> io::Result<GoldenReport> {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir)?;
+        let mut names: Vec<&str> = self.names().collect();
+        names.sort_unstable();
+
+        let mut report = GoldenReport::default();
+        for name in names {
+            if !self.has_codec(name) {
+                report.skipped.push(name.to_string());
+                continue;
+            }
+            let sample = self.construct(name).unwrap();
+            let path = dir.join(file_name(name));
+            let bytes = match fs::read(&path) {
+                Ok(bytes) => bytes,
+                Err(error) if error.kind() == io::ErrorKind::NotFound => {
+                    let bytes = self.encode(&sample).map_err(io::Error::other)?;
+                    fs::write(&path, bytes)?;
+                    report.written.push(name.to_string());
+                    continue;
+                }
+                Err(error) => return Err(error),
+            };
+            match self.decode(&bytes) {
+                Ok(value) if (*value).dyn_eq((*sample).as_any()) => {
+                    report.verified.push(name.to_string())
+                }
+                result => report.failures.push(GoldenFailure {
+                    name: name.to_string(),
+                    path,
+                    error: result.err(),
+                }),
+            }
+        }
+        Ok(report)
+    }
+}
+
+fn file_name(name: &str) -> String {
+    let safe = |c: char| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.');
+    let mut file: String = name.chars().map(|c| if safe(c) { c } else { '_' }).collect();
+    file.push_str(".bin");
+    file
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use super::file_name;
+    use crate::{CodecError, DynEq, TypeRegistry};
+
+    trait Shape: DynEq {}
+    crate::impl_dyn_eq!(Shape);
+
+    #[derive(PartialEq)]
+    struct Circle(u32);
+    impl Shape for Circle {}
+    #[derive(PartialEq)]
+    struct Square(u32);
+    impl Shape for Square {}
+    #[derive(PartialEq)]
+    struct Empty;
+    impl Shape for Empty {}
+
+    fn registry(radius: u32) -> TypeRegistry<dyn Shape> {
+        let mut registry = TypeRegistry::<dyn Shape>::new();
+        registry.register::<Circle>("shape::Circle", move || Box::new(Circle(radius)));
+        registry.register::<Square>("Square", || Box::new(Square(2)));
+        registry.register::<Empty>("Empty", || Box::new(Empty));
+        registry.register_codec::<Circle>(
+            |circle, out| out.extend_from_slice(&circle.0.to_le_bytes()),
+            |bytes| Some(Box::new(Circle(u32::from_le_bytes(bytes.try_into().ok()?)))),
+        );
+        registry.register_codec::<Square>(
+            |square, out| out.push(square.0 as u8),
+            |bytes| Some(Box::new(Square(*bytes.first()? as u32))),
+        );
+        registry
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("pi_any-{}-{}", name, std::process::id()))
+    }
+
+    #[test]
+    fn test() {
+        let dir = temp_dir("golden");
+        let report = registry(1).check_golden_files(&dir).unwrap();
+        assert_eq!(report.written, ["Square", "shape::Circle"]);
+        assert_eq!(report.skipped, ["Empty"]);
+        assert!(dir.join("shape__Circle.bin").exists());
+
+        let report = registry(1).check_golden_files(&dir).unwrap();
+        assert!(report.is_ok() && report.written.is_empty());
+        assert_eq!(report.verified, ["Square", "shape::Circle"]);
+
+        // A changed sample no longer matches, and a cut file no longer decodes.
+        fs::write(dir.join("Square.bin"), &fs::read(dir.join("Square.bin")).unwrap()[..6])
+            .unwrap();
+        let report = registry(2).check_golden_files(&dir).unwrap();
+        assert!(report.verified.is_empty());
+        let [square, circle] = &report.failures[..] else { panic!() };
+        assert_eq!(square.error, Some(CodecError::Length));
+        assert_eq!(circle.error, None);
+        assert!(circle.to_string().contains("into its sample"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_file_name() {
+        assert_eq!(file_name("a::B<c/d>"), "a__B_c_d_.bin");
+        assert_eq!(file_name("ok-1_2.v3"), "ok-1_2.v3.bin");
+    }
+}
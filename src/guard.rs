@@ -0,0 +1,278 @@
+//! `DowncastGuard`, a lock guard of a boxed trait object, mapped to the concrete type, and
+//! borrowing and downcasting of cells and locks in one step.
+
+use alloc::boxed::Box;
+use core::any::{type_name, Any};
+use core::cell::{Ref, RefCell, RefMut};
+use core::fmt;
+use core::marker::PhantomData;
+use core::ops::{Deref, DerefMut};
+#[cfg(feature = "std")]
+use std::sync::{PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use crate::{AsAny, AsMutAny, DowncastError};
+
+/// A guard of a `Box<dyn Trait>` whose value was checked to be a `T`, and which derefs to
+/// it, holding the lock until it is dropped.
+///
+/// Any guard that derefs to the box works: those of `Mutex` and `RwLock`, of `parking_lot`,
+/// or of `RefCell`. The type is checked once by `new`, and can't change while the guard is
+/// held, since only the `T` is reachable through it.
+///
+/// ```
+/// # #[macro_use]
+/// # extern crate pi_any;
+/// use std::sync::Mutex;
+/// use pi_any::{BoxAny, DowncastGuard};
+///
+/// trait Resource: BoxAny + Send {}
+/// impl_downcast!(Resource);
+///
+/// struct Score(u32);
+/// impl Resource for Score {}
+///
+/// # fn main() {
+/// let resource: Mutex<Box<dyn Resource>> = Mutex::new(Box::new(Score(0)));
+/// let mut score = DowncastGuard::<_, Score>::new(resource.lock().unwrap()).ok().unwrap();
+/// score.0 += 10;
+/// drop(score);
+///
+/// let err = DowncastGuard::<_, String>::new(resource.lock().unwrap()).unwrap_err();
+/// assert_eq!(err.found, "rust_out::Score");
+/// drop(err);
+/// assert!(resource.lock().unwrap().downcast_ref::<Score>().is_some_and(|s| s.0 == 10));
+/// # }
+/// ```
+pub struct DowncastGuard<G, T> {
+    guard: G,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<G, D, T> DowncastGuard<G, T>
+where
+    G: Deref<Target = Box<D>>,
+    D: AsAny + ?Sized,
+    T: Any,
+{
+    /// Maps the guard to the value if it is of type `T`, or returns it in the error if it
+    /// isn't.
+    pub fn new(guard: G) -> Result<Self, DowncastError<G>> {
+        let value: &D = &guard;
+        if AsAny::as_any(value).is::<T>() {
+            return Ok(DowncastGuard { guard, marker: PhantomData });
+        }
+        Err(DowncastError {
+            expected: type_name::<T>(),
+            found: AsAny::type_name(value),
+            value: guard,
+        })
+    }
+
+    /// Returns the original guard.
+    pub fn into_inner(self) -> G {
+        self.guard
+    }
+
+    #[cfg(feature = "arc-swap")]
+    pub(crate) fn inner(&self) -> &G {
+        &self.guard
+    }
+}
+
+impl<G, D, T> Deref for DowncastGuard<G, T>
+where
+    G: Deref<Target = Box<D>>,
+    D: AsAny + ?Sized,
+    T: Any,
+{
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        let value: &D = &self.guard;
+        AsAny::as_any(value).downcast_ref().expect("the type was checked by `new`")
+    }
+}
+
+impl<G, D, T> DerefMut for DowncastGuard<G, T>
+where
+    G: DerefMut<Target = Box<D>>,
+    D: AsAny + AsMutAny + ?Sized,
+    T: Any,
+{
+    fn deref_mut(&mut self) -> &mut T {
+        let value: &mut D = &mut self.guard;
+        AsMutAny::as_any_mut(value).downcast_mut().expect("the type was checked by `new`")
+    }
+}
+
+impl<G, D, T> fmt::Debug for DowncastGuard<G, T>
+where
+    G: Deref<Target = Box<D>>,
+    D: AsAny + ?Sized,
+    T: Any + fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+/// Borrowing of a `RefCell<dyn Trait>`, or of an `Rc<RefCell<dyn Trait>>`, as a `T`.
+///
+/// ```
+/// # #[macro_use]
+/// # extern crate pi_any;
+/// use std::cell::RefCell;
+/// use std::rc::Rc;
+/// use pi_any::{BoxAny, RefCellDowncastExt};
+///
+/// trait Widget: BoxAny {}
+/// impl_downcast!(Widget);
+///
+/// struct Label(String);
+/// impl Widget for Label {}
+///
+/// # fn main() {
+/// let widget: Rc<RefCell<dyn Widget>> = Rc::new(RefCell::new(Label("a".into())));
+/// widget.borrow_downcast_mut::<Label>().unwrap().0.push('b');
+/// assert_eq!(widget.borrow_downcast_ref::<Label>().unwrap().0, "ab");
+/// assert!(widget.borrow_downcast_ref::<u32>().is_none());
+/// # }
+/// ```
+pub trait RefCellDowncastExt {
+    /// Borrows the value if it is of type `T`, or returns `None` if it isn't.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the value is mutably borrowed, like `RefCell::borrow`.
+    fn borrow_downcast_ref<T: Any>(&self) -> Option<Ref<'_, T>>;
+
+    /// Mutably borrows the value if it is of type `T`, or returns `None` if it isn't.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the value is borrowed, like `RefCell::borrow_mut`.
+    fn borrow_downcast_mut<T: Any>(&self) -> Option<RefMut<'_, T>>;
+}
+
+impl<D: AsAny + AsMutAny + ?Sized> RefCellDowncastExt for RefCell<D> {
+    fn borrow_downcast_ref<T: Any>(&self) -> Option<Ref<'_, T>> {
+        Ref::filter_map(self.borrow(), |value| AsAny::as_any(value).downcast_ref()).ok()
+    }
+
+    fn borrow_downcast_mut<T: Any>(&self) -> Option<RefMut<'_, T>> {
+        let value = self.borrow_mut();
+        RefMut::filter_map(value, |value| AsMutAny::as_any_mut(value).downcast_mut()).ok()
+    }
+}
+
+/// Locking of an `RwLock<Box<dyn Trait>>`, or of an `Arc` of one, as a `T`.
+///
+/// The value is boxed, unlike with `RefCellDowncastExt`, since the guards of `std` can't be
+/// mapped to part of the value; `DowncastGuard` reaches the `T` through the box instead. A
+/// poisoned lock is locked anyway, as the value is still of the type it was checked to be.
+///
+/// ```
+/// # #[macro_use]
+/// # extern crate pi_any;
+/// use std::sync::{Arc, RwLock};
+/// use pi_any::{BoxAny, RwLockDowncastExt};
+///
+/// trait Widget: BoxAny + Send + Sync {}
+/// impl_downcast!(Widget);
+///
+/// struct Label(String);
+/// impl Widget for Label {}
+///
+/// # fn main() {
+/// let widget: Arc<RwLock<Box<dyn Widget>>> = Arc::new(RwLock::new(Box::new(Label("a".into()))));
+/// widget.write_downcast::<Label>().unwrap().0.push('b');
+/// assert_eq!(widget.read_downcast::<Label>().unwrap().0, "ab");
+/// assert!(widget.read_downcast::<u32>().is_none());
+/// # }
+/// ```
+#[cfg(feature = "std")]
+pub trait RwLockDowncastExt<D: ?Sized> {
+    /// Locks the value for reading if it is of type `T`, or returns `None` if it isn't.
+    fn read_downcast<T: Any>(&self) -> Option<DowncastGuard<RwLockReadGuard<'_, Box<D>>, T>>;
+
+    /// Locks the value for writing if it is of type `T`, or returns `None` if it isn't.
+    fn write_downcast<T: Any>(&self) -> Option<DowncastGuard<RwLockWriteGuard<'_, Box<D>>, T>>;
+}
+
+#[cfg(feature = "std")]
+impl<D: AsAny + ?Sized> RwLockDowncastExt<D> for RwLock<Box<D>> {
+    fn read_downcast<T: Any>(&self) -> Option<DowncastGuard<RwLockReadGuard<'_, Box<D>>, T>> {
+        DowncastGuard::new(self.read().unwrap_or_else(PoisonError::into_inner)).ok()
+    }
+
+    fn write_downcast<T: Any>(&self) -> Option<DowncastGuard<RwLockWriteGuard<'_, Box<D>>, T>> {
+        DowncastGuard::new(self.write().unwrap_or_else(PoisonError::into_inner)).ok()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::sync::RwLock;
+
+    use super::{DowncastGuard, RefCellDowncastExt};
+    use crate::BoxAny;
+
+    trait Item: BoxAny + Send + Sync {}
+    crate::impl_downcast!(Item);
+
+    #[derive(Debug)]
+    struct Foo(u32);
+    impl Item for Foo {}
+
+    #[test]
+    fn test() {
+        let lock: RwLock<Box<dyn Item>> = RwLock::new(Box::new(Foo(1)));
+        {
+            let a = DowncastGuard::<_, Foo>::new(lock.read().unwrap()).unwrap();
+            let b = DowncastGuard::<_, Foo>::new(lock.read().unwrap()).unwrap();
+            assert_eq!(a.0 + b.0, 2);
+            assert_eq!(format!("{:?}", a), "Foo(1)");
+        }
+        let mut foo = DowncastGuard::<_, Foo>::new(lock.write().unwrap()).unwrap();
+        foo.0 = 2;
+        assert!(lock.try_read().is_err());
+        let guard = foo.into_inner();
+        assert_eq!(guard.downcast_ref::<Foo>().unwrap().0, 2);
+        drop(guard);
+
+        let cell: RefCell<Box<dyn Item>> = RefCell::new(Box::new(Foo(3)));
+        let err = DowncastGuard::<_, u8>::new(cell.borrow_mut()).unwrap_err();
+        assert_eq!(err.expected, "u8");
+        drop(err);
+        DowncastGuard::<_, Foo>::new(cell.borrow_mut()).unwrap().0 += 1;
+        assert_eq!(cell.borrow().downcast_ref::<Foo>().unwrap().0, 4);
+    }
+
+    #[test]
+    fn test_ext() {
+        let cell: Rc<RefCell<dyn Item>> = Rc::new(RefCell::new(Foo(1)));
+        {
+            let (a, b) = (cell.borrow_downcast_ref::<Foo>(), cell.borrow_downcast_ref::<Foo>());
+            assert_eq!(a.unwrap().0 + b.unwrap().0, 2);
+        }
+        cell.borrow_downcast_mut::<Foo>().unwrap().0 = 2;
+        assert!(cell.borrow_downcast_mut::<u8>().is_none());
+        assert!(cell.try_borrow_mut().is_ok());
+        assert_eq!(cell.borrow_downcast_ref::<Foo>().unwrap().0, 2);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_rwlock_ext() {
+        use std::sync::Arc;
+
+        use super::RwLockDowncastExt;
+
+        let lock: Arc<RwLock<Box<dyn Item>>> = Arc::new(RwLock::new(Box::new(Foo(3))));
+        lock.write_downcast::<Foo>().unwrap().0 += 1;
+        assert!(lock.write_downcast::<u8>().is_none());
+        assert_eq!(lock.read_downcast::<Foo>().unwrap().0, 4);
+    }
+}
@@ -0,0 +1,146 @@
+//! `AsyncHandlers`, routing type-erased messages to async handlers registered by type.
+
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+
+type Message = Box<dyn Any + Send>;
+
+/// The future of a dispatched message.
+pub type BoxFuture<R> = Pin<Box<dyn Future<Output = R> + Send>>;
+
+type Handler<R> = Box<dyn Fn(Message) -> BoxFuture<R> + Send + Sync>;
+
+/// Async handlers by message type, e.g. the RPC methods of a server, with the requests
+/// decoded to their own types and the responses of type `R`.
+///
+/// `dispatch` downcasts the message and moves it into the handler of its type, returning
+/// the future to await; it runs on whatever executor awaits it.
+///
+/// ```
+/// use std::future::Future;
+/// use std::pin::pin;
+/// use std::task::{Context, Poll, Waker};
+/// use pi_any::AsyncHandlers;
+///
+/// struct Ping;
+/// struct Echo(String);
+///
+/// let mut handlers = AsyncHandlers::<String>::new();
+/// handlers.register(|_: Ping| async { "pong".to_string() });
+/// handlers.register(|echo: Echo| async move { echo.0 });
+///
+/// let future = handlers.dispatch(Box::new(Echo("hi".into()))).ok().unwrap();
+/// let mut cx = Context::from_waker(Waker::noop());
+/// assert_eq!(pin!(future).poll(&mut cx), Poll::Ready("hi".to_string()));
+/// assert!(handlers.dispatch(Box::new(1u8)).is_err());
+/// ```
+pub struct AsyncHandlers<R = ()> {
+    handlers: HashMap<TypeId, Handler<R>>,
+}
+
+impl<R: 'static> AsyncHandlers<R> {
+    pub fn new() -> Self {
+        AsyncHandlers { handlers: HashMap::new() }
+    }
+
+    /// Handles messages of type `T` with `handler`, usually an `async fn(T) -> R` or an
+    /// async closure, replacing an earlier one.
+    pub fn register<T, F, Fut>(&mut self, handler: F)
+    where
+        T: Any + Send,
+        F: Fn(T) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = R> + Send + 'static,
+    {
+        let handler: Handler<R> = Box::new(move |msg| Box::pin(handler(*msg.downcast().unwrap())));
+        self.handlers.insert(TypeId::of::<T>(), handler);
+    }
+
+    /// Returns true if messages of type `T` have a handler.
+    pub fn handles<T: Any>(&self) -> bool {
+        self.handlers.contains_key(&TypeId::of::<T>())
+    }
+
+    /// Removes the handler of messages of type `T`, returning true if there was one.
+    pub fn unregister<T: Any>(&mut self) -> bool {
+        self.handlers.remove(&TypeId::of::<T>()).is_some()
+    }
+
+    /// Returns the future of the handler of the type of `msg`, or returns `msg` if its type
+    /// has no handler.
+    pub fn dispatch(&self, msg: Message) -> Result<BoxFuture<R>, Message> {
+        match self.handlers.get(&(*msg).type_id()) {
+            Some(handler) => Ok(handler(msg)),
+            None => Err(msg),
+        }
+    }
+}
+
+impl<R: 'static> Default for AsyncHandlers<R> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<R> fmt::Debug for AsyncHandlers<R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AsyncHandlers").field("types", &self.handlers.len()).finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{Arc, Mutex};
+    use std::task::{Context, Poll, Waker};
+
+    use super::{AsyncHandlers, BoxFuture};
+
+    struct Add(u32, u32);
+    struct Log(&'static str);
+
+    async fn add(msg: Add) -> u32 {
+        msg.0 + msg.1
+    }
+
+    fn block_on<R>(mut future: BoxFuture<R>) -> R {
+        let mut cx = Context::from_waker(Waker::noop());
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+        }
+    }
+
+    #[test]
+    fn test() {
+        let mut handlers = AsyncHandlers::<u32>::default();
+        handlers.register(add);
+        handlers.register(|s: String| async move { s.len() as u32 });
+        assert!(handlers.handles::<Add>() && !handlers.handles::<Log>());
+        assert_eq!(block_on(handlers.dispatch(Box::new(Add(1, 2))).ok().unwrap()), 3);
+        assert_eq!(block_on(handlers.dispatch(Box::new("abcd".to_string())).ok().unwrap()), 4);
+
+        let err = handlers.dispatch(Box::new(Log("a"))).err().unwrap();
+        assert_eq!(err.downcast_ref::<Log>().unwrap().0, "a");
+        assert!(handlers.unregister::<Add>() && !handlers.unregister::<Add>());
+        assert!(handlers.dispatch(Box::new(Add(1, 2))).is_err());
+        assert_eq!(format!("{:?}", handlers), "AsyncHandlers { types: 1 }");
+    }
+
+    #[test]
+    fn test_send() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut handlers = AsyncHandlers::new();
+        let sink = log.clone();
+        handlers.register(move |msg: Log| {
+            let sink = sink.clone();
+            async move { sink.lock().unwrap().push(msg.0) }
+        });
+        let future = handlers.dispatch(Box::new(Log("x"))).ok().unwrap();
+        // The future can be awaited on another thread.
+        std::thread::spawn(move || block_on(future)).join().unwrap();
+        assert_eq!(*log.lock().unwrap(), ["x"]);
+    }
+}
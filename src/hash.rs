@@ -0,0 +1,88 @@
+//! `DynHash`, hashing trait objects.
+
+use core::any::{Any, TypeId};
+use core::hash::{Hash, Hasher};
+
+use crate::AsAny;
+
+/// Implemented for every `Hash` type, so a trait extending `DynHash` can implement `Hash`
+/// for its trait objects with `impl_dyn_hash!`.
+///
+/// The `TypeId` of the concrete type is hashed before the value, so equal-looking values
+/// of different types, e.g. `1u8` and `true`, hash differently.
+///
+/// ```
+/// # #[macro_use]
+/// # extern crate pi_any;
+/// use std::collections::HashSet;
+/// use pi_any::{DynEq, DynHash};
+///
+/// trait Tag: DynEq + DynHash {}
+/// impl_dyn_eq!(Tag);
+/// impl_dyn_hash!(Tag);
+///
+/// #[derive(PartialEq, Eq, Hash)]
+/// struct Layer(u8);
+/// impl Tag for Layer {}
+/// #[derive(PartialEq, Eq, Hash)]
+/// struct Name(&'static str);
+/// impl Tag for Name {}
+///
+/// # fn main() {
+/// let mut tags: HashSet<Box<dyn Tag>> = HashSet::new();
+/// assert!(tags.insert(Box::new(Layer(1))));
+/// assert!(tags.insert(Box::new(Name("player"))));
+/// assert!(!tags.insert(Box::new(Layer(1))));
+/// assert!(tags.contains(&(Box::new(Name("player")) as Box<dyn Tag>)));
+/// # }
+/// ```
+pub trait DynHash: AsAny {
+    /// Feeds the `TypeId` of the concrete type and then the value into `state`.
+    fn dyn_hash(&self, state: &mut dyn Hasher);
+}
+
+impl<T: Hash + Any> DynHash for T {
+    fn dyn_hash(&self, mut state: &mut dyn Hasher) {
+        TypeId::of::<T>().hash(&mut state);
+        self.hash(&mut state);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::hash_map::DefaultHasher;
+    use std::collections::HashSet;
+    use std::hash::{Hash, Hasher};
+
+    use super::DynHash;
+    use crate::DynEq;
+
+    trait Base: DynEq + DynHash {}
+    crate::impl_dyn_eq!(Base);
+    crate::impl_dyn_hash!(Base);
+
+    #[derive(PartialEq, Eq, Hash)]
+    struct Foo(u32);
+    impl Base for Foo {}
+    #[derive(PartialEq, Eq, Hash)]
+    struct Bar(u32);
+    impl Base for Bar {}
+
+    fn hash(value: &dyn Base) -> u64 {
+        let mut state = DefaultHasher::new();
+        value.hash(&mut state);
+        state.finish()
+    }
+
+    #[test]
+    fn test() {
+        assert_eq!(hash(&Foo(1)), hash(&Foo(1)));
+        assert_ne!(hash(&Foo(1)), hash(&Bar(1)));
+
+        let values: Vec<Box<dyn Base>> =
+            vec![Box::new(Foo(1)), Box::new(Bar(1)), Box::new(Foo(1)), Box::new(Foo(2))];
+        let set: HashSet<_> = values.into_iter().collect();
+        assert_eq!(set.len(), 3);
+        assert!(set.contains(&(Box::new(Bar(1)) as Box<dyn Base>)));
+    }
+}
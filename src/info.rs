@@ -0,0 +1,77 @@
+//! `TypeInfo`, the identity and layout of the concrete type behind a trait object.
+
+use core::alloc::Layout;
+use core::any::{type_name, Any, TypeId};
+
+/// What `TypeInfo` tells about a type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TypeDesc {
+    pub id: TypeId,
+    /// The name of the type, as given by `std::any::type_name`.
+    pub name: &'static str,
+    pub layout: Layout,
+}
+
+impl TypeDesc {
+    pub fn of<T: Any>() -> Self {
+        TypeDesc { id: TypeId::of::<T>(), name: type_name::<T>(), layout: Layout::new::<T>() }
+    }
+}
+
+/// Implemented for every `'static` type, so a trait extending `TypeInfo` tells the id, name
+/// and layout of the concrete type behind its trait objects, e.g. for a memory profiler
+/// counting the bytes of each type in a `Vec<Box<dyn Trait>>`.
+///
+/// A box is a `'static` type as well, so call `type_desc` on the trait object, e.g.
+/// `(**boxed).type_desc()` for a `&Box<dyn Trait>`, not on the box.
+///
+/// ```
+/// use std::alloc::Layout;
+/// use pi_any::TypeInfo;
+///
+/// trait Component: TypeInfo {}
+/// impl Component for u64 {}
+/// impl Component for [u8; 3] {}
+///
+/// let components: Vec<Box<dyn Component>> = vec![Box::new(1u64), Box::new([0u8; 3])];
+/// let bytes: usize = components.iter().map(|c| (**c).type_desc().layout.size()).sum();
+/// assert_eq!(bytes, 11);
+/// assert_eq!((*components[1]).type_desc().name, "[u8; 3]");
+/// assert_eq!(components[1].type_desc().layout, Layout::new::<Box<dyn Component>>());
+/// ```
+pub trait TypeInfo: Any {
+    fn type_desc(&self) -> TypeDesc;
+}
+
+impl<T: Any> TypeInfo for T {
+    fn type_desc(&self) -> TypeDesc {
+        TypeDesc::of::<T>()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::alloc::Layout;
+    use std::any::TypeId;
+
+    use super::{TypeDesc, TypeInfo};
+
+    trait Base: TypeInfo {}
+
+    #[repr(align(32))]
+    struct Aligned(#[allow(dead_code)] [u8; 40]);
+    impl Base for Aligned {}
+    impl Base for () {}
+
+    #[test]
+    fn test() {
+        let bases: Vec<Box<dyn Base>> = vec![Box::new(Aligned([0; 40])), Box::new(())];
+        let desc = (*bases[0]).type_desc();
+        assert_eq!(desc, TypeDesc::of::<Aligned>());
+        assert_eq!((desc.layout.size(), desc.layout.align()), (64, 32));
+        assert_eq!(desc.layout, Layout::for_value(&*bases[0]));
+        assert!(desc.name.ends_with("::Aligned"));
+        assert_eq!((*bases[1]).type_desc().id, TypeId::of::<()>());
+        assert_eq!((*bases[1]).type_desc().layout.size(), 0);
+    }
+}
@@ -0,0 +1,148 @@
+//! `DowncastIterExt`, downcasting the items of iterators over trait objects.
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::any::Any;
+
+use crate::{AsAny, AsMutAny, BoxAny};
+
+/// A reference or box to a trait object, which `DowncastIterExt` downcasts: `&dyn Trait`
+/// into `&T`, `&mut dyn Trait` into `&mut T` and `Box<dyn Trait>` into `Box<T>`.
+pub trait DowncastItem: Sized {
+    /// What the item becomes when downcast to `T`.
+    type Output<T: Any>;
+
+    /// Downcasts the item if it is a `T`, or returns it if it isn't.
+    fn downcast_item<T: Any>(self) -> Result<Self::Output<T>, Self>;
+}
+
+impl<'a, D: AsAny + ?Sized> DowncastItem for &'a D {
+    type Output<T: Any> = &'a T;
+
+    fn downcast_item<T: Any>(self) -> Result<&'a T, Self> {
+        AsAny::as_any(self).downcast_ref().ok_or(self)
+    }
+}
+
+impl<'a, D: AsAny + AsMutAny + ?Sized> DowncastItem for &'a mut D {
+    type Output<T: Any> = &'a mut T;
+
+    fn downcast_item<T: Any>(self) -> Result<&'a mut T, Self> {
+        if !AsAny::as_any(&*self).is::<T>() {
+            return Err(self);
+        }
+        let value = AsMutAny::as_any_mut(self).downcast_mut();
+        Ok(value.expect("`as_any` and `as_any_mut` return different values"))
+    }
+}
+
+impl<D: BoxAny + ?Sized> DowncastItem for Box<D> {
+    type Output<T: Any> = Box<T>;
+
+    fn downcast_item<T: Any>(self) -> Result<Box<T>, Self> {
+        crate::__downcast_box(self)
+    }
+}
+
+/// The type `DowncastItem` `I` becomes when downcast to `T`.
+pub type Downcast<I, T> = <I as DowncastItem>::Output<T>;
+
+/// The items of type `T`, downcast, and the others, as split by `partition_downcast`.
+pub type Partition<I, T> = (Vec<Downcast<I, T>>, Vec<I>);
+
+/// Adapters for iterators over references or boxes to trait objects, implemented for every
+/// iterator.
+///
+/// ```
+/// # #[macro_use]
+/// # extern crate pi_any;
+/// use pi_any::{BoxAny, DowncastIterExt};
+///
+/// trait Shape: BoxAny {}
+/// impl_downcast!(Shape);
+///
+/// struct Circle(f32);
+/// impl Shape for Circle {}
+/// struct Square(f32);
+/// impl Shape for Square {}
+///
+/// # fn main() {
+/// let mut shapes: Vec<Box<dyn Shape>> = vec![Box::new(Circle(1.0)), Box::new(Square(2.0))];
+/// for circle in shapes.iter_mut().map(|s| &mut **s).filter_downcast::<Circle>() {
+///     circle.0 *= 2.0;
+/// }
+/// let circles: Vec<&Circle> = shapes.iter().map(|s| &**s).filter_downcast().collect();
+/// assert_eq!(circles.len(), 1);
+/// assert_eq!(circles[0].0, 2.0);
+///
+/// let (circles, others) = shapes.into_iter().partition_downcast::<Circle>();
+/// assert_eq!((circles[0].0, others.len()), (2.0, 1));
+/// # }
+/// ```
+pub trait DowncastIterExt: Iterator {
+    /// Yields the items that are of type `T`, downcast, and skips the others.
+    fn filter_downcast<T: Any>(self) -> impl Iterator<Item = Downcast<Self::Item, T>>
+    where
+        Self: Sized,
+        Self::Item: DowncastItem,
+    {
+        self.filter_map(|item| item.downcast_item().ok())
+    }
+
+    /// Splits the items into those of type `T`, downcast, and the others, in one pass and
+    /// keeping their order.
+    fn partition_downcast<T: Any>(self) -> Partition<Self::Item, T>
+    where
+        Self: Sized,
+        Self::Item: DowncastItem,
+    {
+        let (mut matched, mut rest) = (Vec::new(), Vec::new());
+        for item in self {
+            match item.downcast_item() {
+                Ok(value) => matched.push(value),
+                Err(item) => rest.push(item),
+            }
+        }
+        (matched, rest)
+    }
+}
+
+impl<I: Iterator> DowncastIterExt for I {}
+
+#[cfg(test)]
+mod test {
+    use super::DowncastIterExt;
+    use crate::BoxAny;
+
+    trait Item: BoxAny {}
+    crate::impl_downcast!(Item);
+
+    struct Foo(u32);
+    impl Item for Foo {}
+    struct Bar;
+    impl Item for Bar {}
+
+    #[test]
+    fn test() {
+        let mut items: Vec<Box<dyn Item>> = vec![Box::new(Foo(1)), Box::new(Bar), Box::new(Foo(2))];
+        items.iter_mut().map(|item| &mut **item).filter_downcast::<Foo>().for_each(|foo| foo.0 += 10);
+        let foos: Vec<u32> = items.iter().map(|item| &**item).filter_downcast::<Foo>().map(|foo| foo.0).collect();
+        assert_eq!(foos, [11, 12]);
+        assert_eq!(items.iter().map(|item| &**item).filter_downcast::<u8>().count(), 0);
+
+        let bars: Vec<Box<Bar>> = items.into_iter().filter_downcast().collect();
+        assert_eq!(bars.len(), 1);
+    }
+
+    #[test]
+    fn test_partition() {
+        let items: Vec<Box<dyn Item>> = vec![Box::new(Foo(1)), Box::new(Bar), Box::new(Foo(2))];
+        let (foos, rest) = items.into_iter().partition_downcast::<Foo>();
+        assert_eq!(foos.iter().map(|foo| foo.0).collect::<Vec<_>>(), [1, 2]);
+        assert_eq!(rest.len(), 1);
+        assert!(rest[0].is::<Bar>());
+
+        let (bars, rest) = rest.iter().map(|item| &**item).partition_downcast::<Bar>();
+        assert_eq!((bars.len(), rest.len()), (1, 0));
+    }
+}
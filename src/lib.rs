@@ -1,4 +1,5 @@
 #![deny(unsafe_code)]
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 //! https://github.com/fkoep/downcast-rs
 //! 该库参考了[downcast-rs](https://github.com/fkoep/downcast-rs), 为Box<dyn Trait>、Rc<dyn Trait>、Arc<dyn Trait>实现了downcast接口（向下造型）
 //!
@@ -22,8 +23,8 @@
 //!
 //! ```
 //! # #[macro_use]
-//! # extern crate any;
-//! # use any::BoxAny;
+//! # extern crate pi_any;
+//! # use pi_any::BoxAny;
 //! trait Trait: BoxAny {}
 //! impl_downcast!(Trait);
 //!
@@ -31,6 +32,23 @@
 //! trait TraitGeneric1<T>: BoxAny {}
 //! impl_downcast!(TraitGeneric1<T>);
 //!
+//! // With lifetime parameters (only the `'static` instantiation can be downcast).
+//! trait TraitLifetime<'a, T>: BoxAny {}
+//! impl_downcast!(TraitLifetime<'a, T>);
+//!
+//! // With const parameters.
+//! trait TraitConst<const N: usize>: BoxAny {}
+//! impl_downcast!(TraitConst<const N: usize>);
+//!
+//! // Traits may be named by a path.
+//! mod render { pub trait Pass: pi_any::BoxAny {} }
+//! impl_downcast!(self::render::Pass);
+//!
+//! // With auto traits. Impls are generated on the exact object type, so list each
+//! // combination in use, e.g. `impl_downcast!(Pooled; Pooled + Send)`.
+//! trait Pooled: BoxAny {}
+//! impl_downcast!(Pooled + Send + Sync);
+//!
 //! // With associated types.
 //! trait TraitGeneric2: BoxAny { type G; type H; }
 //! impl_downcast!(TraitGeneric2 assoc G, H);
@@ -41,12 +59,19 @@
 //! }
 //! impl_downcast!(TraitGeneric3<T> assoc H where T: Copy, H: Clone);
 //!
+//! // Where clauses may use projections and higher-ranked bounds.
+//! trait TraitProjection<T: Iterator, F>: BoxAny where T::Item: Clone {}
+//! impl_downcast!(TraitProjection<T, F> where T: Iterator, T::Item: Clone, F: for<'a> Fn(&'a T::Item));
+//!
 //! // With concrete types.
 //! trait TraitConcrete1<T: Copy>: BoxAny {}
 //! impl_downcast!(concrete TraitConcrete1<u32>);
 //!
 //! trait TraitConcrete2<T: Copy>: BoxAny { type H; }
 //! impl_downcast!(concrete TraitConcrete2<u32> assoc H=f64);
+//!
+//! trait TraitConcrete3<const N: usize>: BoxAny {}
+//! impl_downcast!(concrete TraitConcrete3<16>);
 //! # fn main() {}
 //! ```
 //!
@@ -55,13 +80,13 @@
 //! ```
 //! // Import macro via `macro_use` pre-1.30.
 //! #[macro_use]
-//! extern crate any;
-//! use any::BoxAny;
+//! extern crate pi_any;
+//! use pi_any::BoxAny;
 //!
 //! // To create a trait with downcasting methods, extend `BoxAny` and run
-//! // `impl_downcast!()` on the trait.
+//! // `impl_downcast_box!()` on the trait.
 //! trait Base: BoxAny {}
-//! impl_downcast!(Base);
+//! impl_downcast_box!(Base);
 //!
 //! // Concrete types implementing Base.
 //! #[derive(Debug)]
@@ -73,7 +98,7 @@
 //!
 //! fn main() {
 //!     // Create a trait object.
-//!     let mut base: Box<Base> = Box::new(Foo(42));
+//!     let mut base: Box<dyn Base> = Box::new(Foo(42));
 //!
 //!     // Try sequential downcasts.
 //!     if let Some(foo) = base.downcast_ref::<Foo>() {
@@ -97,13 +122,13 @@
 //!
 //! ```
 //! // Can call macro via namespace since rust 1.30.
-//! extern crate any;
-//! use any::BoxAny;
+//! extern crate pi_any;
+//! use pi_any::BoxAny;
 //!
 //! // To create a trait with downcasting methods, extend `BoxAny` and run
-//! // `impl_downcast!()` on the trait.
+//! // `impl_downcast_box!()` on the trait.
 //! trait Base<T: Clone>: BoxAny { type H: Copy; }
-//! downcast_rs::impl_downcast!(Base<T> assoc H where T: Clone, H: Copy);
+//! pi_any::impl_downcast_box!(Base<T> assoc H where T: Clone, H: Copy);
 //! // or: impl_downcast!(concrete Base<u32> assoc H=f32)
 //!
 //! // Concrete types implementing Base.
@@ -114,7 +139,7 @@
 //!
 //! fn main() {
 //!     // Create a trait object.
-//!     let mut base: Box<Base<u32, H=f32>> = Box::new(Bar(42.0));
+//!     let base: Box<dyn Base<u32, H=f32>> = Box::new(Bar(42.0));
 //!
 //!     // Try sequential downcasts.
 //!     if let Some(foo) = base.downcast_ref::<Foo>() {
@@ -126,17 +151,322 @@
 //!     assert!(base.is::<Bar>());
 //! }
 //! ```
+//!
+//! # `no_std`
+//!
+//! Without the default `std` feature the crate only needs `core` and `alloc`. The traits,
+//! the macros and the containers that need no hash map or lock remain: `AnyArena`,
+//! `AnyCell`, `AnyColumn`, `AnySlot`, `BumpArena`, `CowAny`, `FatAny` and `SmallAny`, along
+//! with `Pod`, `Reflect`, `Tid` and the `Dyn*` traits. Registries such as `DefaultRegistry`
+//! and `set_failure_hook` need `std`.
+//!
+//! # Optional features
+//!
+//! - `abi_stable`: downcasting the `#[sabi_trait]` objects of `abi_stable` passed between
+//!   plugins, see `impl_downcast_abi_stable!`.
+//! - `arc-swap`: `AtomicAnyArc`, a trait object replaced atomically while threads read and
+//!   downcast it.
+//! - `async`: `AsyncHandlers`, routing messages to async handlers registered by type.
+//! - `bumpalo`: `BumpArena`, values allocated in a `bumpalo::Bump` and dropped together.
+//! - `derive`: `#[derive(Reflect)]` and the `#[downcastable]` attribute.
+//! - `erased-serde`: serializing downcastable trait objects through `erased_serde`, see
+//!   `impl_serialize_any!`.
+//! - `ffi`: `AnyHandle`, a type-erased value crossing a C ABI, and the C functions on it.
+//! - `parking_lot`: `parking_lot` locks inside `SharedAnyMap`, `Services` and the failure
+//!   hook.
+//! - `serde`: tagged serialization of trait objects through a `TypeRegistry`, see
+//!   `impl_serde_tagged!`.
+//! - `share`: downcasting the `Share` pointer of `pi_share`, see `impl_downcast_share!`.
+//!   `pi_share` needs a nightly compiler, and so does this feature.
+//! - `std` (default): the registries, maps and other containers needing `std`; see `no_std`
+//!   above for what remains without it.
+//! - `triomphe`: downcasting `triomphe::Arc<dyn Trait>`, see `impl_downcast_triomphe!`.
+//! - `unchecked`: the `unsafe` `*_unchecked` downcasts, skipping the type check.
+//! - `wasm`: `JsValue`s keyed by type in an `AnyMap`, and registered serializable trait
+//!   objects converted to and from `JsValue`s by `TypeRegistry::to_js` and `from_js`.
+
+extern crate alloc;
+
+use alloc::boxed::Box;
+use alloc::rc::Rc;
+use alloc::sync::Arc;
+use core::any::{Any, TypeId};
+
+#[cfg(feature = "abi_stable")]
+#[doc(hidden)]
+pub use abi_stable as __abi_stable;
+#[doc(hidden)]
+pub extern crate alloc as __alloc;
+#[doc(hidden)]
+pub use core as __core;
+#[cfg(feature = "erased-serde")]
+#[doc(hidden)]
+pub use erased_serde as __erased_serde;
+#[cfg(any(feature = "serde", feature = "erased-serde"))]
+#[doc(hidden)]
+pub use serde as __serde;
+#[cfg(feature = "share")]
+#[doc(hidden)]
+pub use pi_share as __pi_share;
+#[cfg(feature = "triomphe")]
+#[doc(hidden)]
+pub use triomphe as __triomphe;
+
+#[cfg(feature = "derive")]
+pub use pi_any_derive::{downcastable, Reflect};
+
+#[cfg(feature = "unchecked")]
+#[allow(unsafe_code)]
+#[doc(hidden)]
+pub mod unchecked;
+
+#[cfg(feature = "std")]
+mod anyfn;
+#[cfg(feature = "std")]
+mod anymap;
+mod arena;
+#[cfg(feature = "arc-swap")]
+mod atomic;
+#[cfg(feature = "std")]
+mod bounds;
+#[cfg(feature = "bumpalo")]
+#[allow(unsafe_code)]
+mod bump;
+#[cfg(feature = "std")]
+mod cast;
+mod cell;
+#[cfg(feature = "std")]
+mod chain;
+#[cfg(feature = "std")]
+mod channel;
+#[allow(unsafe_code)]
+mod clone;
+#[cfg(feature = "std")]
+mod codec;
+mod column;
+#[cfg(feature = "std")]
+mod command;
+mod cow;
+mod debug;
+#[cfg(feature = "std")]
+mod defaults;
+#[cfg(feature = "std")]
+mod descriptor;
+mod eq;
+#[cfg(feature = "erased-serde")]
+mod erased;
+#[cfg(feature = "std")]
+mod events;
+mod failure;
+#[allow(unsafe_code)]
+mod fat;
+#[cfg(feature = "ffi")]
+#[allow(unsafe_code)]
+mod ffi;
+#[cfg(feature = "std")]
+mod golden;
+mod guard;
+#[cfg(feature = "async")]
+mod handlers;
+mod hash;
+mod info;
+mod iter;
+#[cfg(feature = "std")]
+mod local;
+#[cfg(feature = "std")]
+mod lock;
+#[cfg(feature = "std")]
+mod mailbox;
+#[cfg(feature = "std")]
+mod multimap;
+mod ord;
+mod owned;
+mod pod;
+#[cfg(feature = "std")]
+mod pool;
+mod query;
+mod reflect;
+#[cfg(feature = "std")]
+mod registry;
+#[cfg(feature = "abi_stable")]
+mod sabi;
+#[cfg(feature = "std")]
+mod scoped;
+#[cfg(feature = "std")]
+mod services;
+#[cfg(feature = "share")]
+#[allow(unsafe_code)]
+mod share;
+#[cfg(feature = "std")]
+mod shared;
+mod slot;
+#[allow(unsafe_code)]
+mod small;
+#[cfg(feature = "serde")]
+mod tagged;
+#[allow(unsafe_code)]
+mod tid;
+#[cfg(feature = "triomphe")]
+#[allow(unsafe_code)]
+mod triomphe_arc;
+mod upcast;
+#[cfg(feature = "std")]
+mod visitor;
+#[cfg(feature = "wasm")]
+mod wasm;
 
-use std::any::Any;
-use std::sync::Arc;
-use std::rc::Rc;
+#[cfg(feature = "std")]
+pub use anyfn::{AnyFn, FnParam, IntoAnyFn, MissingArg};
+#[cfg(feature = "std")]
+pub use anymap::{AnyMap, AnyMapDiff, AnyMapEntry, MergePolicy};
+pub use arena::{AnyArena, Handle};
+#[cfg(feature = "arc-swap")]
+pub use atomic::AtomicAnyArc;
+#[cfg(feature = "std")]
+pub use bounds::{AnyBounds, Bounded, Bounds, SendBounds, SendSyncBounds};
+#[cfg(feature = "bumpalo")]
+pub use bump::BumpArena;
+#[cfg(feature = "std")]
+pub use cast::CastRegistry;
+pub use cell::{AnyCell, CellError};
+#[cfg(feature = "std")]
+pub use chain::DowncastChain;
+#[cfg(feature = "std")]
+pub use channel::{any_channel, AnyReceiver, AnySelect, AnySender};
+pub use clone::{clone_box, CloneAny, SharedPtr};
+#[doc(hidden)]
+pub use clone::__downcast_make_mut;
+#[cfg(feature = "std")]
+pub use codec::{CodecError, UnknownTypeValue};
+pub use column::AnyColumn;
+#[cfg(feature = "std")]
+pub use command::{Command, CommandDispatcher};
+pub use cow::CowAny;
+pub use debug::DynDebug;
+#[cfg(feature = "std")]
+pub use defaults::DefaultRegistry;
+#[cfg(feature = "std")]
+pub use descriptor::{TypeDescriptor, TypeDescriptorBuilder};
+pub use eq::DynEq;
+#[cfg(feature = "erased-serde")]
+pub use erased::SerializeAny;
+#[cfg(feature = "std")]
+pub use events::EventBus;
+#[doc(hidden)]
+pub use failure::{__downcast_failed, __fail};
+#[cfg(feature = "std")]
+pub use failure::{set_failure_hook, FailureHook};
+pub use failure::{failure_policy, set_failure_policy, Failure, FailurePolicy};
+pub use failure::{DowncastError, MissingValue, TypeMismatch};
+pub use fat::FatAny;
+#[cfg(feature = "ffi")]
+pub use ffi::{any_handle_downcast, any_handle_drop, any_handle_new, AnyHandle, HandleType};
+#[cfg(feature = "std")]
+pub use golden::{GoldenFailure, GoldenReport};
+pub use guard::{DowncastGuard, RefCellDowncastExt};
+#[cfg(feature = "std")]
+pub use guard::RwLockDowncastExt;
+#[cfg(feature = "async")]
+pub use handlers::{AsyncHandlers, BoxFuture};
+pub use hash::DynHash;
+pub use info::{TypeDesc, TypeInfo};
+pub use iter::{Downcast, DowncastItem, DowncastIterExt, Partition};
+#[cfg(feature = "std")]
+pub use local::LocalTypeMap;
+#[cfg(feature = "std")]
+pub use mailbox::{Actor, ActorHandlers, Mailbox};
+#[cfg(feature = "std")]
+pub use multimap::AnyMultiMap;
+pub use ord::DynOrd;
+#[doc(hidden)]
+pub use owned::{
+    __downcast_arc, __downcast_box, __downcast_pin_arc, __downcast_pin_box, __downcast_pin_rc,
+    __downcast_rc, __downcast_vec, __drain_downcast,
+};
+pub use pod::{Pod, PodError, PodLayout};
+#[cfg(feature = "std")]
+pub use pool::AnyPool;
+pub use query::{QueryExt, Queryable};
+#[doc(hidden)]
+pub use query::__Interface;
+pub use reflect::{Reflect, ReflectExt};
+#[cfg(feature = "std")]
+pub use registry::TypeRegistry;
+#[cfg(feature = "std")]
+pub use scoped::ScopedAnyMap;
+#[cfg(feature = "std")]
+pub use services::Services;
+#[cfg(feature = "share")]
+pub use share::ShareAny;
+#[cfg(feature = "share")]
+#[doc(hidden)]
+pub use share::__downcast_share;
+#[cfg(feature = "std")]
+pub use shared::{SharedAnyMap, SharedRead, SharedWrite};
+pub use slot::AnySlot;
+pub use small::SmallAny;
+#[cfg(feature = "serde")]
+pub use tagged::{Tagged, TaggedSeed};
+pub use tid::Tid;
+#[cfg(feature = "triomphe")]
+pub use triomphe_arc::TriompheArcAny;
+#[cfg(feature = "triomphe")]
+#[doc(hidden)]
+pub use triomphe_arc::__downcast_triomphe;
+#[cfg(feature = "std")]
+pub use visitor::{visit, AnyVisitor, TypedVisitor};
+#[cfg(feature = "wasm")]
+pub use wasm::Js;
 
+/// Access to a value as `dyn Any`, implemented for every sized `'static` type.
+///
+/// The blanket impl can't be opted out of for particular types, since stable Rust can't
+/// exclude types from a blanket impl, so a wrapper always downcasts as itself. To reach
+/// the wrapped value, declare a method returning it on the trait being downcast:
+///
+/// ```
+/// # #[macro_use]
+/// # extern crate pi_any;
+/// use pi_any::{AsAny, BoxAny};
+///
+/// trait Layer: BoxAny {
+///     /// The value this layer stands for, itself unless it wraps another layer.
+///     fn inner(&self) -> &dyn AsAny;
+/// }
+/// impl_downcast!(Layer);
+///
+/// struct Blur(f32);
+/// impl Layer for Blur {
+///     fn inner(&self) -> &dyn AsAny { self }
+/// }
+/// struct Cached(Box<dyn Layer>);
+/// impl Layer for Cached {
+///     fn inner(&self) -> &dyn AsAny { self.0.inner() }
+/// }
+///
+/// # fn main() {
+/// let layer: Box<dyn Layer> = Box::new(Cached(Box::new(Blur(2.0))));
+/// assert!(layer.is::<Cached>());
+/// assert_eq!(layer.inner().as_any().downcast_ref::<Blur>().unwrap().0, 2.0);
+/// # }
+/// ```
 pub trait AsAny: Any {
     fn as_any(&self) -> &dyn Any;
+    /// Returns the name of the concrete type, as given by `std::any::type_name`.
+    fn type_name(&self) -> &'static str;
+    /// Returns the `TypeId` of the concrete type.
+    ///
+    /// Unlike `Any::type_id`, it can't be resolved on a `Box` or `&` of a trait object by
+    /// mistake, which would return the `TypeId` of the pointer.
+    fn type_id_of(&self) -> TypeId {
+        self.as_any().type_id()
+    }
 }
 
 impl<T: Any> AsAny for T {
     fn as_any(&self) -> &dyn Any { self }
+    fn type_name(&self) -> &'static str { core::any::type_name::<T>() }
+    fn type_id_of(&self) -> TypeId { TypeId::of::<T>() }
 }
 
 pub trait AsMutAny: Any {
@@ -155,11 +485,11 @@ impl<T: AsAny + AsMutAny> BoxAny for T {
      fn into_any(self: Box<Self>) -> Box<dyn Any> { self }
 }
 
-pub trait RcAny: AsAny + 'static {
+pub trait RcAny: AsAny + AsMutAny + 'static {
     fn into_any(self: Rc<Self>) -> Rc<dyn Any>;
 }
 
-impl<T: AsAny> RcAny for T {
+impl<T: AsAny + AsMutAny> RcAny for T {
      fn into_any(self: Rc<Self>) -> Rc<dyn Any> { self }
 }
 
@@ -171,94 +501,871 @@ impl<T: AsAny + 'static + Send + Sync> ArcAny for T {
      fn into_any(self: Arc<Self>) -> Arc<dyn Any + 'static + Send + Sync> { self }
 }
 
-
 /// Adds downcasting support to traits that extend `any::BoxAny` by defining forwarding
 /// methods to the corresponding implementations on `std::any::Any` in the standard library.
 ///
 /// See https://users.rust-lang.org/t/how-to-create-a-macro-to-impl-a-provided-type-parametrized-trait/5289
 /// for why this is implemented this way to support templatized traits.
+///
+/// Several traits can be given at once, separated by `;` (a comma would be ambiguous with
+/// the list of associated types). Very long lists may need a higher `recursion_limit`.
+///
+/// ```
+/// # #[macro_use]
+/// # extern crate pi_any;
+/// # use pi_any::BoxAny;
+/// trait TraitA: BoxAny {}
+/// trait TraitB<T>: BoxAny {}
+/// trait TraitC: BoxAny { type H; }
+/// impl_downcast_box!(TraitA; TraitB<T>; TraitC assoc H);
+/// # fn main() {}
+/// ```
+///
+/// The owning downcasts also come as `try_downcast` (`try_downcast_box` etc. for
+/// `impl_downcast_all!`), whose `DowncastError` carries both type names and implements
+/// `Error`, for propagating a failure with `?`. `try_downcast_ref` and `try_downcast_mut`
+/// do the same for references, failing with a `TypeMismatch`. Their errors are handled by
+/// the `FailurePolicy` of the crate first.
+///
+/// Pinned pointers downcast with `downcast_pin` (`downcast_box_pin` etc. for
+/// `impl_downcast_all!`), which keeps them pinned, e.g. `Pin<Rc<dyn Trait>>` into
+/// `Pin<Rc<Foo>>`.
+///
+/// For boxes, `downcast_owned` moves the object out of the box, and the associated
+/// functions `downcast_vec` and `drain_downcast` convert a whole `Vec<Box<dyn Trait>>`, or
+/// only its objects of one type, e.g. `<dyn Trait>::drain_downcast::<Foo>(&mut values)`.
+///
+/// With the `unchecked` feature, `unsafe` variants that skip the type check are generated
+/// as well: `downcast_ref_unchecked`, `downcast_mut_unchecked` and `downcast_unchecked`
+/// (`downcast_box_unchecked` etc. for `impl_downcast_all!`).
+///
+/// The generated methods are `pub`. A restricted visibility may be given before the trait
+/// to keep downcasting an implementation detail of the crate:
+///
+/// ```
+/// # #[macro_use]
+/// # extern crate pi_any;
+/// # use pi_any::BoxAny;
+/// pub trait Widget: BoxAny {}
+/// impl_downcast_box!(pub(crate) Widget);
+/// # fn main() {}
+/// ```
+///
+/// Inherent methods on `dyn Trait` shadow trait methods of the same name, and the trait's
+/// own methods shadow them in turn. Prefixing the input with `ext [pub] Name for` puts the
+/// methods into an extension trait `Name`, implemented for the trait object, instead:
+///
+/// ```
+/// # #[macro_use]
+/// # extern crate pi_any;
+/// use pi_any::BoxAny;
+///
+/// trait Shape: BoxAny {
+///     fn is(&self) -> bool { true }
+/// }
+/// impl_downcast_box!(ext ShapeDowncastExt for Shape);
+///
+/// struct Circle;
+/// impl Shape for Circle {}
+///
+/// # fn main() {
+/// let shape: Box<dyn Shape> = Box::new(Circle);
+/// assert!(shape.is());
+/// assert!(ShapeDowncastExt::is::<Circle>(&*shape));
+/// assert!(ShapeDowncastExt::downcast::<Circle>(shape).is_ok());
+/// # }
+/// ```
+///
+/// Prefixing the trait with `debug` also implements `Debug` for the trait object, forwarding
+/// to the implementor. The trait must extend `DynDebug`:
+///
+/// ```
+/// # #[macro_use]
+/// # extern crate pi_any;
+/// use pi_any::{BoxAny, DynDebug};
+///
+/// trait Component: BoxAny + DynDebug {}
+/// impl_downcast_box!(debug Component);
+///
+/// #[derive(Debug)]
+/// struct Health(u32);
+/// impl Component for Health {}
+///
+/// # fn main() {
+/// let components: Vec<Box<dyn Component>> = vec![Box::new(Health(3))];
+/// assert_eq!(format!("{:?}", components), "[Health(3)]");
+/// # }
+/// ```
+///
+/// A supertrait given after the trait and a `:`, along with the helper trait declared for it
+/// by `impl_upcast!`, adds methods upcasting the trait object to it: `as_super`,
+/// `as_super_mut`, `into_super` for boxes, `into_super_rc` and `into_super_arc`. Auto traits
+/// of the trait object are kept if the helper keeps them:
+///
+/// ```
+/// # #[macro_use]
+/// # extern crate pi_any;
+/// use std::fmt::Display;
+/// use pi_any::BoxAny;
+///
+/// impl_upcast!(AsDisplaySend: Display + Send);
+///
+/// trait Label: BoxAny + Display + AsDisplaySend {}
+/// impl_downcast_box!(Label + Send : Display via AsDisplaySend);
+///
+/// impl Label for u32 {}
+///
+/// # fn main() {
+/// let label: Box<dyn Label + Send> = Box::new(7u32);
+/// assert_eq!(label.as_super().to_string(), "7");
+/// let display: Box<dyn Display + Send> = label.into_super();
+/// assert_eq!(display.to_string(), "7");
+/// # }
+/// ```
 #[macro_export(local_inner_macros)]
 macro_rules! impl_downcast {
-    (@impl_full   
-        $trait_:ident [$($param_types:tt)*]
-        for [$($forall_types:ident),*]
+    // The `debug` option: `Debug` for the trait object, then the rest as usual. The option
+    // may come before or after the visibility and extension trait, so it is moved first.
+    (@impl_full [vis $vis:tt debug $($kind:tt)*] $($rest:tt)*) => {
+        impl_downcast! { @impl_full [debug vis $vis $($kind)*] $($rest)* }
+    };
+    (@impl_full [ext $evis:tt $ext:ident debug $($kind:tt)*] $($rest:tt)*) => {
+        impl_downcast! { @impl_full [debug ext $evis $ext $($kind)*] $($rest)* }
+    };
+    (@impl_full
+        [debug $($kind:tt)*]
+        [$($trait_:tt)*] [$($param_types:tt)*] [$($auto:ident)*]
+        for [$($forall_lts:lifetime,)*] [$($forall_types:ident,)*] [$($forall_consts:tt)*]
         where [$($preds:tt)*]
     ) => {
         impl_downcast! {
             @inject_where
-                [impl<$($forall_types),*> dyn $trait_<$($param_types)*>]
+                [impl<$($forall_lts,)* $($forall_types,)* $($forall_consts)*>
+                 $crate::__core::fmt::Debug for dyn $($trait_)*<$($param_types)*> $(+ $auto)*]
                 types [$($forall_types),*]
+                where [$($forall_lts: 'static,)* $($preds)*]
+                [{
+                    fn fmt(&self, f: &mut $crate::__core::fmt::Formatter<'_>) -> $crate::__core::fmt::Result {
+                        $crate::__core::fmt::Debug::fmt($crate::DynDebug::as_debug(self), f)
+                    }
+                }]
+        }
+        impl_downcast! {
+            @impl_full [$($kind)*]
+                [$($trait_)*] [$($param_types)*] [$($auto)*]
+                for [$($forall_lts,)*] [$($forall_types,)*] [$($forall_consts)*]
+                where [$($preds)*]
+        }
+    };
+    // `impl_clone_box!`: the `clone_box` method, in either mode, plus `Clone` for the box.
+    (@impl_full
+        [$(ext $evis:tt $ext:ident)? $(vis $vis:tt)? clone]
+        [$($trait_:tt)*] [$($param_types:tt)*] [$($auto:ident)*]
+        for [$($forall_lts:lifetime,)*] [$($forall_types:ident,)*] [$($forall_consts:tt)*]
+        where [$($preds:tt)*]
+    ) => {
+        impl_downcast! {
+            @impl_full [$(ext $evis $ext)? $(vis $vis)? clone_box]
+                [$($trait_)*] [$($param_types)*] [$($auto)*]
+                for [$($forall_lts,)*] [$($forall_types,)*] [$($forall_consts)*]
                 where [$($preds)*]
+        }
+        impl_downcast! {
+            @inject_where
+                [impl<$($forall_lts,)* $($forall_types,)* $($forall_consts)*>
+                 $crate::__core::clone::Clone for $crate::__alloc::boxed::Box<dyn $($trait_)*<$($param_types)*> $(+ $auto)*>]
+                types [$($forall_types),*]
+                where [$($forall_lts: 'static,)* $($preds)*]
+                [{
+                    fn clone(&self) -> Self {
+                        $crate::clone_box(&**self)
+                    }
+                }]
+        }
+    };
+    // `impl_dyn_eq!`: `PartialEq` for the trait object. A visibility has no effect.
+    (@impl_full
+        [$(vis $vis:tt)? eq]
+        [$($trait_:tt)*] [$($param_types:tt)*] [$($auto:ident)*]
+        for [$($forall_lts:lifetime,)*] [$($forall_types:ident,)*] [$($forall_consts:tt)*]
+        where [$($preds:tt)*]
+    ) => {
+        impl_downcast! {
+            @inject_where
+                [impl<$($forall_lts,)* $($forall_types,)* $($forall_consts)*>
+                 $crate::__core::cmp::PartialEq for dyn $($trait_)*<$($param_types)*> $(+ $auto)*]
+                types [$($forall_types),*]
+                where [$($forall_lts: 'static,)* $($preds)*]
+                [{
+                    fn eq(&self, other: &Self) -> bool {
+                        $crate::DynEq::dyn_eq(self, $crate::AsAny::as_any(other))
+                    }
+                }]
+        }
+    };
+    // `impl_dyn_hash!`: `Hash` and `Eq` for the trait object.
+    (@impl_full
+        [$(vis $vis:tt)? hash]
+        [$($trait_:tt)*] [$($param_types:tt)*] [$($auto:ident)*]
+        for [$($forall_lts:lifetime,)*] [$($forall_types:ident,)*] [$($forall_consts:tt)*]
+        where [$($preds:tt)*]
+    ) => {
+        impl_downcast! {
+            @inject_where
+                [impl<$($forall_lts,)* $($forall_types,)* $($forall_consts)*>
+                 $crate::__core::hash::Hash for dyn $($trait_)*<$($param_types)*> $(+ $auto)*]
+                types [$($forall_types),*]
+                where [$($forall_lts: 'static,)* $($preds)*]
+                [{
+                    fn hash<__H: $crate::__core::hash::Hasher>(&self, state: &mut __H) {
+                        $crate::DynHash::dyn_hash(self, state)
+                    }
+                }]
+        }
+        impl_downcast! {
+            @inject_where
+                [impl<$($forall_lts,)* $($forall_types,)* $($forall_consts)*>
+                 $crate::__core::cmp::Eq for dyn $($trait_)*<$($param_types)*> $(+ $auto)*]
+                types [$($forall_types),*]
+                where [$($forall_lts: 'static,)* $($preds)*]
+                [{}]
+        }
+    };
+    // `impl_dyn_ord!`: `PartialOrd` and `Ord` for the trait object.
+    (@impl_full
+        [$(vis $vis:tt)? ord]
+        [$($trait_:tt)*] [$($param_types:tt)*] [$($auto:ident)*]
+        for [$($forall_lts:lifetime,)*] [$($forall_types:ident,)*] [$($forall_consts:tt)*]
+        where [$($preds:tt)*]
+    ) => {
+        impl_downcast! {
+            @inject_where
+                [impl<$($forall_lts,)* $($forall_types,)* $($forall_consts)*>
+                 $crate::__core::cmp::PartialOrd for dyn $($trait_)*<$($param_types)*> $(+ $auto)*]
+                types [$($forall_types),*]
+                where [$($forall_lts: 'static,)* $($preds)*]
+                [{
+                    fn partial_cmp(&self, other: &Self) -> $crate::__core::option::Option<$crate::__core::cmp::Ordering> {
+                        $crate::__core::option::Option::Some($crate::__core::cmp::Ord::cmp(self, other))
+                    }
+                }]
+        }
+        impl_downcast! {
+            @inject_where
+                [impl<$($forall_lts,)* $($forall_types,)* $($forall_consts)*>
+                 $crate::__core::cmp::Ord for dyn $($trait_)*<$($param_types)*> $(+ $auto)*]
+                types [$($forall_types),*]
+                where [$($forall_lts: 'static,)* $($preds)*]
+                [{
+                    fn cmp(&self, other: &Self) -> $crate::__core::cmp::Ordering {
+                        $crate::DynOrd::dyn_cmp(self, $crate::AsAny::as_any(other))
+                    }
+                }]
+        }
+    };
+    // Extension-trait mode: the methods are provided by a trait implemented for the trait
+    // object instead of inherent methods.
+    (@impl_full
+        [ext [$($vis:tt)*] $ext:ident $($kind:tt)*]
+        [$($trait_:tt)*] [$($param_types:tt)*] [$($auto:ident)*]
+        for [$($forall_lts:lifetime,)*] [$($forall_types:ident,)*] [$(const $forall_consts:ident : $const_ty:ty,)*]
+        where [$($preds:tt)*]
+    ) => {
+        impl_downcast! {
+            @inject_where
+                [#[allow(dead_code)]
+                 $($vis)* trait $ext<$($forall_lts,)* $($forall_types,)* $(const $forall_consts: $const_ty,)*>]
+                types [$($forall_types),*]
+                where [$($forall_lts: 'static,)* $($preds)*]
+                [{
+                    impl_downcast! { @impl_kinds [$($kind)*] [] [$($trait_)*] [$($param_types)*] }
+                }]
+        }
+        impl_downcast! {
+            @inject_where
+                [impl<$($forall_lts,)* $($forall_types,)* $(const $forall_consts: $const_ty,)*>
+                 $ext<$($forall_lts,)* $($forall_types,)* $($forall_consts,)*>
+                 for dyn $($trait_)*<$($param_types)*> $(+ $auto)*]
+                types [$($forall_types),*]
+                where [$($forall_lts: 'static,)* $($preds)*]
+                [{}]
+        }
+    };
+    (@impl_full
+        $kinds:tt
+        [$($trait_:tt)*] [$($param_types:tt)*] [$($auto:ident)*]
+        for [$($forall_lts:lifetime,)*] [$($forall_types:ident,)*] [$($forall_consts:tt)*]
+        where [$($preds:tt)*]
+    ) => {
+        impl_downcast! {
+            @inject_where
+                [#[allow(dead_code)]
+                 impl<$($forall_lts,)* $($forall_types,)* $($forall_consts)*>
+                 dyn $($trait_)*<$($param_types)*> $(+ $auto)*]
+                types [$($forall_types),*]
+                where [$($forall_lts: 'static,)* $($preds)*]
                 [{
-                    impl_downcast! { @impl_body $trait_ [$($param_types)*] }
-                    impl_downcast! { @impl_body_mut $trait_ [$($param_types)*] }
+                    impl_downcast! { @impl_methods $kinds [$($trait_)*] [$($param_types)*] }
                 }]
         }
     };
 
-    (@impl_body_box $trait_:ident [$($types:tt)*]) => {
-        /// Returns true if the trait object wraps an object of type `__T`.
+    (@impl_methods [vis $vis:tt $($kind:tt)*] $trait_:tt $types:tt) => {
+        impl_downcast! { @impl_kinds [$($kind)*] $vis $trait_ $types }
+    };
+    (@impl_methods $kinds:tt $trait_:tt $types:tt) => {
+        impl_downcast! { @impl_kinds $kinds [pub] $trait_ $types }
+    };
+
+    (@impl_kinds [$($kind:tt)*] $vis:tt $trait_:tt $types:tt) => {
+        $( impl_downcast! { @impl_kind $kind $vis $trait_ $types } )*
+    };
+
+    (@impl_kind ref $vis:tt $trait_:tt $types:tt) => { impl_downcast! { @impl_body $vis $trait_ $types } };
+    (@impl_kind mut $vis:tt $trait_:tt $types:tt) => { impl_downcast! { @impl_body_mut $vis $trait_ $types } };
+    (@impl_kind box $vis:tt $trait_:tt $types:tt) => {
+        impl_downcast! { @impl_body_box $vis $trait_ $types downcast downcast_expect try_downcast downcast_unchecked downcast_pin }
+    };
+    (@impl_kind rc $vis:tt $trait_:tt $types:tt) => {
+        impl_downcast! { @impl_body_rc $vis $trait_ $types downcast downcast_expect try_downcast downcast_unchecked downcast_pin }
+    };
+    (@impl_kind arc $vis:tt $trait_:tt $types:tt) => {
+        impl_downcast! { @impl_body_arc $vis $trait_ $types downcast downcast_expect try_downcast downcast_unchecked downcast_pin }
+    };
+    (@impl_kind triomphe $vis:tt $trait_:tt $types:tt) => {
+        impl_downcast! {
+            @impl_body_assoc $vis $trait_ $types [$crate::__triomphe::Arc] [$crate::TriompheArcAny]
+                [$crate::__downcast_triomphe] downcast_triomphe downcast_triomphe_expect try_downcast_triomphe
+        }
+    };
+    (@impl_kind share $vis:tt $trait_:tt $types:tt) => {
+        impl_downcast! {
+            @impl_body_assoc $vis $trait_ $types [$crate::__pi_share::Share] [$crate::ShareAny]
+                [$crate::__downcast_share] downcast_share downcast_share_expect try_downcast_share
+        }
+    };
+    (@impl_kind clone_box $vis:tt $trait_:tt $types:tt) => { impl_downcast! { @impl_body_clone $vis } };
+    (@impl_kind [upcast $supers:tt $autos:tt] [] $trait_:tt $types:tt) => {
+        $crate::__core::compile_error! { "a supertrait cannot be given together with `ext`" }
+    };
+    (@impl_kind [upcast $supers:tt $autos:tt] $vis:tt $trait_:tt $types:tt) => {
+        impl_downcast! { @impl_body_upcast $vis [] $supers $autos }
+    };
+    (@impl_kind all $vis:tt $trait_:tt $types:tt) => {
+        impl_downcast! { @impl_body_box $vis $trait_ $types downcast_box downcast_box_expect try_downcast_box downcast_box_unchecked downcast_box_pin }
+        impl_downcast! { @impl_body_rc $vis $trait_ $types downcast_rc downcast_rc_expect try_downcast_rc downcast_rc_unchecked downcast_rc_pin }
+        impl_downcast! { @impl_body_arc $vis $trait_ $types downcast_arc downcast_arc_expect try_downcast_arc downcast_arc_unchecked downcast_arc_pin }
+    };
+
+    (@impl_body_box [$($vis:tt)*] [$($trait_:tt)*] [$($types:tt)*]
+        $downcast:ident $expect:ident $try:ident $unchecked:ident $pin:ident
+    ) => {
+        /// Converts the box into a `Box<__T>` if the trait object wraps an object of type
+        /// `__T`, or returns the original box if it isn't.
+        #[inline]
+        $($vis)* fn $downcast<__T: $($trait_)*<$($types)*>>(
+            self: $crate::__alloc::boxed::Box<Self>
+        ) -> $crate::__core::result::Result<$crate::__alloc::boxed::Box<__T>, $crate::__alloc::boxed::Box<Self>>
+        where
+            Self: $crate::BoxAny,
+        {
+            $crate::__downcast_box::<__T, Self>(self)
+        }
+        /// Converts the box into a `Box<__T>`, panicking with `msg` and both type names if
+        /// the trait object doesn't wrap an object of type `__T`.
+        #[inline]
+        #[track_caller]
+        $($vis)* fn $expect<__T: $($trait_)*<$($types)*>>(
+            self: $crate::__alloc::boxed::Box<Self>, msg: &str
+        ) -> $crate::__alloc::boxed::Box<__T>
+        where
+            Self: $crate::BoxAny,
+        {
+            match self.$downcast::<__T>() {
+                Ok(v) => v,
+                Err(this) => $crate::__downcast_failed(
+                    msg, $crate::__core::any::type_name::<__T>(), $crate::AsAny::type_name(&*this)),
+            }
+        }
+        /// Converts the box into a `Box<__T>` if the trait object wraps an object of type
+        /// `__T`, or returns a `DowncastError` with both type names and the original box if
+        /// it isn't.
+        #[inline]
+        #[track_caller]
+        $($vis)* fn $try<__T: $($trait_)*<$($types)*>>(
+            self: $crate::__alloc::boxed::Box<Self>
+        ) -> $crate::__core::result::Result<$crate::__alloc::boxed::Box<__T>, $crate::DowncastError<$crate::__alloc::boxed::Box<Self>>>
+        where
+            Self: $crate::BoxAny,
+        {
+            match self.$downcast::<__T>() {
+                Ok(v) => Ok(v),
+                Err(this) => Err($crate::__fail(None, $crate::__core::stringify!($try), $crate::DowncastError {
+                    expected: $crate::__core::any::type_name::<__T>(),
+                    found: $crate::AsAny::type_name(&*this),
+                    value: this,
+                })),
+            }
+        }
+        /// Moves the object out of the box if it is of type `__T`, freeing the box, or returns
+        /// the original box if it isn't.
+        #[inline]
+        $($vis)* fn downcast_owned<__T: $($trait_)*<$($types)*>>(
+            self: $crate::__alloc::boxed::Box<Self>
+        ) -> $crate::__core::result::Result<__T, $crate::__alloc::boxed::Box<Self>>
+        where
+            Self: $crate::BoxAny,
+        {
+            self.$downcast::<__T>().map(|v| *v)
+        }
+        /// Converts every box in `values` into a `Box<__T>` if all of them wrap objects of
+        /// type `__T`, or returns `values` as they were if any doesn't.
+        #[inline]
+        $($vis)* fn downcast_vec<__T: $($trait_)*<$($types)*>>(
+            values: $crate::__alloc::vec::Vec<$crate::__alloc::boxed::Box<Self>>
+        ) -> $crate::__core::result::Result<
+            $crate::__alloc::vec::Vec<$crate::__alloc::boxed::Box<__T>>,
+            $crate::__alloc::vec::Vec<$crate::__alloc::boxed::Box<Self>>,
+        >
+        where
+            Self: $crate::BoxAny,
+        {
+            $crate::__downcast_vec::<__T, Self>(values)
+        }
+        /// Removes the boxes wrapping objects of type `__T` from `values` and returns them
+        /// as `Box<__T>`, keeping the order of both.
         #[inline]
-        pub fn downcast<__T: $trait_<$($types)*>>(
-            self: ::std::boxed::Box<Self>
-        ) -> ::std::result::Result<::std::boxed::Box<__T>, ::std::boxed::Box<Self>> {
-            if self.is::<__T>() {
-                Ok($crate::BoxAny::into_any(self).downcast::<__T>().unwrap())
-            } else {
-                Err(self)
+        $($vis)* fn drain_downcast<__T: $($trait_)*<$($types)*>>(
+            values: &mut $crate::__alloc::vec::Vec<$crate::__alloc::boxed::Box<Self>>
+        ) -> $crate::__alloc::vec::Vec<$crate::__alloc::boxed::Box<__T>>
+        where
+            Self: $crate::BoxAny,
+        {
+            $crate::__drain_downcast::<__T, Self>(values)
+        }
+        /// Converts the box into an `Rc<__T>` if the trait object wraps an object of type
+        /// `__T`, or returns the original box if it isn't.
+        #[inline]
+        $($vis)* fn downcast_into_rc<__T: $($trait_)*<$($types)*>>(
+            self: $crate::__alloc::boxed::Box<Self>
+        ) -> $crate::__core::result::Result<$crate::__alloc::rc::Rc<__T>, $crate::__alloc::boxed::Box<Self>>
+        where
+            Self: $crate::BoxAny,
+        {
+            self.$downcast::<__T>().map($crate::__alloc::rc::Rc::from)
+        }
+        /// Converts the box into an `Arc<__T>` if the trait object wraps an object of type
+        /// `__T`, or returns the original box if it isn't.
+        #[inline]
+        $($vis)* fn downcast_into_arc<__T: $($trait_)*<$($types)*>>(
+            self: $crate::__alloc::boxed::Box<Self>
+        ) -> $crate::__core::result::Result<$crate::__alloc::sync::Arc<__T>, $crate::__alloc::boxed::Box<Self>>
+        where
+            Self: $crate::BoxAny,
+        {
+            self.$downcast::<__T>().map($crate::__alloc::sync::Arc::from)
+        }
+        /// Converts the pinned box into a `Pin<Box<__T>>` if the trait object wraps an object
+        /// of type `__T`, or returns the original pinned box if it isn't. The object stays
+        /// where it was pinned.
+        #[inline]
+        $($vis)* fn $pin<__T: $($trait_)*<$($types)*>>(
+            self: $crate::__core::pin::Pin<$crate::__alloc::boxed::Box<Self>>
+        ) -> $crate::__core::result::Result<
+            $crate::__core::pin::Pin<$crate::__alloc::boxed::Box<__T>>,
+            $crate::__core::pin::Pin<$crate::__alloc::boxed::Box<Self>>,
+        >
+        where
+            Self: $crate::BoxAny,
+        {
+            $crate::__downcast_pin_box::<__T, Self>(self)
+        }
+        __impl_downcast_unchecked! { box [$($vis)*] [$($trait_)*] [$($types)*] $unchecked }
+    };
+
+    (@impl_body_rc [$($vis:tt)*] [$($trait_:tt)*] [$($types:tt)*]
+        $downcast:ident $expect:ident $try:ident $unchecked:ident $pin:ident
+    ) => {
+        /// Converts the `Rc` into an `Rc<__T>` if the trait object wraps an object of type
+        /// `__T`, or returns the original `Rc` if it isn't.
+        #[inline]
+        $($vis)* fn $downcast<__T: $($trait_)*<$($types)*>>(
+            self: $crate::__alloc::rc::Rc<Self>
+        ) -> $crate::__core::result::Result<$crate::__alloc::rc::Rc<__T>, $crate::__alloc::rc::Rc<Self>>
+        where
+            Self: $crate::RcAny,
+        {
+            $crate::__downcast_rc::<__T, Self>(self)
+        }
+        /// Converts the `Rc` into an `Rc<__T>`, panicking with `msg` and both type names if
+        /// the trait object doesn't wrap an object of type `__T`.
+        #[inline]
+        #[track_caller]
+        $($vis)* fn $expect<__T: $($trait_)*<$($types)*>>(
+            self: $crate::__alloc::rc::Rc<Self>, msg: &str
+        ) -> $crate::__alloc::rc::Rc<__T>
+        where
+            Self: $crate::RcAny,
+        {
+            match self.$downcast::<__T>() {
+                Ok(v) => v,
+                Err(this) => $crate::__downcast_failed(
+                    msg, $crate::__core::any::type_name::<__T>(), $crate::AsAny::type_name(&*this)),
+            }
+        }
+        /// Converts the `Rc` into an `Rc<__T>` if the trait object wraps an object of type
+        /// `__T`, or returns a `DowncastError` with both type names and the original `Rc` if
+        /// it isn't.
+        #[inline]
+        #[track_caller]
+        $($vis)* fn $try<__T: $($trait_)*<$($types)*>>(
+            self: $crate::__alloc::rc::Rc<Self>
+        ) -> $crate::__core::result::Result<$crate::__alloc::rc::Rc<__T>, $crate::DowncastError<$crate::__alloc::rc::Rc<Self>>>
+        where
+            Self: $crate::RcAny,
+        {
+            match self.$downcast::<__T>() {
+                Ok(v) => Ok(v),
+                Err(this) => Err($crate::__fail(None, $crate::__core::stringify!($try), $crate::DowncastError {
+                    expected: $crate::__core::any::type_name::<__T>(),
+                    found: $crate::AsAny::type_name(&*this),
+                    value: this,
+                })),
             }
         }
+        /// Returns a mutable reference to the object within the `Rc` if it is of type `__T`
+        /// and there are no other `Rc` or `Weak` pointers to the same allocation, or `None`
+        /// otherwise.
+        #[inline]
+        $($vis)* fn downcast_get_mut<__T: $($trait_)*<$($types)*>>(
+            this: &mut $crate::__alloc::rc::Rc<Self>
+        ) -> $crate::__core::option::Option<&mut __T>
+        where
+            Self: $crate::RcAny,
+        {
+            $crate::__alloc::rc::Rc::get_mut(this)
+                .and_then(|v| $crate::AsMutAny::as_any_mut(v).downcast_mut::<__T>())
+        }
+        /// Converts the pinned `Rc` into a `Pin<Rc<__T>>` if the trait object wraps an object
+        /// of type `__T`, or returns the original pinned `Rc` if it isn't. The object stays
+        /// where it was pinned.
+        #[inline]
+        $($vis)* fn $pin<__T: $($trait_)*<$($types)*>>(
+            self: $crate::__core::pin::Pin<$crate::__alloc::rc::Rc<Self>>
+        ) -> $crate::__core::result::Result<
+            $crate::__core::pin::Pin<$crate::__alloc::rc::Rc<__T>>,
+            $crate::__core::pin::Pin<$crate::__alloc::rc::Rc<Self>>,
+        >
+        where
+            Self: $crate::RcAny,
+        {
+            $crate::__downcast_pin_rc::<__T, Self>(self)
+        }
+        __impl_downcast_unchecked! { rc [$($vis)*] [$($trait_)*] [$($types)*] $unchecked }
     };
 
-    (@impl_body_rc $trait_:ident [$($types:tt)*]) => {
-        /// Returns true if the trait object wraps an object of type `__T`.
+    (@impl_body_arc [$($vis:tt)*] [$($trait_:tt)*] [$($types:tt)*]
+        $downcast:ident $expect:ident $try:ident $unchecked:ident $pin:ident
+    ) => {
+        /// Converts the `Arc` into an `Arc<__T>` if the trait object wraps an object of type
+        /// `__T`, or returns the original `Arc` if it isn't.
+        #[inline]
+        $($vis)* fn $downcast<__T: $($trait_)*<$($types)*>>(
+            self: $crate::__alloc::sync::Arc<Self>
+        ) -> $crate::__core::result::Result<$crate::__alloc::sync::Arc<__T>, $crate::__alloc::sync::Arc<Self>>
+        where
+            Self: $crate::ArcAny,
+        {
+            $crate::__downcast_arc::<__T, Self>(self)
+        }
+        /// Converts the `Arc` into an `Arc<__T>`, panicking with `msg` and both type names if
+        /// the trait object doesn't wrap an object of type `__T`.
         #[inline]
-        pub fn downcast<__T: $trait_<$($types)*>>(
-            self: ::std::rc::Rc<Self>
-        ) -> ::std::result::Result<::std::rc::Rc<__T>, ::std::rc::Rc<Self>> {
-            if self.is::<__T>() {
-                Ok($crate::RcAny::into_any(self).downcast::<__T>().unwrap())
-            } else {
-                Err(self)
+        #[track_caller]
+        $($vis)* fn $expect<__T: $($trait_)*<$($types)*>>(
+            self: $crate::__alloc::sync::Arc<Self>, msg: &str
+        ) -> $crate::__alloc::sync::Arc<__T>
+        where
+            Self: $crate::ArcAny,
+        {
+            match self.$downcast::<__T>() {
+                Ok(v) => v,
+                Err(this) => $crate::__downcast_failed(
+                    msg, $crate::__core::any::type_name::<__T>(), $crate::AsAny::type_name(&*this)),
             }
         }
+        /// Converts the `Arc` into an `Arc<__T>` if the trait object wraps an object of type
+        /// `__T`, or returns a `DowncastError` with both type names and the original `Arc` if
+        /// it isn't.
+        #[inline]
+        #[track_caller]
+        $($vis)* fn $try<__T: $($trait_)*<$($types)*>>(
+            self: $crate::__alloc::sync::Arc<Self>
+        ) -> $crate::__core::result::Result<$crate::__alloc::sync::Arc<__T>, $crate::DowncastError<$crate::__alloc::sync::Arc<Self>>>
+        where
+            Self: $crate::ArcAny,
+        {
+            match self.$downcast::<__T>() {
+                Ok(v) => Ok(v),
+                Err(this) => Err($crate::__fail(None, $crate::__core::stringify!($try), $crate::DowncastError {
+                    expected: $crate::__core::any::type_name::<__T>(),
+                    found: $crate::AsAny::type_name(&*this),
+                    value: this,
+                })),
+            }
+        }
+        /// Converts the pinned `Arc` into a `Pin<Arc<__T>>` if the trait object wraps an object
+        /// of type `__T`, or returns the original pinned `Arc` if it isn't. The object stays
+        /// where it was pinned.
+        #[inline]
+        $($vis)* fn $pin<__T: $($trait_)*<$($types)*>>(
+            self: $crate::__core::pin::Pin<$crate::__alloc::sync::Arc<Self>>
+        ) -> $crate::__core::result::Result<
+            $crate::__core::pin::Pin<$crate::__alloc::sync::Arc<__T>>,
+            $crate::__core::pin::Pin<$crate::__alloc::sync::Arc<Self>>,
+        >
+        where
+            Self: $crate::ArcAny,
+        {
+            $crate::__downcast_pin_arc::<__T, Self>(self)
+        }
+        __impl_downcast_unchecked! { arc [$($vis)*] [$($trait_)*] [$($types)*] $unchecked }
     };
 
-    (@impl_body_arc $trait_:ident [$($types:tt)*]) => {
-        /// Returns true if the trait object wraps an object of type `__T`.
+    // Pointers that can't be `self`, e.g. `triomphe::Arc`: associated functions taking the
+    // pointer, downcast by `$cast`.
+    (@impl_body_assoc [$($vis:tt)*] [$($trait_:tt)*] [$($types:tt)*]
+        [$($ptr:tt)*] [$($bound:tt)*] [$($cast:tt)*] $downcast:ident $expect:ident $try:ident
+    ) => {
+        /// Converts the pointer into one to `__T` if the trait object wraps an object of type
+        /// `__T`, or returns the original pointer if it isn't.
+        #[inline]
+        $($vis)* fn $downcast<__T: $($trait_)*<$($types)*>>(
+            this: $($ptr)*<Self>
+        ) -> $crate::__core::result::Result<$($ptr)*<__T>, $($ptr)*<Self>>
+        where
+            Self: $($bound)*,
+        {
+            $($cast)*::<__T, Self>(this)
+        }
+        /// Converts the pointer into one to `__T`, panicking with `msg` and both type names if
+        /// the trait object doesn't wrap an object of type `__T`.
+        #[inline]
+        #[track_caller]
+        $($vis)* fn $expect<__T: $($trait_)*<$($types)*>>(
+            this: $($ptr)*<Self>, msg: &str
+        ) -> $($ptr)*<__T>
+        where
+            Self: $($bound)*,
+        {
+            match $($cast)*::<__T, Self>(this) {
+                Ok(v) => v,
+                Err(this) => $crate::__downcast_failed(
+                    msg, $crate::__core::any::type_name::<__T>(), $crate::AsAny::type_name(&*this)),
+            }
+        }
+        /// Converts the pointer into one to `__T` if the trait object wraps an object of type
+        /// `__T`, or returns a `DowncastError` with both type names and the original pointer if
+        /// it isn't.
         #[inline]
-        pub fn downcast<__T: $trait_<$($types)*>>(
-            self: ::std::sync::Arc<Self>
-        ) -> ::std::result::Result<::std::sync::Arc<__T>, ::std::sync::Arc<Self>> {
-            if self.is::<__T>() {
-                Ok($crate::ArcAny::into_any(self).downcast::<__T>().unwrap())
-            } else {
-                Err(self)
+        #[track_caller]
+        $($vis)* fn $try<__T: $($trait_)*<$($types)*>>(
+            this: $($ptr)*<Self>
+        ) -> $crate::__core::result::Result<$($ptr)*<__T>, $crate::DowncastError<$($ptr)*<Self>>>
+        where
+            Self: $($bound)*,
+        {
+            match $($cast)*::<__T, Self>(this) {
+                Ok(v) => Ok(v),
+                Err(this) => Err($crate::__fail(None, $crate::__core::stringify!($try), $crate::DowncastError {
+                    expected: $crate::__core::any::type_name::<__T>(),
+                    found: $crate::AsAny::type_name(&*this),
+                    value: this,
+                })),
             }
         }
     };
 
-    (@impl_body_mut $trait_:ident [$($types:tt)*]) => {
+    (@impl_body_clone [$($vis:tt)*]) => {
+        /// Clones the object within the trait object into a new box.
+        #[inline]
+        $($vis)* fn clone_box(&self) -> $crate::__alloc::boxed::Box<Self>
+        where
+            Self: $crate::CloneAny,
+        {
+            $crate::clone_box(self)
+        }
+        /// Returns a mutable reference to the object within the `Rc` or `Arc` if it is of
+        /// type `__T`, cloning it into a new allocation first if the pointer is shared, like
+        /// `Rc::make_mut`, or `None` without cloning if it isn't.
+        #[inline]
+        $($vis)* fn downcast_make_mut<__T: $crate::__core::any::Any>(
+            this: &mut impl $crate::SharedPtr<Self>
+        ) -> $crate::__core::option::Option<&mut __T>
+        where
+            Self: $crate::CloneAny,
+        {
+            $crate::__downcast_make_mut::<__T, Self>(this)
+        }
+    };
+
+    // The supertrait is split from the helper trait declared by `impl_upcast!`, which the
+    // methods go through.
+    (@impl_body_upcast $vis:tt [$($super:tt)*] [via $($helper:tt)+] $autos:tt) => {
+        impl_downcast! { @impl_body_upcast_via $vis [$($super)*] [$($helper)+] $autos }
+    };
+    (@impl_body_upcast $vis:tt [$($super:tt)*] [$next:tt $($rest:tt)*] $autos:tt) => {
+        impl_downcast! { @impl_body_upcast $vis [$($super)* $next] [$($rest)*] $autos }
+    };
+    (@impl_body_upcast $vis:tt $super:tt [] $autos:tt) => {
+        $crate::__core::compile_error! {
+            "name the helper trait declared by `impl_upcast!`, as in `Sub : Super via AsSuper`"
+        }
+    };
+    (@impl_body_upcast_via [$($vis:tt)*] [$($super:tt)+] [$($helper:tt)+] [$($auto:ident)*]) => {
+        /// Upcasts a reference to the trait object to a reference to its supertrait.
+        #[inline]
+        #[allow(unused_parens)]
+        $($vis)* fn as_super(&self) -> &(dyn $($super)+ $(+ $auto)*) {
+            <Self as $($helper)+>::upcast_ref(self)
+        }
+        /// Upcasts a mutable reference to the trait object to one to its supertrait.
+        #[inline]
+        #[allow(unused_parens)]
+        $($vis)* fn as_super_mut(&mut self) -> &mut (dyn $($super)+ $(+ $auto)*) {
+            <Self as $($helper)+>::upcast_mut(self)
+        }
+        /// Upcasts the box into a box of the supertrait.
+        #[inline]
+        $($vis)* fn into_super(self: $crate::__alloc::boxed::Box<Self>) -> $crate::__alloc::boxed::Box<dyn $($super)+ $(+ $auto)*> {
+            <Self as $($helper)+>::upcast_box(self)
+        }
+        /// Upcasts the `Rc` into an `Rc` of the supertrait.
+        #[inline]
+        $($vis)* fn into_super_rc(self: $crate::__alloc::rc::Rc<Self>) -> $crate::__alloc::rc::Rc<dyn $($super)+ $(+ $auto)*> {
+            <Self as $($helper)+>::upcast_rc(self)
+        }
+        /// Upcasts the `Arc` into an `Arc` of the supertrait.
+        #[inline]
+        $($vis)* fn into_super_arc(self: $crate::__alloc::sync::Arc<Self>) -> $crate::__alloc::sync::Arc<dyn $($super)+ $(+ $auto)*> {
+            <Self as $($helper)+>::upcast_arc(self)
+        }
+    };
+
+    (@impl_body_mut [$($vis:tt)*] [$($trait_:tt)*] [$($types:tt)*]) => {
         /// Returns a mutable reference to the object within the trait object if it is of type
         /// `__T`, or `None` if it isn't.
         #[inline]
-        pub fn downcast_mut<__T: $trait_<$($types)*>>(&mut self) -> ::std::option::Option<&mut __T> {
+        $($vis)* fn downcast_mut<__T: $($trait_)*<$($types)*>>(&mut self) -> $crate::__core::option::Option<&mut __T>
+        where
+            Self: $crate::AsAny + $crate::AsMutAny,
+        {
             $crate::AsMutAny::as_any_mut(self).downcast_mut::<__T>()
         }
+        /// Returns a mutable reference to the object within the trait object, panicking with
+        /// `msg` and both type names if it isn't of type `__T`.
+        #[inline]
+        #[track_caller]
+        $($vis)* fn downcast_mut_expect<__T: $($trait_)*<$($types)*>>(&mut self, msg: &str) -> &mut __T
+        where
+            Self: $crate::AsAny + $crate::AsMutAny,
+        {
+            let found = $crate::AsAny::type_name(self);
+            match $crate::AsMutAny::as_any_mut(self).downcast_mut::<__T>() {
+                Some(v) => v,
+                None => $crate::__downcast_failed(msg, $crate::__core::any::type_name::<__T>(), found),
+            }
+        }
+        /// Returns a mutable reference to the object within the trait object, or a
+        /// `TypeMismatch` with both type names if it isn't of type `__T`.
+        #[inline]
+        #[track_caller]
+        $($vis)* fn try_downcast_mut<__T: $($trait_)*<$($types)*>>(
+            &mut self
+        ) -> $crate::__core::result::Result<&mut __T, $crate::TypeMismatch>
+        where
+            Self: $crate::AsAny + $crate::AsMutAny,
+        {
+            let found = $crate::AsAny::type_name(self);
+            match $crate::AsMutAny::as_any_mut(self).downcast_mut::<__T>() {
+                Some(v) => Ok(v),
+                None => Err($crate::__fail(None, "try_downcast_mut", $crate::TypeMismatch {
+                    expected: $crate::__core::any::type_name::<__T>(),
+                    found,
+                })),
+            }
+        }
+        __impl_downcast_unchecked! { mut [$($vis)*] [$($trait_)*] [$($types)*] }
     };
 
-    (@impl_body $trait_:ident [$($types:tt)*]) => {
+    (@impl_body [$($vis:tt)*] [$($trait_:tt)*] [$($types:tt)*]) => {
         /// Returns true if the trait object wraps an object of type `__T`.
         #[inline]
-        pub fn is<__T: $trait_<$($types)*>>(&self) -> bool {
+        $($vis)* fn is<__T: $($trait_)*<$($types)*>>(&self) -> bool
+        where
+            Self: $crate::AsAny,
+        {
             $crate::AsAny::as_any(self).is::<__T>()
         }
         /// Returns a reference to the object within the trait object if it is of type `__T`, or
         /// `None` if it isn't.
         #[inline]
-        pub fn downcast_ref<__T: $trait_<$($types)*>>(&self) -> ::std::option::Option<&__T> {
+        $($vis)* fn downcast_ref<__T: $($trait_)*<$($types)*>>(&self) -> $crate::__core::option::Option<&__T>
+        where
+            Self: $crate::AsAny,
+        {
             $crate::AsAny::as_any(self).downcast_ref::<__T>()
-        }   
+        }
+        /// Returns a reference to the object within the trait object, panicking with both type
+        /// names if it isn't of type `__T`.
+        #[inline]
+        #[track_caller]
+        $($vis)* fn downcast_unwrap<__T: $($trait_)*<$($types)*>>(&self) -> &__T
+        where
+            Self: $crate::AsAny,
+        {
+            match $crate::AsAny::as_any(self).downcast_ref::<__T>() {
+                Some(v) => v,
+                None => $crate::__downcast_failed(
+                    "downcast_unwrap",
+                    $crate::__core::any::type_name::<__T>(),
+                    $crate::AsAny::type_name(self),
+                ),
+            }
+        }
+        /// Returns a reference to the object within the trait object, panicking with `msg`
+        /// and both type names if it isn't of type `__T`.
+        #[inline]
+        #[track_caller]
+        $($vis)* fn downcast_ref_expect<__T: $($trait_)*<$($types)*>>(&self, msg: &str) -> &__T
+        where
+            Self: $crate::AsAny,
+        {
+            match $crate::AsAny::as_any(self).downcast_ref::<__T>() {
+                Some(v) => v,
+                None => $crate::__downcast_failed(
+                    msg, $crate::__core::any::type_name::<__T>(), $crate::AsAny::type_name(self)),
+            }
+        }
+        /// Returns a reference to the object within the trait object, or a `TypeMismatch`
+        /// with both type names if it isn't of type `__T`.
+        #[inline]
+        #[track_caller]
+        $($vis)* fn try_downcast_ref<__T: $($trait_)*<$($types)*>>(
+            &self
+        ) -> $crate::__core::result::Result<&__T, $crate::TypeMismatch>
+        where
+            Self: $crate::AsAny,
+        {
+            match $crate::AsAny::as_any(self).downcast_ref::<__T>() {
+                Some(v) => Ok(v),
+                None => Err($crate::__fail(None, "try_downcast_ref", $crate::TypeMismatch {
+                    expected: $crate::__core::any::type_name::<__T>(),
+                    found: $crate::AsAny::type_name(self),
+                })),
+            }
+        }
+        __impl_downcast_unchecked! { ref [$($vis)*] [$($trait_)*] [$($types)*] }
     };
 
     (@inject_where [$($before:tt)*] types [] where [] [$($after:tt)*]) => {
@@ -269,7 +1376,7 @@ macro_rules! impl_downcast {
         impl_downcast! {
             @as_item
                 $($before)*
-                where $( $types: ::std::any::Any + 'static ),*
+                where $( $types: $crate::__core::any::Any + 'static ),*
                 $($after)*
         }
     };
@@ -278,7 +1385,7 @@ macro_rules! impl_downcast {
             @as_item
                 $($before)*
                 where
-                    $( $types: ::std::any::Any + 'static, )*
+                    $( $types: $crate::__core::any::Any + 'static, )*
                     $($preds)*
                 $($after)*
         }
@@ -286,341 +1393,506 @@ macro_rules! impl_downcast {
 
     (@as_item $i:item) => { $i };
 
-    // No type parameters.
-    ($trait_:ident   ) => { impl_downcast! { @impl_full $trait_ [] for [] where [] } };
-    ($trait_:ident <>) => { impl_downcast! { @impl_full $trait_ [] for [] where [] } };
-    // Type parameters.
-    ($trait_:ident < $($types:ident),* >) => {
-        impl_downcast! { @impl_full $trait_ [$($types),*] for [$($types),*] where [] }
+    // Several traits separated by `;` are split apart first. Up to four tokens are moved per
+    // step to keep long lists within the default recursion limit.
+    (@split $kind:tt [$($cur:tt)*] ; $($rest:tt)*) => {
+        impl_downcast! { @parse $kind $($cur)* }
+        impl_downcast! { @split $kind [] $($rest)* }
     };
-    // Type parameters and where clauses.
-    ($trait_:ident < $($types:ident),* > where $($preds:tt)+) => {
-        impl_downcast! { @impl_full $trait_ [$($types),*] for [$($types),*] where [$($preds)*] }
+    (@split $kind:tt [$($cur:tt)*] $a:tt ; $($rest:tt)*) => {
+        impl_downcast! { @split $kind [$($cur)* $a] ; $($rest)* }
     };
-    // Associated types.
-    ($trait_:ident assoc $($atypes:ident),*) => {
-        impl_downcast! { @impl_full $trait_ [$($atypes = $atypes),*] for [$($atypes),*] where [] }
+    (@split $kind:tt [$($cur:tt)*] $a:tt $b:tt ; $($rest:tt)*) => {
+        impl_downcast! { @split $kind [$($cur)* $a $b] ; $($rest)* }
     };
-    // Associated types and where clauses.
-    ($trait_:ident assoc $($atypes:ident),* where $($preds:tt)+) => {
-        impl_downcast! { @impl_full $trait_ [$($atypes = $atypes),*] for [$($atypes),*] where [$($preds)*] }
+    (@split $kind:tt [$($cur:tt)*] $a:tt $b:tt $c:tt ; $($rest:tt)*) => {
+        impl_downcast! { @split $kind [$($cur)* $a $b $c] ; $($rest)* }
     };
-    // Type parameters and associated types.
-    ($trait_:ident < $($types:ident),* > assoc $($atypes:ident),*) => {
-        impl_downcast! {
-            @impl_full
-                $trait_ [$($types),*, $($atypes = $atypes),*]
-                for [$($types),*, $($atypes),*]
-                where []
-        }
+    (@split $kind:tt [$($cur:tt)*] $a:tt $b:tt $c:tt $d:tt $($rest:tt)*) => {
+        impl_downcast! { @split $kind [$($cur)* $a $b $c $d] $($rest)* }
     };
-    // Type parameters, associated types, and where clauses.
-    ($trait_:ident < $($types:ident),* > assoc $($atypes:ident),* where $($preds:tt)+) => {
-        impl_downcast! {
-            @impl_full
-                $trait_ [$($types),*, $($atypes = $atypes),*]
-                for [$($types),*, $($atypes),*]
-                where [$($preds)*]
-        }
+    (@split $kind:tt []) => {};
+    (@split $kind:tt [$($cur:tt)*] $($rest:tt)*) => { impl_downcast! { @parse $kind $($cur)* $($rest)* } };
+
+    // Generic parameters are sorted into the argument list naming the trait, lifetimes,
+    // type parameters and const parameters one at a time, so the arms below only deal with
+    // what follows the parameter list.
+    // The trait may be named by a path, which is collected into a bracketed group first.
+    (@parse [$($kind:tt)*] pub ( $($restrict:tt)+ ) $($rest:tt)*) => {
+        impl_downcast! { @parse [vis [pub($($restrict)+)] $($kind)*] $($rest)* }
     };
-    // Concretely-parametrized types.
-    (concrete $trait_:ident < $($types:ident),* >) => {
-        impl_downcast! { @impl_full $trait_ [$($types),*] for [] where [] }
+    (@parse $kind:tt pub $($rest:tt)*) => { impl_downcast! { @parse $kind $($rest)* } };
+    (@parse [$($kind:tt)*] ext pub ( $($restrict:tt)+ ) $ext:ident for $($rest:tt)*) => {
+        impl_downcast! { @parse [ext [pub($($restrict)+)] $ext $($kind)*] $($rest)* }
     };
-    // Concretely-associated types types.
-    (concrete $trait_:ident assoc $($atypes:ident = $aty:ty),*) => {
-        impl_downcast! { @impl_full $trait_ [$($atypes = $aty),*] for [] where [] }
+    (@parse [$($kind:tt)*] ext pub $ext:ident for $($rest:tt)*) => {
+        impl_downcast! { @parse [ext [pub] $ext $($kind)*] $($rest)* }
     };
-    // Concretely-parametrized types with concrete associated types.
-    (concrete $trait_:ident < $($types:ident),* > assoc $($atypes:ident = $aty:ty),*) => {
-        impl_downcast! { @impl_full $trait_ [$($types),*, $($atypes = $aty),*] for [] where [] }
+    (@parse [$($kind:tt)*] ext $ext:ident for $($rest:tt)*) => {
+        impl_downcast! { @parse [ext [] $ext $($kind)*] $($rest)* }
     };
-}
+    (@parse $kind:tt debug :: $($rest:tt)*) => { impl_downcast! { @path $kind [] [] debug :: $($rest)* } };
+    (@parse [$($kind:tt)*] debug $($rest:tt)*) => { impl_downcast! { @parse [debug $($kind)*] $($rest)* } };
+    (@parse $kind:tt concrete $($rest:tt)*) => { impl_downcast! { @path $kind [concrete] [] $($rest)* } };
+    (@parse $kind:tt $($rest:tt)*) => { impl_downcast! { @path $kind [] [] $($rest)* } };
 
-#[macro_export(local_inner_macros)]
-macro_rules! impl_downcast_box {
-    (@impl_full  
-        $trait_:ident [$($param_types:tt)*]
-        for [$($forall_types:ident),*]
-        where [$($preds:tt)*]
+    (@path $kind:tt $concrete:tt [$($path:tt)*] :: $($rest:tt)*) => {
+        impl_downcast! { @path $kind $concrete [$($path)* ::] $($rest)* }
+    };
+    (@path $kind:tt $concrete:tt [$($path:tt)*] $seg:ident :: $($rest:tt)*) => {
+        impl_downcast! { @path $kind $concrete [$($path)* $seg ::] $($rest)* }
+    };
+    (@path $kind:tt [] [$($path:tt)*] $trait_:ident < $($rest:tt)*) => {
+        impl_downcast! { @generics $kind [$($path)* $trait_] [] [] [] [] $($rest)* }
+    };
+    (@path $kind:tt [$($concrete:tt)*] [$($path:tt)*] $trait_:ident $($rest:tt)*) => {
+        impl_downcast! { @parse_trait $kind [[] [] [] []] $($concrete)* [$($path)* $trait_] $($rest)* }
+    };
+
+    (@generics $kind:tt $trait_:tt [$($args:tt)*] [$($lts:tt)*] $types:tt $consts:tt
+        $lt:lifetime , $($rest:tt)*
     ) => {
-        impl_downcast! {
-            @inject_where
-                [impl<$($forall_types),*> dyn $trait_<$($param_types)*>]
-                types [$($forall_types),*]
-                where [$($preds)*]
-                [{
-                    impl_downcast! { @impl_body $trait_ [$($param_types)*] }
-                    impl_downcast! { @impl_body_box $trait_ [$($param_types)*] }
-                    impl_downcast! { @impl_body_mut $trait_ [$($param_types)*] }
-                }]
-        }
+        impl_downcast! { @generics $kind $trait_ [$($args)* $lt,] [$($lts)* $lt,] $types $consts $($rest)* }
     };
-
-    (@inject_where [$($before:tt)*] types [] where [] [$($after:tt)*]) => {
-        impl_downcast_box! { @as_item $($before)* $($after)* }
+    (@generics $kind:tt $trait_:tt [$($args:tt)*] [$($lts:tt)*] $types:tt $consts:tt
+        $lt:lifetime > $($rest:tt)*
+    ) => {
+        impl_downcast! { @generics $kind $trait_ [$($args)* $lt,] [$($lts)* $lt,] $types $consts > $($rest)* }
+    };
+    (@generics $kind:tt $trait_:tt [$($args:tt)*] $lts:tt $types:tt [$($consts:tt)*]
+        const $c:ident : $cty:ty , $($rest:tt)*
+    ) => {
+        impl_downcast! { @generics $kind $trait_ [$($args)* $c,] $lts $types [$($consts)* const $c: $cty,] $($rest)* }
+    };
+    (@generics $kind:tt $trait_:tt [$($args:tt)*] $lts:tt $types:tt [$($consts:tt)*]
+        const $c:ident : $cty:ty > $($rest:tt)*
+    ) => {
+        impl_downcast! { @generics $kind $trait_ [$($args)* $c,] $lts $types [$($consts)* const $c: $cty,] > $($rest)* }
+    };
+    (@generics $kind:tt $trait_:tt [$($args:tt)*] $lts:tt [$($types:tt)*] $consts:tt
+        $ty:ident , $($rest:tt)*
+    ) => {
+        impl_downcast! { @generics $kind $trait_ [$($args)* $ty,] $lts [$($types)* $ty,] $consts $($rest)* }
+    };
+    (@generics $kind:tt $trait_:tt [$($args:tt)*] $lts:tt [$($types:tt)*] $consts:tt
+        $ty:ident > $($rest:tt)*
+    ) => {
+        impl_downcast! { @generics $kind $trait_ [$($args)* $ty,] $lts [$($types)* $ty,] $consts > $($rest)* }
+    };
+    (@generics $kind:tt $trait_:tt $args:tt $lts:tt $types:tt $consts:tt > $($rest:tt)*) => {
+        impl_downcast! { @parse_trait $kind [$args $lts $types $consts] $trait_ $($rest)* }
     };
 
-    (@inject_where [$($before:tt)*] types [$($types:ident),*] where [] [$($after:tt)*]) => {
-        impl_downcast_box! {
-            @as_item
-                $($before)*
-                where $( $types: ::std::any::Any + 'static ),*
-                $($after)*
+    // A supertrait to upcast to, which is collected up to `assoc`, `where` or the end and
+    // passed on as a kind.
+    (@parse_trait $kind:tt $generics:tt $trait_:tt $(+ $auto:ident)* : $($rest:tt)+) => {
+        impl_downcast! { @upcast $kind $generics $trait_ [$($auto)*] [] $($rest)+ }
+    };
+    (@upcast [$($kind:tt)*] $generics:tt $trait_:tt [$($auto:ident)*] [$($super:tt)+]) => {
+        impl_downcast! {
+            @parse_trait [$($kind)* [upcast [$($super)+] [$($auto)*]]] $generics $trait_ $(+ $auto)*
         }
     };
-    (@inject_where [$($before:tt)*] types [$($types:ident),*] where [$($preds:tt)+] [$($after:tt)*]) => {
-        impl_downcast_box! {
-            @as_item
-                $($before)*
-                where
-                    $( $types: ::std::any::Any + 'static, )*
-                    $($preds)*
-                $($after)*
+    (@upcast [$($kind:tt)*] $generics:tt $trait_:tt [$($auto:ident)*] [$($super:tt)+] assoc $($rest:tt)+) => {
+        impl_downcast! {
+            @parse_trait [$($kind)* [upcast [$($super)+] [$($auto)*]]] $generics $trait_ $(+ $auto)* assoc $($rest)+
         }
     };
+    (@upcast [$($kind:tt)*] $generics:tt $trait_:tt [$($auto:ident)*] [$($super:tt)+] where $($rest:tt)+) => {
+        impl_downcast! {
+            @parse_trait [$($kind)* [upcast [$($super)+] [$($auto)*]]] $generics $trait_ $(+ $auto)* where $($rest)+
+        }
+    };
+    (@upcast $kind:tt $generics:tt $trait_:tt $autos:tt [$($super:tt)*] $next:tt $($rest:tt)*) => {
+        impl_downcast! { @upcast $kind $generics $trait_ $autos [$($super)* $next] $($rest)* }
+    };
 
-    (@as_item $i:item) => { $i };
-
-    // No type parameters.
-    ($trait_:ident   ) => { impl_downcast_box! { @impl_full $trait_ [] for [] where [] } };
-    ($trait_:ident <>) => { impl_downcast_box! { @impl_full $trait_ [] for [] where [] } };
-    // Type parameters.
-    ($trait_:ident < $($types:ident),* >) => {
-        impl_downcast_box! { @impl_full $trait_ [$($types),*] for [$($types),*] where [] }
+    // No associated types.
+    (@parse_trait $kind:tt [$args:tt $lts:tt $types:tt $consts:tt] $trait_:tt $(+ $auto:ident)*) => {
+        impl_downcast! { @impl_full $kind $trait_ $args [$($auto)*] for $lts $types $consts where [] }
     };
-    // Type parameters and where clauses.
-    ($trait_:ident < $($types:ident),* > where $($preds:tt)+) => {
-        impl_downcast_box! { @impl_full $trait_ [$($types),*] for [$($types),*] where [$($preds)*] }
+    // Where clauses.
+    (@parse_trait $kind:tt [$args:tt $lts:tt $types:tt $consts:tt]
+        $trait_:tt $(+ $auto:ident)* where $($preds:tt)+
+    ) => {
+        impl_downcast! { @impl_full $kind $trait_ $args [$($auto)*] for $lts $types $consts where [$($preds)*] }
     };
     // Associated types.
-    ($trait_:ident assoc $($atypes:ident),*) => {
-        impl_downcast_box! { @impl_full $trait_ [$($atypes = $atypes),*] for [$($atypes),*] where [] }
-    };
-    // Associated types and where clauses.
-    ($trait_:ident assoc $($atypes:ident),* where $($preds:tt)+) => {
-        impl_downcast_box! { @impl_full $trait_ [$($atypes = $atypes),*] for [$($atypes),*] where [$($preds)*] }
-    };
-    // Type parameters and associated types.
-    ($trait_:ident < $($types:ident),* > assoc $($atypes:ident),*) => {
-        impl_downcast_box! {
-            @impl_full
-                $trait_ [$($types),*, $($atypes = $atypes),*]
-                for [$($types),*, $($atypes),*]
+    (@parse_trait $kind:tt [[$($args:tt)*] $lts:tt [$($types:tt)*] $consts:tt]
+        $trait_:tt $(+ $auto:ident)* assoc $($atypes:ident),*
+    ) => {
+        impl_downcast! {
+            @impl_full $kind
+                $trait_ [$($args)* $($atypes = $atypes),*] [$($auto)*]
+                for $lts [$($types)* $($atypes,)*] $consts
                 where []
         }
     };
-    // Type parameters, associated types, and where clauses.
-    ($trait_:ident < $($types:ident),* > assoc $($atypes:ident),* where $($preds:tt)+) => {
-        impl_downcast_box! {
-            @impl_full
-                $trait_ [$($types),*, $($atypes = $atypes),*]
-                for [$($types),*, $($atypes),*]
+    // Associated types and where clauses.
+    (@parse_trait $kind:tt [[$($args:tt)*] $lts:tt [$($types:tt)*] $consts:tt]
+        $trait_:tt $(+ $auto:ident)* assoc $($atypes:ident),* where $($preds:tt)+
+    ) => {
+        impl_downcast! {
+            @impl_full $kind
+                $trait_ [$($args)* $($atypes = $atypes),*] [$($auto)*]
+                for $lts [$($types)* $($atypes,)*] $consts
                 where [$($preds)*]
         }
     };
     // Concretely-parametrized types.
-    (concrete $trait_:ident < $($types:ident),* >) => {
-        impl_downcast_box! { @impl_full $trait_ [$($types),*] for [] where [] }
+    (@parse_trait $kind:tt $generics:tt concrete $trait_:tt < $($types:tt),+ > $(+ $auto:ident)*) => {
+        impl_downcast! { @impl_full $kind $trait_ [$($types),*] [$($auto)*] for [] [] [] where [] }
     };
     // Concretely-associated types types.
-    (concrete $trait_:ident assoc $($atypes:ident = $aty:ty),*) => {
-        impl_downcast_box! { @impl_full $trait_ [$($atypes = $aty),*] for [] where [] }
+    (@parse_trait $kind:tt $generics:tt
+        concrete $trait_:tt $(+ $auto:ident)* assoc $($atypes:ident = $aty:ty),*
+    ) => {
+        impl_downcast! { @impl_full $kind $trait_ [$($atypes = $aty),*] [$($auto)*] for [] [] [] where [] }
     };
     // Concretely-parametrized types with concrete associated types.
-    (concrete $trait_:ident < $($types:ident),* > assoc $($atypes:ident = $aty:ty),*) => {
-        impl_downcast_box! { @impl_full $trait_ [$($types),*, $($atypes = $aty),*] for [] where [] }
-    };
-}
-
-#[macro_export(local_inner_macros)]
-macro_rules! impl_downcast_rc {
-    (@impl_full   
-        $trait_:ident [$($param_types:tt)*]
-        for [$($forall_types:ident),*]
-        where [$($preds:tt)*]
+    (@parse_trait $kind:tt $generics:tt
+        concrete $trait_:tt < $($types:tt),+ > $(+ $auto:ident)* assoc $($atypes:ident = $aty:ty),*
     ) => {
         impl_downcast! {
-            @inject_where
-                [impl<$($forall_types),*> dyn $trait_<$($param_types)*>]
-                types [$($forall_types),*]
-                where [$($preds)*]
-                [{
-                    impl_downcast! { @impl_body $trait_ [$($param_types)*] }
-                    impl_downcast! { @impl_body_rc $trait_ [$($param_types)*] }
-                }]
+            @impl_full $kind $trait_ [$($types),*, $($atypes = $aty),*] [$($auto)*] for [] [] [] where []
         }
     };
 
-    (@inject_where [$($before:tt)*] types [] where [] [$($after:tt)*]) => {
-        impl_downcast_rc! { @as_item $($before)* $($after)* }
-    };
+    (@ $($input:tt)*) => { $crate::__core::compile_error! { "unsupported impl_downcast input" } };
 
-    (@inject_where [$($before:tt)*] types [$($types:ident),*] where [] [$($after:tt)*]) => {
-        impl_downcast_rc! {
-            @as_item
-                $($before)*
-                where $( $types: ::std::any::Any + 'static ),*
-                $($after)*
+    ($($input:tt)+) => { impl_downcast! { @split [ref mut] [] $($input)+ } };
+}
+
+// The `*_unchecked` methods. The feature is checked here, in this crate, rather than in the
+// expansion, which would test the features of the calling crate.
+#[cfg(feature = "unchecked")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __impl_downcast_unchecked {
+    (ref [$($vis:tt)*] [$($trait_:tt)*] [$($types:tt)*]) => {
+        /// Returns a reference to the object within the trait object without checking that
+        /// it is of type `__T`.
+        ///
+        /// # Safety
+        ///
+        /// The trait object must wrap an object of type `__T`, e.g. as checked by `is`.
+        #[inline]
+        #[allow(unsafe_code)]
+        $($vis)* unsafe fn downcast_ref_unchecked<__T: $($trait_)*<$($types)*>>(&self) -> &__T
+        where
+            Self: $crate::AsAny,
+        {
+            unsafe { $crate::unchecked::downcast_ref::<__T>($crate::AsAny::as_any(self)) }
         }
     };
-    (@inject_where [$($before:tt)*] types [$($types:ident),*] where [$($preds:tt)+] [$($after:tt)*]) => {
-        impl_downcast_rc! {
-            @as_item
-                $($before)*
-                where
-                    $( $types: ::std::any::Any + 'static, )*
-                    $($preds)*
-                $($after)*
+    (mut [$($vis:tt)*] [$($trait_:tt)*] [$($types:tt)*]) => {
+        /// Returns a mutable reference to the object within the trait object without
+        /// checking that it is of type `__T`.
+        ///
+        /// # Safety
+        ///
+        /// The trait object must wrap an object of type `__T`, e.g. as checked by `is`.
+        #[inline]
+        #[allow(unsafe_code)]
+        $($vis)* unsafe fn downcast_mut_unchecked<__T: $($trait_)*<$($types)*>>(&mut self) -> &mut __T
+        where
+            Self: $crate::AsAny + $crate::AsMutAny,
+        {
+            unsafe { $crate::unchecked::downcast_mut::<__T>($crate::AsMutAny::as_any_mut(self)) }
         }
     };
-
-    (@as_item $i:item) => { $i };
-
-    // No type parameters.
-    ($trait_:ident   ) => { impl_downcast_rc! { @impl_full $trait_ [] for [] where [] } };
-    ($trait_:ident <>) => { impl_downcast_rc! { @impl_full $trait_ [] for [] where [] } };
-    // Type parameters.
-    ($trait_:ident < $($types:ident),* >) => {
-        impl_downcast_rc! { @impl_full $trait_ [$($types),*] for [$($types),*] where [] }
-    };
-    // Type parameters and where clauses.
-    ($trait_:ident < $($types:ident),* > where $($preds:tt)+) => {
-        impl_downcast_rc! { @impl_full $trait_ [$($types),*] for [$($types),*] where [$($preds)*] }
-    };
-    // Associated types.
-    ($trait_:ident assoc $($atypes:ident),*) => {
-        impl_downcast_rc! { @impl_full $trait_ [$($atypes = $atypes),*] for [$($atypes),*] where [] }
-    };
-    // Associated types and where clauses.
-    ($trait_:ident assoc $($atypes:ident),* where $($preds:tt)+) => {
-        impl_downcast_rc! { @impl_full $trait_ [$($atypes = $atypes),*] for [$($atypes),*] where [$($preds)*] }
-    };
-    // Type parameters and associated types.
-    ($trait_:ident < $($types:ident),* > assoc $($atypes:ident),*) => {
-        impl_downcast_rc! {
-            @impl_full
-                $trait_ [$($types),*, $($atypes = $atypes),*]
-                for [$($types),*, $($atypes),*]
-                where []
+    (box [$($vis:tt)*] [$($trait_:tt)*] [$($types:tt)*] $unchecked:ident) => {
+        /// Converts the box into a `Box<__T>` without checking that the trait object wraps
+        /// an object of type `__T`.
+        ///
+        /// # Safety
+        ///
+        /// The trait object must wrap an object of type `__T`, e.g. as checked by `is`.
+        #[inline]
+        #[allow(unsafe_code)]
+        $($vis)* unsafe fn $unchecked<__T: $($trait_)*<$($types)*>>(
+            self: $crate::__alloc::boxed::Box<Self>
+        ) -> $crate::__alloc::boxed::Box<__T>
+        where
+            Self: $crate::BoxAny,
+        {
+            unsafe { $crate::unchecked::downcast_box::<__T>($crate::BoxAny::into_any(self)) }
         }
     };
-    // Type parameters, associated types, and where clauses.
-    ($trait_:ident < $($types:ident),* > assoc $($atypes:ident),* where $($preds:tt)+) => {
-        impl_downcast_rc! {
-            @impl_full
-                $trait_ [$($types),*, $($atypes = $atypes),*]
-                for [$($types),*, $($atypes),*]
-                where [$($preds)*]
+    (rc [$($vis:tt)*] [$($trait_:tt)*] [$($types:tt)*] $unchecked:ident) => {
+        /// Converts the `Rc` into an `Rc<__T>` without checking that the trait object wraps
+        /// an object of type `__T`.
+        ///
+        /// # Safety
+        ///
+        /// The trait object must wrap an object of type `__T`, e.g. as checked by `is`.
+        #[inline]
+        #[allow(unsafe_code)]
+        $($vis)* unsafe fn $unchecked<__T: $($trait_)*<$($types)*>>(
+            self: $crate::__alloc::rc::Rc<Self>
+        ) -> $crate::__alloc::rc::Rc<__T>
+        where
+            Self: $crate::RcAny,
+        {
+            unsafe { $crate::unchecked::downcast_rc::<__T>($crate::RcAny::into_any(self)) }
         }
     };
-    // Concretely-parametrized types.
-    (concrete $trait_:ident < $($types:ident),* >) => {
-        impl_downcast_rc! { @impl_full $trait_ [$($types),*] for [] where [] }
-    };
-    // Concretely-associated types types.
-    (concrete $trait_:ident assoc $($atypes:ident = $aty:ty),*) => {
-        impl_downcast_rc! { @impl_full $trait_ [$($atypes = $aty),*] for [] where [] }
-    };
-    // Concretely-parametrized types with concrete associated types.
-    (concrete $trait_:ident < $($types:ident),* > assoc $($atypes:ident = $aty:ty),*) => {
-        impl_downcast_rc! { @impl_full $trait_ [$($types),*, $($atypes = $aty),*] for [] where [] }
+    (arc [$($vis:tt)*] [$($trait_:tt)*] [$($types:tt)*] $unchecked:ident) => {
+        /// Converts the `Arc` into an `Arc<__T>` without checking that the trait object wraps
+        /// an object of type `__T`.
+        ///
+        /// # Safety
+        ///
+        /// The trait object must wrap an object of type `__T`, e.g. as checked by `is`.
+        #[inline]
+        #[allow(unsafe_code)]
+        $($vis)* unsafe fn $unchecked<__T: $($trait_)*<$($types)*>>(
+            self: $crate::__alloc::sync::Arc<Self>
+        ) -> $crate::__alloc::sync::Arc<__T>
+        where
+            Self: $crate::ArcAny,
+        {
+            unsafe { $crate::unchecked::downcast_arc::<__T>($crate::ArcAny::into_any(self)) }
+        }
     };
 }
 
+#[cfg(not(feature = "unchecked"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __impl_downcast_unchecked {
+    ($($input:tt)*) => {};
+}
+
+/// Like `impl_downcast!`, and additionally generates `downcast` for `Box<dyn Trait>`.
+/// The trait must extend `BoxAny`.
+#[macro_export(local_inner_macros)]
+macro_rules! impl_downcast_box {
+    ($($input:tt)+) => { impl_downcast! { @split [ref mut box] [] $($input)+ } };
+}
+
+/// Generates `is`, `downcast_ref` and `downcast` for `Rc<dyn Trait>`.
+/// The trait must extend `RcAny`.
+#[macro_export(local_inner_macros)]
+macro_rules! impl_downcast_rc {
+    ($($input:tt)+) => { impl_downcast! { @split [ref rc] [] $($input)+ } };
+}
+
+/// Generates `is`, `downcast_ref` and `downcast` for `Arc<dyn Trait>`.
+/// The trait must extend `ArcAny`.
 #[macro_export(local_inner_macros)]
 macro_rules! impl_downcast_arc {
-    (@impl_full   
-        $trait_:ident [$($param_types:tt)*]
-        for [$($forall_types:ident),*]
-        where [$($preds:tt)*]
-    ) => {
-        impl_downcast! {
-            @inject_where
-                [impl<$($forall_types),*> dyn $trait_<$($param_types)*>]
-                types [$($forall_types),*]
-                where [$($preds)*]
-                [{
-                    impl_downcast! { @impl_body $trait_ [$($param_types)*] }
-                    impl_downcast! { @impl_body_arc $trait_ [$($param_types)*] }
-                }]
-        }
-    };
+    ($($input:tt)+) => { impl_downcast! { @split [ref arc] [] $($input)+ } };
+}
 
-    (@inject_where [$($before:tt)*] types [] where [] [$($after:tt)*]) => {
-        impl_downcast_arc! { @as_item $($before)* $($after)* }
-    };
+/// Generates `clone_box` for `dyn Trait` and implements `Clone` for `Box<dyn Trait>`.
+/// The trait must extend `CloneAny`.
+///
+/// It also generates `downcast_make_mut`, which downcasts an `Rc<dyn Trait>` or
+/// `Arc<dyn Trait>` for mutation, cloning the object first if the pointer is shared, e.g.
+/// `<dyn Trait>::downcast_make_mut::<Foo>(&mut rc)`.
+///
+/// The input is the same as for `impl_downcast!`, including extension-trait mode, which
+/// puts `clone_box` into the extension trait; `Clone` is implemented either way.
+#[macro_export(local_inner_macros)]
+macro_rules! impl_clone_box {
+    ($($input:tt)+) => { impl_downcast! { @split [clone] [] $($input)+ } };
+}
 
-    (@inject_where [$($before:tt)*] types [$($types:ident),*] where [] [$($after:tt)*]) => {
-        impl_downcast_arc! {
-            @as_item
-                $($before)*
-                where $( $types: ::std::any::Any + 'static ),*
-                $($after)*
-        }
+/// Implements `PartialEq` for `dyn Trait`, and so for `Box<dyn Trait>` and the like, by
+/// comparing the concrete types and then the values. The trait must extend `DynEq`.
+///
+/// The input is the same as for `impl_downcast!`, except for extension-trait mode.
+#[macro_export(local_inner_macros)]
+macro_rules! impl_dyn_eq {
+    ($($input:tt)+) => { impl_downcast! { @split [eq] [] $($input)+ } };
+}
+
+/// Implements `Hash` for `dyn Trait` by hashing the `TypeId` of the concrete type and then
+/// the value. The trait must extend `DynHash`.
+///
+/// So that boxed trait objects can be used as `HashSet` or `HashMap` keys, `Eq` is
+/// implemented too, which needs `impl_dyn_eq!` on the same trait. This assumes `PartialEq`
+/// of the hashable implementors is an equivalence, as for derived impls.
+///
+/// The input is the same as for `impl_downcast!`, except for extension-trait mode.
+#[macro_export(local_inner_macros)]
+macro_rules! impl_dyn_hash {
+    ($($input:tt)+) => { impl_downcast! { @split [hash] [] $($input)+ } };
+}
+
+/// Implements `PartialOrd` and `Ord` for `dyn Trait`, ordering by the `TypeId` of the
+/// concrete type and then by value. The trait must extend `DynOrd`.
+///
+/// `Ord` also needs `PartialEq` and `Eq` for `dyn Trait`: the former from `impl_dyn_eq!`,
+/// the latter from `impl_dyn_hash!` or an empty `impl Eq for dyn Trait {}`.
+///
+/// The input is the same as for `impl_downcast!`, except for extension-trait mode.
+#[macro_export(local_inner_macros)]
+macro_rules! impl_dyn_ord {
+    ($($input:tt)+) => { impl_downcast! { @split [ord] [] $($input)+ } };
+}
+
+/// Generates the methods of `impl_downcast!` together with `downcast_box`, `downcast_rc`
+/// and `downcast_arc`, so one trait can be downcast behind every pointer type.
+/// The trait must extend `BoxAny + RcAny + ArcAny`.
+///
+/// ```
+/// # #[macro_use]
+/// # extern crate pi_any;
+/// use std::{rc::Rc, sync::Arc};
+/// use pi_any::{ArcAny, BoxAny, RcAny};
+///
+/// trait Base: BoxAny + RcAny + ArcAny {}
+/// impl_downcast_all!(Base);
+///
+/// struct Foo(u32);
+/// impl Base for Foo {}
+///
+/// # fn main() {
+/// let base: Box<dyn Base> = Box::new(Foo(1));
+/// assert_eq!(base.downcast_box::<Foo>().map_err(|_| ()).unwrap().0, 1);
+/// let base: Rc<dyn Base> = Rc::new(Foo(2));
+/// assert_eq!(base.downcast_rc::<Foo>().map_err(|_| ()).unwrap().0, 2);
+/// let base: Arc<dyn Base> = Arc::new(Foo(3));
+/// assert_eq!(base.downcast_arc::<Foo>().map_err(|_| ()).unwrap().0, 3);
+/// # }
+/// ```
+#[macro_export(local_inner_macros)]
+macro_rules! impl_downcast_all {
+    ($($input:tt)+) => { impl_downcast! { @split [ref mut all] [] $($input)+ } };
+}
+
+/// Switches on the concrete type behind a trait object, trying each arm in order.
+///
+/// `value` is a reference or smart pointer to a trait object with generated downcast
+/// methods. Each arm names a type and a pattern for the downcast value; an optional last
+/// arm `_ => ...` or `other => ...` handles every other type, with `other` bound to the
+/// trait object.
+///
+/// * `match_downcast!(value, { .. })` binds `&T` using `downcast_ref`.
+/// * `match_downcast!(mut value, { .. })` binds `&mut T` using `downcast_mut`.
+/// * `match_downcast!(box value, { .. })` consumes a `Box<dyn Trait>` and binds `Box<T>`
+///   using `downcast`; `other` receives the box back.
+///
+/// ```
+/// # #[macro_use]
+/// # extern crate pi_any;
+/// use pi_any::BoxAny;
+///
+/// trait Shape: BoxAny {}
+/// impl_downcast_box!(Shape);
+///
+/// struct Circle(f32);
+/// impl Shape for Circle {}
+/// struct Rect(f32, f32);
+/// impl Shape for Rect {}
+/// struct Line;
+/// impl Shape for Line {}
+///
+/// fn area(shape: &dyn Shape) -> f32 {
+///     match_downcast!(shape, {
+///         Circle(c) => 3.0 * c.0 * c.0,
+///         Rect(Rect(w, h)) => w * h,
+///         _ => 0.0,
+///     })
+/// }
+///
+/// # fn main() {
+/// assert_eq!(area(&Rect(2.0, 3.0)), 6.0);
+/// assert_eq!(area(&Line), 0.0);
+///
+/// let shape: Box<dyn Shape> = Box::new(Circle(1.0));
+/// let radius = match_downcast!(box shape, {
+///     Circle(c) => c.0,
+///     other => panic!("not a circle: {}", pi_any::AsAny::type_name(&*other)),
+/// });
+/// assert_eq!(radius, 1.0);
+/// # }
+/// ```
+#[macro_export(local_inner_macros)]
+macro_rules! match_downcast {
+    (@ref $value:ident []) => {};
+    (@ref $value:ident [] _ => $($fallback:tt)+) => { match_downcast! { @fallback $($fallback)+ } };
+    (@ref $value:ident [] $other:ident => $($fallback:tt)+) => {{
+        let $other = $value;
+        match_downcast! { @fallback $($fallback)+ }
+    }};
+    (@ref $value:ident [$($ty:tt)+] ( $($bind:tt)* ) => $($rest:tt)+) => {
+        match_downcast! { @arm [$($rest)+] @ref $value [$($ty)+] [$($bind)*] }
     };
-    (@inject_where [$($before:tt)*] types [$($types:ident),*] where [$($preds:tt)+] [$($after:tt)*]) => {
-        impl_downcast_arc! {
-            @as_item
-                $($before)*
-                where
-                    $( $types: ::std::any::Any + 'static, )*
-                    $($preds)*
-                $($after)*
-        }
+    (@ref $value:ident [$($ty:tt)*] $t:tt $($rest:tt)*) => {
+        match_downcast! { @ref $value [$($ty)* $t] $($rest)* }
     };
 
-    (@as_item $i:item) => { $i };
-
-    // No type parameters.
-    ($trait_:ident   ) => { impl_downcast_arc! { @impl_full $trait_ [] for [] where [] } };
-    ($trait_:ident <>) => { impl_downcast_arc! { @impl_full $trait_ [] for [] where [] } };
-    // Type parameters.
-    ($trait_:ident < $($types:ident),* >) => {
-        impl_downcast_arc! { @impl_full $trait_ [$($types),*] for [$($types),*] where [] }
+    (@mut $value:ident []) => {};
+    (@mut $value:ident [] _ => $($fallback:tt)+) => { match_downcast! { @fallback $($fallback)+ } };
+    (@mut $value:ident [] $other:ident => $($fallback:tt)+) => {{
+        let $other = $value;
+        match_downcast! { @fallback $($fallback)+ }
+    }};
+    (@mut $value:ident [$($ty:tt)+] ( $($bind:tt)* ) => $($rest:tt)+) => {
+        match_downcast! { @arm [$($rest)+] @mut $value [$($ty)+] [$($bind)*] }
     };
-    // Type parameters and where clauses.
-    ($trait_:ident < $($types:ident),* > where $($preds:tt)+) => {
-        impl_downcast_arc! { @impl_full $trait_ [$($types),*] for [$($types),*] where [$($preds)*] }
+    (@mut $value:ident [$($ty:tt)*] $t:tt $($rest:tt)*) => {
+        match_downcast! { @mut $value [$($ty)* $t] $($rest)* }
     };
-    // Associated types.
-    ($trait_:ident assoc $($atypes:ident),*) => {
-        impl_downcast_arc! { @impl_full $trait_ [$($atypes = $atypes),*] for [$($atypes),*] where [] }
+
+    (@box $value:ident []) => {{ let _ = $value; }};
+    (@box $value:ident [] _ => $($fallback:tt)+) => { match_downcast! { @fallback $($fallback)+ } };
+    (@box $value:ident [] $other:ident => $($fallback:tt)+) => {{
+        let $other = $value;
+        match_downcast! { @fallback $($fallback)+ }
+    }};
+    (@box $value:ident [$($ty:tt)+] ( $($bind:tt)* ) => $($rest:tt)+) => {
+        match_downcast! { @arm [$($rest)+] @box $value [$($ty)+] [$($bind)*] }
     };
-    // Associated types and where clauses.
-    ($trait_:ident assoc $($atypes:ident),* where $($preds:tt)+) => {
-        impl_downcast_arc! { @impl_full $trait_ [$($atypes = $atypes),*] for [$($atypes),*] where [$($preds)*] }
-    };
-    // Type parameters and associated types.
-    ($trait_:ident < $($types:ident),* > assoc $($atypes:ident),*) => {
-        impl_downcast_arc! {
-            @impl_full
-                $trait_ [$($types),*, $($atypes = $atypes),*]
-                for [$($types),*, $($atypes),*]
-                where []
-        }
+    (@box $value:ident [$($ty:tt)*] $t:tt $($rest:tt)*) => {
+        match_downcast! { @box $value [$($ty)* $t] $($rest)* }
     };
-    // Type parameters, associated types, and where clauses.
-    ($trait_:ident < $($types:ident),* > assoc $($atypes:ident),* where $($preds:tt)+) => {
-        impl_downcast_arc! {
-            @impl_full
-                $trait_ [$($types),*, $($atypes = $atypes),*]
-                for [$($types),*, $($atypes),*]
-                where [$($preds)*]
+
+    // Splits the arm body from the remaining arms.
+    (@arm [$body:block , $($rest:tt)*] $($arm:tt)*) => { match_downcast! { @emit [$body] [$($rest)*] $($arm)* } };
+    (@arm [$body:block $($rest:tt)*] $($arm:tt)*) => { match_downcast! { @emit [$body] [$($rest)*] $($arm)* } };
+    (@arm [$body:expr , $($rest:tt)*] $($arm:tt)*) => { match_downcast! { @emit [$body] [$($rest)*] $($arm)* } };
+    (@arm [$body:expr] $($arm:tt)*) => { match_downcast! { @emit [$body] [] $($arm)* } };
+
+    (@emit [$body:expr] [$($rest:tt)*] @ref $value:ident [$($ty:tt)+] [$($bind:tt)*]) => {
+        if let $crate::__core::option::Option::Some($($bind)*) = $value.downcast_ref::<$($ty)+>() {
+            $body
+        } else {
+            match_downcast! { @ref $value [] $($rest)* }
         }
     };
-    // Concretely-parametrized types.
-    (concrete $trait_:ident < $($types:ident),* >) => {
-        impl_downcast_arc! { @impl_full $trait_ [$($types),*] for [] where [] }
-    };
-    // Concretely-associated types types.
-    (concrete $trait_:ident assoc $($atypes:ident = $aty:ty),*) => {
-        impl_downcast_arc! { @impl_full $trait_ [$($atypes = $aty),*] for [] where [] }
+    (@emit [$body:expr] [$($rest:tt)*] @mut $value:ident [$($ty:tt)+] [$($bind:tt)*]) => {
+        if let $crate::__core::option::Option::Some($($bind)*) = $value.downcast_mut::<$($ty)+>() {
+            $body
+        } else {
+            match_downcast! { @mut $value [] $($rest)* }
+        }
     };
-    // Concretely-parametrized types with concrete associated types.
-    (concrete $trait_:ident < $($types:ident),* > assoc $($atypes:ident = $aty:ty),*) => {
-        impl_downcast_arc! { @impl_full $trait_ [$($types),*, $($atypes = $aty),*] for [] where [] }
+    (@emit [$body:expr] [$($rest:tt)*] @box $value:ident [$($ty:tt)+] [$($bind:tt)*]) => {
+        match $value.downcast::<$($ty)+>() {
+            $crate::__core::result::Result::Ok($($bind)*) => $body,
+            $crate::__core::result::Result::Err($value) => match_downcast! { @box $value [] $($rest)* },
+        }
     };
+
+    (@fallback $body:block $(,)?) => { $body };
+    (@fallback $body:expr $(,)?) => { $body };
+
+    (box $value:expr, { $($arms:tt)* }) => {{
+        let __value = $value;
+        match_downcast! { @box __value [] $($arms)* }
+    }};
+    (mut $value:expr, { $($arms:tt)* }) => {{
+        let __value = &mut *$value;
+        match_downcast! { @mut __value [] $($arms)* }
+    }};
+    ($value:expr, { $($arms:tt)* }) => {{
+        let __value = &*$value;
+        match_downcast! { @ref __value [] $($arms)* }
+    }};
 }
 
 // pub mod m;
@@ -687,6 +1959,98 @@ mod test {
                     assert_eq!(
                         6*9, base.downcast::<Foo>().map_err(|_| "Shouldn't happen.").unwrap().0);
                 }
+
+                #[test]
+                fn test_type_id_of() {
+                    let base: ::std::boxed::Box<$base_type> = ::std::boxed::Box::new(Foo(42));
+                    assert_eq!(base.type_id_of(), ::std::any::TypeId::of::<Foo>());
+                    assert!(base.type_name().ends_with("::Foo"));
+                }
+
+                #[test]
+                fn test_into_rc() {
+                    // Fail to convert Box<Base> into Rc<Bar>, keeping the box.
+                    let base: ::std::boxed::Box<$base_type> = ::std::boxed::Box::new(Foo(7));
+                    let base = base.downcast_into_rc::<Bar>().unwrap_err();
+                    // Convert Box<Base> into Rc<Foo>.
+                    let foo = base.downcast_into_rc::<Foo>().map_err(|_| "Shouldn't happen.").unwrap();
+                    assert_eq!(7, foo.0);
+                }
+
+                #[test]
+                fn test_into_arc() {
+                    // Fail to convert Box<Base> into Arc<Bar>, keeping the box.
+                    let base: ::std::boxed::Box<$base_type> = ::std::boxed::Box::new(Foo(8));
+                    let base = base.downcast_into_arc::<Bar>().unwrap_err();
+                    // Convert Box<Base> into Arc<Foo>.
+                    let foo = base.downcast_into_arc::<Foo>().map_err(|_| "Shouldn't happen.").unwrap();
+                    assert_eq!(8, foo.0);
+                }
+
+                #[test]
+                fn test_expect() {
+                    let mut base: ::std::boxed::Box<$base_type> = ::std::boxed::Box::new(Foo(42));
+                    assert_eq!(base.downcast_ref_expect::<Foo>("ref").0, 42);
+                    assert_eq!(base.downcast_unwrap::<Foo>().0, 42);
+                    base.downcast_mut_expect::<Foo>("mut").0 = 6*9;
+                    assert_eq!(base.downcast_expect::<Foo>("box").0, 6*9);
+                }
+
+                #[test]
+                fn test_vec() {
+                    let bases: ::std::vec::Vec<::std::boxed::Box<$base_type>> =
+                        ::std::vec![::std::boxed::Box::new(Foo(1)), ::std::boxed::Box::new(Bar(2.0)), ::std::boxed::Box::new(Foo(3))];
+                    let mut bases = <$base_type>::downcast_vec::<Foo>(bases).unwrap_err();
+                    let foos = <$base_type>::drain_downcast::<Foo>(&mut bases);
+                    assert_eq!(foos.iter().map(|foo| foo.0).collect::<::std::vec::Vec<_>>(), [1, 3]);
+                    let bars = <$base_type>::downcast_vec::<Bar>(bases).map_err(|_| "Shouldn't happen.").unwrap();
+                    assert_eq!(bars[0].0, 2.0);
+                }
+
+                #[test]
+                fn test_owned() {
+                    let base: ::std::boxed::Box<$base_type> = ::std::boxed::Box::new(Foo(42));
+                    let base = base.downcast_owned::<Bar>().unwrap_err();
+                    let foo: Foo = base.downcast_owned().map_err(|_| "Shouldn't happen.").unwrap();
+                    assert_eq!(foo.0, 42);
+                }
+
+                #[test]
+                fn test_try() {
+                    let base: ::std::boxed::Box<$base_type> = ::std::boxed::Box::new(Foo(42));
+                    let err = base.try_downcast::<Bar>().unwrap_err();
+                    assert!(err.expected.ends_with("::Bar") && err.found.ends_with("::Foo"));
+                    assert_eq!(err.to_string(), ::std::format!("expected `{}`, found `{}`", err.expected, err.found));
+                    assert_eq!(err.into_value().try_downcast::<Foo>().unwrap().0, 42);
+
+                    let mut base: ::std::boxed::Box<$base_type> = ::std::boxed::Box::new(Foo(42));
+                    base.try_downcast_mut::<Foo>().unwrap().0 += 1;
+                    assert_eq!(base.try_downcast_ref::<Foo>().map(|foo| foo.0), Ok(43));
+                    let err = base.try_downcast_ref::<Bar>().unwrap_err();
+                    assert_eq!(base.try_downcast_mut::<Bar>().unwrap_err(), err);
+                    assert!(err.expected.ends_with("::Bar") && err.found.ends_with("::Foo"));
+                }
+
+                #[test]
+                fn test_pin() {
+                    let base: ::std::pin::Pin<::std::boxed::Box<$base_type>> = ::std::boxed::Box::pin(Foo(42));
+                    let base = base.downcast_pin::<Bar>().unwrap_err();
+                    assert_eq!(base.downcast_pin::<Foo>().map_err(|_| "Shouldn't happen.").unwrap().0, 42);
+                }
+
+                #[test]
+                #[should_panic(expected = "wrong type: expected `pi_any::test::")]
+                fn test_expect_failed() {
+                    let base: ::std::boxed::Box<$base_type> = ::std::boxed::Box::new(Foo(42));
+                    base.downcast_expect::<Bar>("wrong type");
+                }
+
+                #[test]
+                #[should_panic(expected = "downcast_unwrap: expected `pi_any::test::")]
+                fn test_unwrap_failed() {
+                    let base: ::std::boxed::Box<$base_type> = ::std::boxed::Box::new(Foo(42));
+                    base.downcast_unwrap::<Bar>();
+                }
             }
         };
 
@@ -754,4 +2118,597 @@ mod test {
         trait Base<T>: crate::BoxAny { type H; }
         impl_downcast_box!(concrete Base<u32> assoc H=f32);
     });
+
+    test_mod!(lifetime, trait Base<'static> {}, type dyn Base<'static>, {
+        trait Base<'a>: BoxAny {}
+        impl_downcast_box!(Base<'a>);
+    });
+
+    test_mod!(lifetime_and_param, trait Base<'static, 'static, u32> {}, type dyn Base<'static, 'static, u32>, {
+        trait Base<'a, 'b, T>: BoxAny {}
+        impl_downcast_box!(Base<'a, 'b, T>);
+    });
+
+    test_mod!(lifetime_and_associated, trait Base<'static> { type H = f32; }, type dyn Base<'static, H=f32>, {
+        trait Base<'cfg>: BoxAny { type H: Copy; }
+        impl_downcast_box!(Base<'cfg> assoc H where H: Copy);
+    });
+
+    test_mod!(constrained_lifetime_param_and_associated, trait Base<'static, u32> { type H = f32; }, type dyn Base<'static, u32, H=f32>, {
+        trait Base<'cfg, T: Clone>: BoxAny { type H; }
+        impl_downcast_box!(Base<'cfg, T> assoc H where T: Clone);
+    });
+
+    test_mod!(concrete_lifetime, trait Base<'static, u32> {}, {
+        trait Base<'a, T>: BoxAny {}
+        impl_downcast_box!(concrete Base<'static, u32>);
+    });
+
+    test_mod!(const_generic, trait Base<16> {}, type dyn Base<16>, {
+        trait Base<const N: usize>: BoxAny {}
+        impl_downcast_box!(Base<const N: usize>);
+    });
+
+    test_mod!(mixed_generics, trait Base<'static, u32, 4, bool> { type H = f32; }, type dyn Base<'static, u32, 4, bool, H=f32>, {
+        trait Base<'a, T, const N: u8, U: Copy>: BoxAny { type H; }
+        impl_downcast_box!(Base<'a, T, const N: u8, U> assoc H where U: Copy);
+    });
+
+    test_mod!(concrete_const_generic, trait Base<16> {}, type dyn Base<16>, {
+        trait Base<const N: usize>: BoxAny {}
+        impl_downcast_box!(concrete Base<16>);
+    });
+
+    test_mod!(projection_predicate, trait Base<::std::vec::IntoIter<u8>> {}, type dyn Base<::std::vec::IntoIter<u8>>, {
+        trait Base<T>: BoxAny where T: Iterator, T::Item: Clone {}
+        impl_downcast_box!(Base<T> where T: Iterator, T::Item: Clone, <T as Iterator>::Item: Copy);
+    });
+
+    test_mod!(higher_ranked_predicate, trait Base<fn(&u8) -> &u8> {}, type dyn Base<fn(&u8) -> &u8>, {
+        trait Base<F>: BoxAny where F: for<'a> Fn(&'a u8) -> &'a u8 {}
+        impl_downcast_box!(Base<F> where F: for<'a> Fn(&'a u8) -> &'a u8, for<'a> &'a F: Copy);
+    });
+
+    mod multiple {
+        use crate::BoxAny;
+
+        trait A: BoxAny {}
+        trait B<'a, T>: BoxAny {}
+        trait C: BoxAny { type H; }
+        trait D<T: Copy, const N: usize>: BoxAny {}
+        impl_downcast_box!(A; B<'a, T>; C assoc H; concrete D<u8, 2>;);
+
+        struct Foo;
+        impl A for Foo {}
+        impl B<'static, u8> for Foo {}
+        impl C for Foo { type H = f32; }
+        impl D<u8, 2> for Foo {}
+
+        #[test]
+        fn test() {
+            let a: Box<dyn A> = Box::new(Foo);
+            let b: Box<dyn B<'static, u8>> = Box::new(Foo);
+            let c: Box<dyn C<H = f32>> = Box::new(Foo);
+            let d: Box<dyn D<u8, 2>> = Box::new(Foo);
+            assert!(a.is::<Foo>() && b.is::<Foo>() && c.is::<Foo>() && d.is::<Foo>());
+            assert!(a.downcast::<Foo>().is_ok());
+        }
+    }
+
+    mod paths {
+        mod render {
+            pub trait Pass: crate::BoxAny {}
+            pub trait Stage<T>: crate::BoxAny { type H; }
+            pub trait Target<const N: usize>: crate::BoxAny {}
+        }
+
+        impl_downcast_box!(
+            self::render::Pass;
+            crate::test::paths::render::Stage<T> assoc H;
+            concrete render::Target<4>
+        );
+
+        struct Foo;
+        impl render::Pass for Foo {}
+        impl render::Stage<u8> for Foo { type H = f32; }
+        impl render::Target<4> for Foo {}
+
+        #[test]
+        fn test() {
+            let pass: Box<dyn render::Pass> = Box::new(Foo);
+            assert!(pass.downcast::<Foo>().is_ok());
+            let stage: Box<dyn render::Stage<u8, H = f32>> = Box::new(Foo);
+            assert!(stage.downcast_ref::<Foo>().is_some());
+            let target: Box<dyn render::Target<4>> = Box::new(Foo);
+            assert!(target.is::<Foo>());
+        }
+    }
+
+    mod rc {
+        use std::rc::Rc;
+        use crate::RcAny;
+
+        trait Base: RcAny {}
+        impl_downcast_rc!(Base);
+
+        struct Foo(u32);
+        impl Base for Foo {}
+        #[derive(Debug)]
+        struct Bar;
+        impl Base for Bar {}
+
+        #[test]
+        fn test_get_mut() {
+            let mut base: Rc<dyn Base> = Rc::new(Foo(42));
+            assert!(<dyn Base>::downcast_get_mut::<Bar>(&mut base).is_none());
+            <dyn Base>::downcast_get_mut::<Foo>(&mut base).unwrap().0 = 6*9;
+            assert_eq!(base.downcast_ref::<Foo>().unwrap().0, 6*9);
+
+            // Shared ownership forbids mutation.
+            let other = base.clone();
+            assert!(<dyn Base>::downcast_get_mut::<Foo>(&mut base).is_none());
+            drop(other);
+            assert!(<dyn Base>::downcast_get_mut::<Foo>(&mut base).is_some());
+
+            let base = base.downcast::<Bar>().unwrap_err();
+            assert_eq!(base.downcast::<Foo>().map_err(|_| "Shouldn't happen.").unwrap().0, 6*9);
+        }
+
+        #[test]
+        #[should_panic(expected = "rc: expected `pi_any::test::rc::Bar`, found `pi_any::test::rc::Foo`")]
+        fn test_expect_failed() {
+            let base: Rc<dyn Base> = Rc::new(Foo(42));
+            assert_eq!(base.clone().downcast_expect::<Foo>("rc").0, 42);
+            base.downcast_expect::<Bar>("rc");
+        }
+
+        #[test]
+        #[should_panic(
+            expected = "downcast_unwrap: expected `pi_any::test::rc::Bar`, found `pi_any::test::rc::Foo`"
+        )]
+        fn test_unwrap_failed() {
+            let base: Rc<dyn Base> = Rc::new(Foo(42));
+            base.downcast_unwrap::<Bar>();
+        }
+    }
+
+    mod all {
+        use std::pin::Pin;
+        use std::rc::Rc;
+        use std::sync::Arc;
+        use crate::{ArcAny, BoxAny, RcAny};
+
+        trait Base<T: Copy>: BoxAny + RcAny + ArcAny { type H; }
+        impl_downcast_all!(Base<T> assoc H where T: Copy);
+
+        #[derive(Debug)]
+        struct Foo(u32);
+        impl Base<u8> for Foo { type H = f32; }
+        #[derive(Debug)]
+        struct Bar;
+        impl Base<u8> for Bar { type H = f32; }
+
+        type Dyn = dyn Base<u8, H = f32>;
+
+        #[test]
+        fn test() {
+            let mut base: Box<Dyn> = Box::new(Foo(42));
+            assert!(base.is::<Foo>());
+            base.downcast_mut::<Foo>().unwrap().0 = 6*9;
+            let base = base.downcast_box::<Bar>().unwrap_err();
+            assert_eq!(base.downcast_box_expect::<Foo>("box").0, 6*9);
+
+            let base: Rc<Dyn> = Rc::new(Foo(1));
+            let base = base.try_downcast_rc::<Bar>().unwrap_err().into_value();
+            assert_eq!(base.downcast_rc::<Foo>().map_err(|_| "Shouldn't happen.").unwrap().0, 1);
+
+            let base: Arc<Dyn> = Arc::new(Foo(2));
+            let err = base.try_downcast_arc::<Bar>().unwrap_err();
+            assert_eq!(err.found, std::any::type_name::<Foo>());
+            let base = err.value;
+            assert_eq!(base.downcast_arc_expect::<Foo>("arc").0, 2);
+        }
+
+        #[test]
+        fn test_pin() {
+            let base: Pin<Box<Dyn>> = Box::pin(Foo(1));
+            assert_eq!(base.downcast_box_pin::<Foo>().map_err(|_| "Shouldn't happen.").unwrap().0, 1);
+
+            let base: Pin<Rc<Dyn>> = Rc::pin(Foo(2));
+            let other = base.clone();
+            let base = base.downcast_rc_pin::<Bar>().unwrap_err();
+            assert_eq!(base.downcast_rc_pin::<Foo>().map_err(|_| "Shouldn't happen.").unwrap().0, 2);
+            assert!(other.is::<Foo>());
+
+            let base: Pin<Arc<Dyn>> = Arc::pin(Bar);
+            assert!(base.downcast_arc_pin::<Bar>().is_ok());
+        }
+    }
+
+    mod auto_traits {
+        use std::sync::Arc;
+        use crate::{ArcAny, BoxAny};
+
+        trait Base: BoxAny {}
+        impl_downcast_box!(Base; Base + Send);
+
+        trait Shared<T>: ArcAny {}
+        impl_downcast_arc!(Shared<T> + Send + Sync; concrete Shared<u8> + Sync);
+
+        #[derive(Debug)]
+        struct Foo(u32);
+        impl Base for Foo {}
+        impl<T> Shared<T> for Foo {}
+        #[derive(Debug)]
+        struct Bar;
+        impl Base for Bar {}
+        impl<T> Shared<T> for Bar {}
+
+        fn send<T: Send>(t: T) -> T {
+            t
+        }
+
+        #[test]
+        fn test_send() {
+            let base: Box<dyn Base + Send> = Box::new(Foo(42));
+            assert!(base.is::<Foo>());
+            let base = send(base.downcast::<Bar>().unwrap_err());
+            assert_eq!(base.downcast::<Foo>().map_err(|_| "Shouldn't happen.").unwrap().0, 42);
+
+            let base: Box<dyn Base> = Box::new(Bar);
+            assert!(base.downcast::<Bar>().is_ok());
+        }
+
+        #[test]
+        fn test_send_sync() {
+            let base: Arc<dyn Shared<u32> + Send + Sync> = Arc::new(Foo(1));
+            let base = base.downcast::<Bar>().unwrap_err();
+            let base = std::thread::spawn(move || base).join().unwrap();
+            assert_eq!(base.downcast::<Foo>().map_err(|_| "Shouldn't happen.").unwrap().0, 1);
+
+            let base: Arc<dyn Shared<u8> + Sync> = Arc::new(Foo(2));
+            assert_eq!(base.downcast_ref::<Foo>().unwrap().0, 2);
+        }
+    }
+
+    mod ext {
+        use std::rc::Rc;
+        use crate::{BoxAny, RcAny};
+
+        trait Base<T: Copy>: BoxAny {
+            type H;
+            fn is(&self) -> bool { true }
+            fn downcast_ref(&self) -> u32 { 7 }
+        }
+        impl_downcast_box!(ext BaseExt for Base<T> assoc H where T: Copy; ext ConcreteExt for concrete Base<u8> assoc H=f32);
+
+        pub trait Node<'a, const N: usize>: RcAny {
+            fn downcast(&self) -> &'a str { "node" }
+        }
+        impl_downcast_rc!(ext pub(crate) NodeExt for Node<'a, const N: usize>);
+
+        #[derive(Debug)]
+        struct Foo(u32);
+        impl Base<u8> for Foo { type H = f32; }
+        impl Node<'static, 2> for Foo {}
+        #[derive(Debug)]
+        struct Bar;
+        impl Base<u8> for Bar { type H = f32; }
+        impl Node<'static, 2> for Bar {}
+
+        #[test]
+        fn test_box() {
+            let mut base: Box<dyn Base<u8, H = f32>> = Box::new(Foo(42));
+            assert!(base.is());
+            assert_eq!(base.downcast_ref(), 7);
+            assert!(BaseExt::is::<Foo>(&*base));
+            assert!(!ConcreteExt::is::<Bar>(&*base));
+            BaseExt::downcast_mut::<Foo>(&mut *base).unwrap().0 = 6*9;
+            assert_eq!(BaseExt::downcast_ref_expect::<Foo>(&*base, "foo").0, 6*9);
+            let base = BaseExt::downcast::<Bar>(base).unwrap_err();
+            assert_eq!(BaseExt::downcast_expect::<Foo>(base, "foo").0, 6*9);
+        }
+
+        #[test]
+        fn test_rc() {
+            let mut base: Rc<dyn Node<'static, 2>> = Rc::new(Foo(1));
+            assert_eq!((*base).downcast(), "node");
+            NodeExt::downcast_get_mut::<Foo>(&mut base).unwrap().0 = 2;
+            let base = NodeExt::downcast::<Bar>(base).unwrap_err();
+            assert_eq!(base.downcast::<Foo>().map_err(|_| "Shouldn't happen.").unwrap().0, 2);
+        }
+    }
+
+    mod clone {
+        use crate::CloneAny;
+
+        trait Base<'a, T: Copy, const N: usize>: CloneAny {
+            type H;
+        }
+        impl_clone_box!(Base<'a, T, const N: usize> assoc H where T: Copy; Base<'a, T, const N: usize> + Send assoc H where T: Copy);
+
+        trait Node: CloneAny {}
+        impl_clone_box!(ext pub(crate) NodeClone for Node);
+
+        #[derive(Clone, Debug, PartialEq)]
+        struct Foo(Vec<u32>);
+        impl Base<'static, u8, 2> for Foo { type H = f32; }
+        impl Node for Foo {}
+
+        #[test]
+        fn test() {
+            let base: Box<dyn Base<'static, u8, 2, H = f32>> = Box::new(Foo(vec![1, 2]));
+            let copy = base.clone_box().into_any().downcast::<Foo>().unwrap();
+            assert_eq!(*copy, Foo(vec![1, 2]));
+
+            let bases: Vec<Box<dyn Base<'static, u8, 2, H = f32> + Send>> = vec![Box::new(Foo(vec![3]))];
+            assert_eq!(bases.clone()[0].clone_box().into_any().downcast_ref::<Foo>(), Some(&Foo(vec![3])));
+
+            let node: Box<dyn Node> = Box::new(Foo(vec![4]));
+            let copies = [node.clone(), NodeClone::clone_box(&*node)];
+            assert!(copies.iter().all(|n| (**n).as_any().downcast_ref() == Some(&Foo(vec![4]))));
+        }
+    }
+
+    mod debug_option {
+        use crate::{BoxAny, DynDebug};
+
+        trait Base<T: Copy>: BoxAny + DynDebug {}
+        impl_downcast_box!(debug Base<T> + Send where T: Copy; pub(crate) debug Base<T> where T: Copy);
+
+        pub trait Node: BoxAny + DynDebug {}
+        impl_downcast_box!(debug ext NodeExt for Node);
+
+        mod debug {
+            pub trait Plain: crate::DynDebug + crate::DynEq {}
+        }
+        // A path starting with a module named `debug` is not the option.
+        impl_dyn_eq!(debug debug::Plain);
+
+        #[derive(Debug, PartialEq)]
+        struct Foo(u32);
+        impl Base<u8> for Foo {}
+        impl Node for Foo {}
+        impl debug::Plain for Foo {}
+
+        #[test]
+        fn test() {
+            let base: Box<dyn Base<u8> + Send> = Box::new(Foo(1));
+            assert_eq!(format!("{:?}", base), "Foo(1)");
+            let base: Box<dyn Base<u8>> = Box::new(Foo(2));
+            assert_eq!(format!("{:?}", base.downcast_ref::<Foo>()), "Some(Foo(2))");
+            assert_eq!(format!("{:#?}", base), "Foo(\n    2,\n)");
+            let node: Box<dyn Node> = Box::new(Foo(3));
+            assert_eq!(format!("{:?}", node), "Foo(3)");
+            assert!(NodeExt::is::<Foo>(&*node));
+            let plain: &dyn debug::Plain = &Foo(4);
+            assert_eq!(format!("{:?}", plain), "Foo(4)");
+            assert!(*plain == *(&Foo(4) as &dyn debug::Plain));
+        }
+    }
+
+    mod upcast {
+        use std::rc::Rc;
+        use std::sync::Arc;
+        use crate::{ArcAny, BoxAny, RcAny};
+
+        trait Named {
+            fn name(&self) -> String;
+        }
+        crate::impl_upcast!(AsNamed: Named);
+        crate::impl_upcast!(AsNamedSend: Named + Send);
+        crate::impl_upcast!(AsNamedSendSync: Named + Send + Sync);
+
+        trait Base: BoxAny + Named + AsNamed + AsNamedSend {}
+        impl_downcast_box!(Base : Named via AsNamed; Base + Send : Named via AsNamedSend);
+
+        trait Source<T> {
+            fn get(&self) -> T;
+        }
+        crate::impl_upcast!(AsSource<T>: Source<T>);
+        trait Node<T: Copy>: RcAny + Source<T> + AsSource<T> {
+            type H;
+        }
+        impl_downcast_rc!(pub(crate) Node<T> : Source<T> via AsSource<T> assoc H where T: Copy);
+
+        trait Shared: ArcAny + Named + AsNamedSendSync {}
+        impl_downcast_arc!(Shared + Send + Sync : Named via AsNamedSendSync);
+
+        // Without `impl_downcast!`, the helper's own methods upcast.
+        trait Plain: Named + AsNamed {}
+
+        #[derive(Debug)]
+        struct Foo(u32);
+        impl Named for Foo {
+            fn name(&self) -> String { format!("foo {}", self.0) }
+        }
+        impl Base for Foo {}
+        impl Source<u8> for Foo {
+            fn get(&self) -> u8 { self.0 as u8 }
+        }
+        impl Node<u8> for Foo { type H = (); }
+        impl Shared for Foo {}
+        impl Plain for Foo {}
+
+        fn send<T: Send>(t: T) -> T {
+            t
+        }
+
+        #[test]
+        fn test() {
+            let mut base: Box<dyn Base> = Box::new(Foo(1));
+            assert_eq!(base.as_super().name(), "foo 1");
+            assert_eq!(base.as_super_mut().name(), "foo 1");
+            let named: Box<dyn Named> = base.into_super();
+            assert_eq!(named.name(), "foo 1");
+
+            let base: Box<dyn Base + Send> = Box::new(Foo(2));
+            assert_eq!(send(base.into_super()).name(), "foo 2");
+
+            let node: Rc<dyn Node<u8, H = ()>> = Rc::new(Foo(3));
+            assert_eq!(node.as_super().get(), 3);
+            assert_eq!(node.into_super_rc().get(), 3);
+
+            let shared: Arc<dyn Shared + Send + Sync> = Arc::new(Foo(4));
+            assert!(shared.is::<Foo>());
+            let named = shared.into_super_arc();
+            assert_eq!(std::thread::spawn(move || named.name()).join().unwrap(), "foo 4");
+
+            let mut plain: Box<dyn Plain> = Box::new(Foo(5));
+            assert_eq!(plain.upcast_mut().name(), "foo 5");
+            assert_eq!(plain.upcast_box().name(), "foo 5");
+        }
+    }
+
+    mod visibility {
+        mod widgets {
+            use crate::BoxAny;
+
+            pub trait Widget: BoxAny {}
+            impl_downcast_box!(pub(crate) Widget; pub(super) concrete Labeled<u8>);
+
+            pub trait Labeled<T>: BoxAny {}
+
+            #[derive(Debug)]
+            pub struct Button(pub u32);
+            impl Widget for Button {}
+            impl Labeled<u8> for Button {}
+        }
+
+        use widgets::{Button, Labeled, Widget};
+
+        #[test]
+        fn test() {
+            let widget: Box<dyn Widget> = Box::new(Button(3));
+            assert!(widget.is::<Button>());
+            assert_eq!(widget.downcast::<Button>().map_err(|_| "Shouldn't happen.").unwrap().0, 3);
+
+            let labeled: Box<dyn Labeled<u8>> = Box::new(Button(4));
+            assert_eq!(labeled.downcast_ref::<Button>().unwrap().0, 4);
+        }
+    }
+
+    mod match_downcast {
+        use std::rc::Rc;
+        use crate::{BoxAny, RcAny};
+
+        trait Base: BoxAny {}
+        impl_downcast_box!(Base);
+
+        trait Shared: RcAny {}
+        impl_downcast_rc!(Shared);
+
+        #[derive(Debug)]
+        struct Foo(u32);
+        impl Base for Foo {}
+        impl Shared for Foo {}
+        #[derive(Debug)]
+        struct Bar(Vec<u8>);
+        impl Base for Bar {}
+        #[derive(Debug)]
+        struct Pair<T>(T, T);
+        impl<T: 'static> Base for Pair<T> {}
+        #[derive(Debug)]
+        struct Baz;
+        impl Base for Baz {}
+
+        fn describe(base: &dyn Base) -> String {
+            match_downcast!(base, {
+                Foo(foo) => format!("foo {}", foo.0),
+                Bar(Bar(bytes)) => {
+                    format!("bar {}", bytes.len())
+                }
+                Pair<(u8, u8)>(Pair(a, _)) => format!("pair {:?}", a),
+                other => format!("other {}", crate::AsAny::type_name(other)),
+            })
+        }
+
+        #[test]
+        fn test_ref() {
+            assert_eq!(describe(&Foo(1)), "foo 1");
+            assert_eq!(describe(&Bar(vec![1, 2])), "bar 2");
+            assert_eq!(describe(&Pair((1u8, 2u8), (3, 4))), "pair (1, 2)");
+            assert!(describe(&Baz).ends_with("Baz"));
+
+            let shared: Rc<dyn Shared> = Rc::new(Foo(3));
+            let mut hit = false;
+            match_downcast!(shared, {
+                Foo(_) => hit = true,
+            });
+            assert!(hit);
+        }
+
+        #[test]
+        fn test_mut() {
+            let mut base: Box<dyn Base> = Box::new(Bar(vec![]));
+            for _ in 0..2 {
+                match_downcast!(mut base, {
+                    Foo(foo) => foo.0 += 1,
+                    Bar(bar) => bar.0.push(1),
+                    _ => unreachable!(),
+                });
+            }
+            assert_eq!(base.downcast_ref::<Bar>().unwrap().0, vec![1, 1]);
+        }
+
+        #[test]
+        fn test_box() {
+            let base: Box<dyn Base> = Box::new(Foo(7));
+            let foo: Box<Foo> = match_downcast!(box base, {
+                Bar(_) => unreachable!(),
+                Foo(foo) => foo,
+                _ => unreachable!(),
+            });
+            assert_eq!(foo.0, 7);
+
+            let base: Box<dyn Base> = Box::new(Baz);
+            let base = match_downcast!(box base, {
+                Foo(_) => None,
+                other => Some(other),
+            });
+            assert!(base.unwrap().is::<Baz>());
+        }
+    }
+
+    #[cfg(feature = "unchecked")]
+    #[allow(unsafe_code)]
+    mod unchecked {
+        use std::rc::Rc;
+        use std::sync::Arc;
+        use crate::{ArcAny, BoxAny, RcAny};
+
+        trait Base<T>: BoxAny + RcAny + ArcAny {}
+        impl_downcast_all!(Base<T>);
+
+        trait Single: BoxAny {}
+        impl_downcast_box!(Single);
+
+        #[derive(Debug)]
+        struct Foo(u32);
+        impl Base<u8> for Foo {}
+        impl Single for Foo {}
+
+        #[test]
+        fn test() {
+            let mut base: Box<dyn Base<u8>> = Box::new(Foo(42));
+            assert!(base.is::<Foo>());
+            unsafe {
+                base.downcast_mut_unchecked::<Foo>().0 = 6*9;
+                assert_eq!(base.downcast_ref_unchecked::<Foo>().0, 6*9);
+                assert_eq!(base.downcast_box_unchecked::<Foo>().0, 6*9);
+            }
+
+            let base: Rc<dyn Base<u8>> = Rc::new(Foo(1));
+            let other = base.clone();
+            let foo = unsafe { base.downcast_rc_unchecked::<Foo>() };
+            assert_eq!(foo.0, 1);
+            assert_eq!(Rc::strong_count(&other), 2);
+
+            let base: Arc<dyn Base<u8>> = Arc::new(Foo(2));
+            assert_eq!(unsafe { base.downcast_arc_unchecked::<Foo>() }.0, 2);
+
+            let single: Box<dyn Single> = Box::new(Foo(3));
+            assert_eq!(unsafe { single.downcast_unchecked::<Foo>() }.0, 3);
+        }
+    }
 }
\ No newline at end of file
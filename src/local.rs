@@ -0,0 +1,174 @@
+//! `LocalTypeMap`, a per-thread typemap in static storage.
+
+use std::any::{type_name, Any, TypeId};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::marker::PhantomData;
+
+thread_local! {
+    // `None` marks a value lent out to a running `with` call.
+    static VALUES: RefCell<HashMap<TypeId, Option<Box<dyn Any>>>> = RefCell::new(HashMap::new());
+}
+
+/// Per-thread storage of one value per type, replacing ad hoc `thread_local!` caches.
+///
+/// Each thread sees its own values. The accessors can be nested for different types, but
+/// accessing a type from within a `with` call on the same type panics.
+///
+/// ```
+/// use pi_any::LocalTypeMap;
+///
+/// #[derive(Default)]
+/// struct Scratch(Vec<u8>);
+///
+/// let len = LocalTypeMap::with(|s: &mut Scratch| {
+///     s.0.extend_from_slice(b"abc");
+///     s.0.len()
+/// });
+/// assert_eq!(len, 3);
+/// assert_eq!(LocalTypeMap::try_with(|s: &mut Scratch| s.0.len()), Some(3));
+///
+/// std::thread::spawn(|| assert!(!LocalTypeMap::contains::<Scratch>())).join().unwrap();
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct LocalTypeMap;
+
+impl LocalTypeMap {
+    /// Calls `f` with this thread's value of type `T`, which is created with `T::default()`
+    /// if there is none.
+    pub fn with<T: Any + Default, R>(f: impl FnOnce(&mut T) -> R) -> R {
+        Self::with_or(T::default, f)
+    }
+
+    /// Calls `f` with this thread's value of type `T`, which is created with `init` if there
+    /// is none.
+    pub fn with_or<T: Any, R>(init: impl FnOnce() -> T, f: impl FnOnce(&mut T) -> R) -> R {
+        let mut lent = Lent::<T>::take().unwrap_or_else(|| Lent::new(Box::new(init())));
+        f(lent.value.as_mut().unwrap().downcast_mut().unwrap())
+    }
+
+    /// Calls `f` with this thread's value of type `T`, if there is one.
+    pub fn try_with<T: Any, R>(f: impl FnOnce(&mut T) -> R) -> Option<R> {
+        let mut lent = Lent::<T>::take()?;
+        Some(f(lent.value.as_mut().unwrap().downcast_mut().unwrap()))
+    }
+
+    /// Returns true if this thread has a value of type `T`.
+    pub fn contains<T: Any>() -> bool {
+        VALUES.with(|values| values.borrow().contains_key(&TypeId::of::<T>()))
+    }
+
+    /// Sets this thread's value of type `T`, returning the previous one.
+    pub fn set<T: Any>(value: T) -> Option<T> {
+        let old = Self::take::<T>();
+        Lent::<T>::new(Box::new(value));
+        old
+    }
+
+    /// Removes and returns this thread's value of type `T`.
+    pub fn take<T: Any>() -> Option<T> {
+        let mut lent = Lent::<T>::take()?;
+        Some(*lent.value.take().unwrap().downcast().unwrap())
+    }
+
+    /// Drops all of this thread's values.
+    ///
+    /// # Panics
+    ///
+    /// If called from within a `with` call.
+    pub fn clear() {
+        let values = VALUES.with(|values| {
+            let mut values = values.borrow_mut();
+            assert!(values.values().all(Option::is_some), "LocalTypeMap::clear called from `with`");
+            std::mem::take(&mut *values)
+        });
+        // Dropped outside the borrow, since the values' destructors may use the map.
+        drop(values);
+    }
+}
+
+/// A value taken out of the map for the duration of a call, and put back on drop, even if
+/// the call panics.
+struct Lent<T: Any> {
+    value: Option<Box<dyn Any>>,
+    _type: PhantomData<T>,
+}
+
+impl<T: Any> Lent<T> {
+    fn take() -> Option<Self> {
+        let value = VALUES.with(|values| {
+            let mut values = values.borrow_mut();
+            let value = values.get_mut(&TypeId::of::<T>())?;
+            Some(value.take().unwrap_or_else(|| in_use::<T>()))
+        })?;
+        Some(Lent { value: Some(value), _type: PhantomData })
+    }
+
+    fn new(value: Box<dyn Any>) -> Self {
+        VALUES.with(|values| {
+            let mut values = values.borrow_mut();
+            if values.insert(TypeId::of::<T>(), None).is_some_and(|old| old.is_none()) {
+                drop(values);
+                in_use::<T>()
+            }
+        });
+        Lent { value: Some(value), _type: PhantomData }
+    }
+}
+
+impl<T: Any> Drop for Lent<T> {
+    fn drop(&mut self) {
+        // `try_with`, because the thread's storage may already be gone.
+        let _ = VALUES.try_with(|values| match self.value.take() {
+            Some(value) => values.borrow_mut().insert(TypeId::of::<T>(), Some(value)),
+            None => values.borrow_mut().remove(&TypeId::of::<T>()),
+        });
+    }
+}
+
+#[cold]
+fn in_use<T>() -> ! {
+    panic!("LocalTypeMap: `{}` is already in use on this thread", type_name::<T>())
+}
+
+#[cfg(test)]
+mod test {
+    use std::panic::catch_unwind;
+
+    use super::LocalTypeMap;
+
+    #[derive(Debug, Default, PartialEq)]
+    struct Foo(u32);
+    #[derive(Debug, Default, PartialEq)]
+    struct Bar(u32);
+
+    #[test]
+    fn test() {
+        assert_eq!(LocalTypeMap::try_with(|foo: &mut Foo| foo.0), None);
+        LocalTypeMap::with(|foo: &mut Foo| foo.0 += 1);
+        LocalTypeMap::with_or(|| Foo(10), |foo: &mut Foo| foo.0 += 1);
+        assert!(LocalTypeMap::contains::<Foo>());
+        assert_eq!(LocalTypeMap::set(Foo(5)), Some(Foo(2)));
+
+        // Different types nest.
+        LocalTypeMap::with(|foo: &mut Foo| {
+            LocalTypeMap::with(|bar: &mut Bar| bar.0 = foo.0);
+        });
+        assert_eq!(LocalTypeMap::take::<Bar>(), Some(Bar(5)));
+        assert!(!LocalTypeMap::contains::<Bar>());
+
+        std::thread::spawn(|| assert_eq!(LocalTypeMap::take::<Foo>(), None)).join().unwrap();
+        LocalTypeMap::clear();
+        assert!(!LocalTypeMap::contains::<Foo>());
+    }
+
+    #[test]
+    fn test_nested_same_type() {
+        let result = catch_unwind(|| {
+            LocalTypeMap::with(|_: &mut Foo| LocalTypeMap::with(|_: &mut Foo| ()));
+        });
+        assert!(result.is_err());
+        // The outer value was put back while unwinding.
+        assert_eq!(LocalTypeMap::take::<Foo>(), Some(Foo(0)));
+    }
+}
@@ -0,0 +1,87 @@
+//! The `RwLock` of `SharedAnyMap`, `Services` and the failure hook: `std::sync::RwLock`, or
+//! `parking_lot::RwLock` with the `parking_lot` feature.
+//!
+//! A std lock poisoned by a panic is used as is, as `parking_lot` locks aren't poisoned.
+
+#[cfg(not(feature = "parking_lot"))]
+use std::sync::PoisonError;
+
+#[cfg(feature = "parking_lot")]
+pub(crate) use parking_lot::{RwLockReadGuard as ReadGuard, RwLockWriteGuard as WriteGuard};
+#[cfg(not(feature = "parking_lot"))]
+pub(crate) use std::sync::{RwLockReadGuard as ReadGuard, RwLockWriteGuard as WriteGuard};
+
+#[cfg(feature = "parking_lot")]
+type Inner<T> = parking_lot::RwLock<T>;
+#[cfg(not(feature = "parking_lot"))]
+type Inner<T> = std::sync::RwLock<T>;
+
+#[derive(Default)]
+pub(crate) struct RwLock<T>(Inner<T>);
+
+impl<T> RwLock<T> {
+    pub(crate) const fn new(value: T) -> Self {
+        RwLock(Inner::new(value))
+    }
+
+    #[cfg(feature = "parking_lot")]
+    pub(crate) fn read(&self) -> ReadGuard<'_, T> {
+        self.0.read()
+    }
+
+    #[cfg(not(feature = "parking_lot"))]
+    pub(crate) fn read(&self) -> ReadGuard<'_, T> {
+        self.0.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    #[cfg(feature = "parking_lot")]
+    pub(crate) fn write(&self) -> WriteGuard<'_, T> {
+        self.0.write()
+    }
+
+    #[cfg(not(feature = "parking_lot"))]
+    pub(crate) fn write(&self) -> WriteGuard<'_, T> {
+        self.0.write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    #[cfg(feature = "parking_lot")]
+    pub(crate) fn get_mut(&mut self) -> &mut T {
+        self.0.get_mut()
+    }
+
+    #[cfg(not(feature = "parking_lot"))]
+    pub(crate) fn get_mut(&mut self) -> &mut T {
+        self.0.get_mut().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    #[cfg(feature = "parking_lot")]
+    pub(crate) fn into_inner(self) -> T {
+        self.0.into_inner()
+    }
+
+    #[cfg(not(feature = "parking_lot"))]
+    pub(crate) fn into_inner(self) -> T {
+        self.0.into_inner().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    use super::RwLock;
+
+    #[test]
+    fn test() {
+        let mut lock = RwLock::new(1);
+        *lock.write() += 1;
+        assert_eq!(*lock.read(), 2);
+        let _ = catch_unwind(AssertUnwindSafe(|| {
+            let _guard = lock.write();
+            panic!("poison");
+        }));
+        *lock.get_mut() += 1;
+        assert_eq!(*lock.read(), 3);
+        assert_eq!(lock.into_inner(), 3);
+    }
+}
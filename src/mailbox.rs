@@ -0,0 +1,221 @@
+//! `Mailbox`, queueing messages for an actor that declares a handler for each message type.
+
+use std::any::{Any, TypeId};
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+
+type Message = Box<dyn Any + Send>;
+type Handler<A> = Box<dyn FnMut(&mut A, Message) + Send>;
+
+/// A type that handles messages, declaring a handler per message type. Implement it with
+/// `impl_actor!`, or by hand to register closures.
+pub trait Actor: Sized + 'static {
+    fn handlers(handlers: &mut ActorHandlers<Self>);
+}
+
+/// The handlers of an actor of type `A`, by message type.
+pub struct ActorHandlers<A> {
+    handlers: HashMap<TypeId, Handler<A>>,
+}
+
+impl<A> ActorHandlers<A> {
+    /// Handles messages of type `T` with `handler`, replacing an earlier one.
+    pub fn on<T: Any>(&mut self, mut handler: impl FnMut(&mut A, T) + Send + 'static) {
+        let handler: Handler<A> = Box::new(move |actor, msg| {
+            handler(actor, *msg.downcast().unwrap());
+        });
+        self.handlers.insert(TypeId::of::<T>(), handler);
+    }
+
+    /// Returns true if messages of type `T` have a handler.
+    pub fn handles<T: Any>(&self) -> bool {
+        self.handlers.contains_key(&TypeId::of::<T>())
+    }
+}
+
+impl<A> fmt::Debug for ActorHandlers<A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ActorHandlers").field("types", &self.handlers.len()).finish()
+    }
+}
+
+/// Implements `Actor` for a type, handling each message type with a method taking
+/// `&mut self` and the message.
+///
+/// ```
+/// # #[macro_use]
+/// # extern crate pi_any;
+/// use pi_any::Mailbox;
+///
+/// struct Add(i32);
+/// struct Reset;
+///
+/// #[derive(Default)]
+/// struct Counter(i32);
+/// impl Counter {
+///     fn add(&mut self, msg: Add) { self.0 += msg.0; }
+///     fn reset(&mut self, _: Reset) { self.0 = 0; }
+/// }
+/// impl_actor!(Counter { Add => add, Reset => reset });
+///
+/// # fn main() {
+/// let mut counter = Counter::default();
+/// let mut mailbox = Mailbox::<Counter>::new();
+/// mailbox.send(Add(2));
+/// mailbox.send(Reset);
+/// mailbox.send(Add(5));
+/// mailbox.send("unknown");
+/// assert_eq!(mailbox.deliver(&mut counter), 3);
+/// assert_eq!(counter.0, 5);
+/// assert_eq!(mailbox.take_unhandled().len(), 1);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! impl_actor {
+    ($ty:ty { $($msg:ty => $method:ident),* $(,)? }) => {
+        impl $crate::Actor for $ty {
+            fn handlers(handlers: &mut $crate::ActorHandlers<Self>) {
+                $( handlers.on(|actor: &mut Self, msg: $msg| actor.$method(msg)); )*
+                let _ = handlers;
+            }
+        }
+    };
+}
+
+/// Queues messages of any type for an actor of type `A`, and delivers them to its handlers
+/// by the `TypeId` of the message.
+///
+/// Messages without a handler are kept, so they can be logged or forwarded, instead of
+/// being lost in a chain of `downcast_ref`s.
+pub struct Mailbox<A> {
+    queue: VecDeque<Message>,
+    handlers: ActorHandlers<A>,
+    unhandled: Vec<Message>,
+}
+
+impl<A: Actor> Mailbox<A> {
+    /// Creates an empty mailbox with the handlers `A` declares.
+    pub fn new() -> Self {
+        let mut handlers = ActorHandlers { handlers: HashMap::new() };
+        A::handlers(&mut handlers);
+        Mailbox { queue: VecDeque::new(), handlers, unhandled: Vec::new() }
+    }
+}
+
+impl<A: Actor> Default for Mailbox<A> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<A> Mailbox<A> {
+    pub fn handlers(&self) -> &ActorHandlers<A> {
+        &self.handlers
+    }
+
+    /// Queues `msg` until the next `deliver`.
+    pub fn send<T: Any + Send>(&mut self, msg: T) {
+        self.queue.push_back(Box::new(msg))
+    }
+
+    /// Queues a boxed message until the next `deliver`.
+    pub fn send_boxed(&mut self, msg: Message) {
+        self.queue.push_back(msg)
+    }
+
+    /// Returns the number of queued messages.
+    pub fn pending(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Delivers the queued messages to `actor` in order, returning how many were handled.
+    /// Those of a type without a handler are kept for `take_unhandled`.
+    pub fn deliver(&mut self, actor: &mut A) -> usize {
+        let mut handled = 0;
+        while let Some(msg) = self.queue.pop_front() {
+            match self.handlers.handlers.get_mut(&(*msg).type_id()) {
+                Some(handler) => {
+                    handler(actor, msg);
+                    handled += 1;
+                }
+                None => self.unhandled.push(msg),
+            }
+        }
+        handled
+    }
+
+    /// Returns the messages that had no handler, in the order they were delivered.
+    pub fn take_unhandled(&mut self) -> Vec<Message> {
+        std::mem::take(&mut self.unhandled)
+    }
+}
+
+impl<A> fmt::Debug for Mailbox<A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Mailbox")
+            .field("pending", &self.queue.len())
+            .field("unhandled", &self.unhandled.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Actor, ActorHandlers, Mailbox};
+
+    struct Push(char);
+    struct Pop;
+    struct Other;
+
+    #[derive(Default)]
+    struct Text(String);
+    impl Text {
+        fn push(&mut self, msg: Push) {
+            self.0.push(msg.0)
+        }
+        fn pop(&mut self, _: Pop) {
+            self.0.pop();
+        }
+    }
+    crate::impl_actor!(Text { Push => push, Pop => pop });
+
+    struct Quiet;
+    crate::impl_actor!(Quiet {});
+
+    struct Log(Vec<u32>);
+    impl Actor for Log {
+        fn handlers(handlers: &mut ActorHandlers<Self>) {
+            handlers.on(|log: &mut Log, n: u32| log.0.push(n));
+            handlers.on(|log: &mut Log, n: u32| log.0.push(n * 10));
+        }
+    }
+
+    #[test]
+    fn test() {
+        let mut text = Text::default();
+        let mut mailbox = Mailbox::<Text>::default();
+        assert!(mailbox.handlers().handles::<Pop>() && !mailbox.handlers().handles::<Other>());
+        for c in "abc".chars() {
+            mailbox.send(Push(c));
+        }
+        mailbox.send(Other);
+        mailbox.send_boxed(Box::new(Pop));
+        assert_eq!(mailbox.pending(), 5);
+        assert_eq!(mailbox.deliver(&mut text), 4);
+        assert_eq!(text.0, "ab");
+        assert_eq!(format!("{:?}", mailbox), "Mailbox { pending: 0, unhandled: 1 }");
+        let unhandled = mailbox.take_unhandled();
+        assert!(unhandled[0].is::<Other>());
+        assert!(mailbox.take_unhandled().is_empty());
+
+        let mut quiet = Mailbox::<Quiet>::new();
+        quiet.send(Pop);
+        assert_eq!(quiet.deliver(&mut Quiet), 0);
+
+        let mut log = Log(Vec::new());
+        let mut mailbox = Mailbox::<Log>::new();
+        mailbox.send(1u32);
+        mailbox.deliver(&mut log);
+        assert_eq!(log.0, [10]);
+    }
+}
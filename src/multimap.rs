@@ -0,0 +1,131 @@
+//! `AnyMultiMap`, a typemap holding any number of values of each type.
+
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::fmt;
+use std::mem;
+
+use crate::AnyColumn;
+
+/// A map from types to the values of that type, stored contiguously in an `AnyColumn` per
+/// type, e.g. the events of a frame, grouped by type.
+///
+/// `clear` keeps the columns and their capacity, so a map refilled every frame settles on
+/// not allocating.
+///
+/// ```
+/// use pi_any::AnyMultiMap;
+///
+/// struct Click(i32, i32);
+/// struct Key(char);
+///
+/// let mut events = AnyMultiMap::new();
+/// events.insert(Click(1, 2));
+/// events.insert(Key('a'));
+/// events.insert(Click(3, 4));
+///
+/// let xs: Vec<i32> = events.get_all::<Click>().iter().map(|c| c.0).collect();
+/// assert_eq!(xs, [1, 3]);
+/// assert_eq!(events.get_all::<Key>().len(), 1);
+/// events.clear();
+/// assert!(events.is_empty());
+/// ```
+#[derive(Default)]
+pub struct AnyMultiMap {
+    columns: HashMap<TypeId, AnyColumn>,
+}
+
+impl AnyMultiMap {
+    pub fn new() -> Self {
+        AnyMultiMap { columns: HashMap::new() }
+    }
+
+    /// Returns the number of values of all types.
+    pub fn len(&self) -> usize {
+        self.columns.values().map(AnyColumn::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.columns.values().all(AnyColumn::is_empty)
+    }
+
+    /// Returns the number of values of type `T`.
+    pub fn count<T: Any>(&self) -> usize {
+        self.get_all::<T>().len()
+    }
+
+    /// Appends `value` to the values of type `T`.
+    pub fn insert<T: Any>(&mut self, value: T) {
+        self.columns.entry(TypeId::of::<T>()).or_insert_with(AnyColumn::new::<T>).push(value)
+    }
+
+    /// Returns the values of type `T`, in the order they were inserted.
+    pub fn get_all<T: Any>(&self) -> &[T] {
+        let column = self.columns.get(&TypeId::of::<T>());
+        column.and_then(AnyColumn::as_slice).unwrap_or(&[])
+    }
+
+    pub fn get_all_mut<T: Any>(&mut self) -> &mut [T] {
+        let column = self.columns.get_mut(&TypeId::of::<T>());
+        column.and_then(AnyColumn::as_mut_slice).unwrap_or(&mut [])
+    }
+
+    /// Removes and returns the values of type `T`, along with the allocation of their column.
+    pub fn take_all<T: Any>(&mut self) -> Vec<T> {
+        let column = self.columns.get_mut(&TypeId::of::<T>());
+        column.and_then(AnyColumn::as_vec_mut).map(mem::take).unwrap_or_default()
+    }
+
+    /// Removes the values of all types, keeping the columns for the next ones.
+    pub fn clear(&mut self) {
+        self.columns.values_mut().for_each(AnyColumn::clear)
+    }
+}
+
+impl fmt::Debug for AnyMultiMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let counts = self.columns.values().filter(|c| !c.is_empty());
+        f.debug_map().entries(counts.map(|c| (c.item_type_name(), c.len()))).finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+
+    use super::AnyMultiMap;
+
+    #[derive(Debug, PartialEq)]
+    struct Foo(u32);
+
+    #[test]
+    fn test() {
+        let mut map = AnyMultiMap::new();
+        assert!(map.is_empty() && map.get_all::<Foo>().is_empty());
+        assert!(map.take_all::<Foo>().is_empty());
+        map.insert(Foo(1));
+        map.insert(1u8);
+        map.insert(Foo(2));
+        assert_eq!((map.len(), map.count::<Foo>(), map.count::<u16>()), (3, 2, 0));
+        map.get_all_mut::<Foo>()[1].0 = 3;
+        assert_eq!(map.get_all::<Foo>(), [Foo(1), Foo(3)]);
+        assert_eq!(format!("{:?}", map.get_all::<u8>()), "[1]");
+
+        assert_eq!(map.take_all::<Foo>(), [Foo(1), Foo(3)]);
+        assert_eq!(map.len(), 1);
+        assert_eq!(format!("{:?}", map), "{\"u8\": 1}");
+        assert!(map.get_all_mut::<u32>().is_empty());
+    }
+
+    #[test]
+    fn test_clear() {
+        let value = Rc::new(());
+        let mut map = AnyMultiMap::new();
+        map.insert(value.clone());
+        map.insert(value.clone());
+        assert_eq!(Rc::strong_count(&value), 3);
+        map.clear();
+        assert_eq!(Rc::strong_count(&value), 1);
+        assert!(map.is_empty());
+    }
+}
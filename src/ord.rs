@@ -0,0 +1,97 @@
+//! `DynOrd`, a total order across trait objects of different concrete types.
+
+use core::any::{Any, TypeId};
+use core::cmp::Ordering;
+
+use crate::AsAny;
+
+/// Implemented for every `Ord` type, so a trait extending `DynOrd` can implement `Ord` for
+/// its trait objects with `impl_dyn_ord!`.
+///
+/// Values are ordered by the `TypeId` of their concrete type first, and values of the same
+/// type by their `Ord`. The order between types is arbitrary but stable within a build.
+///
+/// ```
+/// # #[macro_use]
+/// # extern crate pi_any;
+/// use std::collections::BTreeSet;
+/// use pi_any::{DynEq, DynOrd};
+///
+/// trait Key: DynEq + DynOrd {}
+/// impl_dyn_eq!(Key);
+/// impl_dyn_ord!(Key);
+/// impl Eq for dyn Key {}
+///
+/// #[derive(PartialEq, Eq, PartialOrd, Ord)]
+/// struct Id(u32);
+/// impl Key for Id {}
+///
+/// # fn main() {
+/// let mut keys: BTreeSet<Box<dyn Key>> = BTreeSet::new();
+/// keys.insert(Box::new(Id(2)));
+/// keys.insert(Box::new("name"));
+/// keys.insert(Box::new(Id(1)));
+/// assert!(!keys.insert(Box::new(Id(2))));
+/// assert_eq!(keys.len(), 3);
+/// # }
+/// # impl Key for &'static str {}
+/// ```
+pub trait DynOrd: AsAny {
+    /// Compares the `TypeId`s of the concrete types of `self` and `other`, and then the
+    /// values if the types are the same.
+    fn dyn_cmp(&self, other: &dyn Any) -> Ordering;
+}
+
+impl<T: Ord + Any> DynOrd for T {
+    fn dyn_cmp(&self, other: &dyn Any) -> Ordering {
+        match other.downcast_ref::<T>() {
+            Some(other) => self.cmp(other),
+            None => TypeId::of::<T>().cmp(&other.type_id()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cmp::Ordering;
+    use std::collections::BTreeMap;
+
+    use super::DynOrd;
+    use crate::DynEq;
+
+    trait Base: DynEq + DynOrd {}
+    crate::impl_dyn_eq!(Base);
+    crate::impl_dyn_ord!(Base);
+    impl Eq for dyn Base {}
+
+    #[derive(PartialEq, Eq, PartialOrd, Ord)]
+    struct Foo(u32);
+    impl Base for Foo {}
+    #[derive(PartialEq, Eq, PartialOrd, Ord)]
+    struct Bar(u32);
+    impl Base for Bar {}
+
+    #[test]
+    fn test() {
+        assert_eq!(Foo(1).dyn_cmp(&Foo(2)), Ordering::Less);
+        assert_eq!(Foo(1).dyn_cmp(&Foo(1)), Ordering::Equal);
+        let across = Foo(1).dyn_cmp(&Bar(1));
+        assert_ne!(across, Ordering::Equal);
+        assert_eq!(Bar(0).dyn_cmp(&Foo(9)), across.reverse());
+
+        let mut map: BTreeMap<Box<dyn Base>, &str> = BTreeMap::new();
+        map.insert(Box::new(Foo(2)), "foo 2");
+        map.insert(Box::new(Bar(1)), "bar 1");
+        map.insert(Box::new(Foo(1)), "foo 1");
+        map.insert(Box::new(Foo(2)), "foo 2 again");
+        assert_eq!(map.len(), 3);
+        assert_eq!(map[&(Box::new(Foo(2)) as Box<dyn Base>)], "foo 2 again");
+
+        // Values of one type are adjacent and in their own order.
+        let foos: Vec<_> = map.values().filter(|v| v.starts_with("foo")).collect();
+        assert_eq!(foos, [&"foo 1", &"foo 2 again"]);
+        let a: Box<dyn Base> = Box::new(Foo(1));
+        let b: Box<dyn Base> = Box::new(Foo(3));
+        assert!(a < b);
+    }
+}
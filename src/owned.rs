@@ -0,0 +1,193 @@
+//! The checked owning downcasts behind the generated `downcast` methods.
+//!
+//! They check the type first, to hand the original pointer back if it is another, and then
+//! go through `into_any` and the `downcast` of the standard library. Pinned pointers can't
+//! be unpinned safely, so only `downcast_pin` is allowed to use `unsafe`.
+
+use alloc::boxed::Box;
+use alloc::rc::Rc;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::any::{Any, TypeId};
+use core::mem::{align_of_val, size_of_val};
+use core::ops::Deref;
+use core::pin::Pin;
+use core::ptr;
+
+use crate::{ArcAny, AsAny, BoxAny, RcAny};
+
+/// Returns the `TypeId` of the value, if `as_any` returns the value itself. The blanket
+/// impl of `AsAny` always does, but one written by hand for an unsized type might not, and
+/// then the pointer must not be cast.
+pub(crate) fn concrete_type_id<S: AsAny + ?Sized>(value: &S) -> Option<TypeId> {
+    let any = value.as_any();
+    let same = ptr::addr_eq(any, value)
+        && size_of_val(any) == size_of_val(value)
+        && align_of_val(any) == align_of_val(value);
+    same.then(|| any.type_id())
+}
+
+#[doc(hidden)]
+#[inline]
+pub fn __downcast_box<T: Any, S: BoxAny + ?Sized>(value: Box<S>) -> Result<Box<T>, Box<S>> {
+    if (*value).as_any().is::<T>() {
+        Ok(cast_box(value))
+    } else {
+        Err(value)
+    }
+}
+
+/// Downcasts a box already checked to hold a `T`.
+fn cast_box<T: Any, S: BoxAny + ?Sized>(value: Box<S>) -> Box<T> {
+    let value = BoxAny::into_any(value).downcast();
+    value.expect("`as_any` and `into_any` return different values")
+}
+
+#[doc(hidden)]
+pub fn __downcast_vec<T: Any, S: BoxAny + ?Sized>(
+    values: Vec<Box<S>>,
+) -> Result<Vec<Box<T>>, Vec<Box<S>>> {
+    if !values.iter().all(|value| (**value).as_any().is::<T>()) {
+        return Err(values);
+    }
+    Ok(values.into_iter().map(cast_box).collect())
+}
+
+#[doc(hidden)]
+pub fn __drain_downcast<T: Any, S: BoxAny + ?Sized>(values: &mut Vec<Box<S>>) -> Vec<Box<T>> {
+    let (taken, kept): (Vec<_>, Vec<_>) =
+        values.drain(..).partition(|value| (**value).as_any().is::<T>());
+    *values = kept;
+    taken.into_iter().map(cast_box).collect()
+}
+
+#[doc(hidden)]
+#[inline]
+pub fn __downcast_rc<T: Any, S: RcAny + ?Sized>(value: Rc<S>) -> Result<Rc<T>, Rc<S>> {
+    if (*value).as_any().is::<T>() {
+        let value = RcAny::into_any(value).downcast();
+        Ok(value.expect("`as_any` and `into_any` return different values"))
+    } else {
+        Err(value)
+    }
+}
+
+#[doc(hidden)]
+#[inline]
+pub fn __downcast_arc<T: Any + Send + Sync, S: ArcAny + ?Sized>(
+    value: Arc<S>,
+) -> Result<Arc<T>, Arc<S>> {
+    if (*value).as_any().is::<T>() {
+        let value = ArcAny::into_any(value).downcast();
+        Ok(value.expect("`as_any` and `into_any` return different values"))
+    } else {
+        Err(value)
+    }
+}
+
+/// Downcasts a pinned pointer with one of the functions above, which cast the pointer
+/// without moving the value.
+#[allow(unsafe_code)]
+fn downcast_pin<P: Deref, Q: Deref>(
+    value: Pin<P>,
+    downcast: fn(P) -> Result<Q, P>,
+) -> Result<Pin<Q>, Pin<P>> {
+    // SAFETY: the value stays where it was pinned, and the pointer is pinned again whether
+    // it was downcast or not.
+    unsafe {
+        match downcast(Pin::into_inner_unchecked(value)) {
+            Ok(value) => Ok(Pin::new_unchecked(value)),
+            Err(value) => Err(Pin::new_unchecked(value)),
+        }
+    }
+}
+
+#[doc(hidden)]
+#[inline]
+pub fn __downcast_pin_box<T: Any, S: BoxAny + ?Sized>(
+    value: Pin<Box<S>>,
+) -> Result<Pin<Box<T>>, Pin<Box<S>>> {
+    downcast_pin(value, __downcast_box)
+}
+
+#[doc(hidden)]
+#[inline]
+pub fn __downcast_pin_rc<T: Any, S: RcAny + ?Sized>(
+    value: Pin<Rc<S>>,
+) -> Result<Pin<Rc<T>>, Pin<Rc<S>>> {
+    downcast_pin(value, __downcast_rc)
+}
+
+#[doc(hidden)]
+#[inline]
+pub fn __downcast_pin_arc<T: Any + Send + Sync, S: ArcAny + ?Sized>(
+    value: Pin<Arc<S>>,
+) -> Result<Pin<Arc<T>>, Pin<Arc<S>>> {
+    downcast_pin(value, __downcast_arc)
+}
+
+#[cfg(test)]
+mod test {
+    use std::fmt::Debug;
+    use std::marker::PhantomPinned;
+    use std::pin::Pin;
+    use std::rc::Rc;
+    use std::sync::Arc;
+
+    use super::{__downcast_arc, __downcast_box, __downcast_pin_arc, __downcast_pin_box};
+    use super::{__downcast_pin_rc, __downcast_rc, __downcast_vec, __drain_downcast};
+    use crate::{ArcAny, BoxAny, RcAny};
+
+    trait Base: BoxAny + RcAny + ArcAny + Debug {}
+    impl Base for String {}
+    impl Base for u8 {}
+    #[derive(Debug)]
+    struct Node(u8, PhantomPinned);
+    impl Base for Node {}
+
+    #[test]
+    fn test() {
+        let base: Box<dyn Base> = Box::new(String::from("box"));
+        let base = __downcast_box::<u8, _>(base).unwrap_err();
+        assert_eq!(*__downcast_box::<String, _>(base).unwrap(), "box");
+
+        let base: Rc<dyn Base> = Rc::new(String::from("rc"));
+        let other = base.clone();
+        let base = __downcast_rc::<u8, _>(base).unwrap_err();
+        let base = __downcast_rc::<String, _>(base).unwrap();
+        assert_eq!(*base, "rc");
+        assert_eq!(Rc::strong_count(&other), 2);
+
+        let base: Arc<dyn Base> = Arc::new(7u8);
+        assert_eq!(*__downcast_arc::<u8, _>(base).unwrap(), 7);
+    }
+
+    #[test]
+    fn test_vec() {
+        let bases: Vec<Box<dyn Base>> =
+            vec![Box::new(1u8), Box::new(String::from("a")), Box::new(2u8)];
+        let mut bases = __downcast_vec::<u8, _>(bases).unwrap_err();
+        assert_eq!(bases.len(), 3);
+        let bytes = __drain_downcast::<u8, _>(&mut bases);
+        assert_eq!(bytes.iter().map(|b| **b).collect::<Vec<_>>(), [1, 2]);
+        let strings = __downcast_vec::<String, _>(bases).unwrap();
+        assert_eq!(*strings[0], "a");
+        assert!(__downcast_vec::<u8, dyn Base>(Vec::new()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_pin() {
+        let base: Pin<Box<dyn Base>> = Box::pin(Node(1, PhantomPinned));
+        let addr = &*base as *const dyn Base as *const u8;
+        let base = __downcast_pin_box::<u8, _>(base).unwrap_err();
+        let node = __downcast_pin_box::<Node, _>(base).unwrap();
+        assert_eq!((node.0, &*node as *const Node as *const u8), (1, addr));
+
+        let base: Pin<Rc<dyn Base>> = Rc::pin(Node(2, PhantomPinned));
+        let base = __downcast_pin_rc::<u8, _>(base).unwrap_err();
+        assert_eq!(__downcast_pin_rc::<Node, _>(base).unwrap().0, 2);
+
+        let base: Pin<Arc<dyn Base>> = Arc::pin(3u8);
+        assert_eq!(*__downcast_pin_arc::<u8, _>(base).unwrap(), 3);
+    }
+}
@@ -0,0 +1,119 @@
+//! Plain-old-data types that `AnyColumn` can export to and import from bytes.
+
+use alloc::vec::Vec;
+use core::error::Error;
+use core::fmt;
+
+/// A plain value with a fixed little-endian byte encoding.
+///
+/// The encoding is written value by value, so it does not depend on the layout or byte
+/// order of the host, and reading it back needs no `unsafe` code.
+pub trait Pod: Copy + 'static {
+    /// The size of the encoding in bytes.
+    const SIZE: usize;
+
+    /// Appends the encoding of `self` to `out`.
+    fn write_le(&self, out: &mut Vec<u8>);
+
+    /// Decodes a value from exactly `SIZE` bytes.
+    fn read_le(bytes: &[u8]) -> Self;
+}
+
+macro_rules! impl_pod {
+    ($($ty:ty),*) => {
+        $(
+            impl Pod for $ty {
+                const SIZE: usize = core::mem::size_of::<$ty>();
+
+                fn write_le(&self, out: &mut Vec<u8>) {
+                    out.extend_from_slice(&self.to_le_bytes());
+                }
+
+                fn read_le(bytes: &[u8]) -> Self {
+                    <$ty>::from_le_bytes(bytes.try_into().unwrap())
+                }
+            }
+        )*
+    };
+}
+
+impl_pod!(u8, u16, u32, u64, u128, i8, i16, i32, i64, i128, f32, f64);
+
+impl<T: Pod, const N: usize> Pod for [T; N] {
+    const SIZE: usize = T::SIZE * N;
+
+    fn write_le(&self, out: &mut Vec<u8>) {
+        for v in self {
+            v.write_le(out);
+        }
+    }
+
+    fn read_le(bytes: &[u8]) -> Self {
+        core::array::from_fn(|i| T::read_le(&bytes[i * T::SIZE..(i + 1) * T::SIZE]))
+    }
+}
+
+/// The encoded size and in-memory alignment of a `Pod` type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PodLayout {
+    pub size: usize,
+    pub align: usize,
+}
+
+impl PodLayout {
+    pub fn of<T: Pod>() -> Self {
+        PodLayout { size: T::SIZE, align: core::mem::align_of::<T>() }
+    }
+}
+
+/// Why bytes could not be imported into an `AnyColumn`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PodError {
+    /// The column was not created for a `Pod` type.
+    NotPod { type_name: &'static str },
+    /// The bytes were exported from a column with a different layout.
+    Layout { expected: PodLayout, found: PodLayout },
+    /// The number of bytes is not a multiple of the item size.
+    Length { len: usize, size: usize },
+}
+
+impl fmt::Display for PodError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PodError::NotPod { type_name } => write!(f, "`{}` is not registered as Pod", type_name),
+            PodError::Layout { expected, found } => {
+                write!(f, "layout mismatch: expected {:?}, found {:?}", expected, found)
+            }
+            PodError::Length { len, size } => {
+                write!(f, "{} bytes is not a whole number of {}-byte items", len, size)
+            }
+        }
+    }
+}
+
+impl Error for PodError {}
+
+#[cfg(test)]
+mod test {
+    use super::{Pod, PodLayout};
+
+    fn round_trip<T: Pod + PartialEq + std::fmt::Debug>(value: T) {
+        let mut bytes = Vec::new();
+        value.write_le(&mut bytes);
+        assert_eq!(bytes.len(), T::SIZE);
+        assert_eq!(T::read_le(&bytes), value);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        round_trip(0x1234u16);
+        round_trip(-7i64);
+        round_trip(1.5f32);
+        round_trip([[1u8, 2], [3, 4], [5, 6]]);
+
+        let mut bytes = Vec::new();
+        0x0102_0304u32.write_le(&mut bytes);
+        assert_eq!(bytes, [4, 3, 2, 1]);
+        assert_eq!(PodLayout::of::<[u16; 3]>(), PodLayout { size: 6, align: 2 });
+    }
+}
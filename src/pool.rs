@@ -0,0 +1,153 @@
+//! `AnyPool`, recycling boxed values of any type.
+
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::fmt;
+
+/// A pool of boxed values, kept per type so their allocations can be reused.
+///
+/// Values come back from `take` as they were `put`, so callers reset whatever state they
+/// rely on. Boxed trait objects built on `BoxAny` are returned with `put(value.into_any())`.
+///
+/// ```
+/// use pi_any::AnyPool;
+///
+/// #[derive(Default)]
+/// struct Particles(Vec<(f32, f32)>);
+///
+/// let mut pool = AnyPool::new();
+/// let mut particles = pool.take::<Particles>();
+/// particles.0.push((1.0, 2.0));
+/// let ptr = &*particles as *const Particles;
+/// pool.put(particles);
+///
+/// let mut particles = pool.take::<Particles>();
+/// assert_eq!(&*particles as *const Particles, ptr);
+/// particles.0.clear();
+/// ```
+#[derive(Default)]
+pub struct AnyPool {
+    free: HashMap<TypeId, Vec<Box<dyn Any>>>,
+    limit: Option<usize>,
+}
+
+impl AnyPool {
+    pub fn new() -> Self {
+        AnyPool { free: HashMap::new(), limit: None }
+    }
+
+    /// Creates a pool that keeps at most `limit` values of each type, dropping the rest.
+    pub fn with_limit(limit: usize) -> Self {
+        AnyPool { free: HashMap::new(), limit: Some(limit) }
+    }
+
+    /// Returns a pooled value of type `T`, or a new `T::default()` if there is none.
+    pub fn take<T: Any + Default>(&mut self) -> Box<T> {
+        self.take_with(T::default)
+    }
+
+    /// Returns a pooled value of type `T`, or a new one made by `f` if there is none.
+    pub fn take_with<T: Any>(&mut self, f: impl FnOnce() -> T) -> Box<T> {
+        self.try_take().unwrap_or_else(|| Box::new(f()))
+    }
+
+    /// Returns a pooled value of type `T`, if there is one.
+    pub fn try_take<T: Any>(&mut self) -> Option<Box<T>> {
+        let value = self.free.get_mut(&TypeId::of::<T>())?.pop()?;
+        Some(value.downcast().unwrap())
+    }
+
+    /// Returns `value` to the pool. Returns false if the pool already holds its limit of
+    /// values of that type, in which case `value` is dropped.
+    pub fn put(&mut self, value: Box<dyn Any>) -> bool {
+        let free = self.free.entry((*value).type_id()).or_default();
+        if self.limit.is_some_and(|limit| free.len() >= limit) {
+            return false;
+        }
+        free.push(value);
+        true
+    }
+
+    /// Returns the number of pooled values of type `T`.
+    pub fn available<T: Any>(&self) -> usize {
+        self.free.get(&TypeId::of::<T>()).map_or(0, Vec::len)
+    }
+
+    /// Returns the number of pooled values of all types.
+    pub fn len(&self) -> usize {
+        self.free.values().map(Vec::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.free.values().all(Vec::is_empty)
+    }
+
+    /// Drops the pooled values of type `T`.
+    pub fn clear_type<T: Any>(&mut self) {
+        self.free.remove(&TypeId::of::<T>());
+    }
+
+    pub fn clear(&mut self) {
+        self.free.clear()
+    }
+}
+
+impl fmt::Debug for AnyPool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AnyPool")
+            .field("len", &self.len())
+            .field("limit", &self.limit)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+
+    use super::AnyPool;
+    use crate::BoxAny;
+
+    trait Base: BoxAny {}
+
+    #[derive(Default)]
+    struct Foo(u32);
+    impl Base for Foo {}
+
+    #[test]
+    fn test_recycle() {
+        let mut pool = AnyPool::new();
+        assert!(pool.try_take::<Foo>().is_none());
+        let foo = pool.take_with(|| Foo(7));
+        let ptr = &*foo as *const Foo;
+        assert!(pool.put(foo));
+        assert!(pool.put(Box::new(1u8)));
+        assert_eq!(pool.available::<Foo>(), 1);
+        assert_eq!(pool.len(), 2);
+
+        let foo = pool.take::<Foo>();
+        assert_eq!((&*foo as *const Foo, foo.0), (ptr, 7));
+        assert_eq!(pool.available::<Foo>(), 0);
+        assert_eq!(pool.take::<Foo>().0, 0);
+
+        // Trait objects go back through `into_any`.
+        let base: Box<dyn Base> = foo;
+        pool.put(base.into_any());
+        assert_eq!(pool.try_take::<Foo>().map(|foo| foo.0), Some(7));
+        pool.clear_type::<u8>();
+        assert!(pool.is_empty());
+    }
+
+    #[test]
+    fn test_limit() {
+        let value = Rc::new(());
+        let mut pool = AnyPool::with_limit(2);
+        for _ in 0..3 {
+            pool.put(Box::new(value.clone()));
+        }
+        assert_eq!(pool.available::<Rc<()>>(), 2);
+        assert_eq!(Rc::strong_count(&value), 3);
+        pool.clear();
+        assert_eq!(Rc::strong_count(&value), 1);
+    }
+}
@@ -0,0 +1,165 @@
+//! `Queryable`, objects answering which interfaces, i.e. trait object types, they expose.
+
+use core::any::{Any, TypeId};
+
+use crate::{AsAny, AsMutAny, BoxAny};
+
+/// The casts of one concrete type to the interface `U`, in the table of the type.
+#[doc(hidden)]
+pub struct __Interface<U: ?Sized + 'static> {
+    pub as_ref: fn(&dyn Any) -> &U,
+    pub as_mut: fn(&mut dyn Any) -> &mut U,
+}
+
+/// An object that can be asked for any interface its concrete type exposes, e.g. whether a
+/// `&dyn Plugin` is also a `&dyn Render`, like `QueryInterface` in COM.
+///
+/// Unlike with `CastRegistry`, the interfaces are listed with each type, by
+/// `impl_queryable!`, and need no registry at run time. The queries are in `QueryExt`.
+///
+/// ```
+/// # #[macro_use]
+/// # extern crate pi_any;
+/// use pi_any::{QueryExt, Queryable};
+///
+/// trait Plugin: Queryable {}
+/// trait Render { fn draw(&self) -> String; }
+///
+/// struct Sprite;
+/// impl Plugin for Sprite {}
+/// impl Render for Sprite { fn draw(&self) -> String { "sprite".into() } }
+/// struct Timer;
+/// impl Plugin for Timer {}
+///
+/// impl_queryable!(Sprite => dyn Render; Timer);
+///
+/// # fn main() {
+/// let plugins: Vec<Box<dyn Plugin>> = vec![Box::new(Sprite), Box::new(Timer)];
+/// let renders: Vec<&dyn Render> = plugins.iter().filter_map(|p| p.query()).collect();
+/// assert_eq!(renders.len(), 1);
+/// assert_eq!(renders[0].draw(), "sprite");
+/// assert!(!plugins[1].exposes::<dyn Render>());
+/// # }
+/// ```
+pub trait Queryable: BoxAny {
+    /// Returns the `__Interface` of the interface with the `TypeId` `iface`, if the type
+    /// exposes it.
+    #[doc(hidden)]
+    fn __interface(&self, iface: TypeId) -> Option<&'static dyn Any>;
+}
+
+/// The queries of a `Queryable`, implemented for every one, trait objects included.
+pub trait QueryExt: Queryable {
+    /// Returns true if the concrete type exposes the interface `U`.
+    fn exposes<U: ?Sized + 'static>(&self) -> bool {
+        self.__interface(TypeId::of::<U>()).is_some()
+    }
+
+    /// Returns the object as a `&U` if its concrete type exposes the interface `U`.
+    fn query<U: ?Sized + 'static>(&self) -> Option<&U> {
+        let iface = self.__interface(TypeId::of::<U>())?.downcast_ref::<__Interface<U>>()?;
+        Some((iface.as_ref)(AsAny::as_any(self)))
+    }
+
+    /// Returns the object as a `&mut U` if its concrete type exposes the interface `U`.
+    fn query_mut<U: ?Sized + 'static>(&mut self) -> Option<&mut U> {
+        let iface = self.__interface(TypeId::of::<U>())?.downcast_ref::<__Interface<U>>()?;
+        Some((iface.as_mut)(AsMutAny::as_any_mut(self)))
+    }
+}
+
+impl<Q: Queryable + ?Sized> QueryExt for Q {}
+
+/// Implements `Queryable` for concrete types, each with the interfaces it exposes, which
+/// are usually trait object types.
+///
+/// ```
+/// # #[macro_use]
+/// # extern crate pi_any;
+/// # use pi_any::{QueryExt, Queryable};
+/// # trait A {} trait B {}
+/// # struct Foo; impl A for Foo {} impl B for Foo {}
+/// # struct Bar; impl A for Bar {}
+/// # struct Baz;
+/// impl_queryable!(Foo => dyn A, dyn B; Bar => dyn A; Baz);
+/// # fn main() {
+/// assert!(Foo.exposes::<dyn B>() && !Bar.exposes::<dyn B>() && !Baz.exposes::<dyn A>());
+/// # }
+/// ```
+#[macro_export]
+macro_rules! impl_queryable {
+    ($($ty:ty $(=> $($iface:ty),+)?);+ $(;)?) => {
+        $(
+            impl $crate::Queryable for $ty {
+                fn __interface(
+                    &self,
+                    iface: $crate::__core::any::TypeId,
+                ) -> $crate::__core::option::Option<&'static dyn $crate::__core::any::Any> {
+                    $($(
+                        if iface == $crate::__core::any::TypeId::of::<$iface>() {
+                            static INTERFACE: $crate::__Interface<$iface> = $crate::__Interface {
+                                as_ref: |v| v.downcast_ref::<$ty>().unwrap(),
+                                as_mut: |v| v.downcast_mut::<$ty>().unwrap(),
+                            };
+                            return $crate::__core::option::Option::Some(&INTERFACE);
+                        }
+                    )+)?
+                    let _ = iface;
+                    $crate::__core::option::Option::None
+                }
+            }
+        )+
+    };
+}
+
+#[cfg(test)]
+mod test {
+    use super::{QueryExt, Queryable};
+
+    trait Base: Queryable {}
+    trait Named {
+        fn name(&self) -> String;
+    }
+    trait Counter {
+        fn bump(&mut self) -> u32;
+    }
+
+    struct Foo(u32);
+    impl Base for Foo {}
+    impl Named for Foo {
+        fn name(&self) -> String {
+            format!("foo {}", self.0)
+        }
+    }
+    impl Counter for Foo {
+        fn bump(&mut self) -> u32 {
+            self.0 += 1;
+            self.0
+        }
+    }
+    struct Bar;
+    impl Base for Bar {}
+    impl Named for Bar {
+        fn name(&self) -> String {
+            "bar".to_string()
+        }
+    }
+    struct Baz;
+    impl Base for Baz {}
+
+    crate::impl_queryable!(Foo => dyn Named, dyn Counter; Bar => dyn Named; Baz);
+
+    #[test]
+    fn test() {
+        let mut bases: Vec<Box<dyn Base>> = vec![Box::new(Foo(1)), Box::new(Bar), Box::new(Baz)];
+        assert_eq!(bases[0].query_mut::<dyn Counter>().unwrap().bump(), 2);
+        let names: Vec<_> =
+            bases.iter().filter_map(|b| b.query::<dyn Named>()).map(|n| n.name()).collect();
+        assert_eq!(names, ["foo 2", "bar"]);
+
+        assert!(bases[0].exposes::<dyn Counter>() && !bases[1].exposes::<dyn Counter>());
+        assert!(bases[2].query::<dyn Named>().is_none());
+        // Not even its own type is exposed unless listed.
+        assert!(!bases[0].exposes::<Foo>());
+    }
+}
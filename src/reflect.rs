@@ -0,0 +1,124 @@
+//! `Reflect`, access to the fields of a value by name.
+
+use core::any::Any;
+
+use crate::AsAny;
+
+/// Gives access to the fields of a struct by name, as `dyn Any`, so an editor or a debug
+/// console can read and change them without knowing the concrete type.
+///
+/// With the `derive` feature, `#[derive(Reflect)]` implements it for structs; tuple fields
+/// are named by their index, and fields marked `#[reflect(skip)]` are left out. The typed
+/// accessors are in `ReflectExt`.
+///
+/// ```
+/// use std::any::Any;
+/// use pi_any::{Reflect, ReflectExt};
+///
+/// struct Player { name: String, hp: u32 }
+///
+/// impl Reflect for Player {
+///     fn field_names(&self) -> &'static [&'static str] { &["name", "hp"] }
+///     fn field(&self, name: &str) -> Option<&dyn Any> {
+///         match name { "name" => Some(&self.name), "hp" => Some(&self.hp), _ => None }
+///     }
+///     fn field_mut(&mut self, name: &str) -> Option<&mut dyn Any> {
+///         match name { "name" => Some(&mut self.name), "hp" => Some(&mut self.hp), _ => None }
+///     }
+/// }
+///
+/// let mut player = Player { name: "ann".into(), hp: 10 };
+/// let value: &mut dyn Reflect = &mut player;
+/// assert_eq!(value.get_field::<u32>("hp"), Some(&10));
+/// assert!(value.set_field("hp", 7u32).is_ok());
+/// assert_eq!(value.set_field("hp", 7i64), Err(7));
+/// assert_eq!(player.hp, 7);
+/// ```
+pub trait Reflect: AsAny {
+    /// Returns the names of the fields, in declaration order.
+    fn field_names(&self) -> &'static [&'static str];
+    fn field(&self, name: &str) -> Option<&dyn Any>;
+    fn field_mut(&mut self, name: &str) -> Option<&mut dyn Any>;
+}
+
+/// Typed access to the fields of a `Reflect` value, implemented for every one of them,
+/// trait objects included.
+pub trait ReflectExt: Reflect {
+    /// Returns the field `name` if it is of type `T`.
+    fn get_field<T: Any>(&self, name: &str) -> Option<&T> {
+        self.field(name)?.downcast_ref()
+    }
+
+    /// Returns the field `name` mutably if it is of type `T`.
+    fn get_field_mut<T: Any>(&mut self, name: &str) -> Option<&mut T> {
+        self.field_mut(name)?.downcast_mut()
+    }
+
+    /// Sets the field `name` to `value`, or returns `value` if there is no such field of
+    /// type `T`.
+    fn set_field<T: Any>(&mut self, name: &str, value: T) -> Result<(), T> {
+        match self.get_field_mut(name) {
+            Some(field) => {
+                *field = value;
+                Ok(())
+            }
+            None => Err(value),
+        }
+    }
+
+    /// Returns the fields with their names, in declaration order.
+    fn fields(&self) -> impl Iterator<Item = (&'static str, &dyn Any)> {
+        self.field_names().iter().filter_map(move |&name| Some((name, self.field(name)?)))
+    }
+}
+
+impl<R: Reflect + ?Sized> ReflectExt for R {}
+
+#[cfg(test)]
+mod test {
+    use std::any::{Any, TypeId};
+
+    use super::{Reflect, ReflectExt};
+    use crate::BoxAny;
+
+    trait Component: BoxAny + Reflect {}
+    crate::impl_downcast!(Component);
+
+    struct Pair(u8, &'static str);
+    impl Reflect for Pair {
+        fn field_names(&self) -> &'static [&'static str] {
+            &["0", "1"]
+        }
+        fn field(&self, name: &str) -> Option<&dyn Any> {
+            match name {
+                "0" => Some(&self.0),
+                "1" => Some(&self.1),
+                _ => None,
+            }
+        }
+        fn field_mut(&mut self, name: &str) -> Option<&mut dyn Any> {
+            match name {
+                "0" => Some(&mut self.0),
+                "1" => Some(&mut self.1),
+                _ => None,
+            }
+        }
+    }
+    impl Component for Pair {}
+
+    #[test]
+    fn test() {
+        let mut component: Box<dyn Component> = Box::new(Pair(1, "one"));
+        assert_eq!(component.get_field::<u8>("0"), Some(&1));
+        assert_eq!(component.get_field::<u16>("0"), None);
+        assert_eq!(component.get_field::<u8>("2"), None);
+        *component.get_field_mut::<u8>("0").unwrap() += 1;
+        assert_eq!(component.set_field("1", "two"), Ok(()));
+        assert_eq!(component.set_field("3", 3), Err(3));
+
+        let fields: Vec<_> = component.fields().map(|(name, value)| (name, value.type_id())).collect();
+        assert_eq!(fields, [("0", TypeId::of::<u8>()), ("1", TypeId::of::<&str>())]);
+        let pair = component.downcast_ref::<Pair>().unwrap();
+        assert_eq!((pair.0, pair.1), (2, "two"));
+    }
+}
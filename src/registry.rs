@@ -0,0 +1,274 @@
+//! `TypeRegistry`, constructing trait objects from registered type names.
+
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::codec::Codec;
+#[cfg(feature = "serde")]
+use crate::tagged::SerdeFns;
+use crate::{AsAny, Bounded, Bounds, SendSyncBounds, TypeDescriptor};
+
+pub(crate) struct Entry<B: ?Sized, S: Bounds> {
+    pub(crate) type_id: TypeId,
+    factory: Box<S::Factory<B>>,
+    pub(crate) codec: Option<Codec<B>>,
+    pub(crate) descriptor: Option<TypeDescriptor>,
+    #[cfg(feature = "serde")]
+    pub(crate) serde: Option<SerdeFns<B>>,
+}
+
+/// Maps names to types and to factories producing `Box<B>`, typically `Box<dyn Trait>`, so
+/// values can be created from names found in data such as config files.
+///
+/// ```
+/// use pi_any::{BoxAny, TypeRegistry};
+///
+/// trait Component: BoxAny {}
+/// #[derive(Default)]
+/// struct Health(u32);
+/// impl Component for Health {}
+///
+/// let mut registry = TypeRegistry::<dyn Component>::new();
+/// registry.register::<Health>("Health", || Box::new(Health(100)));
+///
+/// let component = registry.construct("Health").unwrap();
+/// assert!(component.as_any().is::<Health>());
+/// assert!(registry.construct("Mana").is_none());
+/// ```
+///
+/// The factories must be `Send + Sync`, and so is the registry, e.g. to be kept in a
+/// `static`, unless it is made by `with_bounds` with other `Bounds`.
+pub struct TypeRegistry<B: ?Sized, S: Bounds = SendSyncBounds> {
+    pub(crate) entries: HashMap<String, Entry<B, S>>,
+    pub(crate) names: HashMap<TypeId, String>,
+}
+
+impl<B: AsAny + ?Sized> TypeRegistry<B> {
+    pub fn new() -> Self {
+        TypeRegistry::with_bounds(SendSyncBounds)
+    }
+}
+
+impl<B: AsAny + ?Sized, S: Bounds> TypeRegistry<B, S> {
+    /// Creates a registry taking the factories meeting `bounds`.
+    ///
+    /// ```
+    /// use std::rc::Rc;
+    /// use pi_any::{AnyBounds, BoxAny, TypeRegistry};
+    ///
+    /// struct Sprite(Rc<str>);
+    ///
+    /// let atlas: Rc<str> = Rc::from("atlas.png");
+    /// let mut registry = TypeRegistry::<dyn BoxAny, _>::with_bounds(AnyBounds);
+    /// registry.register::<Sprite>("Sprite", move || Box::new(Sprite(atlas.clone())));
+    /// let sprite = registry.construct("Sprite").unwrap();
+    /// assert_eq!(&*sprite.as_any().downcast_ref::<Sprite>().unwrap().0, "atlas.png");
+    /// ```
+    pub fn with_bounds(_bounds: S) -> Self {
+        TypeRegistry { entries: HashMap::new(), names: HashMap::new() }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Registers `T` under `name`, with `factory` producing its values. Returns the type
+    /// previously registered under `name`, whose entry, including its codec, serde
+    /// functions and descriptor, is replaced.
+    ///
+    /// A type registered under several names is named by the last of them that is still
+    /// registered.
+    ///
+    /// `factory` must produce values of type `T`, which `construct` asserts in debug builds.
+    pub fn register<T: Any>(
+        &mut self,
+        name: impl Into<String>,
+        factory: impl Fn() -> Box<B> + Bounded<S>,
+    ) -> Option<TypeId> {
+        let name = name.into();
+        let type_id = TypeId::of::<T>();
+        let entry = Entry {
+            type_id,
+            factory: factory.__into_factory(),
+            codec: None,
+            descriptor: None,
+            #[cfg(feature = "serde")]
+            serde: None,
+        };
+        let old = self.entries.insert(name.clone(), entry);
+        if let Some(old) = &old {
+            if old.type_id != type_id && self.names.get(&old.type_id) == Some(&name) {
+                // Falls back to another name of the replaced type, if it has one.
+                let other = self.entries.iter().find(|(_, entry)| entry.type_id == old.type_id);
+                match other.map(|(name, _)| name.clone()) {
+                    Some(other) => self.names.insert(old.type_id, other),
+                    None => self.names.remove(&old.type_id),
+                };
+            }
+        }
+        self.names.insert(type_id, name);
+        old.map(|old| old.type_id)
+    }
+
+    /// Creates a value of the type registered under `name`.
+    pub fn construct(&self, name: &str) -> Option<Box<B>> {
+        let entry = self.entries.get(name)?;
+        let value = (entry.factory)();
+        debug_assert!(
+            (*value).type_id_of() == entry.type_id,
+            "the factory of `{}` produced a `{}`",
+            name,
+            (*value).type_name()
+        );
+        Some(value)
+    }
+
+    /// Returns true if `value` is of the type registered under `name`.
+    pub fn is_named(&self, value: &B, name: &str) -> bool {
+        self.type_id(name) == Some(value.type_id_of())
+    }
+
+    /// Returns the name the type of `value` is registered under.
+    pub fn name_of_val(&self, value: &B) -> Option<&str> {
+        self.name_of_id(value.type_id_of())
+    }
+
+    /// Returns `value` as a `T` if `name` is registered as `T` and `value` is of type `T`.
+    pub fn downcast_ref_by_name<'v, T: Any>(&self, value: &'v B, name: &str) -> Option<&'v T> {
+        if self.type_id(name) == Some(TypeId::of::<T>()) {
+            value.as_any().downcast_ref()
+        } else {
+            None
+        }
+    }
+}
+
+// Lookups don't need `B: AsAny`, so the downcast methods can take any registry.
+impl<B: ?Sized, S: Bounds> TypeRegistry<B, S> {
+    pub fn contains(&self, name: &str) -> bool {
+        self.entries.contains_key(name)
+    }
+
+    /// Returns the `TypeId` of the type registered under `name`.
+    pub fn type_id(&self, name: &str) -> Option<TypeId> {
+        self.entries.get(name).map(|entry| entry.type_id)
+    }
+
+    /// Returns the name `T` is registered under.
+    pub fn name_of<T: Any>(&self) -> Option<&str> {
+        self.name_of_id(TypeId::of::<T>())
+    }
+
+    /// Returns the name the type with `type_id` is registered under.
+    pub fn name_of_id(&self, type_id: TypeId) -> Option<&str> {
+        self.names.get(&type_id).map(String::as_str)
+    }
+
+    /// Returns the registered names, in no particular order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.entries.keys().map(String::as_str)
+    }
+}
+
+impl<B: AsAny + ?Sized, S: Bounds + Default> Default for TypeRegistry<B, S> {
+    fn default() -> Self {
+        Self::with_bounds(S::default())
+    }
+}
+
+impl<B: ?Sized, S: Bounds> fmt::Debug for TypeRegistry<B, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.entries.keys()).finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::any::TypeId;
+
+    use super::TypeRegistry;
+    use crate::BoxAny;
+
+    trait Base: BoxAny {}
+    crate::impl_downcast_box!(Base);
+
+    #[derive(Debug)]
+    struct Foo(u32);
+    impl Base for Foo {}
+    #[derive(Debug)]
+    struct Bar;
+    impl Base for Bar {}
+    impl Base for () {}
+
+    #[test]
+    fn test() {
+        let mut registry = TypeRegistry::<dyn Base>::new();
+        assert_eq!(registry.register::<Foo>("foo", || Box::new(Foo(1))), None);
+        assert_eq!(registry.register::<Bar>("bar", || Box::new(Bar)), None);
+        assert_eq!(registry.len(), 2);
+        assert!(registry.contains("foo"));
+        assert_eq!(registry.type_id("bar"), Some(TypeId::of::<Bar>()));
+        assert_eq!(registry.name_of::<Foo>(), Some("foo"));
+        let mut names: Vec<_> = registry.names().collect();
+        names.sort();
+        assert_eq!(names, ["bar", "foo"]);
+
+        assert_eq!(registry.construct("foo").unwrap().downcast_ref::<Foo>().unwrap().0, 1);
+        assert!(registry.construct("bar").unwrap().is::<Bar>());
+        assert!(registry.construct("baz").is_none());
+
+        // Re-registering a name replaces the entry and the reverse mapping.
+        assert_eq!(registry.register::<Foo>("bar", || Box::new(Foo(2))), Some(TypeId::of::<Bar>()));
+        assert_eq!(registry.name_of::<Bar>(), None);
+        assert_eq!(registry.name_of::<Foo>(), Some("bar"));
+    }
+
+    #[test]
+    fn test_aliases() {
+        let mut registry = TypeRegistry::<dyn Base>::new();
+        registry.register::<Foo>("foo", || Box::new(Foo(1)));
+        registry.register::<Foo>("Foo", || Box::new(Foo(1)));
+        assert_eq!(registry.name_of::<Foo>(), Some("Foo"));
+
+        // Replacing one name of `Foo` leaves it named by the other.
+        registry.register::<Bar>("Foo", || Box::new(Bar));
+        assert_eq!(registry.name_of::<Foo>(), Some("foo"));
+        assert_eq!(registry.name_of::<Bar>(), Some("Foo"));
+        registry.register::<Bar>("foo", || Box::new(Bar));
+        assert_eq!(registry.name_of::<Foo>(), None);
+        assert_eq!(registry.name_of::<Bar>(), Some("foo"));
+        assert_eq!(registry.len(), 2);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "the factory of `foo` produced a")]
+    fn test_wrong_factory() {
+        let mut registry = TypeRegistry::<dyn Base>::new();
+        registry.register::<Foo>("foo", || Box::new(Bar));
+        registry.construct("foo");
+    }
+
+    #[test]
+    fn test_by_name() {
+        let mut registry = TypeRegistry::<dyn Base>::new();
+        registry.register::<Foo>("foo", || Box::new(Foo(1)));
+        registry.register::<Bar>("bar", || Box::new(Bar));
+
+        let base: Box<dyn Base> = Box::new(Foo(3));
+        assert!(registry.is_named(&*base, "foo"));
+        assert!(!registry.is_named(&*base, "bar"));
+        assert!(!registry.is_named(&*base, "baz"));
+        assert_eq!(registry.name_of_val(&*base), Some("foo"));
+        assert_eq!(registry.downcast_ref_by_name::<Foo>(&*base, "foo").unwrap().0, 3);
+        assert!(registry.downcast_ref_by_name::<Foo>(&*base, "bar").is_none());
+
+        let unnamed: Box<dyn Base> = Box::new(());
+        assert_eq!(registry.name_of_val(&*unnamed), None);
+    }
+}
@@ -0,0 +1,159 @@
+//! `impl_downcast_abi_stable!`, for the trait objects of `abi_stable`, which plugins built
+//! as separate dynamic libraries can pass across the stable ABI.
+//!
+//! The `Trait_TO` objects generated by `#[sabi_trait]` have their own vtables, and `TypeId`s
+//! may differ between libraries, so these downcasts don't go through `AsAny`: they check
+//! the type as `abi_stable` does, which fails for objects made by another library.
+
+/// Generates `is`, `downcast_ref`, `downcast_mut` and `downcast` for the trait object
+/// `Trait_TO` that `#[sabi_trait]` generates, like `impl_downcast!` does for `dyn Trait`.
+/// Type parameters of the trait are given after the name.
+///
+/// The object must have been made with `TD_CanDowncast`, and `downcast` gives the pointer
+/// it was made from, e.g. an `RBox<T>`:
+///
+/// ```
+/// # #[macro_use]
+/// # extern crate pi_any;
+/// # extern crate abi_stable;
+/// use abi_stable::sabi_trait;
+/// use abi_stable::sabi_trait::TD_CanDowncast;
+/// use abi_stable::std_types::RBox;
+///
+/// #[sabi_trait]
+/// pub trait Plugin {
+///     fn name(&self) -> u32;
+/// }
+/// impl_downcast_abi_stable!(Plugin_TO);
+///
+/// struct Audio(u32);
+/// impl Plugin for Audio {
+///     fn name(&self) -> u32 { self.0 }
+/// }
+///
+/// # fn main() {
+/// let plugin: Plugin_TO<'static, RBox<()>> = Plugin_TO::from_value(Audio(7), TD_CanDowncast);
+/// assert!(plugin.is::<Audio>());
+/// assert_eq!(plugin.downcast::<Audio>().ok().unwrap().0, 7);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! impl_downcast_abi_stable {
+    ($($to:ident $(<$($param:ident),* $(,)?>)?);+ $(;)?) => {
+        $( $crate::impl_downcast_abi_stable! { @impl $to [$($($param)*)?] } )+
+    };
+
+    (@impl $to:ident [$($param:ident)*]) => {
+        #[allow(dead_code)]
+        impl<'__lt, __P $(, $param)*> $to<'__lt, __P $(, $param)*>
+        where
+            __P: $crate::__abi_stable::pointer_trait::AsPtr<PtrTarget = ()>,
+        {
+            /// Returns true if the trait object wraps an object of type `__T` made by this
+            /// library.
+            #[inline]
+            pub fn is<__T: 'static>(&self) -> bool
+            where
+                __P: $crate::__abi_stable::pointer_trait::CanTransmuteElement<__T>,
+            {
+                self.obj.downcast_as::<__T>().is_ok()
+            }
+            /// Returns a reference to the object within the trait object if it is of type
+            /// `__T` and was made by this library, or `None` if it isn't.
+            #[inline]
+            pub fn downcast_ref<__T: 'static>(&self) -> $crate::__core::option::Option<&__T>
+            where
+                __P: $crate::__abi_stable::pointer_trait::CanTransmuteElement<__T>,
+            {
+                self.obj.downcast_as::<__T>().ok()
+            }
+            /// Returns a mutable reference to the object within the trait object if it is of
+            /// type `__T` and was made by this library, or `None` if it isn't.
+            #[inline]
+            pub fn downcast_mut<__T: 'static>(&mut self) -> $crate::__core::option::Option<&mut __T>
+            where
+                __P: $crate::__abi_stable::pointer_trait::AsMutPtr<PtrTarget = ()>
+                    + $crate::__abi_stable::pointer_trait::CanTransmuteElement<__T>,
+            {
+                self.obj.downcast_as_mut::<__T>().ok()
+            }
+            /// Converts the trait object into the pointer it was made from if it wraps an
+            /// object of type `__T` made by this library, or returns the trait object if it
+            /// doesn't.
+            #[inline]
+            pub fn downcast<__T: 'static>(
+                self,
+            ) -> $crate::__core::result::Result<
+                <__P as $crate::__abi_stable::pointer_trait::CanTransmuteElement<__T>>::TransmutedPtr,
+                Self,
+            >
+            where
+                __P: $crate::__abi_stable::pointer_trait::CanTransmuteElement<__T>,
+            {
+                self.obj.downcast_into::<__T>().map_err(|e| Self::from_sabi(e.into_inner()))
+            }
+        }
+    };
+}
+
+// `#[sabi_trait]` expands to unsafe impls, and to impls inside a `const _`.
+#[cfg(test)]
+#[allow(unsafe_code, non_local_definitions)]
+mod test {
+    use abi_stable::sabi_trait;
+    use abi_stable::sabi_trait::{TD_CanDowncast, TD_Opaque};
+    use abi_stable::std_types::{RArc, RBox};
+
+    #[sabi_trait]
+    pub trait Plugin {
+        fn id(&self) -> u32;
+    }
+    crate::impl_downcast_abi_stable!(Plugin_TO);
+
+    // Send and Sync make `#[sabi_trait]` use `DynTrait` rather than `RObject`.
+    #[sabi_trait]
+    pub trait Source<T>: Send + Sync {
+        fn get(&self) -> T;
+    }
+    crate::impl_downcast_abi_stable!(Source_TO<T>);
+
+    #[derive(Debug, PartialEq)]
+    struct Audio(u32);
+    impl Plugin for Audio {
+        fn id(&self) -> u32 {
+            self.0
+        }
+    }
+    impl Source<u8> for Audio {
+        fn get(&self) -> u8 {
+            self.0 as u8
+        }
+    }
+    #[derive(Debug)]
+    struct Video;
+    impl Plugin for Video {
+        fn id(&self) -> u32 {
+            0
+        }
+    }
+
+    #[test]
+    fn test() {
+        let mut plugin: Plugin_TO<'static, RBox<()>> =
+            Plugin_TO::from_value(Audio(1), TD_CanDowncast);
+        assert!(plugin.is::<Audio>() && !plugin.is::<Video>());
+        plugin.downcast_mut::<Audio>().unwrap().0 = 2;
+        assert_eq!(plugin.id(), 2);
+        let plugin = plugin.downcast::<Video>().unwrap_err();
+        assert_eq!(plugin.downcast::<Audio>().ok(), Some(RBox::new(Audio(2))));
+
+        let plugin: Plugin_TO<'static, RBox<()>> = Plugin_TO::from_value(Audio(1), TD_Opaque);
+        assert!(plugin.downcast_ref::<Audio>().is_none());
+
+        let source: Source_TO<'static, RArc<()>, u8> =
+            Source_TO::from_ptr(RArc::new(Audio(3)), TD_CanDowncast);
+        assert_eq!(source.downcast_ref::<Audio>(), Some(&Audio(3)));
+        assert_eq!(source.get(), 3);
+        assert_eq!(source.downcast::<Audio>().ok().unwrap().0, 3);
+    }
+}
@@ -0,0 +1,142 @@
+//! `ScopedAnyMap`, a typemap whose lookups fall through to the scopes it is nested in.
+
+use std::any::Any;
+use std::fmt;
+
+use crate::AnyMap;
+
+/// An `AnyMap` with a parent scope, e.g. the context of a widget or a script frame: values
+/// not found in the scope are looked up in its parent, and so on up to the root.
+///
+/// A value inserted in a scope shadows those of its type in the parents, which the scope
+/// only borrows, so dropping it restores them as they were.
+///
+/// ```
+/// use pi_any::ScopedAnyMap;
+///
+/// struct Theme(&'static str);
+/// struct Locale(&'static str);
+///
+/// let mut root = ScopedAnyMap::new();
+/// root.insert(Theme("light"));
+/// root.insert(Locale("en"));
+/// {
+///     let mut dialog = root.scope();
+///     dialog.insert(Theme("dark"));
+///     assert_eq!(dialog.get::<Theme>().unwrap().0, "dark");
+///     assert_eq!(dialog.get::<Locale>().unwrap().0, "en");
+/// }
+/// assert_eq!(root.get::<Theme>().unwrap().0, "light");
+/// ```
+#[derive(Default)]
+pub struct ScopedAnyMap<'a> {
+    local: AnyMap,
+    parent: Option<&'a ScopedAnyMap<'a>>,
+}
+
+impl<'a> ScopedAnyMap<'a> {
+    /// Creates a root scope.
+    pub fn new() -> Self {
+        ScopedAnyMap { local: AnyMap::new(), parent: None }
+    }
+
+    /// Creates an empty scope nested in this one.
+    pub fn scope(&self) -> ScopedAnyMap<'_> {
+        ScopedAnyMap { local: AnyMap::new(), parent: Some(self) }
+    }
+
+    pub fn parent(&self) -> Option<&'a ScopedAnyMap<'a>> {
+        self.parent
+    }
+
+    /// Returns the number of scopes this one is nested in, 0 for a root.
+    pub fn depth(&self) -> usize {
+        self.parent.map_or(0, |parent| parent.depth() + 1)
+    }
+
+    /// Returns true if this scope or a parent holds a value of type `T`.
+    pub fn contains<T: Any>(&self) -> bool {
+        self.get::<T>().is_some()
+    }
+
+    /// Returns the value of type `T` of the innermost scope holding one.
+    pub fn get<T: Any>(&self) -> Option<&T> {
+        self.local.get().or_else(|| self.parent?.get())
+    }
+
+    /// Returns the value of type `T` of this scope, ignoring the parents.
+    pub fn get_local<T: Any>(&self) -> Option<&T> {
+        self.local.get()
+    }
+
+    /// Returns the value of type `T` of this scope mutably; those of the parents are only
+    /// borrowed shared.
+    pub fn get_local_mut<T: Any>(&mut self) -> Option<&mut T> {
+        self.local.get_mut()
+    }
+
+    /// Inserts `value` in this scope, shadowing any of type `T` in the parents, and returns
+    /// the previous value of type `T` of this scope.
+    pub fn insert<T: Any>(&mut self, value: T) -> Option<T> {
+        self.local.insert(value)
+    }
+
+    /// Removes the value of type `T` of this scope, uncovering any in the parents.
+    pub fn remove<T: Any>(&mut self) -> Option<T> {
+        self.local.remove()
+    }
+
+    /// Returns the values of this scope, discarding the link to the parent.
+    pub fn into_local(self) -> AnyMap {
+        self.local
+    }
+}
+
+impl fmt::Debug for ScopedAnyMap<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ScopedAnyMap")
+            .field("local", &self.local)
+            .field("parent", &self.parent)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ScopedAnyMap;
+
+    #[derive(Debug, PartialEq)]
+    struct Foo(u32);
+    #[derive(Debug, PartialEq)]
+    struct Bar;
+
+    #[test]
+    fn test() {
+        let mut root = ScopedAnyMap::new();
+        root.insert(Foo(1));
+        assert_eq!(root.depth(), 0);
+        {
+            let mut child = root.scope();
+            assert_eq!(child.get::<Foo>(), Some(&Foo(1)));
+            assert_eq!(child.get_local::<Foo>(), None);
+            assert_eq!(child.get_local_mut::<Foo>(), None);
+            child.insert(Bar);
+            assert_eq!(child.insert(Foo(2)), None);
+            {
+                let grandchild = child.scope();
+                assert_eq!(grandchild.depth(), 2);
+                assert_eq!(grandchild.get::<Foo>(), Some(&Foo(2)));
+                assert!(grandchild.contains::<Bar>());
+                assert_eq!(grandchild.parent().unwrap().parent().unwrap().depth(), 0);
+            }
+            child.get_local_mut::<Foo>().unwrap().0 = 3;
+            assert_eq!(child.get::<Foo>(), Some(&Foo(3)));
+            assert_eq!(child.remove::<Foo>(), Some(Foo(3)));
+            assert_eq!(child.get::<Foo>(), Some(&Foo(1)));
+            assert_eq!(child.into_local().len(), 1);
+        }
+        assert!(!root.contains::<Bar>());
+        assert_eq!(root.get::<Foo>(), Some(&Foo(1)));
+        assert!(format!("{:?}", root).ends_with("parent: None }"));
+    }
+}
@@ -0,0 +1,265 @@
+//! `Services`, a service locator handing out shared `Arc`s by trait or concrete type.
+
+use std::any::{self, Any, TypeId};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::lock::RwLock;
+use crate::ArcAny;
+
+/// An `Arc<S>`, erased.
+type Erased = Box<dyn Any + Send + Sync>;
+type Provider = Arc<dyn Fn(&Services) -> Erased + Send + Sync>;
+
+enum Slot {
+    Ready(Erased),
+    Lazy(Provider),
+}
+
+thread_local! {
+    /// The lazy services being provided on this thread, by container and key.
+    static RESOLVING: RefCell<Vec<(*const Services, TypeId)>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Marks a lazy service as being provided until dropped, also on unwind.
+struct Resolving;
+
+impl Resolving {
+    /// Panics if the service is already being provided on this thread, as its provider then
+    /// depends on itself and would recurse forever.
+    fn enter(services: &Services, key: TypeId, name: &str) -> Resolving {
+        let entry = (services as *const Services, key);
+        let cyclic = RESOLVING.with(|resolving| {
+            let mut resolving = resolving.borrow_mut();
+            let cyclic = resolving.contains(&entry);
+            if !cyclic {
+                resolving.push(entry);
+            }
+            cyclic
+        });
+        if cyclic {
+            panic!("cyclic dependency: the provider of `{}` resolves it again", name);
+        }
+        Resolving
+    }
+}
+
+impl Drop for Resolving {
+    fn drop(&mut self) {
+        RESOLVING.with(|resolving| resolving.borrow_mut().pop());
+    }
+}
+
+/// A container of shared services, keyed by the service type `S`, which is usually a trait
+/// object such as `dyn Logger` but may also be a concrete type.
+///
+/// Services are provided either ready-made or as a provider that runs on first use, and a
+/// `child` container resolves what it doesn't provide itself from its parent.
+///
+/// ```
+/// use std::sync::Arc;
+/// use pi_any::{ArcAny, Services};
+///
+/// trait Logger: ArcAny {
+///     fn log(&self, msg: &str) -> String;
+/// }
+/// struct Console;
+/// impl Logger for Console {
+///     fn log(&self, msg: &str) -> String { format!("console: {}", msg) }
+/// }
+///
+/// let mut services = Services::new();
+/// services.provide::<dyn Logger>(Arc::new(Console));
+///
+/// let logger = services.resolve::<dyn Logger>().unwrap();
+/// assert_eq!(logger.log("hi"), "console: hi");
+/// assert!(services.resolve_downcast::<dyn Logger, Console>().is_some());
+/// ```
+#[derive(Default)]
+pub struct Services {
+    parent: Option<Arc<Services>>,
+    slots: RwLock<HashMap<TypeId, Slot>>,
+}
+
+impl Services {
+    pub fn new() -> Self {
+        Services { parent: None, slots: RwLock::new(HashMap::new()) }
+    }
+
+    /// Creates an empty container that falls back to `parent` for services it doesn't
+    /// provide itself.
+    pub fn child(parent: &Arc<Services>) -> Self {
+        Services { parent: Some(parent.clone()), slots: RwLock::new(HashMap::new()) }
+    }
+
+    /// Provides `service` as `S`, replacing any earlier provider of `S` in this container.
+    pub fn provide<S: ?Sized + Send + Sync + 'static>(&mut self, service: Arc<S>) {
+        self.slots.write().insert(TypeId::of::<Arc<S>>(), Slot::Ready(Box::new(service)));
+    }
+
+    /// Provides `S` through `provider`, which is called with this container the first time
+    /// `S` is resolved; its result is kept for later calls.
+    ///
+    /// Resolving `S` panics if `provider` resolves `S` itself, directly or through other
+    /// lazy services.
+    pub fn provide_with<S, F>(&mut self, provider: F)
+    where
+        S: ?Sized + Send + Sync + 'static,
+        F: Fn(&Services) -> Arc<S> + Send + Sync + 'static,
+    {
+        let provider: Provider = Arc::new(move |services| Box::new(provider(services)));
+        self.slots.write().insert(TypeId::of::<Arc<S>>(), Slot::Lazy(provider));
+    }
+
+    /// Returns true if this container or one of its parents provides `S`.
+    pub fn contains<S: ?Sized + 'static>(&self) -> bool {
+        let key = TypeId::of::<Arc<S>>();
+        self.slots.read().contains_key(&key)
+            || self.parent.as_ref().is_some_and(|p| p.contains::<S>())
+    }
+
+    /// Returns the service provided as `S` by this container or, failing that, its parents.
+    pub fn resolve<S: ?Sized + Send + Sync + 'static>(&self) -> Option<Arc<S>> {
+        let key = TypeId::of::<Arc<S>>();
+        let provider = match self.slots.read().get(&key) {
+            Some(Slot::Ready(service)) => return service.downcast_ref::<Arc<S>>().cloned(),
+            Some(Slot::Lazy(provider)) => provider.clone(),
+            None => return self.parent.as_ref()?.resolve::<S>(),
+        };
+        // The provider may resolve other services, so no lock is held while it runs. If
+        // another thread finished first, its service wins.
+        let service = {
+            let _resolving = Resolving::enter(self, key, any::type_name::<S>());
+            provider(self)
+        };
+        let mut slots = self.slots.write();
+        let slot = slots.entry(key).or_insert(Slot::Lazy(provider));
+        if let Slot::Lazy(_) = slot {
+            *slot = Slot::Ready(service);
+        }
+        match slot {
+            Slot::Ready(service) => service.downcast_ref::<Arc<S>>().cloned(),
+            Slot::Lazy(_) => unreachable!(),
+        }
+    }
+
+    /// Resolves the service provided as `S` and downcasts it to its concrete type `T`.
+    pub fn resolve_downcast<S, T>(&self) -> Option<Arc<T>>
+    where
+        S: ?Sized + ArcAny,
+        T: Any + Send + Sync,
+    {
+        ArcAny::into_any(self.resolve::<S>()?).downcast::<T>().ok()
+    }
+}
+
+impl fmt::Debug for Services {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Services")
+            .field("len", &self.slots.read().len())
+            .field("parent", &self.parent)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use super::Services;
+    use crate::ArcAny;
+
+    trait Logger: ArcAny {
+        fn name(&self) -> String;
+    }
+    crate::impl_downcast_arc!(Logger);
+
+    struct Console;
+    impl Logger for Console {
+        fn name(&self) -> String {
+            "console".to_string()
+        }
+    }
+    struct Prefixed(Arc<dyn Logger>);
+    impl Logger for Prefixed {
+        fn name(&self) -> String {
+            format!("prefixed {}", self.0.name())
+        }
+    }
+
+    struct Config(u32);
+
+    #[test]
+    fn test_resolve() {
+        let mut services = Services::new();
+        assert!(services.resolve::<dyn Logger>().is_none());
+        services.provide::<dyn Logger>(Arc::new(Console));
+        services.provide(Arc::new(Config(3)));
+
+        assert!(services.contains::<dyn Logger>());
+        assert_eq!(services.resolve::<dyn Logger>().unwrap().name(), "console");
+        assert_eq!(services.resolve::<Config>().unwrap().0, 3);
+        assert!(services.resolve_downcast::<dyn Logger, Console>().is_some());
+        assert!(services.resolve_downcast::<dyn Logger, Prefixed>().is_none());
+    }
+
+    #[test]
+    fn test_lazy() {
+        static CALLS: AtomicUsize = AtomicUsize::new(0);
+        let mut services = Services::new();
+        services.provide::<dyn Logger>(Arc::new(Console));
+        services.provide_with::<Config, _>(|services| {
+            CALLS.fetch_add(1, Ordering::SeqCst);
+            Arc::new(Config(services.resolve::<dyn Logger>().unwrap().name().len() as u32))
+        });
+        assert_eq!(CALLS.load(Ordering::SeqCst), 0);
+        let first = services.resolve::<Config>().unwrap();
+        let second = services.resolve::<Config>().unwrap();
+        assert_eq!(first.0, 7);
+        assert!(Arc::ptr_eq(&first, &second));
+        assert_eq!(CALLS.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_child() {
+        let mut root = Services::new();
+        root.provide::<dyn Logger>(Arc::new(Console));
+        root.provide(Arc::new(Config(1)));
+        let root = Arc::new(root);
+
+        let mut child = Services::child(&root);
+        child.provide_with::<dyn Logger, _>(|_| Arc::new(Prefixed(Arc::new(Console))));
+        assert_eq!(child.resolve::<dyn Logger>().unwrap().name(), "prefixed console");
+        assert_eq!(child.resolve::<Config>().unwrap().0, 1);
+        assert_eq!(root.resolve::<dyn Logger>().unwrap().name(), "console");
+        assert!(!root.contains::<u8>() && !child.contains::<u8>());
+    }
+
+    #[test]
+    #[should_panic(expected = "cyclic dependency: the provider of `dyn pi_any::services")]
+    fn test_cycle() {
+        let mut services = Services::new();
+        services.provide_with::<dyn Logger, _>(|services| {
+            services.resolve::<Config>();
+            Arc::new(Console)
+        });
+        services.provide_with::<Config, _>(|services| {
+            Arc::new(Config(services.resolve::<dyn Logger>().unwrap().name().len() as u32))
+        });
+        services.resolve::<dyn Logger>();
+    }
+
+    #[test]
+    fn test_cycle_unwind() {
+        let mut services = Services::new();
+        services.provide_with::<Config, _>(|services| services.resolve::<Config>().unwrap());
+        let mut services = std::panic::AssertUnwindSafe(services);
+        assert!(std::panic::catch_unwind(|| services.resolve::<Config>()).is_err());
+        // The service being provided was unmarked on unwind.
+        services.provide_with::<Config, _>(|_| Arc::new(Config(2)));
+        assert_eq!(services.resolve::<Config>().unwrap().0, 2);
+    }
+}
@@ -0,0 +1,101 @@
+//! `ShareAny` and `impl_downcast_share!`, for the `Share` pointer of `pi_share`: an `Arc`, or
+//! its own `Xrc` with its `rc` feature, as the engine builds for `wasm32`.
+//!
+//! The downcasts name `pi_share::Share` itself, so they follow whichever pointer the build
+//! of `pi_share` picks, and downstream code needs no cfg'd impls. `pi_share` needs a nightly
+//! compiler, and so does this feature.
+//!
+//! `Xrc` can't be `self` on stable Rust, so the downcasts are associated functions, called
+//! as `<dyn Trait>::downcast_share(share)`, whichever the pointer is.
+
+use core::any::{Any, TypeId};
+
+use pi_share::{Share, ThreadSync};
+
+use crate::owned::concrete_type_id;
+use crate::AsAny;
+
+/// The trait to extend for downcasting `Share<dyn Trait>`. Like `Share` itself, it requires
+/// `Send + Sync` unless `pi_share` is built with its `serial` feature.
+///
+/// ```
+/// # #[macro_use]
+/// # extern crate pi_any;
+/// # extern crate pi_share;
+/// use pi_any::ShareAny;
+/// use pi_share::Share;
+///
+/// trait Asset: ShareAny {}
+/// impl_downcast_share!(Asset);
+///
+/// struct Texture(u32);
+/// impl Asset for Texture {}
+///
+/// # fn main() {
+/// let asset: Share<dyn Asset> = Share::new(Texture(64));
+/// assert!(asset.is::<Texture>());
+/// assert_eq!(<dyn Asset>::downcast_share::<Texture>(asset).ok().unwrap().0, 64);
+/// # }
+/// ```
+pub trait ShareAny: AsAny + ThreadSync + 'static {}
+
+impl<T: AsAny + ThreadSync + 'static> ShareAny for T {}
+
+/// Generates `is`, `downcast_ref`, and the associated functions `downcast_share`,
+/// `downcast_share_expect` and `try_downcast_share` for `Share<dyn Trait>`. The trait must
+/// extend `ShareAny`.
+#[macro_export(local_inner_macros)]
+macro_rules! impl_downcast_share {
+    ($($input:tt)+) => { impl_downcast! { @split [ref share] [] $($input)+ } };
+}
+
+#[doc(hidden)]
+#[inline]
+pub fn __downcast_share<T: Any, S: AsAny + ?Sized>(value: Share<S>) -> Result<Share<T>, Share<S>> {
+    if concrete_type_id(&*value) == Some(TypeId::of::<T>()) {
+        // SAFETY: as in `__downcast_rc`; `Xrc::from_raw` finds the counters from the
+        // alignment of `T` as `Arc::from_raw` does.
+        Ok(unsafe { Share::from_raw(Share::into_raw(value).cast::<T>()) })
+    } else {
+        Err(value)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pi_share::Share;
+
+    use super::ShareAny;
+
+    trait Node<T>: ShareAny {}
+    crate::impl_downcast_share!(Node<T>);
+
+    #[derive(Debug)]
+    struct Leaf(u32);
+    impl Node<u8> for Leaf {}
+    #[derive(Debug)]
+    struct Branch;
+    impl Node<u8> for Branch {}
+
+    #[test]
+    fn test() {
+        let node: Share<dyn Node<u8>> = Share::new(Leaf(1));
+        let other = node.clone();
+        assert!(node.is::<Leaf>());
+        assert_eq!(node.downcast_ref::<Leaf>().unwrap().0, 1);
+        let node = <dyn Node<u8>>::downcast_share::<Branch>(node).unwrap_err();
+        let err = <dyn Node<u8>>::try_downcast_share::<Branch>(node).unwrap_err();
+        assert!(err.to_string().contains("Branch"));
+        let leaf = <dyn Node<u8>>::downcast_share_expect::<Leaf>(err.value, "leaf");
+        assert_eq!(leaf.0, 1);
+        drop(leaf);
+        assert_eq!(Share::strong_count(&other), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "leaf")]
+    fn test_expect() {
+        let node: Share<dyn Node<u8>> = Share::new(Branch);
+        <dyn Node<u8>>::downcast_share_expect::<Leaf>(node, "leaf");
+    }
+}
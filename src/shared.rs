@@ -0,0 +1,221 @@
+//! `SharedAnyMap`, a typemap whose values can be locked one at a time from several threads.
+
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+use crate::lock::{ReadGuard, RwLock, WriteGuard};
+use crate::{ArcAny, BoxAny};
+
+/// A value stored in a `SharedAnyMap`.
+trait Resource: BoxAny + ArcAny {}
+impl<T: BoxAny + ArcAny> Resource for T {}
+crate::impl_downcast_box!(Resource);
+
+/// A map from types to a value of that type, with a lock per value.
+///
+/// The set of values changes only through `&mut self`, so once the map is shared, e.g. in an
+/// `Arc`, `read` and `write` lock just the value they return: systems working on different
+/// types never wait for each other, and readers of the same type run in parallel.
+///
+/// ```
+/// use std::sync::Arc;
+/// use pi_any::SharedAnyMap;
+///
+/// struct Time(f64);
+/// struct Score(u32);
+///
+/// let mut map = SharedAnyMap::new();
+/// map.insert(Time(0.5));
+/// map.insert(Score(0));
+/// let map = Arc::new(map);
+///
+/// let writer = {
+///     let map = map.clone();
+///     std::thread::spawn(move || map.write::<Score>().unwrap().0 += 10)
+/// };
+/// assert_eq!(map.read::<Time>().unwrap().0, 0.5);
+/// writer.join().unwrap();
+/// assert_eq!(map.read::<Score>().unwrap().0, 10);
+/// ```
+#[derive(Default)]
+pub struct SharedAnyMap {
+    values: HashMap<TypeId, RwLock<Box<dyn Resource>>>,
+}
+
+impl SharedAnyMap {
+    pub fn new() -> Self {
+        SharedAnyMap { values: HashMap::new() }
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Returns true if the map holds a value of type `T`.
+    pub fn contains<T: Any>(&self) -> bool {
+        self.values.contains_key(&TypeId::of::<T>())
+    }
+
+    /// Inserts `value`, returning the previous value of type `T` if there was one.
+    pub fn insert<T: Any + Send + Sync>(&mut self, value: T) -> Option<T> {
+        self.values.insert(TypeId::of::<T>(), RwLock::new(Box::new(value))).map(unlock)
+    }
+
+    /// Removes and returns the value of type `T`.
+    pub fn remove<T: Any + Send + Sync>(&mut self) -> Option<T> {
+        self.values.remove(&TypeId::of::<T>()).map(unlock)
+    }
+
+    /// Moves the value of type `T` from `from` to `to`, with its lock, replacing the value
+    /// `to` held, if any. Returns false if `from` holds no value of type `T`.
+    ///
+    /// The value stays at the same address, as `AnyMap::move_entry` keeps it.
+    pub fn move_entry<T: Any + Send + Sync>(from: &mut Self, to: &mut Self) -> bool {
+        match from.values.remove(&TypeId::of::<T>()) {
+            Some(value) => {
+                to.values.insert(TypeId::of::<T>(), value);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns the value of type `T` without locking, which `&mut self` makes unnecessary.
+    pub fn get_mut<T: Any + Send + Sync>(&mut self) -> Option<&mut T> {
+        let value = self.values.get_mut(&TypeId::of::<T>())?;
+        value.get_mut().downcast_mut()
+    }
+
+    /// Locks the value of type `T` for reading, blocking while it is locked for writing.
+    ///
+    /// A lock poisoned by a panicking writer is ignored: the value is still returned.
+    pub fn read<T: Any + Send + Sync>(&self) -> Option<SharedRead<'_, T>> {
+        let value = self.values.get(&TypeId::of::<T>())?;
+        Some(SharedRead { guard: value.read(), _type: PhantomData })
+    }
+
+    /// Locks the value of type `T` for writing, blocking while it is locked.
+    ///
+    /// A lock poisoned by a panicking writer is ignored: the value is still returned.
+    pub fn write<T: Any + Send + Sync>(&self) -> Option<SharedWrite<'_, T>> {
+        let value = self.values.get(&TypeId::of::<T>())?;
+        Some(SharedWrite { guard: value.write(), _type: PhantomData })
+    }
+}
+
+/// Unwraps a value stored under the `TypeId` of `T`.
+fn unlock<T: Any + Send + Sync>(value: RwLock<Box<dyn Resource>>) -> T {
+    *value.into_inner().downcast::<T>().map_err(|_| ()).unwrap()
+}
+
+impl fmt::Debug for SharedAnyMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SharedAnyMap").field("len", &self.len()).finish()
+    }
+}
+
+/// A read lock on a value of type `T` in a `SharedAnyMap`.
+pub struct SharedRead<'a, T> {
+    guard: ReadGuard<'a, Box<dyn Resource>>,
+    _type: PhantomData<&'a T>,
+}
+
+impl<T: Any + Send + Sync> Deref for SharedRead<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.guard.downcast_ref().unwrap()
+    }
+}
+
+/// A write lock on a value of type `T` in a `SharedAnyMap`.
+pub struct SharedWrite<'a, T> {
+    guard: WriteGuard<'a, Box<dyn Resource>>,
+    _type: PhantomData<&'a mut T>,
+}
+
+impl<T: Any + Send + Sync> Deref for SharedWrite<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.guard.downcast_ref().unwrap()
+    }
+}
+
+impl<T: Any + Send + Sync> DerefMut for SharedWrite<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.guard.downcast_mut().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Barrier;
+
+    use super::SharedAnyMap;
+
+    #[derive(Debug, PartialEq)]
+    struct Foo(u32);
+    #[derive(Debug, PartialEq)]
+    struct Bar(u32);
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn test() {
+        assert_send_sync::<SharedAnyMap>();
+        let mut map = SharedAnyMap::new();
+        assert_eq!(map.insert(Foo(1)), None);
+        assert_eq!(map.insert(Foo(2)), Some(Foo(1)));
+        assert!(map.contains::<Foo>());
+        assert!(map.read::<Bar>().is_none());
+        map.get_mut::<Foo>().unwrap().0 += 1;
+        assert_eq!(*map.read::<Foo>().unwrap(), Foo(3));
+        assert_eq!(map.remove::<Foo>(), Some(Foo(3)));
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn test_move_entry() {
+        let (mut from, mut to) = (SharedAnyMap::new(), SharedAnyMap::new());
+        from.insert(Foo(1));
+        to.insert(Foo(2));
+        let addr = from.get_mut::<Foo>().unwrap() as *const Foo;
+        assert!(SharedAnyMap::move_entry::<Foo>(&mut from, &mut to));
+        assert!(!SharedAnyMap::move_entry::<Foo>(&mut from, &mut to));
+        assert!(from.is_empty());
+        assert_eq!(*to.read::<Foo>().unwrap(), Foo(1));
+        assert_eq!(to.get_mut::<Foo>().unwrap() as *const Foo, addr);
+    }
+
+    #[test]
+    fn test_parallel() {
+        let mut map = SharedAnyMap::new();
+        map.insert(Foo(0));
+        map.insert(Bar(0));
+        // Both readers hold their guard at the barrier, so they must not block each other,
+        // nor the writer of the other type.
+        let barrier = Barrier::new(3);
+        std::thread::scope(|s| {
+            for _ in 0..2 {
+                s.spawn(|| {
+                    let foo = map.read::<Foo>().unwrap();
+                    barrier.wait();
+                    assert_eq!(foo.0, 0);
+                });
+            }
+            s.spawn(|| {
+                let mut bar = map.write::<Bar>().unwrap();
+                barrier.wait();
+                bar.0 += 1;
+            });
+        });
+        assert_eq!(map.read::<Bar>().unwrap().0, 1);
+    }
+}
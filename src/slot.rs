@@ -0,0 +1,167 @@
+//! `AnySlot`, an optional boxed trait object that is swapped and taken out by type.
+
+use alloc::boxed::Box;
+use core::any::Any;
+use core::fmt;
+
+use crate::{AsAny, AsMutAny, BoxAny};
+
+/// A place for a `Box<dyn Trait>` that may be empty, like an `Option<Box<dyn Trait>>` that
+/// downcasts, e.g. the current strategy of a system, swapped out as a whole.
+///
+/// ```
+/// # #[macro_use]
+/// # extern crate pi_any;
+/// use pi_any::{AnySlot, BoxAny};
+///
+/// trait Layout: BoxAny {}
+/// impl_downcast!(Layout);
+///
+/// struct Grid(u32);
+/// impl Layout for Grid {}
+/// struct Flow;
+/// impl Layout for Flow {}
+///
+/// # fn main() {
+/// let mut layout: AnySlot<dyn Layout> = AnySlot::new(Box::new(Grid(4)));
+/// let old = layout.replace(Box::new(Flow)).unwrap();
+/// assert_eq!(old.downcast_ref::<Grid>().unwrap().0, 4);
+///
+/// assert!(layout.take_downcast::<Grid>().is_none() && layout.is::<Flow>());
+/// assert!(layout.take_downcast::<Flow>().is_some() && layout.is_empty());
+/// # }
+/// ```
+pub struct AnySlot<D: ?Sized> {
+    value: Option<Box<D>>,
+}
+
+impl<D: ?Sized> AnySlot<D> {
+    pub fn new(value: Box<D>) -> Self {
+        AnySlot { value: Some(value) }
+    }
+
+    pub fn empty() -> Self {
+        AnySlot { value: None }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.value.is_none()
+    }
+
+    pub fn get(&self) -> Option<&D> {
+        self.value.as_deref()
+    }
+
+    pub fn get_mut(&mut self) -> Option<&mut D> {
+        self.value.as_deref_mut()
+    }
+
+    /// Puts `value` in the slot, returning the value it held, if any.
+    pub fn replace(&mut self, value: Box<D>) -> Option<Box<D>> {
+        self.value.replace(value)
+    }
+
+    /// Takes the value out, leaving the slot empty.
+    pub fn take(&mut self) -> Option<Box<D>> {
+        self.value.take()
+    }
+
+    pub fn into_inner(self) -> Option<Box<D>> {
+        self.value
+    }
+}
+
+impl<D: AsAny + AsMutAny + ?Sized> AnySlot<D> {
+    /// Returns true if the slot holds a value of type `T`.
+    #[inline]
+    pub fn is<T: Any>(&self) -> bool {
+        self.get().is_some_and(|value| AsAny::as_any(value).is::<T>())
+    }
+
+    /// Returns a reference to the value if it is of type `T`, or `None` if it isn't or the
+    /// slot is empty.
+    #[inline]
+    pub fn downcast_ref<T: Any>(&self) -> Option<&T> {
+        AsAny::as_any(self.get()?).downcast_ref()
+    }
+
+    /// Returns a mutable reference to the value if it is of type `T`, or `None` if it isn't
+    /// or the slot is empty.
+    #[inline]
+    pub fn downcast_mut<T: Any>(&mut self) -> Option<&mut T> {
+        AsMutAny::as_any_mut(self.get_mut()?).downcast_mut()
+    }
+
+    /// Takes the value out if it is of type `T`, leaving the slot empty, or leaves any
+    /// other value in place and returns `None`.
+    pub fn take_downcast<T: Any>(&mut self) -> Option<Box<T>>
+    where
+        D: BoxAny,
+    {
+        match crate::__downcast_box(self.value.take()?) {
+            Ok(value) => Some(value),
+            Err(value) => {
+                self.value = Some(value);
+                None
+            }
+        }
+    }
+}
+
+impl<D: ?Sized> Default for AnySlot<D> {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+impl<D: ?Sized> From<Box<D>> for AnySlot<D> {
+    fn from(value: Box<D>) -> Self {
+        Self::new(value)
+    }
+}
+
+impl<D: AsAny + ?Sized> fmt::Debug for AnySlot<D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let found = self.value.as_deref().map(AsAny::type_name);
+        f.debug_struct("AnySlot").field("type", &found).finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::AnySlot;
+    use crate::BoxAny;
+
+    trait Strategy: BoxAny {
+        fn cost(&self) -> u32;
+    }
+
+    struct Fast;
+    impl Strategy for Fast {
+        fn cost(&self) -> u32 { 1 }
+    }
+    struct Slow(u32);
+    impl Strategy for Slow {
+        fn cost(&self) -> u32 { self.0 }
+    }
+
+    #[test]
+    fn test() {
+        let mut slot = AnySlot::<dyn Strategy>::default();
+        assert!(slot.is_empty() && !slot.is::<Fast>());
+        assert!(slot.take_downcast::<Fast>().is_none());
+        assert!(slot.replace(Box::new(Slow(5))).is_none());
+        assert_eq!(slot.get().unwrap().cost(), 5);
+        slot.downcast_mut::<Slow>().unwrap().0 = 7;
+        assert!(slot.downcast_ref::<Fast>().is_none());
+        assert!(format!("{:?}", slot).ends_with("::Slow\") }"));
+
+        let old = slot.replace(Box::new(Fast)).unwrap();
+        assert_eq!(old.cost(), 7);
+        assert!(slot.take_downcast::<Slow>().is_none());
+        assert_eq!(slot.get_mut().unwrap().cost(), 1);
+        assert!(slot.take_downcast::<Fast>().is_some());
+        assert!(slot.take().is_none());
+        assert!(AnySlot::from(old).into_inner().is_some());
+    }
+}
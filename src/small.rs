@@ -0,0 +1,222 @@
+//! `SmallAny`, a type-erased value stored inline when it is small enough.
+//!
+//! Keeping a value of an erased type in a byte buffer needs raw pointer casts, so this
+//! module is allowed to use `unsafe` like `clone`.
+
+use alloc::boxed::Box;
+use core::any::{type_name, Any, TypeId};
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::mem::{align_of, size_of, ManuallyDrop, MaybeUninit};
+use core::ptr;
+
+/// The inline buffer, aligned for every primitive type. The value may be mutated through
+/// `&self`, e.g. a `Cell`, so the buffer is an `UnsafeCell`.
+#[repr(C, align(16))]
+struct Inline<const N: usize>(UnsafeCell<MaybeUninit<[u8; N]>>);
+
+impl<const N: usize> Inline<N> {
+    fn ptr<T>(&self) -> *mut T {
+        self.0.get().cast()
+    }
+}
+
+enum Storage<const N: usize> {
+    // `drop` drops the value in `data`, which is of the type of the `SmallAny`.
+    Inline { data: Inline<N>, drop: unsafe fn(*mut u8) },
+    Heap(Box<dyn Any + Send>),
+}
+
+/// A value of any `Send` type, stored inline if it takes at most `N` bytes and no more than
+/// 16-byte alignment, and boxed otherwise.
+///
+/// Tiny messages, such as events carrying a few numbers, can then be passed around erased
+/// without an allocation each.
+///
+/// ```
+/// use pi_any::SmallAny;
+///
+/// struct Resize(u16, u16);
+///
+/// let mut msg: SmallAny = SmallAny::new(Resize(640, 480));
+/// assert!(msg.is_inline());
+/// msg.downcast_mut::<Resize>().unwrap().0 = 800;
+/// assert_eq!(msg.downcast_ref::<Resize>().unwrap().0, 800);
+///
+/// let big: SmallAny = SmallAny::new([0u64; 8]);
+/// assert!(!big.is_inline());
+/// let big = big.take::<String>().unwrap_err();
+/// assert_eq!(big.take::<[u64; 8]>().ok(), Some([0; 8]));
+/// ```
+pub struct SmallAny<const N: usize = 16> {
+    type_id: TypeId,
+    type_name: &'static str,
+    storage: Storage<N>,
+}
+
+impl<const N: usize> SmallAny<N> {
+    pub fn new<T: Any + Send>(value: T) -> Self {
+        let storage = if Self::fits::<T>() {
+            let data = Inline(UnsafeCell::new(MaybeUninit::uninit()));
+            // SAFETY: `fits` checked that the buffer is large and aligned enough for a `T`.
+            unsafe { ptr::write(data.ptr::<T>(), value) };
+            Storage::Inline { data, drop: drop_inline::<T> }
+        } else {
+            Storage::Heap(Box::new(value))
+        };
+        SmallAny { type_id: TypeId::of::<T>(), type_name: type_name::<T>(), storage }
+    }
+
+    /// Returns true if values of type `T` are stored inline.
+    pub fn fits<T>() -> bool {
+        size_of::<T>() <= N && align_of::<T>() <= align_of::<Inline<N>>()
+    }
+
+    pub fn is_inline(&self) -> bool {
+        matches!(self.storage, Storage::Inline { .. })
+    }
+
+    pub fn type_id(&self) -> TypeId {
+        self.type_id
+    }
+
+    /// Returns the name of the type of the value, as given by `std::any::type_name`.
+    pub fn type_name(&self) -> &'static str {
+        self.type_name
+    }
+
+    /// Returns true if the value is of type `T`.
+    #[inline]
+    pub fn is<T: Any>(&self) -> bool {
+        self.type_id == TypeId::of::<T>()
+    }
+
+    /// Returns a reference to the value if it is of type `T`, or `None` if it isn't.
+    pub fn downcast_ref<T: Any>(&self) -> Option<&T> {
+        if !self.is::<T>() {
+            return None;
+        }
+        match &self.storage {
+            // SAFETY: the buffer holds a `T`, as `type_id` says.
+            Storage::Inline { data, .. } => Some(unsafe { &*data.ptr::<T>() }),
+            Storage::Heap(value) => value.downcast_ref(),
+        }
+    }
+
+    /// Returns a mutable reference to the value if it is of type `T`, or `None` if it isn't.
+    pub fn downcast_mut<T: Any>(&mut self) -> Option<&mut T> {
+        if !self.is::<T>() {
+            return None;
+        }
+        match &mut self.storage {
+            // SAFETY: as in `downcast_ref`.
+            Storage::Inline { data, .. } => Some(unsafe { &mut *data.ptr::<T>() }),
+            Storage::Heap(value) => value.downcast_mut(),
+        }
+    }
+
+    /// Moves the value out if it is of type `T`, or returns `self` if it isn't.
+    pub fn take<T: Any>(self) -> Result<T, Self> {
+        if !self.is::<T>() {
+            return Err(self);
+        }
+        // The value is moved out of the buffer, which must then not be dropped again.
+        let this = ManuallyDrop::new(self);
+        // SAFETY: `this` is never used or dropped after its storage is read out.
+        match unsafe { ptr::read(&this.storage) } {
+            // SAFETY: as in `downcast_ref`; the buffer is forgotten, so the value is read once.
+            Storage::Inline { data, .. } => Ok(unsafe { ptr::read(data.ptr::<T>()) }),
+            Storage::Heap(value) => Ok(*value.downcast().unwrap()),
+        }
+    }
+}
+
+/// # Safety
+///
+/// `data` must point to a valid `T`, which is not used afterwards.
+unsafe fn drop_inline<T>(data: *mut u8) {
+    // SAFETY: guaranteed by the caller.
+    unsafe { ptr::drop_in_place(data.cast::<T>()) }
+}
+
+impl<const N: usize> Drop for SmallAny<N> {
+    fn drop(&mut self) {
+        if let Storage::Inline { data, drop } = &mut self.storage {
+            // SAFETY: the buffer holds a value of the type `drop` was made for, and this is
+            // the last use of it.
+            unsafe { drop(data.ptr()) }
+        }
+    }
+}
+
+impl<const N: usize> fmt::Debug for SmallAny<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("SmallAny").field(&self.type_name).finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::Cell;
+    use std::rc::Rc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use super::SmallAny;
+
+    struct Counted(Arc<AtomicUsize>);
+    impl Drop for Counted {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[repr(align(32))]
+    struct Aligned;
+
+    #[test]
+    fn test() {
+        let mut value: SmallAny<8> = SmallAny::new(7u64);
+        assert!(value.is_inline() && value.is::<u64>());
+        assert!(value.downcast_ref::<u32>().is_none());
+        *value.downcast_mut::<u64>().unwrap() += 1;
+        assert_eq!(value.take::<u64>().ok(), Some(8));
+
+        let value: SmallAny<8> = SmallAny::new(String::from("heap"));
+        assert!(!value.is_inline());
+        assert_eq!(value.downcast_ref::<String>().unwrap(), "heap");
+        assert_eq!(format!("{:?}", value), "SmallAny(\"alloc::string::String\")");
+
+        assert!(SmallAny::<64>::fits::<[u8; 64]>() && !SmallAny::<64>::fits::<Aligned>());
+        assert!(SmallAny::<0>::new(()).is_inline());
+        assert!(!SmallAny::<64>::new(Aligned).is_inline());
+        assert!(SmallAny::<8>::fits::<Rc<u8>>());
+    }
+
+    #[test]
+    fn test_drop() {
+        let drops = Arc::new(AtomicUsize::new(0));
+        let value: SmallAny = SmallAny::new(Counted(drops.clone()));
+        assert!(value.is_inline());
+        let value = value.take::<u8>().unwrap_err();
+        assert_eq!(drops.load(Ordering::SeqCst), 0);
+        let counted = value.take::<Counted>().ok().unwrap();
+        assert_eq!(drops.load(Ordering::SeqCst), 0);
+        drop(counted);
+        assert_eq!(drops.load(Ordering::SeqCst), 1);
+
+        drop(SmallAny::<16>::new(Counted(drops.clone())));
+        drop(SmallAny::<0>::new(Counted(drops.clone())));
+        assert_eq!(drops.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn test_interior_mutability() {
+        let value: SmallAny = SmallAny::new(Cell::new(1u32));
+        assert!(value.is_inline());
+        let cell = value.downcast_ref::<Cell<u32>>().unwrap();
+        cell.set(cell.get() + 1);
+        assert_eq!(value.downcast_ref::<Cell<u32>>().unwrap().get(), 2);
+        assert_eq!(value.take::<Cell<u32>>().ok().unwrap().into_inner(), 2);
+    }
+}
@@ -0,0 +1,311 @@
+//! Serde support for trait objects whose types are in a `TypeRegistry`, like `typetag` but
+//! with an explicit registry.
+//!
+//! A value is externally tagged: it is represented as a map with a single entry, from the
+//! registered name of its concrete type to the value itself, e.g. in JSON:
+//!
+//! ```text
+//! {"circle": {"radius": 1.0}}
+//! ```
+
+use std::any::{type_name, Any};
+use std::fmt;
+
+use serde::de::{self, DeserializeOwned, DeserializeSeed, Deserializer, IgnoredAny, MapAccess};
+use serde::ser::{self, Serialize, SerializeMap, Serializer};
+
+use crate::registry::Entry;
+use crate::{AsAny, Bounds, SendSyncBounds, TypeRegistry};
+
+type DeserializeFn<B> = Box<
+    dyn Fn(&mut dyn erased_serde::Deserializer<'_>) -> Result<Box<B>, erased_serde::Error>
+        + Send
+        + Sync,
+>;
+
+pub(crate) struct SerdeFns<B: ?Sized> {
+    serialize: fn(&dyn Any) -> &dyn erased_serde::Serialize,
+    deserialize: DeserializeFn<B>,
+}
+
+fn erase<T: Any + Serialize>(value: &dyn Any) -> &dyn erased_serde::Serialize {
+    value.downcast_ref::<T>().unwrap()
+}
+
+impl<B: AsAny + ?Sized, S: Bounds> TypeRegistry<B, S> {
+    /// Makes `T`, which must already be registered, serializable behind `B` under its
+    /// registered name. `wrap` boxes deserialized values, e.g. `|v| Box::new(v)`. Returns
+    /// false if `T` is not registered.
+    pub fn register_serde<T: Any + Serialize + DeserializeOwned>(
+        &mut self,
+        wrap: impl Fn(T) -> Box<B> + Send + Sync + 'static,
+    ) -> bool {
+        let Some(name) = self.name_of::<T>().map(str::to_string) else {
+            return false;
+        };
+        let deserialize: DeserializeFn<B> =
+            Box::new(move |deserializer| erased_serde::deserialize::<T>(deserializer).map(&wrap));
+        let fns = SerdeFns { serialize: erase::<T>, deserialize };
+        self.entries.get_mut(&name).unwrap().serde = Some(fns);
+        true
+    }
+
+    /// Returns true if the type registered under `name` was made serializable.
+    pub fn has_serde(&self, name: &str) -> bool {
+        self.entries.get(name).is_some_and(|entry| entry.serde.is_some())
+    }
+
+    /// Returns `value` tagged with the registered name of its concrete type, for serializing.
+    pub fn tagged<'a>(&'a self, value: &'a B) -> Tagged<'a, B, S> {
+        Tagged { registry: self, value }
+    }
+
+    /// Returns a seed deserializing a tagged value into a `Box<B>`, e.g. with
+    /// `DeserializeSeed::deserialize(registry.tagged_seed(), deserializer)`.
+    pub fn tagged_seed(&self) -> TaggedSeed<'_, B, S> {
+        TaggedSeed { registry: self }
+    }
+}
+
+/// A trait object tagged with the registered name of its concrete type, as returned by
+/// `TypeRegistry::tagged`.
+///
+/// Serializing fails if the type is not registered or was not made serializable.
+pub struct Tagged<'a, B: ?Sized, S: Bounds = SendSyncBounds> {
+    registry: &'a TypeRegistry<B, S>,
+    value: &'a B,
+}
+
+impl<B: AsAny + ?Sized, S: Bounds> Serialize for Tagged<'_, B, S> {
+    fn serialize<Ser: Serializer>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error> {
+        let any = self.value.as_any();
+        let name = self.registry.name_of_id(any.type_id()).ok_or_else(|| {
+            ser::Error::custom(format_args!("`{}` is not registered", self.value.type_name()))
+        })?;
+        let fns = self.registry.entries[name]
+            .serde
+            .as_ref()
+            .ok_or_else(|| ser::Error::custom(format_args!("`{}` is not serializable", name)))?;
+        let mut map = serializer.serialize_map(Some(1))?;
+        map.serialize_entry(name, (fns.serialize)(any))?;
+        map.end()
+    }
+}
+
+impl<B: AsAny + ?Sized, S: Bounds> fmt::Debug for Tagged<'_, B, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Tagged").field("type", &self.value.type_name()).finish()
+    }
+}
+
+/// Deserializes a value tagged with a registered name into a `Box<B>`, as returned by
+/// `TypeRegistry::tagged_seed`.
+pub struct TaggedSeed<'a, B: ?Sized, S: Bounds = SendSyncBounds> {
+    registry: &'a TypeRegistry<B, S>,
+}
+
+impl<'de, B: AsAny + ?Sized, S: Bounds> DeserializeSeed<'de> for TaggedSeed<'_, B, S> {
+    type Value = Box<B>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Box<B>, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de, B: AsAny + ?Sized, S: Bounds> de::Visitor<'de> for TaggedSeed<'_, B, S> {
+    type Value = Box<B>;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a map from a registered type name to a value")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Box<B>, A::Error> {
+        let name: String = map.next_key()?.ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        let entry = self.registry.entries.get(&name).ok_or_else(|| {
+            de::Error::custom(format_args!("no type is registered as `{}`", name))
+        })?;
+        let value = map.next_value_seed(EntrySeed { name: &name, entry })?;
+        if map.next_key::<IgnoredAny>()?.is_some() {
+            return Err(de::Error::invalid_length(2, &self));
+        }
+        Ok(value)
+    }
+}
+
+impl<B: ?Sized, S: Bounds> fmt::Debug for TaggedSeed<'_, B, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TaggedSeed").field("registry", self.registry).finish()
+    }
+}
+
+/// Deserializes the value of the type registered under `name`.
+struct EntrySeed<'a, B: ?Sized, S: Bounds> {
+    name: &'a str,
+    entry: &'a Entry<B, S>,
+}
+
+impl<'de, B: AsAny + ?Sized, S: Bounds> DeserializeSeed<'de> for EntrySeed<'_, B, S> {
+    type Value = Box<B>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Box<B>, D::Error> {
+        let fns = self.entry.serde.as_ref().ok_or_else(|| {
+            de::Error::custom(format_args!("`{}` is not serializable", self.name))
+        })?;
+        let mut deserializer = <dyn erased_serde::Deserializer>::erase(deserializer);
+        let value = (fns.deserialize)(&mut deserializer).map_err(de::Error::custom)?;
+        debug_assert!(
+            (*value).type_id_of() == self.entry.type_id,
+            "the serde functions of `{}` produced a `{}`, not a `{}`",
+            self.name,
+            (*value).type_name(),
+            type_name::<B>()
+        );
+        Ok(value)
+    }
+}
+
+/// Implements `Serialize` for `dyn Trait` and `Deserialize` for `Box<dyn Trait>`, going
+/// through the registry that `$registry` evaluates to, so trait objects can be fields of
+/// types deriving serde's traits.
+///
+/// ```
+/// # #[macro_use]
+/// # extern crate pi_any;
+/// use std::sync::OnceLock;
+/// use pi_any::{BoxAny, TypeRegistry};
+/// # extern crate serde;
+/// # extern crate serde_json;
+/// use serde::{Deserialize, Serialize};
+///
+/// trait Shape: BoxAny {
+///     fn area(&self) -> f32;
+/// }
+///
+/// #[derive(Default, Serialize, Deserialize)]
+/// struct Square { side: f32 }
+/// impl Shape for Square {
+///     fn area(&self) -> f32 { self.side * self.side }
+/// }
+///
+/// fn shapes() -> &'static TypeRegistry<dyn Shape> {
+///     static SHAPES: OnceLock<TypeRegistry<dyn Shape>> = OnceLock::new();
+///     SHAPES.get_or_init(|| {
+///         let mut shapes = TypeRegistry::<dyn Shape>::new();
+///         shapes.register::<Square>("square", || Box::new(Square::default()));
+///         shapes.register_serde::<Square>(|v| Box::new(v));
+///         shapes
+///     })
+/// }
+/// impl_serde_tagged!(Shape, shapes());
+///
+/// # fn main() {
+/// let shape: Box<dyn Shape> = Box::new(Square { side: 2.0 });
+/// let json = serde_json::to_string(&shape).unwrap();
+/// assert_eq!(json, r#"{"square":{"side":2.0}}"#);
+/// let shape: Box<dyn Shape> = serde_json::from_str(&json).unwrap();
+/// assert_eq!(shape.area(), 4.0);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! impl_serde_tagged {
+    ($trait_:path, $registry:expr) => {
+        impl $crate::__serde::Serialize for dyn $trait_ {
+            fn serialize<__S: $crate::__serde::Serializer>(
+                &self,
+                serializer: __S,
+            ) -> $crate::__core::result::Result<__S::Ok, __S::Error> {
+                $crate::__serde::Serialize::serialize(&$registry.tagged(self), serializer)
+            }
+        }
+
+        impl<'de> $crate::__serde::Deserialize<'de> for $crate::__alloc::boxed::Box<dyn $trait_> {
+            fn deserialize<__D: $crate::__serde::Deserializer<'de>>(
+                deserializer: __D,
+            ) -> $crate::__core::result::Result<Self, __D::Error> {
+                $crate::__serde::de::DeserializeSeed::deserialize(
+                    $registry.tagged_seed(),
+                    deserializer,
+                )
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::OnceLock;
+
+    use serde::de::DeserializeSeed;
+    use serde::{Deserialize, Serialize};
+
+    use crate::{BoxAny, TypeRegistry};
+
+    trait Item: BoxAny {}
+    crate::impl_downcast_box!(Item);
+
+    #[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+    struct Sword {
+        damage: u32,
+    }
+    impl Item for Sword {}
+    #[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+    struct Potion(String);
+    impl Item for Potion {}
+    #[derive(Default)]
+    struct Key;
+    impl Item for Key {}
+    impl Item for u8 {}
+
+    fn items() -> &'static TypeRegistry<dyn Item> {
+        static ITEMS: OnceLock<TypeRegistry<dyn Item>> = OnceLock::new();
+        ITEMS.get_or_init(|| {
+            let mut items = TypeRegistry::<dyn Item>::new();
+            items.register::<Sword>("sword", || Box::new(Sword::default()));
+            items.register::<Potion>("potion", || Box::new(Potion::default()));
+            items.register::<Key>("key", || Box::new(Key));
+            assert!(items.register_serde::<Sword>(|v| Box::new(v)));
+            assert!(items.register_serde::<Potion>(|v| Box::new(v)));
+            assert!(!items.register_serde::<u8>(|v| Box::new(v)));
+            items
+        })
+    }
+    crate::impl_serde_tagged!(Item, items());
+
+    #[derive(Serialize, Deserialize)]
+    struct Inventory {
+        items: Vec<Box<dyn Item>>,
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let registry = items();
+        assert!(registry.has_serde("sword") && !registry.has_serde("key"));
+        let json = serde_json::to_string(&registry.tagged(&Potion("heal".into()))).unwrap();
+        assert_eq!(json, r#"{"potion":"heal"}"#);
+        let mut deserializer = serde_json::Deserializer::from_str(&json);
+        let potion = registry.tagged_seed().deserialize(&mut deserializer).unwrap();
+        assert_eq!(potion.downcast_ref(), Some(&Potion("heal".into())));
+
+        let inventory = Inventory { items: vec![Box::new(Sword { damage: 3 }), potion] };
+        let json = serde_json::to_string(&inventory).unwrap();
+        assert_eq!(json, r#"{"items":[{"sword":{"damage":3}},{"potion":"heal"}]}"#);
+        let inventory: Inventory = serde_json::from_str(&json).unwrap();
+        assert_eq!(inventory.items[0].downcast_ref(), Some(&Sword { damage: 3 }));
+        assert!(inventory.items[1].is::<Potion>());
+    }
+
+    #[test]
+    fn test_errors() {
+        let error = |value: Box<dyn Item>| serde_json::to_string(&value).unwrap_err().to_string();
+        assert_eq!(error(Box::new(Key)), "`key` is not serializable");
+        assert_eq!(error(Box::new(1u8)), "`u8` is not registered");
+
+        let error = |json| serde_json::from_str::<Box<dyn Item>>(json).err().unwrap().to_string();
+        assert!(error(r#"{"shield":1}"#).starts_with("no type is registered as `shield`"));
+        assert!(error(r#"{"key":null}"#).starts_with("`key` is not serializable"));
+        assert!(error(r#"{"sword":{"damage":-1}}"#).starts_with("invalid value"));
+        assert!(error(r#"{"potion":"a","sword":{"damage":1}}"#).starts_with("invalid length 2"));
+        assert!(error("{}").starts_with("invalid length 0"));
+        assert!(error("[]").starts_with("invalid type"));
+    }
+}
@@ -0,0 +1,201 @@
+//! `Tid`, downcasting of types that borrow data, which `Any` can't erase.
+//!
+//! A type is identified by the `TypeId` of its `'static` version, and the pointer is cast
+//! once the ids match, so this module is allowed to use `unsafe` like `clone`.
+
+use alloc::boxed::Box;
+use core::any::TypeId;
+
+/// A type that may borrow data for `'a`, identified by the same type with `'a` replaced
+/// by `'static`.
+///
+/// `dyn Tid<'a> + 'a` then downcasts like `dyn Any`, and so does a trait object extending
+/// `Tid<'a>` through `as_tid`, `as_tid_mut` and `into_tid`, e.g. values holding references
+/// into a frame arena. Implement it with `impl_tid!`, for types with no lifetime parameter
+/// or exactly one.
+///
+/// ```
+/// # #[macro_use]
+/// # extern crate pi_any;
+/// use pi_any::Tid;
+///
+/// trait Event<'a>: Tid<'a> {}
+///
+/// struct Text<'a>(&'a str);
+/// impl<'a> Event<'a> for Text<'a> {}
+/// struct Quit;
+/// impl Event<'_> for Quit {}
+///
+/// impl_tid!(Text<'a>, Quit);
+///
+/// # fn main() {
+/// let frame = String::from("hello");
+/// let events: Vec<Box<dyn Event<'_>>> = vec![Box::new(Text(&frame)), Box::new(Quit)];
+/// let event = events[0].as_tid();
+/// assert_eq!(event.downcast_ref::<Text>().unwrap().0, "hello");
+/// assert!(!event.is::<Quit>());
+/// # }
+/// ```
+///
+/// # Safety
+///
+/// `Self` must have no lifetime parameter other than `'a`, and both methods must return
+/// the `TypeId` of `Self` with `'a` replaced by `'static`, and the conversions to `dyn Tid`
+/// must return the value itself. A value erased as `dyn Tid<'a>`
+/// is then of a type that differs from the one downcast to at most in `'a`, which is the
+/// same for both.
+pub unsafe trait Tid<'a>: 'a {
+    /// Returns the `TypeId` identifying the type of the value.
+    fn self_id(&self) -> TypeId;
+
+    /// Returns the `TypeId` identifying `Self`.
+    fn id() -> TypeId
+    where
+        Self: Sized;
+
+    /// Returns the value as a `dyn Tid`, e.g. from a trait object extending `Tid`.
+    fn as_tid(&self) -> &(dyn Tid<'a> + 'a);
+
+    /// Returns the value as a mutable `dyn Tid`.
+    fn as_tid_mut(&mut self) -> &mut (dyn Tid<'a> + 'a);
+
+    /// Converts the box into a `Box<dyn Tid>`.
+    fn into_tid(self: Box<Self>) -> Box<dyn Tid<'a> + 'a>;
+}
+
+impl<'a> dyn Tid<'a> + 'a {
+    /// Returns true if the value is of type `T`.
+    #[inline]
+    pub fn is<T: Tid<'a>>(&self) -> bool {
+        self.self_id() == T::id()
+    }
+
+    /// Returns a reference to the value if it is of type `T`, or `None` if it isn't.
+    #[inline]
+    pub fn downcast_ref<T: Tid<'a>>(&self) -> Option<&T> {
+        if self.is::<T>() {
+            // SAFETY: the value is a `T`, as guaranteed by the impls of `Tid`.
+            Some(unsafe { &*(self as *const Self).cast::<T>() })
+        } else {
+            None
+        }
+    }
+
+    /// Returns a mutable reference to the value if it is of type `T`, or `None` if it isn't.
+    #[inline]
+    pub fn downcast_mut<T: Tid<'a>>(&mut self) -> Option<&mut T> {
+        if self.is::<T>() {
+            // SAFETY: as in `downcast_ref`.
+            Some(unsafe { &mut *(self as *mut Self).cast::<T>() })
+        } else {
+            None
+        }
+    }
+
+    /// Converts the box into a `Box<T>` if the value is of type `T`, or returns the
+    /// original box if it isn't.
+    #[inline]
+    pub fn downcast<T: Tid<'a>>(self: Box<Self>) -> Result<Box<T>, Box<Self>> {
+        if self.is::<T>() {
+            // SAFETY: as in `downcast_ref`, in an allocation made for a `T`.
+            Ok(unsafe { Box::from_raw(Box::into_raw(self).cast::<T>()) })
+        } else {
+            Err(self)
+        }
+    }
+}
+
+/// Implements `Tid` for types with no lifetime parameter or exactly one, given as
+/// `Name<'a>`.
+#[macro_export]
+macro_rules! impl_tid {
+    ($($ty:ident $(<$lt:lifetime>)?),+ $(,)?) => {
+        $( $crate::impl_tid! { @one $ty $(<$lt>)? } )+
+    };
+    (@one $ty:ident <$lt:lifetime>) => {
+        unsafe impl<$lt> $crate::Tid<$lt> for $ty<$lt> {
+            fn self_id(&self) -> $crate::__core::any::TypeId {
+                $crate::__core::any::TypeId::of::<$ty<'static>>()
+            }
+            fn id() -> $crate::__core::any::TypeId {
+                $crate::__core::any::TypeId::of::<$ty<'static>>()
+            }
+            fn as_tid(&self) -> &(dyn $crate::Tid<$lt> + $lt) {
+                self
+            }
+            fn as_tid_mut(&mut self) -> &mut (dyn $crate::Tid<$lt> + $lt) {
+                self
+            }
+            fn into_tid(
+                self: $crate::__alloc::boxed::Box<Self>,
+            ) -> $crate::__alloc::boxed::Box<dyn $crate::Tid<$lt> + $lt> {
+                self
+            }
+        }
+    };
+    (@one $ty:ident) => {
+        unsafe impl<'__a> $crate::Tid<'__a> for $ty {
+            fn self_id(&self) -> $crate::__core::any::TypeId {
+                $crate::__core::any::TypeId::of::<$ty>()
+            }
+            fn id() -> $crate::__core::any::TypeId {
+                $crate::__core::any::TypeId::of::<$ty>()
+            }
+            fn as_tid(&self) -> &(dyn $crate::Tid<'__a> + '__a) {
+                self
+            }
+            fn as_tid_mut(&mut self) -> &mut (dyn $crate::Tid<'__a> + '__a) {
+                self
+            }
+            fn into_tid(
+                self: $crate::__alloc::boxed::Box<Self>,
+            ) -> $crate::__alloc::boxed::Box<dyn $crate::Tid<'__a> + '__a> {
+                self
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod test {
+    use super::Tid;
+
+    trait Node<'a>: Tid<'a> {
+        fn text(&self) -> &'a str;
+    }
+
+    struct Word<'a>(&'a str);
+    impl<'a> Node<'a> for Word<'a> {
+        fn text(&self) -> &'a str { self.0 }
+    }
+    struct Pair<'a>(&'a str, &'a str);
+    impl<'a> Node<'a> for Pair<'a> {
+        fn text(&self) -> &'a str { self.1 }
+    }
+    #[derive(Debug)]
+    struct Empty;
+    impl Node<'_> for Empty {
+        fn text(&self) -> &'static str { "" }
+    }
+    crate::impl_tid!(Word<'a>, Pair<'a>, Empty);
+
+    #[test]
+    fn test() {
+        let text = String::from("a b");
+        let mut nodes: Vec<Box<dyn Node<'_>>> =
+            vec![Box::new(Word(&text[..1])), Box::new(Pair(&text[..1], &text[2..])), Box::new(Empty)];
+        assert_eq!(<Word as Tid>::id(), <Word<'static> as Tid>::id());
+        assert_ne!(<Word as Tid>::id(), <Pair as Tid>::id());
+
+        let node = nodes[1].as_tid_mut();
+        assert!(node.is::<Pair>() && !node.is::<Word>());
+        assert!(node.downcast_ref::<Word>().is_none());
+        node.downcast_mut::<Pair>().unwrap().0 = &text[2..];
+        assert_eq!(nodes[1].text(), "b");
+
+        let word = nodes.remove(0).into_tid();
+        let word = word.downcast::<Empty>().unwrap_err();
+        let word: &str = word.downcast::<Word>().ok().unwrap().0;
+        assert_eq!(word, "a");
+    }
+}
@@ -0,0 +1,104 @@
+//! `TriompheArcAny` and `impl_downcast_triomphe!`, for the `Arc` of `triomphe`, which has no
+//! weak count.
+//!
+//! Stable Rust only takes `self` by the pointers of the standard library, so the downcasts
+//! are associated functions, called as `<dyn Trait>::downcast_triomphe(arc)`.
+
+use core::any::{Any, TypeId};
+
+use triomphe::Arc;
+
+use crate::owned::concrete_type_id;
+use crate::AsAny;
+
+/// The trait to extend for downcasting `triomphe::Arc<dyn Trait>`. Like `ArcAny`, it
+/// requires `Send + Sync`.
+///
+/// `triomphe` makes an `Arc<dyn Trait>` with its `unsize` feature, which this feature
+/// enables:
+///
+/// ```
+/// # #[macro_use]
+/// # extern crate pi_any;
+/// # extern crate triomphe;
+/// # extern crate unsize;
+/// use pi_any::TriompheArcAny;
+/// use triomphe::Arc;
+/// use unsize::{CoerceUnsize, Coercion};
+///
+/// trait Asset: TriompheArcAny {}
+/// impl_downcast_triomphe!(Asset);
+///
+/// struct Texture(u32);
+/// impl Asset for Texture {}
+///
+/// # fn main() {
+/// let asset: Arc<dyn Asset> = Arc::new(Texture(64)).unsize(Coercion!(to dyn Asset));
+/// assert!(asset.is::<Texture>());
+/// let texture = <dyn Asset>::downcast_triomphe::<Texture>(asset).ok().unwrap();
+/// assert_eq!(texture.0, 64);
+/// # }
+/// ```
+pub trait TriompheArcAny: AsAny + Send + Sync + 'static {}
+
+impl<T: AsAny + Send + Sync + 'static> TriompheArcAny for T {}
+
+/// Generates `is`, `downcast_ref`, and the associated functions `downcast_triomphe`,
+/// `downcast_triomphe_expect` and `try_downcast_triomphe` for `triomphe::Arc<dyn Trait>`.
+/// The trait must extend `TriompheArcAny`.
+#[macro_export(local_inner_macros)]
+macro_rules! impl_downcast_triomphe {
+    ($($input:tt)+) => { impl_downcast! { @split [ref triomphe] [] $($input)+ } };
+}
+
+#[doc(hidden)]
+#[inline]
+pub fn __downcast_triomphe<T: Any, S: AsAny + ?Sized>(value: Arc<S>) -> Result<Arc<T>, Arc<S>> {
+    if concrete_type_id(&*value) == Some(TypeId::of::<T>()) {
+        // SAFETY: as in `__downcast_rc`; `triomphe::Arc::from_raw` finds the counter from
+        // the alignment of `T`, which is that of the trait object.
+        Ok(unsafe { Arc::from_raw(Arc::into_raw(value).cast::<T>()) })
+    } else {
+        Err(value)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use triomphe::Arc;
+    use unsize::{CoerceUnsize, Coercion};
+
+    use super::TriompheArcAny;
+
+    trait Node<T>: TriompheArcAny {}
+    crate::impl_downcast_triomphe!(Node<T>);
+
+    #[derive(Debug)]
+    struct Leaf(u64);
+    impl Node<u8> for Leaf {}
+    #[derive(Debug)]
+    struct Branch;
+    impl Node<u8> for Branch {}
+
+    #[test]
+    fn test() {
+        let node: Arc<dyn Node<u8>> = Arc::new(Leaf(1)).unsize(Coercion!(to dyn Node<u8>));
+        let other = node.clone();
+        assert!(node.is::<Leaf>());
+        assert_eq!(node.downcast_ref::<Leaf>().unwrap().0, 1);
+        let node = <dyn Node<u8>>::downcast_triomphe::<Branch>(node).unwrap_err();
+        let err = <dyn Node<u8>>::try_downcast_triomphe::<Branch>(node).unwrap_err();
+        assert!(err.to_string().contains("Branch"));
+        let leaf = <dyn Node<u8>>::downcast_triomphe_expect::<Leaf>(err.value, "leaf");
+        assert_eq!(leaf.0, 1);
+        drop(leaf);
+        assert!(Arc::is_unique(&other));
+    }
+
+    #[test]
+    #[should_panic(expected = "leaf")]
+    fn test_expect() {
+        let node: Arc<dyn Node<u8>> = Arc::new(Branch).unsize(Coercion!(to dyn Node<u8>));
+        <dyn Node<u8>>::downcast_triomphe_expect::<Leaf>(node, "leaf");
+    }
+}
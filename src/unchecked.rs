@@ -0,0 +1,62 @@
+//! Casts used by the `*_unchecked` downcast methods, enabled by the `unchecked` feature.
+//!
+//! Like `clone`, this module is allowed to use `unsafe`. Every function assumes
+//! the caller has already checked the concrete type, e.g. with `is::<T>()`, which debug
+//! builds assert again.
+
+use alloc::boxed::Box;
+use alloc::rc::Rc;
+use alloc::sync::Arc;
+use core::any::Any;
+
+/// # Safety
+///
+/// The value behind `any` must be of type `T`.
+#[inline]
+pub unsafe fn downcast_ref<T: Any>(any: &dyn Any) -> &T {
+    debug_assert!(any.is::<T>());
+    // SAFETY: the caller guarantees the erased type is `T`, so the data pointer of the
+    // trait object points to a valid `T`.
+    unsafe { &*(any as *const dyn Any as *const T) }
+}
+
+/// # Safety
+///
+/// The value behind `any` must be of type `T`.
+#[inline]
+pub unsafe fn downcast_mut<T: Any>(any: &mut dyn Any) -> &mut T {
+    debug_assert!(any.is::<T>());
+    // SAFETY: as in `downcast_ref`; the borrow is unique because `any` is.
+    unsafe { &mut *(any as *mut dyn Any as *mut T) }
+}
+
+/// # Safety
+///
+/// The value in the box must be of type `T`.
+#[inline]
+pub unsafe fn downcast_box<T: Any>(any: Box<dyn Any>) -> Box<T> {
+    debug_assert!(any.is::<T>());
+    // SAFETY: the allocation was made for a `T`, so it can be owned by a `Box<T>`.
+    unsafe { Box::from_raw(Box::into_raw(any) as *mut T) }
+}
+
+/// # Safety
+///
+/// The value in the `Rc` must be of type `T`.
+#[inline]
+pub unsafe fn downcast_rc<T: Any>(any: Rc<dyn Any>) -> Rc<T> {
+    debug_assert!(any.is::<T>());
+    // SAFETY: `Rc::from_raw` accepts a pointer to a `T` obtained from `Rc::into_raw` on an
+    // `Rc` of a type with the same layout, which the erased `T` has.
+    unsafe { Rc::from_raw(Rc::into_raw(any) as *const T) }
+}
+
+/// # Safety
+///
+/// The value in the `Arc` must be of type `T`.
+#[inline]
+pub unsafe fn downcast_arc<T: Any + Send + Sync>(any: Arc<dyn Any + Send + Sync>) -> Arc<T> {
+    debug_assert!(any.is::<T>());
+    // SAFETY: as in `downcast_rc`.
+    unsafe { Arc::from_raw(Arc::into_raw(any) as *const T) }
+}
@@ -0,0 +1,128 @@
+//! `impl_upcast!`, declaring helper traits that upcast trait objects to a supertrait.
+
+/// Declares a helper trait upcasting to `dyn Super`, implemented for every `'static` type
+/// implementing `Super`. Listing the helper as a supertrait of a trait `Sub` puts the
+/// upcasts in the vtable of `dyn Sub`, so they work without the trait upcasting coercion
+/// of newer compilers.
+///
+/// The helper has the methods `upcast_ref`, `upcast_mut`, `upcast_box`, `upcast_rc` and
+/// `upcast_arc`. Auto traits given after the supertrait are kept by the upcasts, which then
+/// need the trait object to have them, as `dyn Sub + Send` has, while the types
+/// implementing `Sub` still don't.
+///
+/// `impl_downcast!(Sub : Super via Helper)` adds `as_super`, `as_super_mut`, `into_super`,
+/// `into_super_rc` and `into_super_arc` to `dyn Sub`, going through the helper, which picks
+/// the right one when there is a helper for each set of auto traits:
+///
+/// ```
+/// # #[macro_use]
+/// # extern crate pi_any;
+/// use std::fmt::Display;
+/// use pi_any::BoxAny;
+///
+/// impl_upcast!(AsDisplay: Display);
+/// impl_upcast!(AsDisplaySend: Display + Send);
+///
+/// trait Label: BoxAny + Display + AsDisplay + AsDisplaySend {}
+/// impl_downcast_box!(Label : Display via AsDisplay; Label + Send : Display via AsDisplaySend);
+///
+/// impl Label for u32 {}
+///
+/// # fn main() {
+/// let label: Box<dyn Label + Send> = Box::new(7u32);
+/// assert_eq!(label.as_super().to_string(), "7");
+/// let display: Box<dyn Display + Send> = label.into_super();
+/// assert_eq!(display.to_string(), "7");
+/// # }
+/// ```
+#[macro_export]
+macro_rules! impl_upcast {
+    ($vis:vis $helper:ident $(<$($param:ident),* $(,)?>)? : $($rest:tt)+) => {
+        $crate::impl_upcast! { @split [$vis $helper [$($($param),*)?]] [] $($rest)+ }
+    };
+
+    // The supertrait is collected up to the auto traits, which are the `+ Ident`s at the end.
+    (@split $head:tt [$($super:tt)+] $(+ $auto:ident)*) => {
+        $crate::impl_upcast! { @impl $head [$($super)+] [$($auto)*] }
+    };
+    (@split $head:tt [$($super:tt)*] $next:tt $($rest:tt)*) => {
+        $crate::impl_upcast! { @split $head [$($super)* $next] $($rest)* }
+    };
+
+    (@impl [$vis:vis $helper:ident [$($param:ident),*]] [$($super:tt)+] [$($auto:ident)*]) => {
+        #[allow(unused_parens)]
+        $vis trait $helper<$($param),*> {
+            /// Upcasts a reference to a reference to the supertrait.
+            fn upcast_ref(&self) -> &(dyn $($super)+ $(+ $auto)*)
+            where
+                Self: $($auto +)*;
+            /// Upcasts a mutable reference to one to the supertrait.
+            fn upcast_mut(&mut self) -> &mut (dyn $($super)+ $(+ $auto)*)
+            where
+                Self: $($auto +)*;
+            /// Upcasts the box into a box of the supertrait.
+            fn upcast_box(
+                self: $crate::__alloc::boxed::Box<Self>,
+            ) -> $crate::__alloc::boxed::Box<dyn $($super)+ $(+ $auto)*>
+            where
+                Self: $($auto +)*;
+            /// Upcasts the `Rc` into an `Rc` of the supertrait.
+            fn upcast_rc(
+                self: $crate::__alloc::rc::Rc<Self>,
+            ) -> $crate::__alloc::rc::Rc<dyn $($super)+ $(+ $auto)*>
+            where
+                Self: $($auto +)*;
+            /// Upcasts the `Arc` into an `Arc` of the supertrait.
+            fn upcast_arc(
+                self: $crate::__alloc::sync::Arc<Self>,
+            ) -> $crate::__alloc::sync::Arc<dyn $($super)+ $(+ $auto)*>
+            where
+                Self: $($auto +)*;
+        }
+
+        #[allow(unused_parens)]
+        impl<$($param,)* __T: $($super)+ + 'static> $helper<$($param),*> for __T {
+            #[inline]
+            fn upcast_ref(&self) -> &(dyn $($super)+ $(+ $auto)*)
+            where
+                Self: $($auto +)*,
+            {
+                self
+            }
+            #[inline]
+            fn upcast_mut(&mut self) -> &mut (dyn $($super)+ $(+ $auto)*)
+            where
+                Self: $($auto +)*,
+            {
+                self
+            }
+            #[inline]
+            fn upcast_box(
+                self: $crate::__alloc::boxed::Box<Self>,
+            ) -> $crate::__alloc::boxed::Box<dyn $($super)+ $(+ $auto)*>
+            where
+                Self: $($auto +)*,
+            {
+                self
+            }
+            #[inline]
+            fn upcast_rc(
+                self: $crate::__alloc::rc::Rc<Self>,
+            ) -> $crate::__alloc::rc::Rc<dyn $($super)+ $(+ $auto)*>
+            where
+                Self: $($auto +)*,
+            {
+                self
+            }
+            #[inline]
+            fn upcast_arc(
+                self: $crate::__alloc::sync::Arc<Self>,
+            ) -> $crate::__alloc::sync::Arc<dyn $($super)+ $(+ $auto)*>
+            where
+                Self: $($auto +)*,
+            {
+                self
+            }
+        }
+    };
+}
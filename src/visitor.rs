@@ -0,0 +1,181 @@
+//! `AnyVisitor` and the `visit` driver, walking collections of trait objects.
+
+use std::any::{Any, TypeId};
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::AsAny;
+
+type Callback<'f, A> = Box<dyn FnMut(&A) + 'f>;
+
+/// Visits `&V` values, typically `&dyn Trait`, one at a time.
+///
+/// `TypedVisitor` implements it with callbacks per concrete type; implement it by hand for
+/// visitors that dispatch some other way.
+pub trait AnyVisitor<V: ?Sized> {
+    fn visit(&mut self, value: &V);
+}
+
+/// Calls `visitor` with every value of `values`, such as a `&Vec<Box<dyn Trait>>`.
+///
+/// ```
+/// use pi_any::{visit, BoxAny, TypedVisitor};
+///
+/// trait Node: BoxAny {}
+/// struct Text(&'static str);
+/// impl Node for Text {}
+/// struct Image;
+/// impl Node for Image {}
+/// struct Break;
+/// impl Node for Break {}
+///
+/// let nodes: Vec<Box<dyn Node>> = vec![Box::new(Text("a")), Box::new(Image), Box::new(Break)];
+/// let (mut text, mut images, mut others) = (String::new(), 0, 0);
+/// visit(
+///     &nodes,
+///     &mut TypedVisitor::<dyn Node>::new()
+///         .on::<Text>(|t| text.push_str(t.0))
+///         .on::<Image>(|_| images += 1)
+///         .otherwise(|_| others += 1),
+/// );
+/// assert_eq!((text.as_str(), images, others), ("a", 1, 1));
+/// ```
+pub fn visit<'a, V, B, I>(values: I, visitor: &mut impl AnyVisitor<V>)
+where
+    V: ?Sized,
+    B: Borrow<V> + ?Sized + 'a,
+    I: IntoIterator<Item = &'a B>,
+{
+    for value in values {
+        visitor.visit(value.borrow());
+    }
+}
+
+/// An `AnyVisitor` calling the callback registered for the concrete type of each value,
+/// or the `otherwise` callback for types without one.
+///
+/// Callbacks are `FnMut`, so they can accumulate into their captures; see `visit`.
+pub struct TypedVisitor<'f, V: ?Sized> {
+    callbacks: HashMap<TypeId, Callback<'f, dyn Any>>,
+    otherwise: Option<Callback<'f, V>>,
+}
+
+impl<'f, V: AsAny + ?Sized> TypedVisitor<'f, V> {
+    pub fn new() -> Self {
+        TypedVisitor { callbacks: HashMap::new(), otherwise: None }
+    }
+
+    /// Registers `callback` for values of type `T`. If `T` already has a callback, the
+    /// first one is kept, as in `DowncastChain`.
+    pub fn on<T: Any>(mut self, mut callback: impl FnMut(&T) + 'f) -> Self {
+        self.callbacks.entry(TypeId::of::<T>()).or_insert_with(|| {
+            Box::new(move |any: &dyn Any| callback(any.downcast_ref::<T>().unwrap()))
+        });
+        self
+    }
+
+    /// Sets the callback for values of all types without a callback of their own.
+    pub fn otherwise(mut self, callback: impl FnMut(&V) + 'f) -> Self {
+        self.otherwise = Some(Box::new(callback));
+        self
+    }
+
+    /// Returns true if values of type `T` have a callback of their own.
+    pub fn handles<T: Any>(&self) -> bool {
+        self.callbacks.contains_key(&TypeId::of::<T>())
+    }
+}
+
+impl<'f, V: AsAny + ?Sized> AnyVisitor<V> for TypedVisitor<'f, V> {
+    fn visit(&mut self, value: &V) {
+        let any = value.as_any();
+        match self.callbacks.get_mut(&any.type_id()) {
+            Some(callback) => callback(any),
+            None => {
+                if let Some(otherwise) = &mut self.otherwise {
+                    otherwise(value)
+                }
+            }
+        }
+    }
+}
+
+impl<'f, V: AsAny + ?Sized> Default for TypedVisitor<'f, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<V: ?Sized> fmt::Debug for TypedVisitor<'_, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TypedVisitor")
+            .field("callbacks", &self.callbacks.len())
+            .field("otherwise", &self.otherwise.is_some())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+
+    use super::{visit, AnyVisitor, TypedVisitor};
+    use crate::BoxAny;
+
+    trait Base: BoxAny {}
+
+    struct Foo(u32);
+    impl Base for Foo {}
+    struct Bar;
+    impl Base for Bar {}
+    struct Baz;
+    impl Base for Baz {}
+
+    #[test]
+    fn test_typed() {
+        let values: Vec<Box<dyn Base>> =
+            vec![Box::new(Foo(1)), Box::new(Bar), Box::new(Foo(2)), Box::new(Baz)];
+        let (mut sum, mut bars) = (0, 0);
+        let mut visitor = TypedVisitor::<dyn Base>::new()
+            .on::<Foo>(|foo| sum += foo.0)
+            .on::<Foo>(|_| unreachable!())
+            .on::<Bar>(|_| bars += 1);
+        assert!(visitor.handles::<Foo>());
+        assert!(!visitor.handles::<Baz>());
+        // Without an `otherwise` callback, `Baz` is skipped.
+        visit(&values, &mut visitor);
+        drop(visitor);
+        assert_eq!((sum, bars), (3, 1));
+    }
+
+    #[test]
+    fn test_otherwise() {
+        let values: Vec<Rc<dyn Base>> = vec![Rc::new(Foo(1)), Rc::new(Baz), Rc::new(Baz)];
+        let mut others = Vec::new();
+        visit(
+            &values,
+            &mut TypedVisitor::<dyn Base>::default()
+                .on::<Foo>(|_| ())
+                .otherwise(|other| others.push(other.type_name())),
+        );
+        assert_eq!(others.len(), 2);
+        assert!(others.iter().all(|name| name.ends_with("Baz")));
+    }
+
+    #[test]
+    fn test_custom() {
+        // A visitor that dispatches on something other than the concrete type.
+        struct Names(Vec<&'static str>);
+        impl AnyVisitor<dyn Base> for Names {
+            fn visit(&mut self, value: &dyn Base) {
+                let name = value.type_name();
+                self.0.push(&name[name.rfind(':').map_or(0, |i| i + 1)..]);
+            }
+        }
+        let values: [&dyn Base; 2] = [&Bar, &Foo(0)];
+        let mut names = Names(Vec::new());
+        visit(&values, &mut names);
+        assert_eq!(names.0, ["Bar", "Foo"]);
+    }
+}
@@ -0,0 +1,118 @@
+//! `wasm-bindgen` interop: `JsValue`s keyed by type in an `AnyMap`, and trait objects whose
+//! types are in a `TypeRegistry` converted to and from `JsValue`s.
+
+use std::fmt;
+use std::marker::PhantomData;
+
+use serde::de::DeserializeSeed;
+use serde::Serialize;
+use wasm_bindgen::JsValue;
+
+use crate::{AnyMap, AsAny, Bounds, TypeRegistry};
+
+/// A `JsValue` keyed by the marker type `T`, so that several JS values, e.g. the canvas and
+/// the audio context of a game, can be kept in one `AnyMap`.
+pub struct Js<T: ?Sized> {
+    value: JsValue,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T: ?Sized> Js<T> {
+    pub fn new(value: JsValue) -> Self {
+        Js { value, marker: PhantomData }
+    }
+
+    pub fn value(&self) -> &JsValue {
+        &self.value
+    }
+
+    pub fn into_value(self) -> JsValue {
+        self.value
+    }
+}
+
+impl<T: ?Sized> Clone for Js<T> {
+    fn clone(&self) -> Self {
+        Js::new(self.value.clone())
+    }
+}
+
+impl<T: ?Sized> fmt::Debug for Js<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Js").field(&std::any::type_name::<T>()).finish()
+    }
+}
+
+impl AnyMap {
+    /// Inserts a `JsValue` keyed by `T`, returning the one it replaces.
+    pub fn insert_js<T: ?Sized + 'static>(&mut self, value: JsValue) -> Option<JsValue> {
+        self.insert(Js::<T>::new(value)).map(Js::into_value)
+    }
+
+    pub fn get_js<T: ?Sized + 'static>(&self) -> Option<&JsValue> {
+        self.get::<Js<T>>().map(Js::value)
+    }
+
+    pub fn remove_js<T: ?Sized + 'static>(&mut self) -> Option<JsValue> {
+        self.remove::<Js<T>>().map(Js::into_value)
+    }
+}
+
+impl<B: AsAny + ?Sized, S: Bounds> TypeRegistry<B, S> {
+    /// Converts `value` to a JS object tagged with the registered name of its concrete type,
+    /// as serialized by `tagged`. Its type must have been made serializable by
+    /// `register_serde`.
+    ///
+    /// ```no_run
+    /// # extern crate serde;
+    /// use pi_any::{BoxAny, TypeRegistry};
+    /// use serde::{Deserialize, Serialize};
+    ///
+    /// trait Shape: BoxAny {}
+    ///
+    /// #[derive(Default, Serialize, Deserialize)]
+    /// struct Square { side: f32 }
+    /// impl Shape for Square {}
+    ///
+    /// let mut shapes = TypeRegistry::<dyn Shape>::new();
+    /// shapes.register::<Square>("square", || Box::new(Square::default()));
+    /// shapes.register_serde::<Square>(|v| Box::new(v));
+    ///
+    /// let js = shapes.to_js(&Square { side: 2.0 }).unwrap();
+    /// let shape: Box<dyn Shape> = shapes.from_js(js).unwrap();
+    /// ```
+    pub fn to_js(&self, value: &B) -> Result<JsValue, serde_wasm_bindgen::Error> {
+        self.tagged(value).serialize(&serde_wasm_bindgen::Serializer::new())
+    }
+
+    /// Converts a JS object made by `to_js` back into a `Box<B>`.
+    pub fn from_js(&self, value: JsValue) -> Result<Box<B>, serde_wasm_bindgen::Error> {
+        self.tagged_seed().deserialize(serde_wasm_bindgen::Deserializer::from(value))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use wasm_bindgen::JsValue;
+
+    use super::Js;
+    use crate::AnyMap;
+
+    struct Canvas;
+    struct Audio;
+
+    // Only the constant values are used, as calling into JS, even to compare, panics off
+    // wasm.
+    #[test]
+    fn test_map() {
+        let mut map = AnyMap::new();
+        assert!(map.insert_js::<Canvas>(JsValue::NULL).is_none());
+        assert!(map.insert_js::<Audio>(JsValue::UNDEFINED).is_none());
+        assert!(map.insert_js::<Canvas>(JsValue::TRUE).is_some());
+        assert!(map.get_js::<Canvas>().is_some() && map.contains::<Js<Audio>>());
+        assert!(map.get_js::<dyn std::any::Any>().is_none());
+        assert!(map.remove_js::<Audio>().is_some() && map.remove_js::<Audio>().is_none());
+        assert_eq!(map.len(), 1);
+        assert!(format!("{:?}", Js::<Canvas>::new(JsValue::NULL)).contains("Canvas"));
+    }
+}